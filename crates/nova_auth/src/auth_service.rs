@@ -1,10 +1,14 @@
 use arc_swap::ArcSwapOption;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use chrono::Duration;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use serde::Deserialize;
 use thiserror::Error;
 use tracing::{error, info, warn, debug};
 use nova_api::authenticator_api::{AuthApiError, AuthenticatorApi};
+use nova_crypto::password_strength::{StrengthEstimator, MIN_ACCOUNT_SCORE};
 use nova_rate_limit::fixed::RateLimiter;
 
 use crate::session_manager::SessionManager;
@@ -15,6 +19,21 @@ pub struct Tokens {
     pub refresh: String,
 }
 
+impl Tokens {
+    /// The `sub` claim of the access token. The signature is not checked here, the server does that
+    /// on every request, so the subject is only good for attributing local actions to the user.
+    pub fn subject(&self) -> Option<String> {
+        #[derive(Deserialize)]
+        struct Claims {
+            sub: String,
+        }
+
+        let payload = self.access.split('.').nth(1)?;
+        let claims: Claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+        Some(claims.sub)
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum LoginError {
     #[error("Rate limit exceeded.")]
@@ -31,6 +50,9 @@ pub enum LoginError {
 
     #[error("User already exists")]
     UserAlreadyExists,
+
+    #[error("Password is too weak: {0}")]
+    WeakPassword(String),
 }
 
 pub struct AuthService {
@@ -95,8 +117,17 @@ impl AuthService {
             return Err(LoginError::RateLimitReached);
         }
 
+        // the authenticator enforces the same minimum, checking here only saves the round trip and keeps the feedback
+        let estimate = StrengthEstimator::default().estimate(password, &[username]);
+        if !estimate.meets(MIN_ACCOUNT_SCORE) {
+            return Err(LoginError::WeakPassword(estimate.feedback.to_string()));
+        }
+
         self.auth_api.signup(username, password).await.map_err(|e| match e {
             AuthApiError::HttpStatus(reqwest::StatusCode::CONFLICT) => LoginError::UserAlreadyExists,
+            AuthApiError::HttpStatus(reqwest::StatusCode::UNPROCESSABLE_ENTITY) => {
+                LoginError::WeakPassword("The server rejected the password as too weak.".to_string())
+            }
             AuthApiError::HttpStatus(reqwest::StatusCode::TOO_MANY_REQUESTS) => LoginError::RateLimitReached,
             _ => LoginError::FailedToLogin(e.to_string())
         })?;
@@ -148,6 +179,11 @@ impl AuthService {
         Ok(())
     }
 
+    /// The subject of the logged in user, e.g. to record who did what in a project's audit log.
+    pub fn subject(&self) -> Option<String> {
+        self.tokens.load().as_ref().and_then(|tokens| tokens.subject())
+    }
+
     pub async fn logout(&self) -> bool {
         debug!("Trying to logout user");
 
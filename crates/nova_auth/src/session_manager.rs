@@ -1,11 +1,13 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 
+use chrono::{DateTime, Utc};
 use keyring::Entry;
 use nova_crypto::key_derivation::PASSPHRASE_LENGTH;
+use nova_crypto::key_ring::{KeyMaterial, KeyRing, KeyRingError};
 use nova_crypto::password_generator::{PasswordGenerator};
-use nova_crypto::crypto::*;
-use chacha20poly1305::XChaCha20Poly1305;
+use nova_crypto::envelope::{open_legacy_str, Algorithm, EnvelopeError};
 use nova_fs::folder_resolver::FolderResolver;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
@@ -15,6 +17,12 @@ const USER_NAME: &str = "nova_token";
 const AAD: &str = "nova_persist";
 const SESSION_VERSION: &str = "1.0.0";
 
+/// How long a session passphrase is used before the session is re-encrypted under a new one.
+const KEY_ROTATION_DAYS: i64 = 30;
+
+/// The passphrase stored before rotation, as a bare string in the keyring entry.
+const LEGACY_KEY_ID: &str = "legacy";
+
 #[derive(Serialize, Deserialize)]
 struct Session {
     session: SessionData,
@@ -32,32 +40,116 @@ struct SessionKeys {
     refresh: String,
 }
 
+/// What the keyring entry holds: the passphrase sessions are sealed with, plus the ones
+/// that still open a session written before an interrupted rotation.
+#[derive(Serialize, Deserialize)]
+struct SessionKeyring {
+    primary: String,
+    keys: BTreeMap<String, StoredKey>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredKey {
+    passphrase: String,
+    /// `None` for the legacy passphrase, which is always due for rotation.
+    created_at: Option<DateTime<Utc>>,
+}
+
+impl SessionKeyring {
+    fn generate() -> anyhow::Result<Self> {
+        let mut keys = Self { primary: String::new(), keys: BTreeMap::new() };
+        keys.rotate()?;
+        Ok(keys)
+    }
+
+    /// Reads a keyring entry. An entry written before rotation holds a bare passphrase.
+    fn from_stored(stored: String) -> Self {
+        serde_json::from_str(&stored).unwrap_or_else(|_| Self::legacy(stored))
+    }
+
+    fn legacy(passphrase: String) -> Self {
+        Self {
+            primary: LEGACY_KEY_ID.to_string(),
+            keys: BTreeMap::from([(LEGACY_KEY_ID.to_string(), StoredKey { passphrase, created_at: None })]),
+        }
+    }
+
+    /// Adds a new primary passphrase. The previous ones stay until [`SessionKeyring::prune`].
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        let Some(passphrase) = PasswordGenerator::default().generate(PASSPHRASE_LENGTH) else {
+            anyhow::bail!("Failed to generate encryption passphrase");
+        };
+
+        let created_at = Utc::now();
+        let key_id = format!("session-{}", created_at.format("%Y%m%dT%H%M%S%.3f"));
+
+        self.keys.insert(key_id.clone(), StoredKey { passphrase, created_at: Some(created_at) });
+        self.primary = key_id;
+        Ok(())
+    }
+
+    /// Drops every passphrase but the primary.
+    fn prune(&mut self) {
+        self.keys.retain(|key_id, _| *key_id == self.primary);
+    }
+
+    fn is_due(&self) -> bool {
+        self.keys
+            .get(&self.primary)
+            .and_then(|key| key.created_at)
+            .is_none_or(|created_at| Utc::now() - created_at >= chrono::Duration::days(KEY_ROTATION_DAYS))
+    }
+
+    fn primary_passphrase(&self) -> &str {
+        self.keys.get(&self.primary).map(|key| key.passphrase.as_str()).unwrap_or_default()
+    }
+
+    fn key_ring(&self) -> anyhow::Result<KeyRing> {
+        let Some(primary) = self.keys.get(&self.primary) else {
+            anyhow::bail!("Primary session key {} is missing", self.primary);
+        };
+
+        let mut ring = KeyRing::new(&self.primary, KeyMaterial::passphrase(&primary.passphrase));
+        for (key_id, key) in self.keys.iter().filter(|(key_id, _)| **key_id != self.primary) {
+            ring.add_decrypt_only(key_id, KeyMaterial::passphrase(&key.passphrase))?;
+        }
+
+        Ok(ring)
+    }
+
+    /// Opens the sealed refresh token. Also returns whether the session has to be written again: because it
+    /// has no envelope header yet, its passphrase is due, or a rotation was interrupted before pruning.
+    fn open_session(&self, sealed: &str) -> anyhow::Result<(String, bool)> {
+        let (token, legacy) = match self.key_ring()?.open_str(sealed, AAD) {
+            Ok(token) => (token, false),
+            Err(KeyRingError::Envelope(EnvelopeError::NotAnEnvelope)) => {
+                (open_legacy_str(sealed, Algorithm::XChaCha20Poly1305, self.primary_passphrase(), AAD, None)?, true)
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok((token, legacy || self.is_due() || self.keys.len() > 1))
+    }
+}
+
 pub struct SessionManager {}
 
 impl SessionManager {
     pub fn persist_login(token: &str) -> anyhow::Result<()> {
         debug!("Attempting to persist login state...");
 
-        let entry = Entry::new(SERVICE_NAME, USER_NAME)?;
-
-        match entry.get_password() {
-            Ok(password) => {
+        let keys = match SessionManager::fetch_keyring_keys()? {
+            Some(keys) => {
                 debug!("Found existing keyring entry. Using stored passphrase.");
-                SessionManager::persist_session(token, &password)?;
+                keys
             }
-            Err(_) => {
+            None => {
                 debug!("No existing keyring entry found. Creating a new passphrase.");
-
-                if let Some(password) = PasswordGenerator::default().generate(PASSPHRASE_LENGTH) {
-                    SessionManager::store_keyring_password(&password)?;
-                    SessionManager::persist_session(token, &password)?;
-                }
-                else {
-                    debug!("Failed to generate encryption passphrase.");
-                    anyhow::bail!("Failed to persist login state");
-                }
+                SessionKeyring::generate()?
             }
-        }
+        };
+
+        SessionManager::write_session(token, keys)?;
 
         debug!("Login state successfully persisted.");
         Ok(())
@@ -74,14 +166,34 @@ impl SessionManager {
         let file_content = fs::read_to_string(&file_path)?;
         let parsed: Session = toml::from_str(&file_content)?;
 
-        let keyring_password = SessionManager::fetch_keyring_password()?;
-        let token = decrypt_str::<XChaCha20Poly1305>(&parsed.keys.refresh, &keyring_password, AAD, None)?;
+        let Some(keys) = SessionManager::fetch_keyring_keys()? else {
+            anyhow::bail!("Failed to load session. No keyring entry");
+        };
+
+        let (token, rewrite) = keys.open_session(&parsed.keys.refresh)?;
+
+        if rewrite {
+            debug!("Session is stored without envelope header, under an old passphrase or mid rotation. Rewriting it.");
+            SessionManager::write_session(&token, keys)?;
+        }
 
         debug!("Session loaded successfully");
 
         Ok(token)
     }
 
+    /// Re-encrypts the stored session under a new passphrase, regardless of the schedule.
+    pub fn rotate_key() -> anyhow::Result<()> {
+        let token = SessionManager::load_session()?;
+
+        let Some(mut keys) = SessionManager::fetch_keyring_keys()? else {
+            anyhow::bail!("Failed to rotate session key. No keyring entry");
+        };
+
+        keys.rotate()?;
+        SessionManager::write_session(&token, keys)
+    }
+
     pub fn remove_session() -> anyhow::Result<()> {
         let file_path = SessionManager::session_path();
 
@@ -103,23 +215,49 @@ impl SessionManager {
         Ok(())
     }
 
-    fn store_keyring_password(password: &str) -> anyhow::Result<()> {
-        Entry::new(SERVICE_NAME, USER_NAME)?.set_password(password)?;
+    /// Seals the session with the primary passphrase, rotating it first if it is due. Old passphrases are
+    /// only dropped from the keyring once the session file no longer needs them.
+    fn write_session(token: &str, mut keys: SessionKeyring) -> anyhow::Result<()> {
+        if keys.is_due() {
+            debug!("Session passphrase is due for rotation.");
+            keys.rotate()?;
+        }
+
+        SessionManager::store_keyring_keys(&keys)?;
+        SessionManager::persist_session(token, &keys.key_ring()?)?;
+
+        if keys.keys.len() > 1 {
+            keys.prune();
+            SessionManager::store_keyring_keys(&keys)?;
+            debug!("Rotated session passphrase to {}", keys.primary);
+        }
+
+        Ok(())
+    }
+
+    fn store_keyring_keys(keys: &SessionKeyring) -> anyhow::Result<()> {
+        Entry::new(SERVICE_NAME, USER_NAME)?.set_password(&serde_json::to_string(keys)?)?;
         debug!("Stored password to keyring entry. service={SERVICE_NAME}, user={USER_NAME}");
         Ok(())
     }
 
-    fn fetch_keyring_password() -> anyhow::Result<String> {
+    /// `None` if there is no entry yet.
+    fn fetch_keyring_keys() -> anyhow::Result<Option<SessionKeyring>> {
         debug!("Fetch password from keyring entry. service={SERVICE_NAME}, user={USER_NAME}");
 
-        let password = Entry::new(SERVICE_NAME, USER_NAME)?.get_password()?;
-        Ok(password)
+        let stored = match Entry::new(SERVICE_NAME, USER_NAME)?.get_password() {
+            Ok(stored) => stored,
+            Err(keyring::Error::NoEntry) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Some(SessionKeyring::from_stored(stored)))
     }
 
-    fn persist_session(token: &str, password: &str) -> anyhow::Result<()> {
+    fn persist_session(token: &str, key_ring: &KeyRing) -> anyhow::Result<()> {
         let file_path = SessionManager::session_path();
 
-        let encrypted = encrypt_str::<XChaCha20Poly1305>(token, password, AAD, None)?;
+        let encrypted = key_ring.seal_str(token, AAD)?;
 
         let toml = Session {
             session: SessionData {
@@ -143,3 +281,93 @@ impl SessionManager {
         FolderResolver::resolve_session_dir().join("nova_session.toml")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chacha20poly1305::XChaCha20Poly1305;
+    use nova_crypto::crypto::encrypt_str;
+
+    const TOKEN: &str = "refresh-token";
+
+    fn seal(keys: &SessionKeyring) -> String {
+        keys.key_ring().unwrap().seal_str(TOKEN, AAD).unwrap()
+    }
+
+    fn passphrase() -> String {
+        PasswordGenerator::default().generate(PASSPHRASE_LENGTH).unwrap()
+    }
+
+    #[test]
+    fn bare_keyring_entry_is_read_as_legacy_passphrase() {
+        let keys = SessionKeyring::from_stored("old passphrase".to_string());
+        assert_eq!(keys.primary, LEGACY_KEY_ID);
+        assert_eq!(keys.primary_passphrase(), "old passphrase");
+        assert!(keys.is_due());
+
+        let generated = SessionKeyring::generate().unwrap();
+        let stored = SessionKeyring::from_stored(serde_json::to_string(&generated).unwrap());
+        assert_eq!(stored.primary, generated.primary);
+        assert_eq!(stored.primary_passphrase(), generated.primary_passphrase());
+        assert!(!stored.is_due());
+    }
+
+    #[test]
+    fn legacy_session_is_opened_and_migrated() {
+        let passphrase = passphrase();
+        let mut keys = SessionKeyring::legacy(passphrase.clone());
+        let legacy = encrypt_str::<XChaCha20Poly1305>(TOKEN, &passphrase, AAD, None).unwrap();
+        assert_eq!(keys.open_session(&legacy).unwrap(), (TOKEN.to_string(), true));
+
+        // What `write_session` does with it.
+        keys.rotate().unwrap();
+        let migrated = seal(&keys);
+        keys.prune();
+
+        assert!(!keys.keys.contains_key(LEGACY_KEY_ID));
+        assert_eq!(keys.open_session(&migrated).unwrap(), (TOKEN.to_string(), false));
+    }
+
+    #[test]
+    fn legacy_session_needs_the_legacy_passphrase() {
+        let keys = SessionKeyring::legacy(passphrase());
+        let legacy = encrypt_str::<XChaCha20Poly1305>(TOKEN, &passphrase(), AAD, None).unwrap();
+        assert!(keys.open_session(&legacy).is_err());
+    }
+
+    #[test]
+    fn interrupted_rotation_still_opens_the_session() {
+        let mut keys = SessionKeyring::generate().unwrap();
+        let sealed = seal(&keys);
+        let previous = keys.primary.clone();
+
+        // The keyring entry is stored with the new passphrase before the session file is rewritten.
+        keys.rotate().unwrap();
+        assert_ne!(keys.primary, previous);
+        assert_eq!(keys.open_session(&sealed).unwrap(), (TOKEN.to_string(), true));
+
+        let rewritten = seal(&keys);
+        keys.prune();
+        assert_eq!(keys.keys.keys().collect::<Vec<_>>(), vec![&keys.primary]);
+        assert_eq!(keys.open_session(&rewritten).unwrap(), (TOKEN.to_string(), false));
+        assert!(keys.open_session(&sealed).is_err());
+    }
+
+    #[test]
+    fn passphrase_is_due_after_the_rotation_period() {
+        let mut keys = SessionKeyring::generate().unwrap();
+        assert!(!keys.is_due());
+
+        let created_at = Utc::now() - chrono::Duration::days(KEY_ROTATION_DAYS);
+        keys.keys.get_mut(&keys.primary).unwrap().created_at = Some(created_at);
+        assert!(keys.is_due());
+        assert_eq!(keys.open_session(&seal(&keys)).unwrap(), (TOKEN.to_string(), true));
+    }
+
+    #[test]
+    fn missing_primary_passphrase_is_an_error() {
+        let mut keys = SessionKeyring::generate().unwrap();
+        keys.primary = "gone".to_string();
+        assert!(keys.key_ring().is_err());
+    }
+}
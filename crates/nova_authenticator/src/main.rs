@@ -7,7 +7,9 @@ use crate::net::webserver::webserver::WebServer;
 use clap::Parser;
 use mimalloc::MiMalloc;
 use std::path::{Path, PathBuf};
-use tracing::Level;
+use anyhow::Context;
+use tracing::{info, Level};
+use nova_crypto::breach_list::BreachList;
 use nova_di::ioc;
 use crate::crypto::vault::Vault;
 
@@ -24,6 +26,13 @@ pub struct CliArgs {
         help = "Path to the vault configuration file"
     )]
     pub vault_config_path: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "SHA-1 hashes of breached passwords (HASH or HASH:COUNT per line) rejected at signup"
+    )]
+    pub breach_list: Option<PathBuf>,
 }
 
 fn default_vault_config_path() -> PathBuf  {
@@ -43,9 +52,25 @@ fn init_vault(vault_config_path: &Path) {
         .expect("Failed to initialize vault"));
 }
 
-async fn run_webserver() -> anyhow::Result<()> {
+/// The bundled list is only a placeholder, a real one has to be passed with `--breach-list`.
+fn load_breach_list(path: Option<&Path>) -> anyhow::Result<BreachList> {
+    let mut breach_list = BreachList::bundled();
+
+    match path {
+        Some(path) => {
+            let loaded = BreachList::load(path).with_context(|| format!("failed to load breach list {path:?}"))?;
+            info!("Loaded {} breached password hashes from {:?}", loaded.len(), path);
+            breach_list.merge(loaded);
+        }
+        None => info!("No breach list configured, only the bundled common passwords are rejected"),
+    }
+
+    Ok(breach_list)
+}
+
+async fn run_webserver(breach_list: BreachList) -> anyhow::Result<()> {
     WebServer::install_crypto_provider()?;
-    let app = WebServer::new().await;
+    let app = WebServer::new(breach_list).await;
 
     app.run().await?;
     Ok(())
@@ -57,5 +82,7 @@ async fn main() -> anyhow::Result<()> {
     
     init_vault(&args.vault_config_path);
     init_logger();
-    run_webserver().await
+
+    let breach_list = load_breach_list(args.breach_list.as_deref())?;
+    run_webserver(breach_list).await
 }
\ No newline at end of file
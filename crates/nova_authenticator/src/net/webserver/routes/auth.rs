@@ -41,6 +41,12 @@ pub async fn handle_signup(State(auth_state): State<AuthState>, Json(payload): J
                         "User already exists".to_string()
                     ).into_response()
                 }
+                LoginFailureReason::WeakPassword(feedback) => {
+                    (
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        feedback
+                    ).into_response()
+                }
                 _ => {
                     (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")).into_response()
                 }
@@ -14,7 +14,9 @@ use rustls::crypto::CryptoProvider;
 use serde::Deserialize;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use nova_crypto::crypto::{decrypt_str, CryptoAlgo};
+use nova_crypto::breach_list::BreachList;
+use nova_crypto::crypto::CryptoAlgo;
+use nova_crypto::envelope::{open_str_or_legacy, Algorithm};
 use nova_crypto::key_derivation::SALT_LEN;
 use thiserror::Error;
 use tracing::{debug, error, info, warn};
@@ -66,9 +68,9 @@ pub struct TlsConfig {
 }
 
 impl WebServer {
-    pub async fn new() -> Self {
+    pub async fn new(breach_list: BreachList) -> Self {
         Self {
-            auth_service: Arc::new(Auth::new().await),
+            auth_service: Arc::new(Auth::new(breach_list).await),
             ports: Ports {
                 http: 24982,
                 https: 5643,
@@ -122,7 +124,8 @@ impl WebServer {
         let VaultSecrets { password, pepper } = ioc().resolve::<Vault>().fetch_tls_secrets().await?;
         let pepper_bytes = pepper.as_bytes();
 
-        let plaintext = decrypt_str::<XChaCha20Poly1305>(encrypted, &password, AAD, Some(pepper_bytes))?;
+        // accepts both an envelope written by `nova-seal` and the older headerless format
+        let (plaintext, _) = open_str_or_legacy(encrypted.trim(), Algorithm::XChaCha20Poly1305, &password, AAD, Some(pepper_bytes))?;
 
         let cfg: TlsConfig = toml::from_str(&plaintext)?;
         let dec_pem = Self::decrypt_pkcs8_pem(&cfg.server_key_pem, &cfg.server_key_password)?;
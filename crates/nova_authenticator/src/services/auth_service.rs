@@ -3,12 +3,15 @@ use thiserror::Error;
 use tracing::{error, info};
 use tracing::log::debug;
 use nova_di::ioc::singleton::ioc;
+use nova_crypto::breach_list::BreachList;
 use nova_crypto::jwt::JwtTokens;
+use nova_crypto::password_strength::{StrengthEstimator, MIN_ACCOUNT_SCORE};
 use crate::crypto::vault::{Vault, VaultError};
 use crate::services::auth_db::{AuthDb, AuthDbError};
 
 pub struct Auth {
     auth_db: AuthDb,
+    strength_estimator: StrengthEstimator,
 }
 
 #[derive(Debug, Error)]
@@ -22,6 +25,9 @@ pub enum LoginFailureReason {
     #[error("wrong username or password")]
     WrongCredentials,
 
+    #[error("password is too weak: {0}")]
+    WeakPassword(String),
+
     #[error("failed to parse password hash")]
     PasswordHashError,
 
@@ -42,9 +48,10 @@ pub enum RefreshFailureReason {
 }
 
 impl Auth {
-    pub async fn new() -> Self {
+    pub async fn new(breach_list: BreachList) -> Self {
         Self {
             auth_db: AuthDb::new().await,
+            strength_estimator: StrengthEstimator::new(Some(breach_list)),
         }
     }
 
@@ -91,6 +98,8 @@ impl Auth {
     }
 
     pub async fn signup(&self, username: &str, password: &str) -> Result<(), LoginFailureReason>  {
+        check_password_strength(&self.strength_estimator, username, password)?;
+
         self.auth_db.create_user(username, password).await?;
         Ok(())
     }
@@ -112,4 +121,49 @@ impl Auth {
         let new_tokens = jwt.create_tokens(&decoded.claims.sub).ok_or(RefreshFailureReason::InternalError)?;
         Ok(new_tokens)
     }
+}
+/// Rejects passwords below [`MIN_ACCOUNT_SCORE`] and breached ones, however strong they look otherwise.
+fn check_password_strength(strength_estimator: &StrengthEstimator, username: &str, password: &str) -> Result<(), LoginFailureReason> {
+    let estimate = strength_estimator.estimate(password, &[username]);
+    if !estimate.meets(MIN_ACCOUNT_SCORE) {
+        debug!("Rejected signup password with score {} (breached: {})", estimate.score, estimate.is_breached());
+        return Err(LoginFailureReason::WeakPassword(estimate.feedback.to_string()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nova_crypto::breach_list::sha1_hex;
+
+    const STRONG: &str = "kX9#vq2!Lm@7zR";
+
+    #[test]
+    fn passwords_below_the_account_minimum_are_rejected() {
+        let estimator = StrengthEstimator::new(Some(BreachList::default()));
+
+        let err = check_password_strength(&estimator, "alice", "a").unwrap_err();
+        assert!(matches!(err, LoginFailureReason::WeakPassword(feedback) if !feedback.is_empty()));
+        assert!(check_password_strength(&estimator, "alice", "alice1").is_err());
+    }
+
+    #[test]
+    fn breached_passwords_are_rejected_however_strong() {
+        let clean = StrengthEstimator::new(Some(BreachList::default()));
+        assert!(check_password_strength(&clean, "alice", STRONG).is_ok());
+
+        let breached = StrengthEstimator::new(Some(BreachList::parse(&sha1_hex(STRONG)).unwrap()));
+        assert!(matches!(check_password_strength(&breached, "alice", STRONG), Err(LoginFailureReason::WeakPassword(_))));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a running database"]
+    async fn signup_rejects_weak_passwords_before_creating_the_user() {
+        let auth = Auth::new(BreachList::default()).await;
+
+        let err = auth.signup("alice", "a").await.unwrap_err();
+        assert!(matches!(err, LoginFailureReason::WeakPassword(_)));
+    }
 }
\ No newline at end of file
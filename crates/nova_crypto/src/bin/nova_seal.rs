@@ -0,0 +1,450 @@
+//! Encrypts, decrypts, rotates and inspects config blobs like the authenticator's `tls_config.txt`.
+//!
+//! Secrets are never taken as arguments. Each one is read from its environment variable if that is
+//! set, otherwise from the next line of stdin, in the order password, pepper, new password, new pepper.
+//! An empty pepper means none.
+
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use base64::prelude::*;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use zeroize::Zeroizing;
+use nova_crypto::envelope::{inspect, is_envelope, open_str_or_legacy, seal_str, Algorithm, EnvelopeError, Kdf, SealOptions, Secret};
+use nova_crypto::key_derivation::Argon2Preset;
+
+/// The associated data the authenticator opens its config with.
+const DEFAULT_AAD: &str = "nova-config-v1";
+
+const PASSWORD_ENV: &str = "NOVA_SEAL_PASSWORD";
+const PEPPER_ENV: &str = "NOVA_SEAL_PEPPER";
+const NEW_PASSWORD_ENV: &str = "NOVA_SEAL_NEW_PASSWORD";
+const NEW_PEPPER_ENV: &str = "NOVA_SEAL_NEW_PEPPER";
+
+#[derive(Debug, thiserror::Error)]
+enum SealError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("{0} is not set and stdin has no line left for it")]
+    MissingSecret(&'static str),
+
+    #[error("the password must not be empty")]
+    EmptyPassword,
+
+    #[error("{0:?} already exists, pass --force to overwrite it")]
+    OutputExists(PathBuf),
+
+    #[error("base64 decode failed: {0}")]
+    Base64DecodeFailed(#[from] base64::DecodeError),
+
+    #[error(transparent)]
+    Envelope(#[from] EnvelopeError),
+}
+
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Encrypts, decrypts, rotates and inspects config blobs")]
+struct CliArgs {
+    /// Associated data the blob is bound to.
+    #[arg(long, global = true, default_value = DEFAULT_AAD)]
+    aad: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Seals a plaintext file into a base64 envelope.
+    Encrypt {
+        input: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        seal: SealArgs,
+    },
+    /// Opens an envelope, or a blob in the legacy format without a header.
+    Decrypt {
+        input: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        open: OpenArgs,
+    },
+    /// Opens a blob and seals it again as an envelope under the new password and pepper.
+    Rotate {
+        input: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        open: OpenArgs,
+        #[command(flatten)]
+        seal: SealArgs,
+        /// Keep the current password and pepper, to only upgrade the format or the Argon2 cost.
+        #[arg(long)]
+        keep_secret: bool,
+    },
+    /// Prints the header of an envelope. Needs no secret.
+    Inspect {
+        input: PathBuf,
+    },
+}
+
+#[derive(Args, Debug)]
+struct OutputArgs {
+    /// Written to stdout if not given.
+    #[arg(short, long, value_name = "FILE")]
+    out: Option<PathBuf>,
+    /// Overwrite the output file if it exists.
+    #[arg(long, requires = "out")]
+    force: bool,
+}
+
+#[derive(Args, Debug)]
+struct OpenArgs {
+    /// The algorithm of blobs in the legacy format, which don't name it.
+    #[arg(long, value_enum, default_value_t)]
+    legacy_algorithm: AlgorithmArg,
+}
+
+#[derive(Args, Debug)]
+struct SealArgs {
+    #[arg(long, value_enum, default_value_t)]
+    algorithm: AlgorithmArg,
+    #[arg(long, value_enum, default_value_t)]
+    preset: PresetArg,
+    /// Written to the header, to tell apart blobs sealed with different passwords.
+    #[arg(long)]
+    key_id: Option<String>,
+}
+
+impl SealArgs {
+    fn options(&self) -> SealOptions<'_> {
+        SealOptions {
+            algorithm: self.algorithm.into(),
+            key_id: self.key_id.as_deref(),
+            argon2: Argon2Preset::from(self.preset).params(),
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default)]
+enum AlgorithmArg {
+    #[default]
+    Xchacha20Poly1305,
+    Chacha20Poly1305,
+    Aes256Gcm,
+}
+
+impl From<AlgorithmArg> for Algorithm {
+    fn from(algorithm: AlgorithmArg) -> Self {
+        match algorithm {
+            AlgorithmArg::Xchacha20Poly1305 => Algorithm::XChaCha20Poly1305,
+            AlgorithmArg::Chacha20Poly1305 => Algorithm::ChaCha20Poly1305,
+            AlgorithmArg::Aes256Gcm => Algorithm::Aes256Gcm,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default)]
+enum PresetArg {
+    #[default]
+    Interactive,
+    Moderate,
+    Sensitive,
+}
+
+impl From<PresetArg> for Argon2Preset {
+    fn from(preset: PresetArg) -> Self {
+        match preset {
+            PresetArg::Interactive => Argon2Preset::Interactive,
+            PresetArg::Moderate => Argon2Preset::Moderate,
+            PresetArg::Sensitive => Argon2Preset::Sensitive,
+        }
+    }
+}
+
+/// A password and an optional pepper, as the authenticator fetches them from the Vault.
+struct Credentials {
+    password: Zeroizing<String>,
+    pepper: Option<Zeroizing<String>>,
+}
+
+impl Credentials {
+    fn read(stdin: &mut impl BufRead, password_env: &'static str, pepper_env: &'static str) -> Result<Self, SealError> {
+        let password = read_secret(stdin, password_env)?;
+        if password.is_empty() {
+            return Err(SealError::EmptyPassword);
+        }
+
+        let pepper = Some(read_secret(stdin, pepper_env)?).filter(|pepper| !pepper.is_empty());
+        Ok(Self { password, pepper })
+    }
+
+    fn pepper(&self) -> Option<&[u8]> {
+        self.pepper.as_ref().map(|pepper| pepper.as_bytes())
+    }
+
+    fn secret(&self) -> Secret<'_> {
+        Secret::Passphrase { passphrase: &self.password, pepper: self.pepper() }
+    }
+}
+
+fn read_secret(stdin: &mut impl BufRead, env: &'static str) -> Result<Zeroizing<String>, SealError> {
+    if let Ok(value) = std::env::var(env) {
+        return Ok(Zeroizing::new(value));
+    }
+
+    let mut line = Zeroizing::new(String::new());
+    if stdin.read_line(&mut line)? == 0 {
+        return Err(SealError::MissingSecret(env));
+    }
+
+    Ok(Zeroizing::new(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+fn encrypt(plain: &str, credentials: &Credentials, options: SealOptions, aad: &str) -> Result<String, SealError> {
+    Ok(seal_str(plain, credentials.secret(), aad, options)?)
+}
+
+fn decrypt(encoded: &str, credentials: &Credentials, open: &OpenArgs, aad: &str) -> Result<Zeroizing<String>, SealError> {
+    let (plain, _) = open_str_or_legacy(encoded.trim(), open.legacy_algorithm.into(), &credentials.password, aad, credentials.pepper())?;
+    Ok(Zeroizing::new(plain))
+}
+
+fn describe(encoded: &str) -> Result<String, SealError> {
+    let decoded = BASE64_STANDARD.decode(encoded.trim())?;
+
+    if !is_envelope(&decoded) {
+        return Ok(format!("format: legacy, without a header\nsize: {} bytes", decoded.len()));
+    }
+
+    let header = inspect(&decoded)?;
+    let kdf = match header.kdf {
+        Kdf::None => "none".to_string(),
+        Kdf::Argon2id { params, salt } => format!(
+            "argon2id, {} KiB, {} passes, {} lanes, {} byte salt",
+            params.memory_kib, params.iterations, params.parallelism, salt.len()
+        ),
+    };
+
+    Ok(format!(
+        "format: envelope v{}\nalgorithm: {}\nkdf: {}\nkey id: {}\nsize: {} bytes",
+        header.version,
+        header.algorithm.name(),
+        kdf,
+        header.key_id.as_deref().unwrap_or("none"),
+        decoded.len()
+    ))
+}
+
+/// Files get the text as it is, since the authenticator embeds them with `include_str!`. They can hold a
+/// decrypted secret, so on unix only the owner may read them.
+fn write_output(output: &OutputArgs, text: &str) -> Result<(), SealError> {
+    let Some(path) = &output.out else {
+        writeln!(io::stdout(), "{text}")?;
+        return Ok(());
+    };
+
+    let mut options = std::fs::OpenOptions::new();
+    match output.force {
+        true => options.write(true).create(true).truncate(true),
+        false => options.write(true).create_new(true),
+    };
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = match options.open(path) {
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => return Err(SealError::OutputExists(path.clone())),
+        result => result?,
+    };
+
+    // The mode only applies to new files.
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+
+    file.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+fn read_input(path: &Path) -> Result<Zeroizing<String>, SealError> {
+    Ok(Zeroizing::new(std::fs::read_to_string(path)?))
+}
+
+fn run(args: CliArgs) -> Result<(), SealError> {
+    let mut stdin = io::stdin().lock();
+
+    match args.command {
+        Command::Encrypt { input, output, seal } => {
+            let plain = read_input(&input)?;
+            let credentials = Credentials::read(&mut stdin, PASSWORD_ENV, PEPPER_ENV)?;
+            write_output(&output, &encrypt(&plain, &credentials, seal.options(), &args.aad)?)
+        }
+        Command::Decrypt { input, output, open } => {
+            let encoded = read_input(&input)?;
+            let credentials = Credentials::read(&mut stdin, PASSWORD_ENV, PEPPER_ENV)?;
+            write_output(&output, &decrypt(&encoded, &credentials, &open, &args.aad)?)
+        }
+        Command::Rotate { input, output, open, seal, keep_secret } => {
+            let encoded = read_input(&input)?;
+            let credentials = Credentials::read(&mut stdin, PASSWORD_ENV, PEPPER_ENV)?;
+            let plain = decrypt(&encoded, &credentials, &open, &args.aad)?;
+
+            let new_credentials = if keep_secret {
+                credentials
+            } else {
+                Credentials::read(&mut stdin, NEW_PASSWORD_ENV, NEW_PEPPER_ENV)?
+            };
+            write_output(&output, &encrypt(&plain, &new_credentials, seal.options(), &args.aad)?)
+        }
+        Command::Inspect { input } => write_output(&OutputArgs { out: None, force: false }, &describe(&read_input(&input)?)?),
+    }
+}
+
+fn main() {
+    if let Err(err) = run(CliArgs::parse()) {
+        eprintln!("nova-seal: {err}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nova_crypto::crypto::encrypt_str;
+    use chacha20poly1305::XChaCha20Poly1305;
+
+    /// The legacy format wants passphrases of at least [`nova_crypto::key_derivation::PASSPHRASE_LENGTH`].
+    const OLD_PASSWORD: &str = "an old password long enough for it";
+
+    fn credentials(password: &str, pepper: Option<&str>) -> Credentials {
+        Credentials { password: Zeroizing::new(password.to_string()), pepper: pepper.map(|pepper| Zeroizing::new(pepper.to_string())) }
+    }
+
+    const CONFIG: &str = "server_key_password = \"x\"";
+
+    fn open_args(legacy_algorithm: AlgorithmArg) -> OpenArgs {
+        OpenArgs { legacy_algorithm }
+    }
+
+    fn seal_args(algorithm: AlgorithmArg, key_id: Option<&str>) -> SealArgs {
+        SealArgs { algorithm, preset: PresetArg::Interactive, key_id: key_id.map(str::to_string) }
+    }
+
+    fn legacy_blob() -> String {
+        encrypt_str::<XChaCha20Poly1305>(CONFIG, OLD_PASSWORD, DEFAULT_AAD, Some(b"pepper")).unwrap()
+    }
+
+    #[test]
+    fn legacy_blob_is_described_without_header() {
+        let legacy = legacy_blob();
+        let size = BASE64_STANDARD.decode(&legacy).unwrap().len();
+        assert_eq!(describe(&legacy).unwrap(), format!("format: legacy, without a header\nsize: {size} bytes"));
+    }
+
+    #[test]
+    fn envelope_is_described_by_its_header() {
+        let seal = seal_args(AlgorithmArg::Aes256Gcm, Some("tls-2025"));
+        let sealed = encrypt(CONFIG, &credentials("new password", None), seal.options(), DEFAULT_AAD).unwrap();
+
+        let description = describe(&sealed).unwrap();
+        assert!(description.starts_with("format: envelope v"));
+        assert!(description.contains("algorithm: AES-256-GCM"));
+        assert!(description.contains("kdf: argon2id, 65536 KiB, 2 passes, 1 lanes"));
+        assert!(description.contains("key id: tls-2025"));
+
+        let unnamed = encrypt(CONFIG, &credentials("new password", None), seal_args(AlgorithmArg::default(), None).options(), DEFAULT_AAD).unwrap();
+        assert!(describe(&unnamed).unwrap().contains("key id: none"));
+    }
+
+    #[test]
+    fn describe_rejects_text_that_is_not_base64() {
+        assert!(matches!(describe("not base64!"), Err(SealError::Base64DecodeFailed(_))));
+    }
+
+    #[test]
+    fn legacy_blob_is_rotated_into_an_envelope() {
+        let open = open_args(AlgorithmArg::default());
+        let (old, new) = (credentials(OLD_PASSWORD, Some("pepper")), credentials("new password", None));
+
+        // Files usually end with a newline.
+        let plain = decrypt(&format!("{}\n", legacy_blob()), &old, &open, DEFAULT_AAD).unwrap();
+        let rotated = encrypt(&plain, &new, seal_args(AlgorithmArg::Aes256Gcm, Some("tls-2025")).options(), DEFAULT_AAD).unwrap();
+
+        assert!(is_envelope(&BASE64_STANDARD.decode(&rotated).unwrap()));
+        assert_eq!(decrypt(&rotated, &new, &open, DEFAULT_AAD).unwrap().as_str(), CONFIG);
+        assert!(decrypt(&rotated, &old, &open, DEFAULT_AAD).is_err());
+    }
+
+    #[test]
+    fn rotation_can_keep_the_secret() {
+        let open = open_args(AlgorithmArg::default());
+        let old = credentials(OLD_PASSWORD, Some("pepper"));
+
+        let plain = decrypt(&legacy_blob(), &old, &open, DEFAULT_AAD).unwrap();
+        let upgraded = encrypt(&plain, &old, seal_args(AlgorithmArg::default(), None).options(), DEFAULT_AAD).unwrap();
+        assert_eq!(decrypt(&upgraded, &old, &open, DEFAULT_AAD).unwrap().as_str(), CONFIG);
+    }
+
+    #[test]
+    fn legacy_blob_needs_its_algorithm_pepper_and_aad() {
+        let legacy = legacy_blob();
+        let open = open_args(AlgorithmArg::default());
+
+        assert!(decrypt(&legacy, &credentials(OLD_PASSWORD, Some("pepper")), &open, DEFAULT_AAD).is_ok());
+        assert!(decrypt(&legacy, &credentials(OLD_PASSWORD, Some("pepper")), &open_args(AlgorithmArg::Aes256Gcm), DEFAULT_AAD).is_err());
+        assert!(decrypt(&legacy, &credentials(OLD_PASSWORD, None), &open, DEFAULT_AAD).is_err());
+        assert!(decrypt(&legacy, &credentials(OLD_PASSWORD, Some("pepper")), &open, "other-config").is_err());
+    }
+
+    #[test]
+    fn credentials_are_read_line_by_line() {
+        let mut stdin = io::Cursor::new("password\r\n\nsecond\npepper\n");
+
+        let read = Credentials::read(&mut stdin, "NOVA_SEAL_TEST_PASSWORD", "NOVA_SEAL_TEST_PEPPER").unwrap();
+        assert_eq!((read.password.as_str(), read.pepper()), ("password", None));
+
+        let read = Credentials::read(&mut stdin, "NOVA_SEAL_TEST_PASSWORD", "NOVA_SEAL_TEST_PEPPER").unwrap();
+        assert_eq!((read.password.as_str(), read.pepper()), ("second", Some(&b"pepper"[..])));
+
+        let result = Credentials::read(&mut stdin, "NOVA_SEAL_TEST_PASSWORD", "NOVA_SEAL_TEST_PEPPER");
+        assert!(matches!(result, Err(SealError::MissingSecret("NOVA_SEAL_TEST_PASSWORD"))));
+    }
+
+    #[test]
+    fn empty_password_is_refused() {
+        let mut stdin = io::Cursor::new("\npepper\n");
+        let result = Credentials::read(&mut stdin, "NOVA_SEAL_TEST_PASSWORD", "NOVA_SEAL_TEST_PEPPER");
+        assert!(matches!(result, Err(SealError::EmptyPassword)));
+    }
+
+    #[test]
+    fn output_file_is_not_overwritten_unless_forced() {
+        let path = std::env::temp_dir().join(format!("nova-seal-test-{}-overwrite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        write_output(&OutputArgs { out: Some(path.clone()), force: false }, "first").unwrap();
+        let refused = write_output(&OutputArgs { out: Some(path.clone()), force: false }, "second");
+        assert!(matches!(refused, Err(SealError::OutputExists(existing)) if existing == path));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first");
+
+        write_output(&OutputArgs { out: Some(path.clone()), force: true }, "second").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_file_is_only_readable_by_its_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("nova-seal-test-{}-mode", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        write_output(&OutputArgs { out: Some(path.clone()), force: false }, CONFIG).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write_output(&OutputArgs { out: Some(path.clone()), force: true }, CONFIG).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
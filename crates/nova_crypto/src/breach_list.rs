@@ -0,0 +1,232 @@
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use sha1::{Digest, Sha1};
+
+/// A placeholder: the hashes of the passwords in `wordlists/common_passwords.txt`, one uppercase
+/// SHA-1 per line. It finds nothing the common password dictionary doesn't already, it only keeps
+/// the check working until a real list, like a Pwned Passwords download, is loaded next to it.
+const BUNDLED: &str = include_str!("wordlists/breached_sha1.txt");
+
+/// Hashes are looked up by their first five hex digits, like the Pwned Passwords range API, so
+/// a remote list would only ever learn the prefix of the password asked about.
+pub const PREFIX_LEN: usize = 5;
+
+const SHA1_HEX_LEN: usize = 40;
+const SHA1_LEN: usize = 20;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BreachListError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("line {0} is not a SHA-1 hash")]
+    InvalidLine(usize),
+}
+
+/// SHA-1 hashes of leaked passwords, sorted by digest so every lookup is a binary search.
+///
+/// Each hash takes 24 bytes, so a full Pwned Passwords download still needs tens of GB. Deployments
+/// without that much memory load a subset, e.g. only the hashes seen more than a few times.
+#[derive(Debug, Clone, Default)]
+pub struct BreachList {
+    /// Sorted and without duplicates. Counts saturate at `u32::MAX`.
+    entries: Vec<([u8; SHA1_LEN], u32)>,
+}
+
+impl BreachList {
+    /// The placeholder that ships with the crate, see `BUNDLED`. Deployments merge a real list
+    /// into it with [`BreachList::load`] and [`BreachList::merge`].
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED).expect("the bundled breach list is valid")
+    }
+
+    /// Reads `HASH` or `HASH:COUNT` lines, the format the Pwned Passwords downloader writes.
+    /// Empty lines are skipped.
+    pub fn read(reader: impl BufRead) -> Result<Self, BreachListError> {
+        let mut entries = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (hash, count) = match line.split_once(':') {
+                Some((hash, count)) => (hash, count.trim().parse::<u64>().map_err(|_| BreachListError::InvalidLine(index + 1))?),
+                None => (line, 1),
+            };
+
+            let digest = decode_sha1_hex(hash).ok_or(BreachListError::InvalidLine(index + 1))?;
+            entries.push((digest, saturate(count)));
+        }
+
+        Ok(Self::from_unsorted(entries))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, BreachListError> {
+        Self::read(text.as_bytes())
+    }
+
+    /// Streams the file, so only the hashes are held in memory.
+    pub fn load(path: &Path) -> Result<Self, BreachListError> {
+        Self::read(BufReader::new(std::fs::File::open(path)?))
+    }
+
+    /// Adds the hashes of `other`, summing the counts of hashes in both.
+    pub fn merge(&mut self, other: BreachList) {
+        let mut entries = std::mem::take(&mut self.entries);
+        entries.extend(other.entries);
+        *self = Self::from_unsorted(entries);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Uppercase hex suffixes and counts of every hash starting with `prefix`.
+    pub fn range(&self, prefix: &str) -> impl Iterator<Item = (String, u64)> + '_ {
+        let prefix = prefix.to_ascii_uppercase();
+        let bounds = decode_sha1_hex(&format!("{prefix:0<SHA1_HEX_LEN$}")).filter(|_| prefix.len() == PREFIX_LEN);
+
+        let entries = match bounds {
+            Some(lowest) => {
+                let start = self.entries.partition_point(|(digest, _)| *digest < lowest);
+                &self.entries[start..]
+            }
+            None => &[][..],
+        };
+
+        entries
+            .iter()
+            .map(|(digest, count)| (to_hex(digest), u64::from(*count)))
+            .take_while(move |(hash, _)| hash.starts_with(&prefix))
+            .map(|(hash, count)| (hash[PREFIX_LEN..].to_string(), count))
+    }
+
+    /// How often `password` was seen in breaches, `None` if it isn't on the list.
+    pub fn occurrences(&self, password: &str) -> Option<u64> {
+        let digest: [u8; SHA1_LEN] = Sha1::digest(password.as_bytes()).into();
+        let index = self.entries.binary_search_by(|(candidate, _)| candidate.cmp(&digest)).ok()?;
+        Some(u64::from(self.entries[index].1))
+    }
+
+    pub fn contains(&self, password: &str) -> bool {
+        self.occurrences(password).is_some()
+    }
+
+    fn from_unsorted(mut entries: Vec<([u8; SHA1_LEN], u32)>) -> Self {
+        entries.sort_unstable_by_key(|(digest, _)| *digest);
+        entries.dedup_by(|(digest, count), (kept, kept_count)| {
+            let duplicate = digest == kept;
+            if duplicate {
+                *kept_count = kept_count.saturating_add(*count);
+            }
+            duplicate
+        });
+        entries.shrink_to_fit();
+
+        Self { entries }
+    }
+}
+
+fn saturate(count: u64) -> u32 {
+    u32::try_from(count).unwrap_or(u32::MAX)
+}
+
+fn decode_sha1_hex(hash: &str) -> Option<[u8; SHA1_LEN]> {
+    if hash.len() != SHA1_HEX_LEN || !hash.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let mut digest = [0u8; SHA1_LEN];
+    for (byte, pair) in digest.iter_mut().zip(hash.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(digest)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).collect()
+}
+
+/// Uppercase hex, as breach lists are published.
+pub fn sha1_hex(password: &str) -> String {
+    to_hex(&Sha1::digest(password.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha1_hex_is_uppercase() {
+        assert_eq!(sha1_hex("password"), "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8");
+    }
+
+    #[test]
+    fn bundled_list_holds_common_passwords() {
+        let bundled = BreachList::bundled();
+
+        assert!(bundled.contains("password") && bundled.contains("qwerty123"));
+        // Passwords are compared exactly.
+        assert!(!bundled.contains("Password") && !bundled.contains("correct horse battery staple"));
+    }
+
+    #[test]
+    fn ranges_are_looked_up_by_prefix() {
+        let bundled = BreachList::bundled();
+        let hash = sha1_hex("password");
+
+        let range: Vec<String> = bundled.range("5baa6").map(|(suffix, _)| suffix).collect();
+        assert!(range.iter().all(|suffix| suffix.len() == SHA1_HEX_LEN - PREFIX_LEN));
+        assert_eq!(range.iter().filter(|suffix| hash.ends_with(suffix.as_str())).count(), 1);
+        assert_eq!(bundled.range("00000").count(), 0);
+        assert_eq!(bundled.range("5BAA").count(), 0);
+        assert_eq!(bundled.range("zzzzz").count(), 0);
+    }
+
+    #[test]
+    fn parse_reads_counts_and_skips_empty_lines() {
+        let list = BreachList::parse(&format!("{}:10\n\n{}", sha1_hex("password"), sha1_hex("hunter2").to_lowercase())).unwrap();
+
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.occurrences("password"), Some(10));
+        assert_eq!(list.occurrences("hunter2"), Some(1));
+        assert_eq!(list.occurrences("letmein"), None);
+    }
+
+    #[test]
+    fn merge_sums_counts() {
+        let mut list = BreachList::parse(&format!("{}:10", sha1_hex("password"))).unwrap();
+        list.merge(BreachList::parse(&format!("{}:2\n{}:3", sha1_hex("password"), sha1_hex("hunter2"))).unwrap());
+
+        assert_eq!((list.len(), list.occurrences("password"), list.occurrences("hunter2")), (2, Some(12), Some(3)));
+    }
+
+    #[test]
+    fn read_streams_lines_and_sums_duplicates() {
+        let text = format!("{}:3\r\n{}:4\r\n{}:{}\r\n", sha1_hex("password"), sha1_hex("password"), sha1_hex("hunter2"), u64::MAX);
+        let list = BreachList::read(std::io::BufReader::with_capacity(16, text.as_bytes())).unwrap();
+
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.occurrences("password"), Some(7));
+        assert_eq!(list.occurrences("hunter2"), Some(u64::from(u32::MAX)));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_lines() {
+        let hash = sha1_hex("password");
+
+        assert!(matches!(BreachList::parse("password"), Err(BreachListError::InvalidLine(1))));
+        assert!(matches!(BreachList::parse(&format!("{hash}\n{hash}:many")), Err(BreachListError::InvalidLine(2))));
+        assert!(matches!(BreachList::parse(&hash[1..]), Err(BreachListError::InvalidLine(1))));
+        assert!(matches!(BreachList::parse(&format!("{}G", &hash[1..])), Err(BreachListError::InvalidLine(1))));
+        assert!(matches!(BreachList::parse(&format!("+{}", &hash[1..])), Err(BreachListError::InvalidLine(1))));
+        assert!(BreachList::parse("").unwrap().is_empty());
+    }
+}
@@ -32,7 +32,7 @@ pub enum CryptoError {
     #[error("utf8 decode error: {0}")]
     Utf8Error(#[source] std::string::FromUtf8Error),
 
-    #[error("data truncated (missing nonce)")]
+    #[error("data truncated (missing salt, nonce or tag)")]
     TruncatedInput,
 
     #[error("Key derivation error: {0:?}")]
@@ -41,10 +41,12 @@ pub enum CryptoError {
 
 pub trait CryptoAlgo: Aead + KeyInit {
     const NONCE_SIZE: usize;
+    const TAG_SIZE: usize;
 }
 
 impl<T> CryptoAlgo for T where  T: Aead + KeyInit {
     const NONCE_SIZE: usize = <T::NonceSize as Unsigned>::USIZE;
+    const TAG_SIZE: usize = <T::TagSize as Unsigned>::USIZE;
 }
 pub fn encrypt<Algo: CryptoAlgo>(plain: &[u8], key: &[u8], aad: &[u8]) -> Result<(Vec<u8>, Vec<u8>), CryptoError> {
     if key.len() < KEY_LENGTH {
@@ -62,7 +64,7 @@ pub fn encrypt<Algo: CryptoAlgo>(plain: &[u8], key: &[u8], aad: &[u8]) -> Result
     let nonce = Nonce::<Algo>::from_slice(nonce_bytes.as_slice());
 
     let ciphertext = cipher
-        .encrypt(&nonce, Payload { msg: plain, aad })
+        .encrypt(nonce, Payload { msg: plain, aad })
         .map_err(|_| CryptoError::EncryptionFailed)?;
 
     Ok((ciphertext, nonce_bytes))
@@ -100,23 +102,28 @@ pub fn decrypt<Algo: CryptoAlgo>(key: &[u8], ciphertext: &[u8], nonce: &[u8], aa
     let nonce = Nonce::<Algo>::from_slice(nonce);
 
     let plaintext = cipher
-        .decrypt(&nonce, Payload { msg: ciphertext, aad })
+        .decrypt(nonce, Payload { msg: ciphertext, aad })
         .map_err(|_| CryptoError::DecryptionFailed)?;
 
     Ok(plaintext)
 }
 
 pub fn decrypt_str<Algo: CryptoAlgo>(base64_cipher: &str, passphrase: &str, aad: &str, pepper: Option<&[u8]>) -> Result<String, CryptoError> {
+    decrypt_str_with::<Algo>(base64_cipher, passphrase, aad, &KeyDerivation::new(pepper)?)
+}
+
+/// [`decrypt_str`] with the key derived by `key_derivation`, for text sealed on a machine whose
+/// CPU count gave another lane count than this one.
+pub fn decrypt_str_with<Algo: CryptoAlgo>(base64_cipher: &str, passphrase: &str, aad: &str, key_derivation: &KeyDerivation) -> Result<String, CryptoError> {
     let decoded = BASE64_STANDARD.decode(base64_cipher).map_err(CryptoError::Base64DecodeFailed)?;
 
-    if decoded.len() < Algo::NONCE_SIZE {
+    if decoded.len() < SALT_LEN + Algo::NONCE_SIZE + Algo::TAG_SIZE {
         return Err(CryptoError::TruncatedInput);
     }
 
     let (salt, rest) = decoded.split_at(SALT_LEN);
     let (nonce, ciphertext) = rest.split_at(Algo::NONCE_SIZE);
 
-    let key_derivation = KeyDerivation::new(pepper)?;
     let key = key_derivation.derive(passphrase, salt)?;
 
     let plaintext_bytes = decrypt::<Algo>(key.as_ref(), ciphertext, nonce, aad.as_bytes())?;
@@ -276,4 +283,18 @@ mod tests {
 
         assert!(result.is_err());
     }
-}
+
+    #[test]
+    fn decrypt_str_short_input_fails() {
+        let key = "01234567890123456789012345678901";
+        let short = |len: usize| BASE64_STANDARD.encode(vec![7u8; len]);
+
+        // Anything shorter than salt, nonce and tag, with or without a whole salt.
+        for len in [0, 12, 24, 31, 32, 32 + 24, 32 + 24 + 15] {
+            assert!(matches!(decrypt_str::<XChaCha20Poly1305>(&short(len), key, "test", None), Err(CryptoError::TruncatedInput)), "{len} bytes");
+        }
+        for len in [0, 12, 31, 32 + 12, 32 + 12 + 15] {
+            assert!(matches!(decrypt_str::<Aes256Gcm>(&short(len), key, "test", None), Err(CryptoError::TruncatedInput)), "{len} bytes");
+        }
+    }
+}
\ No newline at end of file
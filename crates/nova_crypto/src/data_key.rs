@@ -0,0 +1,75 @@
+use rand::rngs::OsRng;
+use rand::TryRngCore;
+use zeroize::Zeroizing;
+use crate::crypto::CryptoError;
+use crate::envelope::{open, seal, EnvelopeError, SealOptions, Secret};
+use crate::key_derivation::KEY_LENGTH;
+use crate::sealed_box::{self, KeyPair, RecipientKey, SealedBoxError};
+
+const AAD: &[u8] = b"nova-data-key";
+
+/// A random key that encrypts data directly. It is only ever stored wrapped, i.e. sealed
+/// with a key encryption key derived from a passphrase or kept elsewhere.
+pub struct DataKey(Zeroizing<[u8; KEY_LENGTH]>);
+
+impl DataKey {
+    pub fn generate() -> Result<Self, CryptoError> {
+        let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+        OsRng.try_fill_bytes(key.as_mut()).map_err(CryptoError::NonceGenerationFailed)?;
+        Ok(Self(key))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; KEY_LENGTH] {
+        &self.0
+    }
+
+    /// Seals the key into an envelope that names `key_id`.
+    pub fn wrap(&self, secret: Secret, key_id: &str) -> Result<Vec<u8>, EnvelopeError> {
+        seal(self.0.as_ref(), secret, AAD, SealOptions { key_id: Some(key_id), ..SealOptions::default() })
+    }
+
+    pub fn unwrap(wrapped: &[u8], secret: Secret) -> Result<Self, EnvelopeError> {
+        let bytes = Zeroizing::new(open(wrapped, secret, AAD)?);
+
+        let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+        if bytes.len() != KEY_LENGTH {
+            return Err(CryptoError::InvalidKeyLength.into());
+        }
+        key.copy_from_slice(&bytes);
+        Ok(Self(key))
+    }
+
+    /// Seals the key for the public keys of other users, any of whom can then unwrap it with their key pair.
+    pub fn wrap_for(&self, recipients: &[RecipientKey]) -> Result<Vec<u8>, SealedBoxError> {
+        sealed_box::seal(self.0.as_ref(), recipients, AAD)
+    }
+
+    pub fn unwrap_with(wrapped: &[u8], key_pair: &KeyPair) -> Result<Self, SealedBoxError> {
+        let bytes = Zeroizing::new(sealed_box::open(wrapped, key_pair, AAD)?);
+
+        let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+        if bytes.len() != KEY_LENGTH {
+            return Err(CryptoError::InvalidKeyLength.into());
+        }
+        key.copy_from_slice(&bytes);
+        Ok(Self(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::inspect;
+
+    #[test]
+    fn wrap_unwrap() {
+        let key = DataKey::generate().unwrap();
+        let secret = Secret::Passphrase { passphrase: "correct horse battery staple", pepper: None };
+
+        let wrapped = key.wrap(secret, "project-1").unwrap();
+        assert_eq!(inspect(&wrapped).unwrap().key_id.as_deref(), Some("project-1"));
+
+        assert_eq!(DataKey::unwrap(&wrapped, secret).unwrap().as_bytes(), key.as_bytes());
+        assert!(DataKey::unwrap(&wrapped, Secret::Passphrase { passphrase: "wrong", pepper: None }).is_err());
+    }
+}
@@ -0,0 +1,529 @@
+use aes_gcm::Aes256Gcm;
+use base64::prelude::*;
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
+use zeroize::Zeroizing;
+use crate::crypto::{decrypt, decrypt_str_with, encrypt, CryptoAlgo, CryptoError};
+use crate::key_derivation::{Argon2Preset, KeyDerivation, KeyDerivationError, LEGACY_MAX_PARALLELISM};
+
+pub use crate::key_derivation::Argon2Params;
+
+/// First bytes of every envelope.
+pub const MAGIC: [u8; 4] = *b"NOVA";
+
+/// Bump whenever the header layout changes. [`open`] keeps reading every older version.
+pub const ENVELOPE_VERSION: u8 = 1;
+
+/// Key ids are stored with a one byte length.
+pub const MAX_KEY_ID_LEN: usize = u8::MAX as usize;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EnvelopeError {
+    #[error("not an envelope (magic bytes missing)")]
+    NotAnEnvelope,
+
+    #[error("unsupported envelope version {0} (newest supported is {ENVELOPE_VERSION})")]
+    UnsupportedVersion(u8),
+
+    #[error("unknown algorithm id {0}")]
+    UnknownAlgorithm(u8),
+
+    #[error("unknown key derivation id {0}")]
+    UnknownKdf(u8),
+
+    #[error("envelope header is truncated")]
+    TruncatedHeader,
+
+    #[error("key id is longer than {MAX_KEY_ID_LEN} bytes or not valid UTF-8")]
+    InvalidKeyId,
+
+    #[error("Argon2 parameters {0:?} exceed the supported limits")]
+    Argon2ParamsOutOfRange(Argon2Params),
+
+    #[error("envelope was sealed with a {expected}, got a {got}")]
+    SecretMismatch { expected: &'static str, got: &'static str },
+
+    #[error("base64 decode failed: {0}")]
+    Base64DecodeFailed(#[source] base64::DecodeError),
+
+    #[error("utf8 decode error: {0}")]
+    Utf8Error(#[source] std::string::FromUtf8Error),
+
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+
+    #[error("Key derivation error: {0:?}")]
+    KeyDerivation(#[from] KeyDerivationError),
+}
+
+/// The AEAD the payload is sealed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Algorithm {
+    XChaCha20Poly1305 = 1,
+    ChaCha20Poly1305 = 2,
+    Aes256Gcm = 3,
+}
+
+impl Algorithm {
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_id(id: u8) -> Result<Self, EnvelopeError> {
+        match id {
+            1 => Ok(Algorithm::XChaCha20Poly1305),
+            2 => Ok(Algorithm::ChaCha20Poly1305),
+            3 => Ok(Algorithm::Aes256Gcm),
+            _ => Err(EnvelopeError::UnknownAlgorithm(id)),
+        }
+    }
+
+    pub fn nonce_size(self) -> usize {
+        match self {
+            Algorithm::XChaCha20Poly1305 => XChaCha20Poly1305::NONCE_SIZE,
+            Algorithm::ChaCha20Poly1305 => ChaCha20Poly1305::NONCE_SIZE,
+            Algorithm::Aes256Gcm => Aes256Gcm::NONCE_SIZE,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::XChaCha20Poly1305 => "XChaCha20-Poly1305",
+            Algorithm::ChaCha20Poly1305 => "ChaCha20-Poly1305",
+            Algorithm::Aes256Gcm => "AES-256-GCM",
+        }
+    }
+
+    fn encrypt(self, plain: &[u8], key: &[u8], aad: &[u8]) -> Result<(Vec<u8>, Vec<u8>), CryptoError> {
+        match self {
+            Algorithm::XChaCha20Poly1305 => encrypt::<XChaCha20Poly1305>(plain, key, aad),
+            Algorithm::ChaCha20Poly1305 => encrypt::<ChaCha20Poly1305>(plain, key, aad),
+            Algorithm::Aes256Gcm => encrypt::<Aes256Gcm>(plain, key, aad),
+        }
+    }
+
+    fn decrypt(self, key: &[u8], ciphertext: &[u8], nonce: &[u8], aad: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        match self {
+            Algorithm::XChaCha20Poly1305 => decrypt::<XChaCha20Poly1305>(key, ciphertext, nonce, aad),
+            Algorithm::ChaCha20Poly1305 => decrypt::<ChaCha20Poly1305>(key, ciphertext, nonce, aad),
+            Algorithm::Aes256Gcm => decrypt::<Aes256Gcm>(key, ciphertext, nonce, aad),
+        }
+    }
+}
+
+/// How the payload key was obtained.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Kdf {
+    /// The caller passed the key itself.
+    None,
+    /// The key was derived from a passphrase.
+    Argon2id { params: Argon2Params, salt: Vec<u8> },
+}
+
+impl Kdf {
+    fn id(&self) -> u8 {
+        match self {
+            Kdf::None => 0,
+            Kdf::Argon2id { .. } => 1,
+        }
+    }
+}
+
+/// Everything needed to open an envelope apart from the secret. Authenticated together with the payload.
+///
+/// Layout: magic, version, algorithm id, KDF id, KDF params, key id length, key id, nonce, ciphertext.
+/// Integers are little endian.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub algorithm: Algorithm,
+    pub kdf: Kdf,
+    /// Names the key the envelope was sealed with, so the right one can be picked to open it.
+    pub key_id: Option<String>,
+}
+
+impl Header {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(64);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(self.version);
+        bytes.push(self.algorithm.id());
+        bytes.push(self.kdf.id());
+
+        if let Kdf::Argon2id { params, salt } = &self.kdf {
+            bytes.extend_from_slice(&params.memory_kib.to_le_bytes());
+            bytes.extend_from_slice(&params.iterations.to_le_bytes());
+            bytes.extend_from_slice(&params.parallelism.to_le_bytes());
+            bytes.push(salt.len() as u8);
+            bytes.extend_from_slice(salt);
+        }
+
+        let key_id = self.key_id.as_deref().unwrap_or_default();
+        bytes.push(key_id.len() as u8);
+        bytes.extend_from_slice(key_id.as_bytes());
+        bytes
+    }
+
+    /// Returns the header and its length in bytes.
+    fn decode(bytes: &[u8]) -> Result<(Self, usize), EnvelopeError> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if !bytes.starts_with(&MAGIC) {
+            return Err(EnvelopeError::NotAnEnvelope);
+        }
+        reader.take(MAGIC.len())?;
+
+        let version = reader.u8()?;
+        if version == 0 || version > ENVELOPE_VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(version));
+        }
+
+        let algorithm = Algorithm::from_id(reader.u8()?)?;
+
+        let kdf = match reader.u8()? {
+            0 => Kdf::None,
+            1 => {
+                let params = Argon2Params {
+                    memory_kib: reader.u32()?,
+                    iterations: reader.u32()?,
+                    parallelism: reader.u32()?,
+                };
+                if !params.is_within_limits() {
+                    return Err(EnvelopeError::Argon2ParamsOutOfRange(params));
+                }
+                let salt_len = reader.u8()? as usize;
+                Kdf::Argon2id { params, salt: reader.take(salt_len)?.to_vec() }
+            }
+            id => return Err(EnvelopeError::UnknownKdf(id)),
+        };
+
+        let key_id_len = reader.u8()? as usize;
+        let key_id = match key_id_len {
+            0 => None,
+            len => Some(String::from_utf8(reader.take(len)?.to_vec()).map_err(|_| EnvelopeError::InvalidKeyId)?),
+        };
+
+        Ok((Self { version, algorithm, kdf, key_id }, reader.position))
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], EnvelopeError> {
+        let end = self.position.checked_add(len).ok_or(EnvelopeError::TruncatedHeader)?;
+        let slice = self.bytes.get(self.position..end).ok_or(EnvelopeError::TruncatedHeader)?;
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, EnvelopeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, EnvelopeError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// What an envelope is sealed and opened with.
+#[derive(Clone, Copy)]
+pub enum Secret<'a> {
+    /// A key of [`KEY_LENGTH`](crate::key_derivation::KEY_LENGTH) bytes.
+    Key(&'a [u8]),
+    /// Stretched with Argon2id. Judging its strength is up to the caller.
+    Passphrase { passphrase: &'a str, pepper: Option<&'a [u8]> },
+}
+
+impl Secret<'_> {
+    fn kind(&self) -> &'static str {
+        match self {
+            Secret::Key(_) => "key",
+            Secret::Passphrase { .. } => "passphrase",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SealOptions<'a> {
+    pub algorithm: Algorithm,
+    pub key_id: Option<&'a str>,
+    /// Only used with a passphrase. Stored in the header, so changing it never breaks existing envelopes.
+    pub argon2: Argon2Params,
+}
+
+impl Default for SealOptions<'_> {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::XChaCha20Poly1305,
+            key_id: None,
+            argon2: Argon2Preset::default().params(),
+        }
+    }
+}
+
+/// Encrypts `plain` into a self-describing envelope. The header is authenticated along with `aad`.
+pub fn seal(plain: &[u8], secret: Secret, aad: &[u8], options: SealOptions) -> Result<Vec<u8>, EnvelopeError> {
+    if options.key_id.is_some_and(|key_id| key_id.len() > MAX_KEY_ID_LEN) {
+        return Err(EnvelopeError::InvalidKeyId);
+    }
+
+    let (kdf, key) = match secret {
+        Secret::Key(key) => (Kdf::None, Zeroizing::new(key.to_vec())),
+        Secret::Passphrase { passphrase, pepper } => {
+            if passphrase.is_empty() {
+                return Err(CryptoError::InvalidPassphraseLength.into());
+            }
+            // Never write what `open` would refuse to read.
+            if !options.argon2.is_within_limits() {
+                return Err(EnvelopeError::Argon2ParamsOutOfRange(options.argon2));
+            }
+
+            let key_derivation = KeyDerivation::from_params(pepper, options.argon2)?;
+            let salt = key_derivation.generate_salt()?;
+            let key = key_derivation.derive(passphrase, &salt)?;
+
+            (Kdf::Argon2id { params: options.argon2, salt: salt.to_vec() }, Zeroizing::new(key.to_vec()))
+        }
+    };
+
+    let header = Header {
+        version: ENVELOPE_VERSION,
+        algorithm: options.algorithm,
+        kdf,
+        key_id: options.key_id.map(str::to_string),
+    };
+    let mut sealed = header.encode();
+
+    let (ciphertext, nonce) = options.algorithm.encrypt(plain, &key, &associated_data(&sealed, aad))?;
+
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypts an envelope with the algorithm and key derivation its header names.
+pub fn open(sealed: &[u8], secret: Secret, aad: &[u8]) -> Result<Vec<u8>, EnvelopeError> {
+    let (header, header_len) = Header::decode(sealed)?;
+
+    let key = match (&header.kdf, secret) {
+        (Kdf::None, Secret::Key(key)) => Zeroizing::new(key.to_vec()),
+        (Kdf::Argon2id { params, salt }, Secret::Passphrase { passphrase, pepper }) => {
+            let key_derivation = KeyDerivation::from_params(pepper, *params)?;
+            Zeroizing::new(key_derivation.derive(passphrase, salt)?.to_vec())
+        }
+        (Kdf::None, secret) => return Err(EnvelopeError::SecretMismatch { expected: "key", got: secret.kind() }),
+        (Kdf::Argon2id { .. }, secret) => {
+            return Err(EnvelopeError::SecretMismatch { expected: "passphrase", got: secret.kind() });
+        }
+    };
+
+    let rest = &sealed[header_len..];
+    if rest.len() < header.algorithm.nonce_size() {
+        return Err(CryptoError::TruncatedInput.into());
+    }
+    let (nonce, ciphertext) = rest.split_at(header.algorithm.nonce_size());
+
+    let plain = header
+        .algorithm
+        .decrypt(&key, ciphertext, nonce, &associated_data(&sealed[..header_len], aad))?;
+    Ok(plain)
+}
+
+/// Reads the header without decrypting anything.
+pub fn inspect(sealed: &[u8]) -> Result<Header, EnvelopeError> {
+    Header::decode(sealed).map(|(header, _)| header)
+}
+
+pub fn is_envelope(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// [`seal`] for text, encoded as base64.
+pub fn seal_str(plain: &str, secret: Secret, aad: &str, options: SealOptions) -> Result<String, EnvelopeError> {
+    Ok(BASE64_STANDARD.encode(seal(plain.as_bytes(), secret, aad.as_bytes(), options)?))
+}
+
+/// [`open`] for text sealed with [`seal_str`].
+pub fn open_str(encoded: &str, secret: Secret, aad: &str) -> Result<String, EnvelopeError> {
+    let sealed = BASE64_STANDARD.decode(encoded).map_err(EnvelopeError::Base64DecodeFailed)?;
+    String::from_utf8(open(&sealed, secret, aad.as_bytes())?).map_err(EnvelopeError::Utf8Error)
+}
+
+/// Opens text written by [`crate::crypto::encrypt_str`], which carries no header. The algorithm has to be known up front.
+///
+/// The Argon2 lane count followed the CPU count of the machine that sealed and isn't stored either, so every
+/// count the legacy format could have used is tried, this machine's first. A wrong secret costs one derivation per count.
+pub fn open_legacy_str(encoded: &str, algorithm: Algorithm, passphrase: &str, aad: &str, pepper: Option<&[u8]>) -> Result<String, EnvelopeError> {
+    let local = KeyDerivation::default_parallelism();
+    let others = (1..=LEGACY_MAX_PARALLELISM).filter(|parallelism| *parallelism != local);
+
+    for parallelism in std::iter::once(local).chain(others) {
+        let key_derivation = KeyDerivation::from_params(pepper, Argon2Params::legacy(parallelism))?;
+        let result = match algorithm {
+            Algorithm::XChaCha20Poly1305 => decrypt_str_with::<XChaCha20Poly1305>(encoded, passphrase, aad, &key_derivation),
+            Algorithm::ChaCha20Poly1305 => decrypt_str_with::<ChaCha20Poly1305>(encoded, passphrase, aad, &key_derivation),
+            Algorithm::Aes256Gcm => decrypt_str_with::<Aes256Gcm>(encoded, passphrase, aad, &key_derivation),
+        };
+
+        match result {
+            Err(CryptoError::DecryptionFailed) => continue,
+            result => return Ok(result?),
+        }
+    }
+
+    Err(CryptoError::DecryptionFailed.into())
+}
+
+/// Opens an envelope, or falls back to the legacy format for text without a header.
+/// Returns whether the legacy format was read, so the caller can write the text back as an envelope.
+pub fn open_str_or_legacy(encoded: &str, legacy_algorithm: Algorithm, passphrase: &str, aad: &str, pepper: Option<&[u8]>) -> Result<(String, bool), EnvelopeError> {
+    let decoded = BASE64_STANDARD.decode(encoded).map_err(EnvelopeError::Base64DecodeFailed)?;
+
+    if is_envelope(&decoded) {
+        let plain = open(&decoded, Secret::Passphrase { passphrase, pepper }, aad.as_bytes())?;
+        return Ok((String::from_utf8(plain).map_err(EnvelopeError::Utf8Error)?, false));
+    }
+
+    Ok((open_legacy_str(encoded, legacy_algorithm, passphrase, aad, pepper)?, true))
+}
+
+fn associated_data(header: &[u8], aad: &[u8]) -> Vec<u8> {
+    let mut associated = Vec::with_capacity(header.len() + aad.len());
+    associated.extend_from_slice(header);
+    associated.extend_from_slice(aad);
+    associated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::encrypt_str;
+    use crate::key_derivation::KEY_LENGTH;
+
+    const PASSPHRASE: &str = "01234567890123456789012345678901";
+
+    #[test]
+    fn seal_open_with_every_algorithm() {
+        let key = [7u8; KEY_LENGTH];
+
+        for algorithm in [Algorithm::XChaCha20Poly1305, Algorithm::ChaCha20Poly1305, Algorithm::Aes256Gcm] {
+            let options = SealOptions { algorithm, key_id: Some("session-2024"), ..SealOptions::default() };
+            let sealed = seal(b"hello world", Secret::Key(&key), b"test", options).unwrap();
+
+            let header = inspect(&sealed).unwrap();
+            assert_eq!(header.algorithm, algorithm);
+            assert_eq!(header.kdf, Kdf::None);
+            assert_eq!(header.key_id.as_deref(), Some("session-2024"));
+
+            assert_eq!(open(&sealed, Secret::Key(&key), b"test").unwrap(), b"hello world");
+            assert!(open(&sealed, Secret::Key(&key), b"other").is_err());
+        }
+    }
+
+    #[test]
+    fn passphrase_envelope_records_kdf_params() {
+        let secret = Secret::Passphrase { passphrase: PASSPHRASE, pepper: None };
+        let argon2 = Argon2Params { memory_kib: 8 * 1024, iterations: 1, parallelism: 2 };
+        let sealed = seal_str("hello string", secret, "test", SealOptions { argon2, ..SealOptions::default() }).unwrap();
+
+        let header = inspect(&BASE64_STANDARD.decode(&sealed).unwrap()).unwrap();
+        assert!(matches!(header.kdf, Kdf::Argon2id { params, ref salt } if params == argon2 && salt.len() == 32));
+
+        assert_eq!(open_str(&sealed, secret, "test").unwrap(), "hello string");
+        assert!(matches!(
+            open_str(&sealed, Secret::Key(&[0u8; KEY_LENGTH]), "test"),
+            Err(EnvelopeError::SecretMismatch { expected: "passphrase", .. })
+        ));
+    }
+
+    #[test]
+    fn tampered_header_is_rejected() {
+        let key = [7u8; KEY_LENGTH];
+        let mut sealed = seal(b"hello", Secret::Key(&key), b"", SealOptions { algorithm: Algorithm::Aes256Gcm, key_id: Some("a"), ..SealOptions::default() }).unwrap();
+
+        // Renaming the key id changes the authenticated header.
+        let key_id_position = MAGIC.len() + 4;
+        assert_eq!(sealed[key_id_position], b'a');
+        sealed[key_id_position] = b'b';
+        assert!(matches!(open(&sealed, Secret::Key(&key), b""), Err(EnvelopeError::Crypto(CryptoError::DecryptionFailed))));
+
+        sealed[MAGIC.len()] = ENVELOPE_VERSION + 1;
+        assert!(matches!(open(&sealed, Secret::Key(&key), b""), Err(EnvelopeError::UnsupportedVersion(_))));
+
+        assert!(matches!(inspect(&sealed[..6]), Err(EnvelopeError::UnsupportedVersion(_) | EnvelopeError::TruncatedHeader)));
+        assert!(matches!(inspect(b"plain"), Err(EnvelopeError::NotAnEnvelope)));
+    }
+
+    #[test]
+    fn excessive_argon2_params_are_rejected_before_deriving() {
+        let secret = Secret::Passphrase { passphrase: PASSPHRASE, pepper: None };
+        let argon2 = Argon2Params { memory_kib: 8 * 1024, iterations: 1, parallelism: 1 };
+        let sealed = seal(b"hello", secret, b"", SealOptions { argon2, ..SealOptions::default() }).unwrap();
+
+        let params_position = MAGIC.len() + 3;
+        let with_params = |memory_kib: u32, iterations: u32, parallelism: u32| {
+            let mut crafted = sealed.clone();
+            crafted[params_position..params_position + 4].copy_from_slice(&memory_kib.to_le_bytes());
+            crafted[params_position + 4..params_position + 8].copy_from_slice(&iterations.to_le_bytes());
+            crafted[params_position + 8..params_position + 12].copy_from_slice(&parallelism.to_le_bytes());
+            crafted
+        };
+
+        let sensitive = Argon2Preset::Sensitive.params();
+        for (memory_kib, iterations, parallelism) in [
+            (u32::MAX, 1, 1),
+            (sensitive.memory_kib, sensitive.iterations + 1, 1),
+            (8 * 1024, u32::MAX, 1),
+            (8 * 1024, 1, 0),
+            (8 * 1024, 0, 1),
+        ] {
+            let crafted = with_params(memory_kib, iterations, parallelism);
+            assert!(matches!(open(&crafted, secret, b""), Err(EnvelopeError::Argon2ParamsOutOfRange(_))), "{memory_kib} {iterations} {parallelism}");
+            assert!(matches!(inspect(&crafted), Err(EnvelopeError::Argon2ParamsOutOfRange(_))));
+        }
+
+        let argon2 = Argon2Params { iterations: sensitive.iterations * 2, ..sensitive };
+        assert!(matches!(seal(b"hello", secret, b"", SealOptions { argon2, ..SealOptions::default() }), Err(EnvelopeError::Argon2ParamsOutOfRange(_))));
+    }
+
+    #[test]
+    fn legacy_ciphertext_is_still_readable() {
+        let legacy = encrypt_str::<ChaCha20Poly1305>("hello string", PASSPHRASE, "test", None).unwrap();
+
+        let (plain, was_legacy) = open_str_or_legacy(&legacy, Algorithm::ChaCha20Poly1305, PASSPHRASE, "test", None).unwrap();
+        assert_eq!((plain.as_str(), was_legacy), ("hello string", true));
+
+        let secret = Secret::Passphrase { passphrase: PASSPHRASE, pepper: None };
+        let sealed = seal_str(&plain, secret, "test", SealOptions::default()).unwrap();
+        let (plain, was_legacy) = open_str_or_legacy(&sealed, Algorithm::ChaCha20Poly1305, PASSPHRASE, "test", None).unwrap();
+        assert_eq!((plain.as_str(), was_legacy), ("hello string", false));
+    }
+
+    /// What [`encrypt_str`] writes on a machine with `parallelism` lanes.
+    fn legacy_from_another_machine(plain: &str, parallelism: u32) -> String {
+        let key_derivation = KeyDerivation::from_params(None, Argon2Params::legacy(parallelism)).unwrap();
+        let salt = key_derivation.generate_salt().unwrap();
+        let key = key_derivation.derive(PASSPHRASE, &salt).unwrap();
+        let (ciphertext, nonce) = encrypt::<XChaCha20Poly1305>(plain.as_bytes(), key.as_ref(), b"test").unwrap();
+
+        BASE64_STANDARD.encode([&salt[..], &nonce, &ciphertext].concat())
+    }
+
+    #[test]
+    fn legacy_ciphertext_from_another_lane_count_is_readable() {
+        let local = KeyDerivation::default_parallelism();
+        let parallelism = if local == LEGACY_MAX_PARALLELISM { 1 } else { local + 1 };
+        let legacy = legacy_from_another_machine("hello string", parallelism);
+
+        let (plain, was_legacy) = open_str_or_legacy(&legacy, Algorithm::XChaCha20Poly1305, PASSPHRASE, "test", None).unwrap();
+        assert_eq!((plain.as_str(), was_legacy), ("hello string", true));
+
+        let wrong = open_legacy_str(&legacy, Algorithm::XChaCha20Poly1305, "another passphrase of enough length", "test", None);
+        assert!(matches!(wrong, Err(EnvelopeError::Crypto(CryptoError::DecryptionFailed))));
+    }
+}
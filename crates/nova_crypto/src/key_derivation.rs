@@ -1,5 +1,7 @@
+use std::time::{Duration, Instant};
 use argon2::{Algorithm, Argon2, Params, Version};
 use rand::{rngs::OsRng, TryRngCore};
+use serde::{Deserialize, Serialize};
 use zeroize::{Zeroizing};
 
 #[derive(Debug, thiserror::Error)]
@@ -26,6 +28,111 @@ pub const PASSPHRASE_LENGTH: usize = 32;
 const DEFAULT_MEMORY_COST: u32 = 64 * 1024;
 const DEFAULT_TIME_COST: u32 = 3;
 
+/// The legacy format used one lane per CPU, up to this many.
+pub const LEGACY_MAX_PARALLELISM: u32 = 8;
+
+/// Upper bound for [`Argon2Params::calibrate`], so a coarse clock can't produce absurd costs.
+const MAX_CALIBRATED_ITERATIONS: u32 = 1024;
+
+/// Lanes an Argon2 derivation may use. They are computed one after another, so more only adds overhead.
+pub const MAX_ARGON2_PARALLELISM: u32 = 16;
+
+/// Argon2id cost parameters, stored so a ciphertext stays readable when the defaults change
+/// or it is opened on a machine with a different number of CPUs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Argon2Params {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Argon2Params {
+    /// What the legacy format derived its keys with. It never stored them, and `parallelism` was
+    /// the lane count of the machine that sealed, see [`KeyDerivation::new`].
+    pub fn legacy(parallelism: u32) -> Self {
+        Self { memory_kib: DEFAULT_MEMORY_COST, iterations: DEFAULT_TIME_COST, parallelism }
+    }
+
+    pub fn to_params(self) -> Result<Params, KeyDerivationError> {
+        Params::new(self.memory_kib, self.iterations, self.parallelism, Some(KEY_LENGTH))
+            .map_err(|e| KeyDerivationError::InvalidParams(e.to_string()))
+    }
+
+    /// Whether a derivation stays within the cost of [`Argon2Preset::Sensitive`]: no more memory, and
+    /// no more passes over it than it takes. Parameters read from a ciphertext are untrusted and must
+    /// be checked with this before anything is derived, or a crafted header can ask for terabytes.
+    pub fn is_within_limits(&self) -> bool {
+        let limit = Argon2Preset::Sensitive.params();
+
+        self.iterations > 0
+            && (1..=MAX_ARGON2_PARALLELISM).contains(&self.parallelism)
+            && self.memory_kib <= limit.memory_kib
+            && u64::from(self.memory_kib) * u64::from(self.iterations) <= u64::from(limit.memory_kib) * u64::from(limit.iterations)
+    }
+
+    /// Picks the number of iterations that makes one derivation with `memory_kib` take about `target`
+    /// on this machine. Memory is left to the caller; if a single pass already takes longer than
+    /// `target`, the result has one iteration and the caller has to settle for less memory.
+    pub fn calibrate(target: Duration, memory_kib: u32) -> Result<Self, KeyDerivationError> {
+        let single_pass = Self { memory_kib, iterations: 1, parallelism: 1 };
+        let key_derivation = KeyDerivation::new(None)?.with_params(single_pass.to_params()?);
+        let salt = key_derivation.generate_salt()?;
+
+        let started = Instant::now();
+        key_derivation.derive("calibration", &salt)?;
+        let elapsed = started.elapsed().max(Duration::from_micros(1));
+
+        // No more passes than `open` accepts, see [`Argon2Params::is_within_limits`].
+        let limit = Argon2Preset::Sensitive.params();
+        let max_iterations = (u64::from(limit.memory_kib) * u64::from(limit.iterations) / u64::from(memory_kib.max(1))) as u32;
+
+        let iterations = (target.as_secs_f64() / elapsed.as_secs_f64()).floor() as u32;
+        Ok(Self { iterations: iterations.clamp(1, MAX_CALIBRATED_ITERATIONS.min(max_iterations).max(1)), ..single_pass })
+    }
+}
+
+impl From<&Params> for Argon2Params {
+    fn from(params: &Params) -> Self {
+        Self {
+            memory_kib: params.m_cost(),
+            iterations: params.t_cost(),
+            parallelism: params.p_cost(),
+        }
+    }
+}
+
+impl From<Argon2Preset> for Argon2Params {
+    fn from(preset: Argon2Preset) -> Self {
+        preset.params()
+    }
+}
+
+/// Fixed costs after libsodium's limits. All of them use a single lane, which costs the same on
+/// every machine since lanes are computed one after another anyway.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Argon2Preset {
+    /// 64 MiB, 2 passes. For secrets that are entered or unlocked often, like the session store.
+    #[default]
+    Interactive,
+    /// 256 MiB, 3 passes.
+    Moderate,
+    /// 1 GiB, 4 passes. For secrets that are rarely opened and worth a few seconds.
+    Sensitive,
+}
+
+impl Argon2Preset {
+    pub fn params(self) -> Argon2Params {
+        let (memory_kib, iterations) = match self {
+            Self::Interactive => (64 * 1024, 2),
+            Self::Moderate => (256 * 1024, 3),
+            Self::Sensitive => (1024 * 1024, 4),
+        };
+
+        Argon2Params { memory_kib, iterations, parallelism: 1 }
+    }
+}
+
 #[derive(Clone)]
 pub struct KeyDerivation<'a> {
     params: Params,
@@ -33,16 +140,14 @@ pub struct KeyDerivation<'a> {
 }
 
 impl<'a> KeyDerivation<'a> {
+    /// Uses the parameters of the legacy format, which doesn't store them. Their lane count follows
+    /// the CPU count, so anything new should use [`KeyDerivation::from_params`] and store the params.
     pub fn new(pepper: Option<&'a [u8]>) -> Result<Self, KeyDerivationError> {
-        let params = Params::new(DEFAULT_MEMORY_COST, DEFAULT_TIME_COST, KeyDerivation::default_parallelism(), Some(KEY_LENGTH))
-            .map_err(|e| KeyDerivationError::InvalidParams(e.to_string()))?;
+        Self::from_params(pepper, Argon2Params::legacy(KeyDerivation::default_parallelism()))
+    }
 
-        Ok(
-            Self {
-                params,
-                pepper,
-            }
-        )
+    pub fn from_params(pepper: Option<&'a [u8]>, params: Argon2Params) -> Result<Self, KeyDerivationError> {
+        Ok(Self { params: params.to_params()?, pepper })
     }
 
     pub fn with_params(mut self, params: Params) -> Self {
@@ -50,6 +155,10 @@ impl<'a> KeyDerivation<'a> {
         self
     }
 
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
     pub fn generate_salt(&self) -> Result<[u8; SALT_LEN], KeyDerivationError> {
         let mut salt = [0u8; SALT_LEN];
         OsRng.try_fill_bytes(&mut salt).map_err(|_| KeyDerivationError::SaltGenerationFailed)?;
@@ -82,9 +191,9 @@ impl<'a> KeyDerivation<'a> {
         Ok(key)
     }
 
-    fn default_parallelism() -> u32 {
+    pub(crate) fn default_parallelism() -> u32 {
         let cpus = num_cpus::get() as u32;
-        cpus.min(8)
+        cpus.min(LEGACY_MAX_PARALLELISM)
     }
 }
 
@@ -154,6 +263,35 @@ mod tests {
         assert_ne!(key_without_pepper.as_ref(), key_with_pepper.as_ref());
     }
 
+    #[test]
+    fn presets_and_calibration() {
+        assert_eq!(Argon2Preset::default().params(), Argon2Params { memory_kib: 64 * 1024, iterations: 2, parallelism: 1 });
+        assert!(Argon2Preset::Sensitive.params().memory_kib > Argon2Preset::Moderate.params().memory_kib);
+
+        let quick = Argon2Params::calibrate(Duration::ZERO, 1024).unwrap();
+        assert_eq!(quick, Argon2Params { memory_kib: 1024, iterations: 1, parallelism: 1 });
+
+        let slow = Argon2Params::calibrate(Duration::from_millis(200), 1024).unwrap();
+        assert!(slow.iterations >= quick.iterations);
+        assert!(KeyDerivation::from_params(None, slow).is_ok());
+    }
+
+    #[test]
+    fn limits_admit_every_preset_and_calibration() {
+        for preset in [Argon2Preset::Interactive, Argon2Preset::Moderate, Argon2Preset::Sensitive] {
+            assert!(preset.params().is_within_limits(), "{preset:?}");
+        }
+
+        // Calibration trades memory for passes, which stays within the same amount of work.
+        let calibrated = Argon2Params { memory_kib: 1024, iterations: MAX_CALIBRATED_ITERATIONS, parallelism: 1 };
+        assert!(calibrated.is_within_limits());
+
+        let sensitive = Argon2Preset::Sensitive.params();
+        assert!(!Argon2Params { memory_kib: sensitive.memory_kib * 2, iterations: 1, ..sensitive }.is_within_limits());
+        assert!(!Argon2Params { parallelism: 0, ..sensitive }.is_within_limits());
+        assert!(!Argon2Params { parallelism: MAX_ARGON2_PARALLELISM + 1, ..sensitive }.is_within_limits());
+    }
+
     #[test]
     fn short_salt_rejected() {
         let key_derivation = KeyDerivation::new(None).unwrap();
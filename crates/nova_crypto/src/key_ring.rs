@@ -0,0 +1,377 @@
+use std::collections::BTreeMap;
+use base64::prelude::*;
+use zeroize::Zeroizing;
+use crate::envelope::{inspect, open, seal, Algorithm, Argon2Params, EnvelopeError, Kdf, SealOptions, Secret};
+use crate::key_derivation::Argon2Preset;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeyRingError {
+    #[error("no key with id {0:?} in the key ring")]
+    UnknownKeyId(String),
+
+    #[error("a key with id {0:?} is already in the key ring")]
+    DuplicateKeyId(String),
+
+    #[error("the primary key {0:?} can't be removed")]
+    RemovePrimary(String),
+
+    #[error("no key in the key ring opens this envelope")]
+    NoMatchingKey,
+
+    #[error("base64 decode failed: {0}")]
+    Base64DecodeFailed(#[source] base64::DecodeError),
+
+    #[error("utf8 decode error: {0}")]
+    Utf8Error(#[source] std::string::FromUtf8Error),
+
+    #[error(transparent)]
+    Envelope(#[from] EnvelopeError),
+}
+
+/// One key of a [`KeyRing`], either raw or a passphrase that is stretched on use.
+pub enum KeyMaterial {
+    Key(Zeroizing<Vec<u8>>),
+    Passphrase(Zeroizing<String>),
+}
+
+impl KeyMaterial {
+    pub fn key(key: &[u8]) -> Self {
+        Self::Key(Zeroizing::new(key.to_vec()))
+    }
+
+    pub fn passphrase(passphrase: &str) -> Self {
+        Self::Passphrase(Zeroizing::new(passphrase.to_string()))
+    }
+
+    fn secret<'a>(&'a self, pepper: Option<&'a [u8]>) -> Secret<'a> {
+        match self {
+            Self::Key(key) => Secret::Key(key),
+            Self::Passphrase(passphrase) => Secret::Passphrase { passphrase, pepper },
+        }
+    }
+}
+
+/// Several keys told apart by the key id in the envelope header. New envelopes are always sealed
+/// with the primary key, every other key only opens what was sealed before it was replaced.
+///
+/// Rotating is [`KeyRing::rotate`] followed by [`KeyRing::reencrypt`] of every stored envelope,
+/// after which the old keys can be removed.
+pub struct KeyRing {
+    primary: String,
+    keys: BTreeMap<String, KeyMaterial>,
+    algorithm: Algorithm,
+    argon2: Argon2Params,
+    pepper: Option<Vec<u8>>,
+}
+
+impl KeyRing {
+    pub fn new(key_id: &str, material: KeyMaterial) -> Self {
+        Self {
+            primary: key_id.to_string(),
+            keys: BTreeMap::from([(key_id.to_string(), material)]),
+            algorithm: Algorithm::XChaCha20Poly1305,
+            argon2: Argon2Preset::default().params(),
+            pepper: None,
+        }
+    }
+
+    /// The algorithm new envelopes are sealed with. Opening follows whatever the header names.
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// The cost of stretching passphrase keys for new envelopes.
+    pub fn with_argon2(mut self, argon2: Argon2Params) -> Self {
+        self.argon2 = argon2;
+        self
+    }
+
+    /// Mixed into every passphrase key.
+    pub fn with_pepper(mut self, pepper: &[u8]) -> Self {
+        self.pepper = Some(pepper.to_vec());
+        self
+    }
+
+    pub fn primary_id(&self) -> &str {
+        &self.primary
+    }
+
+    /// Every key id, the primary included.
+    pub fn key_ids(&self) -> impl Iterator<Item = &str> {
+        self.keys.keys().map(String::as_str)
+    }
+
+    pub fn contains(&self, key_id: &str) -> bool {
+        self.keys.contains_key(key_id)
+    }
+
+    /// Adds a key that only opens existing envelopes.
+    pub fn add_decrypt_only(&mut self, key_id: &str, material: KeyMaterial) -> Result<(), KeyRingError> {
+        if self.keys.contains_key(key_id) {
+            return Err(KeyRingError::DuplicateKeyId(key_id.to_string()));
+        }
+
+        self.keys.insert(key_id.to_string(), material);
+        Ok(())
+    }
+
+    /// Makes a new key the primary. The previous primary stays as a decrypt-only key.
+    pub fn rotate(&mut self, key_id: &str, material: KeyMaterial) -> Result<(), KeyRingError> {
+        self.add_decrypt_only(key_id, material)?;
+        self.primary = key_id.to_string();
+        Ok(())
+    }
+
+    /// Makes a key that is already in the ring the primary.
+    pub fn set_primary(&mut self, key_id: &str) -> Result<(), KeyRingError> {
+        if !self.keys.contains_key(key_id) {
+            return Err(KeyRingError::UnknownKeyId(key_id.to_string()));
+        }
+
+        self.primary = key_id.to_string();
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key_id: &str) -> Result<KeyMaterial, KeyRingError> {
+        if key_id == self.primary {
+            return Err(KeyRingError::RemovePrimary(key_id.to_string()));
+        }
+
+        self.keys.remove(key_id).ok_or_else(|| KeyRingError::UnknownKeyId(key_id.to_string()))
+    }
+
+    /// Seals with the primary key and names it in the header.
+    pub fn seal(&self, plain: &[u8], aad: &[u8]) -> Result<Vec<u8>, KeyRingError> {
+        let options = SealOptions { algorithm: self.algorithm, key_id: Some(&self.primary), argon2: self.argon2 };
+        Ok(seal(plain, self.material(&self.primary)?.secret(self.pepper.as_deref()), aad, options)?)
+    }
+
+    /// Opens with the key the header names. Envelopes without a key id, sealed before key ids were
+    /// used, are tried against every key, primary first.
+    pub fn open(&self, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, KeyRingError> {
+        if let Some(key_id) = inspect(sealed)?.key_id {
+            return Ok(open(sealed, self.material(&key_id)?.secret(self.pepper.as_deref()), aad)?);
+        }
+
+        let primary = self.keys.get_key_value(&self.primary);
+        let others = self.keys.iter().filter(|(key_id, _)| **key_id != self.primary);
+
+        for (_, material) in primary.into_iter().chain(others) {
+            if let Ok(plain) = open(sealed, material.secret(self.pepper.as_deref()), aad) {
+                return Ok(plain);
+            }
+        }
+
+        Err(KeyRingError::NoMatchingKey)
+    }
+
+    /// Whether the envelope is sealed with anything but the primary key, algorithm and Argon2
+    /// parameters, so raising the cost with [`KeyRing::with_argon2`] upgrades passphrase envelopes too.
+    pub fn needs_reencrypt(&self, sealed: &[u8]) -> Result<bool, KeyRingError> {
+        let header = inspect(sealed)?;
+        let outdated_kdf = matches!(header.kdf, Kdf::Argon2id { params, .. } if params != self.argon2);
+        Ok(header.key_id.as_deref() != Some(self.primary.as_str()) || header.algorithm != self.algorithm || outdated_kdf)
+    }
+
+    /// Migrates an envelope to the primary key. Returns `None` if it already is.
+    pub fn reencrypt(&self, sealed: &[u8], aad: &[u8]) -> Result<Option<Vec<u8>>, KeyRingError> {
+        if !self.needs_reencrypt(sealed)? {
+            return Ok(None);
+        }
+
+        let plain = Zeroizing::new(self.open(sealed, aad)?);
+        self.seal(&plain, aad).map(Some)
+    }
+
+    /// [`KeyRing::seal`] for text, encoded as base64.
+    pub fn seal_str(&self, plain: &str, aad: &str) -> Result<String, KeyRingError> {
+        Ok(BASE64_STANDARD.encode(self.seal(plain.as_bytes(), aad.as_bytes())?))
+    }
+
+    pub fn open_str(&self, encoded: &str, aad: &str) -> Result<String, KeyRingError> {
+        let sealed = BASE64_STANDARD.decode(encoded).map_err(KeyRingError::Base64DecodeFailed)?;
+        String::from_utf8(self.open(&sealed, aad.as_bytes())?).map_err(KeyRingError::Utf8Error)
+    }
+
+    pub fn reencrypt_str(&self, encoded: &str, aad: &str) -> Result<Option<String>, KeyRingError> {
+        let sealed = BASE64_STANDARD.decode(encoded).map_err(KeyRingError::Base64DecodeFailed)?;
+        Ok(self.reencrypt(&sealed, aad.as_bytes())?.map(|sealed| BASE64_STANDARD.encode(sealed)))
+    }
+
+    fn material(&self, key_id: &str) -> Result<&KeyMaterial, KeyRingError> {
+        self.keys.get(key_id).ok_or_else(|| KeyRingError::UnknownKeyId(key_id.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_derivation::KEY_LENGTH;
+
+    const FAST: Argon2Params = Argon2Params { memory_kib: 1024, iterations: 1, parallelism: 1 };
+
+    fn ring() -> KeyRing {
+        KeyRing::new("2024", KeyMaterial::key(&[1u8; KEY_LENGTH]))
+    }
+
+    fn key_id(sealed: &[u8]) -> Option<String> {
+        inspect(sealed).unwrap().key_id
+    }
+
+    #[test]
+    fn seals_with_the_primary_key() {
+        let mut ring = ring();
+        assert_eq!(key_id(&ring.seal(b"secret", b"aad").unwrap()).as_deref(), Some("2024"));
+
+        ring.rotate("2025", KeyMaterial::key(&[2u8; KEY_LENGTH])).unwrap();
+        assert_eq!(ring.primary_id(), "2025");
+        assert_eq!(ring.key_ids().collect::<Vec<_>>(), vec!["2024", "2025"]);
+        assert_eq!(key_id(&ring.seal(b"secret", b"aad").unwrap()).as_deref(), Some("2025"));
+    }
+
+    #[test]
+    fn rotated_keys_still_open_what_they_sealed() {
+        let mut ring = ring();
+        let old = ring.seal(b"secret", b"aad").unwrap();
+
+        ring.rotate("2025", KeyMaterial::key(&[2u8; KEY_LENGTH])).unwrap();
+        assert_eq!(ring.open(&old, b"aad").unwrap(), b"secret");
+        assert!(ring.open(&old, b"other").is_err());
+    }
+
+    #[test]
+    fn envelopes_without_key_id_are_tried_against_every_key() {
+        let mut ring = ring();
+        let unnamed = seal(b"unnamed", Secret::Key(&[1u8; KEY_LENGTH]), b"aad", SealOptions::default()).unwrap();
+
+        ring.rotate("2025", KeyMaterial::key(&[2u8; KEY_LENGTH])).unwrap();
+        assert_eq!(ring.open(&unnamed, b"aad").unwrap(), b"unnamed");
+
+        let foreign = seal(b"foreign", Secret::Key(&[9u8; KEY_LENGTH]), b"aad", SealOptions::default()).unwrap();
+        assert!(matches!(ring.open(&foreign, b"aad"), Err(KeyRingError::NoMatchingKey)));
+    }
+
+    #[test]
+    fn unknown_key_id_is_reported() {
+        let other = KeyRing::new("elsewhere", KeyMaterial::key(&[1u8; KEY_LENGTH]));
+        let sealed = other.seal(b"secret", b"aad").unwrap();
+
+        assert!(matches!(ring().open(&sealed, b"aad"), Err(KeyRingError::UnknownKeyId(key_id)) if key_id == "elsewhere"));
+    }
+
+    #[test]
+    fn reencrypt_moves_envelopes_to_the_primary_key() {
+        let mut ring = ring();
+        let old = ring.seal(b"secret", b"aad").unwrap();
+        let unnamed = seal(b"unnamed", Secret::Key(&[1u8; KEY_LENGTH]), b"aad", SealOptions::default()).unwrap();
+        ring.rotate("2025", KeyMaterial::key(&[2u8; KEY_LENGTH])).unwrap();
+
+        for (sealed, plain) in [(old, &b"secret"[..]), (unnamed, &b"unnamed"[..])] {
+            assert!(ring.needs_reencrypt(&sealed).unwrap());
+            let migrated = ring.reencrypt(&sealed, b"aad").unwrap().unwrap();
+            assert_eq!(key_id(&migrated).as_deref(), Some("2025"));
+            assert_eq!(ring.open(&migrated, b"aad").unwrap(), plain);
+            assert!(ring.reencrypt(&migrated, b"aad").unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn reencrypt_follows_a_new_algorithm() {
+        let sealed = ring().seal(b"secret", b"aad").unwrap();
+        let ring = ring().with_algorithm(Algorithm::Aes256Gcm);
+
+        let migrated = ring.reencrypt(&sealed, b"aad").unwrap().unwrap();
+        assert_eq!(inspect(&migrated).unwrap().algorithm, Algorithm::Aes256Gcm);
+        assert!(!ring.needs_reencrypt(&migrated).unwrap());
+    }
+
+    #[test]
+    fn reencrypt_follows_new_argon2_params() {
+        let stronger = Argon2Params { memory_kib: 2048, ..FAST };
+        let sealed = KeyRing::new("user", KeyMaterial::passphrase("correct horse")).with_argon2(FAST).seal(b"secret", b"aad").unwrap();
+        let ring = KeyRing::new("user", KeyMaterial::passphrase("correct horse")).with_argon2(stronger);
+
+        assert!(ring.needs_reencrypt(&sealed).unwrap());
+        let migrated = ring.reencrypt(&sealed, b"aad").unwrap().unwrap();
+        assert!(matches!(inspect(&migrated).unwrap().kdf, Kdf::Argon2id { params, .. } if params == stronger));
+        assert_eq!(ring.open(&migrated, b"aad").unwrap(), b"secret");
+        assert!(!ring.needs_reencrypt(&migrated).unwrap());
+        assert!(ring.reencrypt(&migrated, b"aad").unwrap().is_none());
+    }
+
+    #[test]
+    fn removed_keys_no_longer_open_anything() {
+        let mut ring = ring();
+        let old = ring.seal(b"secret", b"aad").unwrap();
+        let unnamed = seal(b"unnamed", Secret::Key(&[1u8; KEY_LENGTH]), b"aad", SealOptions::default()).unwrap();
+        ring.rotate("2025", KeyMaterial::key(&[2u8; KEY_LENGTH])).unwrap();
+        let migrated = ring.reencrypt(&old, b"aad").unwrap().unwrap();
+
+        ring.remove("2024").unwrap();
+        assert!(!ring.contains("2024"));
+        assert_eq!(ring.open(&migrated, b"aad").unwrap(), b"secret");
+        assert!(matches!(ring.open(&old, b"aad"), Err(KeyRingError::UnknownKeyId(_))));
+        assert!(matches!(ring.open(&unnamed, b"aad"), Err(KeyRingError::NoMatchingKey)));
+    }
+
+    #[test]
+    fn primary_and_unknown_keys_cant_be_removed() {
+        let mut ring = ring();
+
+        assert!(matches!(ring.remove("2024"), Err(KeyRingError::RemovePrimary(_))));
+        assert!(matches!(ring.remove("2023"), Err(KeyRingError::UnknownKeyId(_))));
+        assert!(ring.contains("2024"));
+    }
+
+    #[test]
+    fn key_ids_are_unique() {
+        let mut ring = ring();
+
+        assert!(matches!(ring.rotate("2024", KeyMaterial::key(&[2u8; KEY_LENGTH])), Err(KeyRingError::DuplicateKeyId(_))));
+        assert!(matches!(ring.add_decrypt_only("2024", KeyMaterial::key(&[2u8; KEY_LENGTH])), Err(KeyRingError::DuplicateKeyId(_))));
+        assert_eq!(ring.primary_id(), "2024");
+
+        // the first key is still the one in use
+        let sealed = ring.seal(b"secret", b"aad").unwrap();
+        assert_eq!(open(&sealed, Secret::Key(&[1u8; KEY_LENGTH]), b"aad").unwrap(), b"secret");
+    }
+
+    #[test]
+    fn set_primary_needs_a_known_key() {
+        let mut ring = ring();
+        ring.add_decrypt_only("2025", KeyMaterial::key(&[2u8; KEY_LENGTH])).unwrap();
+        assert_eq!(ring.primary_id(), "2024");
+
+        assert!(matches!(ring.set_primary("2026"), Err(KeyRingError::UnknownKeyId(_))));
+        ring.set_primary("2025").unwrap();
+        assert_eq!(key_id(&ring.seal(b"secret", b"aad").unwrap()).as_deref(), Some("2025"));
+    }
+
+    #[test]
+    fn passphrase_keys_use_the_pepper() {
+        let ring = KeyRing::new("user", KeyMaterial::passphrase("correct horse")).with_argon2(FAST).with_pepper(b"pepper");
+        let sealed = ring.seal(b"secret", b"aad").unwrap();
+        assert_eq!(ring.open(&sealed, b"aad").unwrap(), b"secret");
+
+        let unpeppered = KeyRing::new("user", KeyMaterial::passphrase("correct horse"));
+        assert!(unpeppered.open(&sealed, b"aad").is_err());
+    }
+
+    #[test]
+    fn text_helpers_round_trip() {
+        let mut ring = ring();
+        let old = ring.seal_str("secret", "aad").unwrap();
+
+        assert_eq!(ring.open_str(&old, "aad").unwrap(), "secret");
+        assert!(ring.reencrypt_str(&old, "aad").unwrap().is_none());
+        assert!(matches!(ring.open_str("not base64!", "aad"), Err(KeyRingError::Base64DecodeFailed(_))));
+
+        ring.rotate("2025", KeyMaterial::key(&[2u8; KEY_LENGTH])).unwrap();
+        let migrated = ring.reencrypt_str(&old, "aad").unwrap().unwrap();
+        assert_eq!(ring.open_str(&migrated, "aad").unwrap(), "secret");
+
+        let binary = BASE64_STANDARD.encode(ring.seal(&[0xff, 0xfe], b"aad").unwrap());
+        assert!(matches!(ring.open_str(&binary, "aad"), Err(KeyRingError::Utf8Error(_))));
+    }
+}
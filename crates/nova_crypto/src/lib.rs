@@ -2,3 +2,12 @@ pub mod crypto;
 pub mod key_derivation;
 pub mod password_generator;
 pub mod jwt;
+
+pub mod envelope;
+pub mod stream;
+pub mod data_key;
+pub mod key_ring;
+pub mod sealed_box;
+pub mod signing;
+pub mod breach_list;
+pub mod password_strength;
\ No newline at end of file
@@ -1,4 +1,10 @@
-use rand::seq::{IteratorRandom, SliceRandom};
+use rand::seq::{IndexedMutRandom, IndexedRandom, IteratorRandom, SliceRandom};
+
+/// Characters that are easily mistaken for one another when read or typed off paper.
+pub const AMBIGUOUS_CHARACTERS: &str = "Il1O0o|`'\"";
+
+/// The BIP39 English list. Every word is 3 to 8 letters and unique in its first four.
+const ENGLISH_WORDS: &str = include_str!("wordlists/english.txt");
 
 pub struct PasswordSpecification {
     pub include_upper: bool,
@@ -14,9 +20,37 @@ pub struct PasswordCharacters {
     pub symbols: String
 }
 
+/// How many characters of each included class a password must at least contain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClassMinimums {
+    pub upper: usize,
+    pub lower: usize,
+    pub numbers: usize,
+    pub symbols: usize
+}
+
+impl Default for ClassMinimums {
+    fn default() -> Self {
+        Self { upper: 1, lower: 1, numbers: 1, symbols: 1 }
+    }
+}
+
+/// A generated password or passphrase with an estimate of how hard it is to guess, in bits.
+///
+/// The estimate assumes the attacker knows exactly how the value was generated, so it only
+/// counts the random choices made, never the length alone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedPassword {
+    pub value: String,
+    pub entropy_bits: f64
+}
+
 pub struct PasswordGenerator {
     pw_spec: PasswordSpecification,
-    pub chars: PasswordCharacters
+    pub chars: PasswordCharacters,
+    pub minimums: ClassMinimums,
+    /// Never generated, whatever the class.
+    pub excluded: String
 }
 
 impl Default for PasswordGenerator {
@@ -41,65 +75,219 @@ impl PasswordGenerator {
                 lower: String::from("abcdefghijklmnopqrstuvwxyz"),
                 numbers: String::from("0123456789"),
                 symbols: String::from("!@#$%^&*()")
-            }
+            },
+            minimums: ClassMinimums::default(),
+            excluded: String::new()
         }
     }
 
+    pub fn with_minimums(mut self, minimums: ClassMinimums) -> Self {
+        self.minimums = minimums;
+        self
+    }
+
+    pub fn with_symbols(mut self, symbols: &str) -> Self {
+        self.chars.symbols = symbols.to_string();
+        self
+    }
+
+    pub fn exclude(mut self, chars: &str) -> Self {
+        self.excluded.push_str(chars);
+        self
+    }
+
+    /// Leaves out [`AMBIGUOUS_CHARACTERS`].
+    pub fn exclude_ambiguous(self) -> Self {
+        self.exclude(AMBIGUOUS_CHARACTERS)
+    }
+
     pub fn generate(&self, len: usize) -> Option<String> {
+        self.generate_password(len).map(|generated| generated.value)
+    }
+
+    /// Like [`PasswordGenerator::generate`], with the entropy of the result. `None` if `len` can't
+    /// fit the minimums, or exclusions leave an included class without characters.
+    pub fn generate_password(&self, len: usize) -> Option<GeneratedPassword> {
         if len < self.min_size() {
             return None;
         }
 
-        let mut chars = String::new();
-        let mut password = String::with_capacity(len);
+        let classes = self.classes()?;
+        let chars: Vec<char> = classes.iter().flat_map(|(class, _)| class.iter().copied()).collect();
+        let mut password = Vec::with_capacity(len);
         let mut rng = rand::rng();
-        let spec = &self.pw_spec;
 
-        if spec.include_upper {
-            chars.push_str(&self.chars.upper);
-            password.push(self.chars.upper.chars().choose(&mut rng)?);
+        for (class, minimum) in &classes {
+            for _ in 0..*minimum {
+                password.push(*class.choose(&mut rng)?);
+            }
         }
 
-        if spec.include_lower {
-            chars.push_str(&self.chars.lower);
-            password.push(self.chars.lower.chars().choose(&mut rng)?);
+        for _ in password.len()..len {
+            password.push(*chars.choose(&mut rng)?);
         }
 
-        if spec.include_numbers {
-            chars.push_str(&self.chars.numbers);
-            password.push(self.chars.numbers.chars().choose(&mut rng)?);
-        }
+        password.shuffle(&mut rng);
 
-        if spec.include_symbols {
-            chars.push_str(&self.chars.symbols);
-            password.push(self.chars.symbols.chars().choose(&mut rng)?);
-        }
+        let generated: String = password.iter().collect();
 
-        for _ in password.len()..len {
-            password.push(chars.chars().choose(&mut rng)?);
+        match generated.is_empty() {
+            true => None,
+            false => Some(GeneratedPassword { value: generated, entropy_bits: self.entropy_bits(len)? })
         }
+    }
 
-        let mut shuffled: Vec<_> = password.chars().collect();
-        shuffled.shuffle(&mut rng);
+    /// Bits of entropy of a password of `len` characters. The characters placed to meet the
+    /// minimums only count for their own class, and their shuffled positions aren't counted.
+    pub fn entropy_bits(&self, len: usize) -> Option<f64> {
+        let classes = self.classes()?;
+        let pool: usize = classes.iter().map(|(class, _)| class.len()).sum();
+        let forced: usize = classes.iter().map(|(_, minimum)| minimum).sum();
 
-        let generated: String = shuffled.iter().collect();
+        let forced_bits: f64 = classes.iter().map(|(class, minimum)| *minimum as f64 * (class.len() as f64).log2()).sum();
+        Some(forced_bits + len.saturating_sub(forced) as f64 * (pool as f64).log2())
+    }
 
-        match generated.is_empty() {
+    /// Every included class without the excluded characters, with its minimum.
+    fn classes(&self) -> Option<Vec<(Vec<char>, usize)>> {
+        let spec = &self.pw_spec;
+        let classes = [
+            (spec.include_upper, &self.chars.upper, self.minimums.upper),
+            (spec.include_lower, &self.chars.lower, self.minimums.lower),
+            (spec.include_numbers, &self.chars.numbers, self.minimums.numbers),
+            (spec.include_symbols, &self.chars.symbols, self.minimums.symbols),
+        ];
+
+        let mut included = Vec::new();
+        for (_, chars, minimum) in classes.into_iter().filter(|(include, _, _)| *include) {
+            let mut class: Vec<char> = chars.chars().filter(|c| !self.excluded.contains(*c)).collect();
+            class.sort_unstable();
+            class.dedup();
+
+            if class.is_empty() {
+                return None;
+            }
+            included.push((class, minimum));
+        }
+
+        match included.is_empty() {
             true => None,
-            false => Some(generated)
+            false => Some(included)
         }
     }
 
     fn min_size(&self) -> usize {
         [
-            self.pw_spec.include_upper,
-            self.pw_spec.include_lower,
-            self.pw_spec.include_numbers,
-            self.pw_spec.include_symbols,
+            (self.pw_spec.include_upper, self.minimums.upper),
+            (self.pw_spec.include_lower, self.minimums.lower),
+            (self.pw_spec.include_numbers, self.minimums.numbers),
+            (self.pw_spec.include_symbols, self.minimums.symbols),
         ]
         .into_iter()
-        .filter(|expression| *expression)
-        .count()
+        .filter(|(included, _)| *included)
+        .map(|(_, minimum)| minimum)
+        .sum()
+    }
+}
+
+/// Builds memorable passphrases from randomly chosen words, diceware style.
+pub struct PassphraseGenerator {
+    words: Vec<String>,
+    pub separator: String,
+    /// Capitalizes every word. Adds no entropy, only satisfies policies that want upper case.
+    pub capitalize: bool,
+    /// Appends a random digit to one random word.
+    pub include_number: bool
+}
+
+impl Default for PassphraseGenerator {
+    fn default() -> Self {
+        Self {
+            words: ENGLISH_WORDS.lines().map(str::to_string).collect(),
+            separator: String::from("-"),
+            capitalize: false,
+            include_number: false
+        }
+    }
+}
+
+impl PassphraseGenerator {
+    /// Uses a custom word list. Duplicates are dropped, since they would only inflate the entropy
+    /// estimate. `None` if fewer than two distinct words remain.
+    pub fn with_words<S: AsRef<str>>(words: &[S]) -> Option<Self> {
+        let mut words: Vec<String> = words
+            .iter()
+            .map(|word| word.as_ref().trim().to_string())
+            .filter(|word| !word.is_empty())
+            .collect();
+        words.sort_unstable();
+        words.dedup();
+
+        match words.len() < 2 {
+            true => None,
+            false => Some(Self { words, ..Self::default() })
+        }
+    }
+
+    pub fn with_separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    pub fn with_capitalization(mut self, capitalize: bool) -> Self {
+        self.capitalize = capitalize;
+        self
+    }
+
+    pub fn with_number(mut self, include_number: bool) -> Self {
+        self.include_number = include_number;
+        self
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn generate(&self, word_count: usize) -> Option<GeneratedPassword> {
+        if word_count == 0 {
+            return None;
+        }
+
+        let mut rng = rand::rng();
+        let mut words = Vec::with_capacity(word_count);
+
+        for _ in 0..word_count {
+            let word = self.words.choose(&mut rng)?;
+            words.push(match self.capitalize {
+                true => capitalize(word),
+                false => word.clone()
+            });
+        }
+
+        if self.include_number {
+            let digit = ('0'..='9').choose(&mut rng)?;
+            words.choose_mut(&mut rng)?.push(digit);
+        }
+
+        Some(GeneratedPassword { value: words.join(&self.separator), entropy_bits: self.entropy_bits(word_count) })
+    }
+
+    /// Bits of entropy of a passphrase of `word_count` words.
+    pub fn entropy_bits(&self, word_count: usize) -> f64 {
+        let words = word_count as f64 * (self.words.len() as f64).log2();
+
+        match self.include_number && word_count > 0 {
+            true => words + 10f64.log2() + (word_count as f64).log2(),
+            false => words
+        }
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
     }
 }
 
@@ -214,4 +402,42 @@ mod tests {
         assert!(contains_any(&password, &password_generator.chars.numbers));
         assert!(contains_none(&password, &password_generator.chars.symbols));
     }
+
+    #[test]
+    fn test_minimums_and_exclusions() {
+        let password_generator = PasswordGenerator::default()
+            .with_minimums(ClassMinimums { upper: 1, lower: 1, numbers: 3, symbols: 2 })
+            .with_symbols("-_")
+            .exclude_ambiguous();
+
+        assert!(password_generator.generate(6).is_none());
+
+        let generated = password_generator.generate_password(12).unwrap();
+        assert_eq!(generated.value.len(), 12);
+        assert!(generated.value.chars().filter(|c| c.is_ascii_digit()).count() >= 3);
+        assert!(generated.value.chars().filter(|c| "-_".contains(*c)).count() >= 2);
+        assert!(contains_none(&generated.value, AMBIGUOUS_CHARACTERS));
+        // 24 upper and lower, 8 digits and 2 symbols are left, the 5 unconstrained characters draw from all 58
+        let expected = 2.0 * 24f64.log2() + 3.0 * 8f64.log2() + 2.0 + 5.0 * 58f64.log2();
+        assert!((generated.entropy_bits - expected).abs() < 1e-9);
+
+        assert!(PasswordGenerator::default().exclude("0123456789").generate(10).is_none());
+    }
+
+    #[test]
+    fn test_passphrases() {
+        let passphrase_generator = PassphraseGenerator::default().with_capitalization(true).with_number(true);
+        assert_eq!(passphrase_generator.word_count(), 2048);
+
+        let generated = passphrase_generator.generate(6).unwrap();
+        assert_eq!(generated.value.split('-').count(), 6);
+        assert!(generated.value.split('-').all(|word| word.starts_with(|c: char| c.is_ascii_uppercase())));
+        assert_eq!(generated.value.chars().filter(|c| c.is_ascii_digit()).count(), 1);
+        assert!((generated.entropy_bits - (66.0 + 10f64.log2() + 6f64.log2())).abs() < 1e-9);
+
+        let coin = PassphraseGenerator::with_words(&["heads", "tails", "heads"]).unwrap().with_separator(" ");
+        assert_eq!(coin.entropy_bits(8), 8.0);
+        assert!(PassphraseGenerator::with_words(&["only"]).is_none());
+        assert!(PassphraseGenerator::default().generate(0).is_none());
+    }
 }
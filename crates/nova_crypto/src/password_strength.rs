@@ -0,0 +1,780 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::LazyLock;
+use chrono::{Datelike, Utc};
+use crate::breach_list::BreachList;
+
+const COMMON_PASSWORDS: &str = include_str!("wordlists/common_passwords.txt");
+const ENGLISH_WORDS: &str = include_str!("wordlists/english.txt");
+
+/// The score new account passwords need, enforced by the authenticator and checked ahead by clients.
+pub const MIN_ACCOUNT_SCORE: u8 = 3;
+
+/// Only this many characters are matched against patterns, every further one counts as random.
+const MAX_ANALYZED_LEN: usize = 100;
+
+/// Guesses per character for anything no pattern explains.
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_GUESSES_MULTI_CHAR: f64 = 50.0;
+
+/// Years close to now are guessed first. Even the current year is worth this many guesses.
+const MIN_YEAR_SPACE: i32 = 20;
+const DATE_SEPARATORS: &[char] = &[' ', '-', '/', '.', '_', '\\'];
+
+const KEYBOARD_ROWS: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm", "qwertzuiop", "yxcvbnm", "azertyuiop"];
+/// Roughly the number of places a keyboard walk can start in, times its two directions.
+const KEYBOARD_STARTS: f64 = 80.0;
+
+/// Substitutions tried when looking up a word. Ambiguous characters are tried with every letter.
+const L33T_TABLE: &[(char, &[char])] = &[
+    ('4', &['a']),
+    ('@', &['a']),
+    ('8', &['b']),
+    ('(', &['c']),
+    ('3', &['e']),
+    ('6', &['g']),
+    ('1', &['i', 'l']),
+    ('!', &['i']),
+    ('|', &['i', 'l']),
+    ('0', &['o']),
+    ('$', &['s']),
+    ('5', &['s']),
+    ('7', &['t']),
+    ('+', &['t']),
+    ('2', &['z']),
+];
+
+static DICTIONARIES: LazyLock<[Dictionary; 2]> = LazyLock::new(|| {
+    [
+        Dictionary::ranked(DictionaryKind::CommonPasswords, COMMON_PASSWORDS.lines()),
+        // unordered, so every word is as likely as any other
+        Dictionary::uniform(DictionaryKind::EnglishWords, ENGLISH_WORDS.lines()),
+    ]
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DictionaryKind {
+    CommonPasswords,
+    EnglishWords,
+    /// The username and anything else the service knows about the user.
+    UserInputs,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Dictionary { dictionary: DictionaryKind, word: String, rank: usize, l33t: bool, reversed: bool },
+    Sequence { ascending: bool },
+    Keyboard,
+    Repeat { base: String, count: usize },
+    Date { year: i32, separator: bool },
+    Year { year: i32 },
+    BruteForce,
+}
+
+/// A part of the password and how many guesses it takes to find it, given its pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub pattern: Pattern,
+    pub token: String,
+    /// Character positions, the end exclusive.
+    pub start: usize,
+    pub end: usize,
+    pub guesses: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Feedback {
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+impl fmt::Display for Feedback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<&str> = self.warning.iter().chain(&self.suggestions).map(String::as_str).collect();
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrengthEstimate {
+    /// 0 (guessable in a few attempts) to 4 (safe against an offline attack on a slow hash).
+    pub score: u8,
+    /// Estimated number of guesses an attacker needs, as a power of ten.
+    pub guesses_log10: f64,
+    /// How often the password was seen in breaches, if it is on the breach list.
+    pub breach_count: Option<u64>,
+    pub feedback: Feedback,
+    /// The patterns that explain the password best, in order.
+    pub sequence: Vec<Match>,
+}
+
+impl StrengthEstimate {
+    pub fn is_breached(&self) -> bool {
+        self.breach_count.is_some()
+    }
+
+    /// Whether the password is good enough for a policy that wants at least `min_score`.
+    /// A breached password never is, however it scores.
+    pub fn meets(&self, min_score: u8) -> bool {
+        !self.is_breached() && self.score >= min_score
+    }
+}
+
+/// Estimates how many guesses a password takes, zxcvbn style: the password is split into the
+/// dictionary words, sequences, keyboard walks, repeats and dates that make it cheapest to
+/// guess, and the rest is counted as random characters.
+pub struct StrengthEstimator {
+    breach_list: Option<BreachList>,
+}
+
+impl Default for StrengthEstimator {
+    fn default() -> Self {
+        Self::new(Some(BreachList::bundled()))
+    }
+}
+
+impl StrengthEstimator {
+    pub fn new(breach_list: Option<BreachList>) -> Self {
+        Self { breach_list }
+    }
+
+    /// `user_inputs` are words an attacker would try first, like the username or email address.
+    pub fn estimate(&self, password: &str, user_inputs: &[&str]) -> StrengthEstimate {
+        let user_dictionary = Dictionary::user_inputs(user_inputs);
+        let (guesses_log10, sequence) = most_guessable(password, &user_dictionary);
+
+        let score = match guesses_log10 {
+            g if g < 3.0 => 0,
+            g if g < 6.0 => 1,
+            g if g < 8.0 => 2,
+            g if g < 10.0 => 3,
+            _ => 4,
+        };
+
+        let breach_count = self.breach_list.as_ref().and_then(|list| list.occurrences(password));
+        let feedback = feedback(score, &sequence, breach_count.is_some(), password.is_empty());
+
+        StrengthEstimate { score, guesses_log10, breach_count, feedback, sequence }
+    }
+}
+
+struct Dictionary {
+    kind: DictionaryKind,
+    ranks: HashMap<String, usize>,
+    max_len: usize,
+}
+
+impl Dictionary {
+    fn ranked<'a>(kind: DictionaryKind, words: impl Iterator<Item = &'a str>) -> Self {
+        let mut ranks = HashMap::new();
+        for word in words.map(|word| word.trim().to_lowercase()).filter(|word| !word.is_empty()) {
+            let rank = ranks.len() + 1;
+            ranks.entry(word).or_insert(rank);
+        }
+
+        let max_len = ranks.keys().map(|word| word.chars().count()).max().unwrap_or_default();
+        Self { kind, ranks, max_len }
+    }
+
+    fn uniform<'a>(kind: DictionaryKind, words: impl Iterator<Item = &'a str>) -> Self {
+        let mut dictionary = Self::ranked(kind, words);
+        let size = dictionary.ranks.len();
+        dictionary.ranks.values_mut().for_each(|rank| *rank = size);
+        dictionary
+    }
+
+    /// Every input as a whole and split into its alphanumeric parts, so `jane.doe@example.org`
+    /// also matches `jane` and `doe`.
+    fn user_inputs(inputs: &[&str]) -> Self {
+        let parts = inputs.iter().flat_map(|input| {
+            std::iter::once(*input).chain(input.split(|c: char| !c.is_alphanumeric()).filter(|part| part.chars().count() >= 3))
+        });
+        Self::ranked(DictionaryKind::UserInputs, parts)
+    }
+
+    fn rank(&self, word: &str) -> Option<usize> {
+        self.ranks.get(word).copied()
+    }
+}
+
+/// The cheapest way to guess `password`, as the log10 of guesses and the matches that add up to it.
+fn most_guessable(password: &str, user_dictionary: &Dictionary) -> (f64, Vec<Match>) {
+    let all: Vec<char> = password.chars().collect();
+    let chars = &all[..all.len().min(MAX_ANALYZED_LEN)];
+    let unanalyzed = (all.len() - chars.len()) as f64 * BRUTEFORCE_CARDINALITY.log10();
+
+    let mut matches = Vec::new();
+    for dictionary in DICTIONARIES.iter().chain(std::iter::once(user_dictionary)) {
+        dictionary_matches(chars, dictionary, &mut matches);
+    }
+    sequence_matches(chars, &mut matches);
+    keyboard_matches(chars, &mut matches);
+    repeat_matches(chars, user_dictionary, &mut matches);
+    date_matches(chars, &mut matches);
+
+    let (guesses_log10, sequence) = cheapest_sequence(chars, &matches);
+    (guesses_log10 + unanalyzed, sequence)
+}
+
+/// Splits the password into the matches, or runs of random characters, with the fewest guesses
+/// in total. Guessing `l` parts also means guessing their order, hence the `l!`.
+fn cheapest_sequence(chars: &[char], matches: &[Match]) -> (f64, Vec<Match>) {
+    #[derive(Clone, Copy)]
+    struct Step {
+        guesses_log10: f64,
+        start: usize,
+        /// `None` for a run of random characters.
+        matched: Option<usize>,
+    }
+
+    let n = chars.len();
+    if n == 0 {
+        return (0.0, Vec::new());
+    }
+
+    // best[k][l]: the cheapest way to cover the first k characters with l parts
+    let mut best: Vec<Vec<Option<Step>>> = vec![vec![None; n + 1]; n + 1];
+    best[0][0] = Some(Step { guesses_log10: 0.0, start: 0, matched: None });
+
+    let relax = |best: &mut Vec<Vec<Option<Step>>>, start: usize, end: usize, guesses: f64, matched: Option<usize>| {
+        for parts in 1..=end {
+            let Some(previous) = best[start][parts - 1] else { continue };
+            let candidate = previous.guesses_log10 + guesses.log10();
+
+            if best[end][parts].is_none_or(|step| candidate < step.guesses_log10) {
+                best[end][parts] = Some(Step { guesses_log10: candidate, start, matched });
+            }
+        }
+    };
+
+    for end in 1..=n {
+        for (index, matched) in matches.iter().enumerate().filter(|(_, matched)| matched.end == end) {
+            // a match that is only part of the password is never cheaper than a few random characters
+            let guesses = match matched.end - matched.start {
+                len if len == n => matched.guesses,
+                1 => matched.guesses.max(MIN_GUESSES_SINGLE_CHAR),
+                _ => matched.guesses.max(MIN_GUESSES_MULTI_CHAR),
+            };
+            relax(&mut best, matched.start, end, guesses, Some(index));
+        }
+
+        for start in 0..end {
+            relax(&mut best, start, end, bruteforce_guesses(end - start), None);
+        }
+    }
+
+    let (mut parts, guesses_log10) = (1..=n)
+        .filter_map(|parts| best[n][parts].map(|step| (parts, step.guesses_log10 + log10_factorial(parts))))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("a run of random characters always covers the password");
+
+    let mut sequence = Vec::with_capacity(parts);
+    let mut end = n;
+    while parts > 0 {
+        let step = best[end][parts].expect("every step was reached from the one before");
+        sequence.push(match step.matched {
+            Some(index) => matches[index].clone(),
+            None => Match {
+                pattern: Pattern::BruteForce,
+                token: chars[step.start..end].iter().collect(),
+                start: step.start,
+                end,
+                guesses: bruteforce_guesses(end - step.start),
+            },
+        });
+        end = step.start;
+        parts -= 1;
+    }
+    sequence.reverse();
+
+    (guesses_log10, sequence)
+}
+
+fn bruteforce_guesses(len: usize) -> f64 {
+    let min = if len == 1 { MIN_GUESSES_SINGLE_CHAR + 1.0 } else { MIN_GUESSES_MULTI_CHAR + 1.0 };
+    BRUTEFORCE_CARDINALITY.powi(len as i32).max(min)
+}
+
+fn dictionary_matches(chars: &[char], dictionary: &Dictionary, matches: &mut Vec<Match>) {
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+
+    for start in 0..lower.len() {
+        for end in start + 1..=lower.len().min(start + dictionary.max_len) {
+            let token: String = chars[start..end].iter().collect();
+            let word: String = lower[start..end].iter().collect();
+            let mut push = |word: String, rank: usize, l33t_variations: f64, reversed: bool| {
+                let guesses = rank as f64 * uppercase_variations(&token) * l33t_variations * if reversed { 2.0 } else { 1.0 };
+                let pattern = Pattern::Dictionary { dictionary: dictionary.kind, word, rank, l33t: l33t_variations > 1.0, reversed };
+                matches.push(Match { pattern, token: token.clone(), start, end, guesses });
+            };
+
+            if let Some(rank) = dictionary.rank(&word) {
+                push(word.clone(), rank, 1.0, false);
+            }
+
+            let reversed: String = word.chars().rev().collect();
+            if reversed != word && let Some(rank) = dictionary.rank(&reversed) {
+                push(reversed, rank, 1.0, true);
+            }
+
+            for (unleeted, variations) in unleet(&lower[start..end]) {
+                if let Some(rank) = dictionary.rank(&unleeted) {
+                    push(unleeted, rank, variations, false);
+                }
+            }
+        }
+    }
+}
+
+/// Every reading of `token` with its l33t characters replaced, and the guesses the substitutions add.
+fn unleet(token: &[char]) -> Vec<(String, f64)> {
+    let leet: Vec<(char, &[char])> = L33T_TABLE
+        .iter()
+        .filter(|(leet, _)| token.contains(leet))
+        .map(|(leet, letters)| (*leet, *letters))
+        .collect();
+
+    if leet.is_empty() {
+        return Vec::new();
+    }
+
+    // one letter per l33t character, trying each alternative of the ambiguous ones
+    let mut readings: Vec<Vec<(char, char)>> = vec![Vec::new()];
+    for (leet, letters) in leet {
+        readings = readings
+            .into_iter()
+            .flat_map(|reading| letters.iter().map(move |letter| [reading.clone(), vec![(leet, *letter)]].concat()))
+            .collect();
+    }
+
+    readings
+        .into_iter()
+        .map(|substitutions| {
+            let word: String = token
+                .iter()
+                .map(|c| substitutions.iter().find(|(leet, _)| leet == c).map_or(*c, |(_, letter)| *letter))
+                .collect();
+
+            let variations = substitutions
+                .iter()
+                .map(|(leet, letter)| {
+                    let subbed = token.iter().filter(|c| *c == leet).count();
+                    let unsubbed = token.iter().filter(|c| *c == letter).count();
+                    match unsubbed {
+                        0 => 2.0,
+                        _ => (1..=subbed.min(unsubbed)).map(|i| n_choose_k(subbed + unsubbed, i)).sum(),
+                    }
+                })
+                .product();
+
+            (word, variations)
+        })
+        .collect()
+}
+
+/// Lowercase, capitalized and all caps words are tried first, anything else costs a lot more.
+fn uppercase_variations(token: &str) -> f64 {
+    let upper = token.chars().filter(|c| c.is_uppercase()).count();
+    let lower = token.chars().filter(|c| c.is_lowercase()).count();
+
+    let first_upper = token.chars().next().is_some_and(char::is_uppercase);
+    let last_upper = token.chars().last().is_some_and(char::is_uppercase);
+
+    match (upper, lower) {
+        (0, _) => 1.0,
+        (_, 0) => 2.0,
+        (1, _) if first_upper || last_upper => 2.0,
+        _ => (1..=upper.min(lower)).map(|i| n_choose_k(upper + lower, i)).sum(),
+    }
+}
+
+/// Runs like `abcd`, `9753` or `ZYX` with the same step between neighbors.
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    fn class(c: char) -> Option<u8> {
+        match c {
+            'a'..='z' => Some(0),
+            'A'..='Z' => Some(1),
+            '0'..='9' => Some(2),
+            _ => None,
+        }
+    }
+
+    let mut start = 0;
+    while start + 2 < chars.len() {
+        let delta = chars[start + 1] as i64 - chars[start] as i64;
+        let mut end = start + 2;
+        while end < chars.len() && chars[end] as i64 - chars[end - 1] as i64 == delta {
+            end += 1;
+        }
+
+        let same_class = class(chars[start]).is_some() && chars[start..end].iter().all(|c| class(*c) == class(chars[start]));
+        if end - start >= 3 && (1..=5).contains(&delta.abs()) && same_class {
+            let first = chars[start];
+            let base = match first {
+                'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9' => 4.0,
+                '0'..='9' => 10.0,
+                _ => 26.0,
+            };
+            let ascending = delta > 0;
+            let guesses = base * (end - start) as f64 * if ascending { 1.0 } else { 2.0 };
+
+            let token = chars[start..end].iter().collect();
+            matches.push(Match { pattern: Pattern::Sequence { ascending }, token, start, end, guesses });
+        }
+
+        start = end - 1;
+    }
+}
+
+/// Walks along a row of the keyboard, like `qwerty` or `lkjhg`.
+fn keyboard_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let lower: String = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let lower: Vec<char> = lower.chars().collect();
+
+    let rows: Vec<String> = KEYBOARD_ROWS.iter().flat_map(|row| [row.to_string(), row.chars().rev().collect()]).collect();
+
+    for start in 0..lower.len() {
+        let longest = (start + 4..=lower.len())
+            .rev()
+            .find(|end| {
+                let walk: String = lower[start..*end].iter().collect();
+                rows.iter().any(|row| row.contains(&walk))
+            });
+
+        if let Some(end) = longest {
+            let token: String = chars[start..end].iter().collect();
+            let guesses = KEYBOARD_STARTS * (end - start) as f64 * uppercase_variations(&token);
+            matches.push(Match { pattern: Pattern::Keyboard, token, start, end, guesses });
+        }
+    }
+}
+
+/// The same character or group repeated, like `aaaa` or `abcabcabc`, which is barely harder
+/// than the group itself.
+fn repeat_matches(chars: &[char], user_dictionary: &Dictionary, matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start < chars.len() {
+        let mut longest: Option<(usize, usize)> = None;
+
+        for base_len in 1..=(chars.len() - start) / 2 {
+            let base = &chars[start..start + base_len];
+            let count = 1 + chars[start + base_len..]
+                .chunks_exact(base_len)
+                .take_while(|chunk| *chunk == base)
+                .count();
+
+            if count >= 2 && longest.is_none_or(|(len, times)| base_len * count > len * times) {
+                longest = Some((base_len, count));
+            }
+        }
+
+        let Some((base_len, count)) = longest else {
+            start += 1;
+            continue;
+        };
+
+        let base: String = chars[start..start + base_len].iter().collect();
+        let (base_guesses_log10, _) = most_guessable(&base, user_dictionary);
+        let end = start + base_len * count;
+
+        matches.push(Match {
+            pattern: Pattern::Repeat { base, count },
+            token: chars[start..end].iter().collect(),
+            start,
+            end,
+            guesses: 10f64.powf(base_guesses_log10) * count as f64,
+        });
+        start = end;
+    }
+}
+
+/// Years from 1900 to 2050, and dates with or without separators, day, month and year in any
+/// common order.
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let reference_year = Utc::now().year();
+    let year_space = |year: i32| (year - reference_year).abs().max(MIN_YEAR_SPACE) as f64;
+
+    for start in 0..chars.len() {
+        for end in start + 4..=chars.len().min(start + 10) {
+            let token: String = chars[start..end].iter().collect();
+
+            if end - start == 4 && let Ok(year) = token.parse::<i32>() && (1900..=2050).contains(&year) {
+                matches.push(Match { pattern: Pattern::Year { year }, token: token.clone(), start, end, guesses: year_space(year) });
+            }
+
+            if let Some((year, separator)) = parse_date(&token) {
+                let guesses = year_space(year) * 365.0 * if separator { 4.0 } else { 1.0 };
+                matches.push(Match { pattern: Pattern::Date { year, separator }, token, start, end, guesses });
+            }
+        }
+    }
+}
+
+/// The year of `token` if it reads as a valid date, and whether it uses a separator.
+fn parse_date(token: &str) -> Option<(i32, bool)> {
+    let parts: Vec<&str> = match token.chars().find(|c| DATE_SEPARATORS.contains(c)) {
+        Some(separator) => token.split(separator).collect(),
+        None if token.chars().all(|c| c.is_ascii_digit()) => match token.len() {
+            6 => vec![&token[..2], &token[2..4], &token[4..]],
+            8 => return [(&token[..4], &token[4..6], &token[6..]), (&token[..2], &token[2..4], &token[4..])]
+                .into_iter()
+                .find_map(|(a, b, c)| date_year(a, b, c))
+                .map(|year| (year, false)),
+            _ => return None,
+        },
+        None => return None,
+    };
+
+    let separator = parts.len() == 3 && token.chars().any(|c| DATE_SEPARATORS.contains(&c));
+    if parts.len() != 3 || parts.iter().any(|part| part.is_empty() || part.len() > 4 || !part.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+
+    date_year(parts[0], parts[1], parts[2]).map(|year| (year, separator))
+}
+
+/// Tries year first, then year last with day and month either way round.
+fn date_year(a: &str, b: &str, c: &str) -> Option<i32> {
+    fn year(part: &str) -> Option<i32> {
+        let value: i32 = part.parse().ok()?;
+        match part.len() {
+            2 if value > 50 => Some(1900 + value),
+            2 => Some(2000 + value),
+            4 if (1000..=2050).contains(&value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn day_month(first: &str, second: &str) -> bool {
+        let (Ok(first), Ok(second)) = (first.parse::<u32>(), second.parse::<u32>()) else { return false };
+        let valid = |day: u32, month: u32| (1..=31).contains(&day) && (1..=12).contains(&month);
+        valid(first, second) || valid(second, first)
+    }
+
+    if a.len() == 4 && b.len() <= 2 && c.len() <= 2 && day_month(b, c) {
+        return year(a);
+    }
+    if a.len() <= 2 && b.len() <= 2 && day_month(a, b) && let Some(year) = year(c) {
+        return Some(year);
+    }
+    if a.len() == 2 && b.len() <= 2 && c.len() <= 2 && day_month(b, c) {
+        return year(a);
+    }
+    None
+}
+
+fn feedback(score: u8, sequence: &[Match], breached: bool, empty: bool) -> Feedback {
+    if breached {
+        return Feedback {
+            warning: Some("This password has appeared in a data breach.".to_string()),
+            suggestions: vec!["Choose a password you have never used anywhere else.".to_string()],
+        };
+    }
+
+    if empty {
+        return Feedback {
+            warning: None,
+            suggestions: vec![
+                "Use a few words, avoid common phrases.".to_string(),
+                "No need for symbols, digits, or uppercase letters.".to_string(),
+            ],
+        };
+    }
+
+    if score >= 3 {
+        return Feedback::default();
+    }
+
+    let mut suggestions = vec!["Add another word or two. Uncommon words are better.".to_string()];
+    let Some(longest) = sequence.iter().max_by_key(|matched| matched.end - matched.start) else {
+        return Feedback { warning: None, suggestions };
+    };
+
+    let warning = match &longest.pattern {
+        Pattern::Dictionary { dictionary, rank, l33t, reversed, .. } => {
+            if longest.token.chars().next().is_some_and(char::is_uppercase) {
+                suggestions.push("Capitalization doesn't help very much.".to_string());
+            }
+            if *reversed {
+                suggestions.push("Reversed words aren't much harder to guess.".to_string());
+            }
+            if *l33t {
+                suggestions.push("Predictable substitutions like '@' instead of 'a' don't help very much.".to_string());
+            }
+
+            let whole = sequence.len() == 1;
+            match dictionary {
+                DictionaryKind::CommonPasswords if whole && *rank <= 10 => Some("This is a top-10 common password."),
+                DictionaryKind::CommonPasswords if whole && *rank <= 100 => Some("This is a top-100 common password."),
+                DictionaryKind::CommonPasswords if whole => Some("This is a very common password."),
+                DictionaryKind::CommonPasswords => Some("This is similar to a commonly used password."),
+                DictionaryKind::EnglishWords if whole => Some("A word by itself is easy to guess."),
+                DictionaryKind::UserInputs => Some("Avoid using your username or other personal details."),
+                DictionaryKind::EnglishWords => None,
+            }
+        }
+        Pattern::Sequence { .. } => {
+            suggestions.push("Avoid sequences.".to_string());
+            Some("Sequences like abc or 6543 are easy to guess.")
+        }
+        Pattern::Keyboard => {
+            suggestions.push("Use a longer keyboard pattern with more turns.".to_string());
+            Some("Straight rows of keys are easy to guess.")
+        }
+        Pattern::Repeat { base, .. } => {
+            suggestions.push("Avoid repeated words and characters.".to_string());
+            match base.chars().count() {
+                1 => Some("Repeats like \"aaa\" are easy to guess."),
+                _ => Some("Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\"."),
+            }
+        }
+        Pattern::Date { .. } | Pattern::Year { .. } => {
+            suggestions.push("Avoid dates and years that are associated with you.".to_string());
+            Some("Dates are often easy to guess.")
+        }
+        Pattern::BruteForce => None,
+    };
+
+    Feedback { warning: warning.map(str::to_string), suggestions }
+}
+
+fn n_choose_k(n: usize, k: usize) -> f64 {
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+fn log10_factorial(n: usize) -> f64 {
+    (2..=n).map(|i| (i as f64).log10()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(estimate: &StrengthEstimate) -> Vec<&Pattern> {
+        estimate.sequence.iter().map(|matched| &matched.pattern).collect()
+    }
+
+    #[test]
+    fn trivial_passwords_score_zero() {
+        let estimator = StrengthEstimator::default();
+
+        let weak = estimator.estimate("a", &[]);
+        assert_eq!(weak.score, 0);
+        assert!(!weak.meets(1) && !weak.meets(MIN_ACCOUNT_SCORE));
+
+        let empty = estimator.estimate("", &[]);
+        assert_eq!((empty.score, empty.guesses_log10), (0, 0.0));
+        assert!(empty.sequence.is_empty() && !empty.feedback.suggestions.is_empty());
+    }
+
+    #[test]
+    fn dictionary_words_and_l33t() {
+        let estimator = StrengthEstimator::default();
+
+        let common = estimator.estimate("password", &[]);
+        assert!(matches!(
+            patterns(&common)[..],
+            [Pattern::Dictionary { dictionary: DictionaryKind::CommonPasswords, l33t: false, reversed: false, .. }]
+        ));
+        assert_eq!(common.score, 0);
+
+        let leet = estimator.estimate("P4ssw0rd", &[]);
+        assert!(matches!(&patterns(&leet)[..], [Pattern::Dictionary { l33t: true, word, .. }] if word == "password"));
+        assert!(leet.score <= 1);
+
+        let reversed = estimator.estimate("drowssap", &[]);
+        assert!(matches!(patterns(&reversed)[..], [Pattern::Dictionary { reversed: true, .. }]));
+        assert!(reversed.score <= 1);
+    }
+
+    #[test]
+    fn sequences() {
+        let estimator = StrengthEstimator::default();
+
+        let ascending = estimator.estimate("lmnopqrs", &[]);
+        assert!(matches!(patterns(&ascending)[..], [Pattern::Sequence { ascending: true }]));
+        assert!(ascending.score <= 1);
+
+        let descending = estimator.estimate("98765432", &[]);
+        assert!(matches!(patterns(&descending)[..], [Pattern::Sequence { ascending: false }]));
+    }
+
+    #[test]
+    fn keyboard_walks() {
+        let walk = StrengthEstimator::default().estimate("ertyuiop", &[]);
+
+        assert!(matches!(patterns(&walk)[..], [Pattern::Keyboard]));
+        assert!(walk.score <= 1);
+    }
+
+    #[test]
+    fn repeats() {
+        let repeat = StrengthEstimator::default().estimate("abcabcabcabc", &[]);
+
+        assert!(matches!(&patterns(&repeat)[..], [Pattern::Repeat { base, count: 4 }] if base == "abc"));
+        assert!(repeat.score <= 1);
+    }
+
+    #[test]
+    fn dates() {
+        let date = StrengthEstimator::default().estimate("13.05.1987", &[]);
+
+        assert!(matches!(patterns(&date)[..], [Pattern::Date { year: 1987, separator: true }]));
+        assert!(date.score <= 2);
+    }
+
+    #[test]
+    fn user_inputs_are_guessed_first() {
+        let estimator = StrengthEstimator::default();
+
+        let with_inputs = estimator.estimate("jane13.05.1987", &["jane.doe@example.org"]);
+        assert!(matches!(
+            patterns(&with_inputs)[..],
+            [Pattern::Dictionary { dictionary: DictionaryKind::UserInputs, .. }, Pattern::Date { year: 1987, separator: true }]
+        ));
+        assert!(with_inputs.score <= 2);
+
+        let without_inputs = estimator.estimate("jane13.05.1987", &[]);
+        assert!(without_inputs.guesses_log10 > with_inputs.guesses_log10);
+    }
+
+    #[test]
+    fn breached_passwords_never_meet_a_policy() {
+        let strong = "kX9#vq2!Lm@7zR";
+        let list = BreachList::parse(&crate::breach_list::sha1_hex(strong)).unwrap();
+
+        let breached = StrengthEstimator::new(Some(list)).estimate(strong, &[]);
+        assert_eq!(breached.score, 4);
+        assert!(breached.is_breached() && !breached.meets(0));
+        assert!(breached.feedback.warning.is_some());
+
+        let unlisted = StrengthEstimator::new(None).estimate(strong, &[]);
+        assert!(!unlisted.is_breached() && unlisted.meets(MIN_ACCOUNT_SCORE));
+
+        assert!(StrengthEstimator::default().estimate("qwerty123", &[]).is_breached());
+    }
+
+    #[test]
+    fn only_the_start_of_long_passwords_is_analyzed() {
+        let long = "ab".repeat(MAX_ANALYZED_LEN);
+        let estimate = StrengthEstimator::default().estimate(&long, &[]);
+
+        assert!(estimate.sequence.iter().all(|matched| matched.end <= MAX_ANALYZED_LEN));
+        // The rest counts as random characters, however repetitive it is.
+        assert!(estimate.guesses_log10 >= MAX_ANALYZED_LEN as f64);
+        assert_eq!(estimate.score, 4);
+    }
+
+    #[test]
+    fn passphrases_and_random_passwords_score_four() {
+        let estimator = StrengthEstimator::default();
+
+        let passphrase = estimator.estimate("staple orbit velvet canyon", &[]);
+        assert_eq!(passphrase.score, 4);
+        assert!(passphrase.meets(MIN_ACCOUNT_SCORE) && passphrase.feedback.warning.is_none());
+
+        let random = estimator.estimate("kX9#vq2!Lm@7zR", &[]);
+        assert_eq!(random.score, 4);
+    }
+}
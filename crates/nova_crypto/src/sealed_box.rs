@@ -0,0 +1,481 @@
+use std::fmt;
+use std::path::Path;
+use base64::prelude::*;
+use chacha20poly1305::XChaCha20Poly1305;
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use rand::TryRngCore;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, SharedSecret, StaticSecret};
+use zeroize::Zeroizing;
+use crate::crypto::{decrypt, encrypt, CryptoAlgo, CryptoError};
+use crate::envelope::{self, EnvelopeError, SealOptions, Secret};
+use crate::key_derivation::KEY_LENGTH;
+
+/// First bytes of every sealed box.
+pub const MAGIC: [u8; 4] = *b"NVSB";
+
+/// Bump whenever the layout changes. [`open`] keeps reading every older version.
+pub const SEALED_BOX_VERSION: u8 = 1;
+
+/// Recipients are counted with two bytes.
+pub const MAX_RECIPIENTS: usize = u16::MAX as usize;
+
+pub const FINGERPRINT_LEN: usize = 8;
+
+const PUBLIC_KEY_LEN: usize = 32;
+const HKDF_INFO: &[u8] = b"nova-sealed-box-v1";
+const KEY_PAIR_AAD: &[u8] = b"nova-key-pair";
+
+/// One recipient entry: fingerprint, nonce and the wrapped content key with its tag.
+const WRAPPED_KEY_LEN: usize = KEY_LENGTH + 16;
+const RECIPIENT_LEN: usize = FINGERPRINT_LEN + XChaCha20Poly1305::NONCE_SIZE + WRAPPED_KEY_LEN;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SealedBoxError {
+    #[error("not a sealed box (magic bytes missing)")]
+    NotASealedBox,
+
+    #[error("unsupported sealed box version {0} (newest supported is {SEALED_BOX_VERSION})")]
+    UnsupportedVersion(u8),
+
+    #[error("sealed box is truncated")]
+    Truncated,
+
+    #[error("a sealed box needs between 1 and {MAX_RECIPIENTS} recipients")]
+    InvalidRecipientCount,
+
+    #[error("the key {0} is not among the recipients")]
+    NotARecipient(Fingerprint),
+
+    #[error("invalid public key")]
+    InvalidPublicKey,
+
+    #[error("base64 decode failed: {0}")]
+    Base64DecodeFailed(#[source] base64::DecodeError),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+
+    #[error(transparent)]
+    Envelope(#[from] EnvelopeError),
+}
+
+/// Short id of a public key, used to find a recipient's entry without trying every one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint([u8; FINGERPRINT_LEN]);
+
+impl Fingerprint {
+    fn of(public: &PublicKey) -> Self {
+        let mut fingerprint = [0u8; FINGERPRINT_LEN];
+        fingerprint.copy_from_slice(&blake3::hash(public.as_bytes()).as_bytes()[..FINGERPRINT_LEN]);
+        Self(fingerprint)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; FINGERPRINT_LEN] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+/// The public half of a [`KeyPair`], which is safe to hand out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecipientKey(PublicKey);
+
+impl RecipientKey {
+    pub fn from_bytes(bytes: [u8; PUBLIC_KEY_LEN]) -> Self {
+        Self(PublicKey::from(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; PUBLIC_KEY_LEN] {
+        self.0.as_bytes()
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint::of(&self.0)
+    }
+
+    pub fn to_base64(&self) -> String {
+        BASE64_STANDARD.encode(self.as_bytes())
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, SealedBoxError> {
+        let bytes = BASE64_STANDARD.decode(encoded.trim()).map_err(SealedBoxError::Base64DecodeFailed)?;
+        let bytes: [u8; PUBLIC_KEY_LEN] = bytes.try_into().map_err(|_| SealedBoxError::InvalidPublicKey)?;
+        Ok(Self::from_bytes(bytes))
+    }
+}
+
+/// An X25519 key pair. The secret half is only ever stored sealed, see [`KeyPair::protect`].
+pub struct KeyPair {
+    secret: StaticSecret,
+    public: RecipientKey,
+}
+
+impl KeyPair {
+    pub fn generate() -> Result<Self, CryptoError> {
+        Ok(Self::from_secret(random_secret()?))
+    }
+
+    fn from_secret(secret: StaticSecret) -> Self {
+        let public = RecipientKey(PublicKey::from(&secret));
+        Self { secret, public }
+    }
+
+    pub fn public_key(&self) -> RecipientKey {
+        self.public
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.public.fingerprint()
+    }
+
+    /// Seals the secret key into an envelope named after its fingerprint.
+    pub fn protect(&self, secret: Secret) -> Result<Vec<u8>, SealedBoxError> {
+        let key_id = self.fingerprint().to_string();
+        let options = SealOptions { key_id: Some(&key_id), ..SealOptions::default() };
+        Ok(envelope::seal(self.secret.as_bytes(), secret, KEY_PAIR_AAD, options)?)
+    }
+
+    pub fn unprotect(protected: &[u8], secret: Secret) -> Result<Self, SealedBoxError> {
+        let bytes = Zeroizing::new(envelope::open(protected, secret, KEY_PAIR_AAD)?);
+
+        let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+        if bytes.len() != KEY_LENGTH {
+            return Err(CryptoError::InvalidKeyLength.into());
+        }
+        key.copy_from_slice(&bytes);
+        Ok(Self::from_secret(StaticSecret::from(*key)))
+    }
+
+    /// [`KeyPair::protect`] into a file.
+    pub fn save(&self, path: &Path, secret: Secret) -> Result<(), SealedBoxError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.protect(secret)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path, secret: Secret) -> Result<Self, SealedBoxError> {
+        Self::unprotect(&std::fs::read(path)?, secret)
+    }
+}
+
+/// Encrypts `plain` so that any one of `recipients` can open it.
+///
+/// The payload is an envelope sealed with a random content key. For every recipient, that key is
+/// wrapped with a key agreed between an ephemeral key and the recipient's public key through X25519
+/// and HKDF-SHA256. The recipient list is authenticated along with the payload and `aad`.
+///
+/// Layout: magic, version, ephemeral public key, recipient count, recipients, envelope.
+/// Each recipient is a fingerprint, a nonce and the wrapped content key.
+pub fn seal(plain: &[u8], recipients: &[RecipientKey], aad: &[u8]) -> Result<Vec<u8>, SealedBoxError> {
+    if recipients.is_empty() || recipients.len() > MAX_RECIPIENTS {
+        return Err(SealedBoxError::InvalidRecipientCount);
+    }
+
+    let ephemeral = random_secret()?;
+    let ephemeral_public = PublicKey::from(&ephemeral);
+
+    let mut content_key = Zeroizing::new([0u8; KEY_LENGTH]);
+    OsRng.try_fill_bytes(content_key.as_mut()).map_err(CryptoError::NonceGenerationFailed)?;
+
+    let mut sealed = Vec::with_capacity(64 + recipients.len() * RECIPIENT_LEN + plain.len());
+    sealed.extend_from_slice(&MAGIC);
+    sealed.push(SEALED_BOX_VERSION);
+    sealed.extend_from_slice(ephemeral_public.as_bytes());
+    sealed.extend_from_slice(&(recipients.len() as u16).to_le_bytes());
+
+    for recipient in recipients {
+        let fingerprint = recipient.fingerprint();
+        let wrap_key = wrap_key(&ephemeral.diffie_hellman(&recipient.0), &ephemeral_public, &recipient.0);
+        let (wrapped, nonce) = encrypt::<XChaCha20Poly1305>(content_key.as_ref(), wrap_key.as_ref(), fingerprint.as_bytes())?;
+
+        sealed.extend_from_slice(fingerprint.as_bytes());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&wrapped);
+    }
+
+    let payload = envelope::seal(plain, Secret::Key(content_key.as_ref()), &associated_data(&sealed, aad), SealOptions::default())?;
+    sealed.extend_from_slice(&payload);
+    Ok(sealed)
+}
+
+/// Decrypts a sealed box with the recipient's key pair.
+pub fn open(sealed: &[u8], key_pair: &KeyPair, aad: &[u8]) -> Result<Vec<u8>, SealedBoxError> {
+    let layout = Layout::decode(sealed)?;
+    let fingerprint = key_pair.fingerprint();
+
+    let entry = layout
+        .recipients()
+        .find(|entry| entry[..FINGERPRINT_LEN] == *fingerprint.as_bytes())
+        .ok_or(SealedBoxError::NotARecipient(fingerprint))?;
+
+    let (nonce, wrapped) = entry[FINGERPRINT_LEN..].split_at(XChaCha20Poly1305::NONCE_SIZE);
+    let shared = key_pair.secret.diffie_hellman(&layout.ephemeral_public);
+    let wrap_key = wrap_key(&shared, &layout.ephemeral_public, &key_pair.public.0);
+    let content_key = Zeroizing::new(decrypt::<XChaCha20Poly1305>(wrap_key.as_ref(), wrapped, nonce, fingerprint.as_bytes())?);
+
+    let header = &sealed[..layout.header_len];
+    Ok(envelope::open(&sealed[layout.header_len..], Secret::Key(&content_key), &associated_data(header, aad))?)
+}
+
+/// Fingerprints of everyone who can open the sealed box, without decrypting anything.
+pub fn recipients(sealed: &[u8]) -> Result<Vec<Fingerprint>, SealedBoxError> {
+    let layout = Layout::decode(sealed)?;
+
+    Ok(layout
+        .recipients()
+        .map(|entry| {
+            let mut fingerprint = [0u8; FINGERPRINT_LEN];
+            fingerprint.copy_from_slice(&entry[..FINGERPRINT_LEN]);
+            Fingerprint(fingerprint)
+        })
+        .collect())
+}
+
+pub fn is_sealed_box(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+struct Layout<'a> {
+    ephemeral_public: PublicKey,
+    entries: &'a [u8],
+    header_len: usize,
+}
+
+impl<'a> Layout<'a> {
+    fn decode(sealed: &'a [u8]) -> Result<Self, SealedBoxError> {
+        if !is_sealed_box(sealed) {
+            return Err(SealedBoxError::NotASealedBox);
+        }
+
+        let fixed_len = MAGIC.len() + 1 + PUBLIC_KEY_LEN + 2;
+        let fixed = sealed.get(..fixed_len).ok_or(SealedBoxError::Truncated)?;
+
+        let version = fixed[MAGIC.len()];
+        if version == 0 || version > SEALED_BOX_VERSION {
+            return Err(SealedBoxError::UnsupportedVersion(version));
+        }
+
+        let mut ephemeral_public = [0u8; PUBLIC_KEY_LEN];
+        ephemeral_public.copy_from_slice(&fixed[MAGIC.len() + 1..MAGIC.len() + 1 + PUBLIC_KEY_LEN]);
+
+        let count = u16::from_le_bytes([fixed[fixed_len - 2], fixed[fixed_len - 1]]) as usize;
+        if count == 0 {
+            return Err(SealedBoxError::InvalidRecipientCount);
+        }
+
+        let header_len = fixed_len + count * RECIPIENT_LEN;
+        let entries = sealed.get(fixed_len..header_len).ok_or(SealedBoxError::Truncated)?;
+
+        Ok(Self { ephemeral_public: PublicKey::from(ephemeral_public), entries, header_len })
+    }
+
+    fn recipients(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.entries.chunks_exact(RECIPIENT_LEN)
+    }
+}
+
+fn random_secret() -> Result<StaticSecret, CryptoError> {
+    let mut bytes = Zeroizing::new([0u8; KEY_LENGTH]);
+    OsRng.try_fill_bytes(bytes.as_mut()).map_err(CryptoError::NonceGenerationFailed)?;
+    Ok(StaticSecret::from(*bytes))
+}
+
+/// Both public keys go into the salt, so a wrap key is bound to the exact pair it was agreed between.
+fn wrap_key(shared: &SharedSecret, ephemeral_public: &PublicKey, recipient: &PublicKey) -> Zeroizing<[u8; KEY_LENGTH]> {
+    let mut salt = [0u8; 2 * PUBLIC_KEY_LEN];
+    salt[..PUBLIC_KEY_LEN].copy_from_slice(ephemeral_public.as_bytes());
+    salt[PUBLIC_KEY_LEN..].copy_from_slice(recipient.as_bytes());
+
+    let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+    Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+        .expand(HKDF_INFO, key.as_mut())
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+fn associated_data(header: &[u8], aad: &[u8]) -> Vec<u8> {
+    let mut associated = Vec::with_capacity(header.len() + aad.len());
+    associated.extend_from_slice(header);
+    associated.extend_from_slice(aad);
+    associated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPHEMERAL_AT: usize = MAGIC.len() + 1;
+    const COUNT_AT: usize = EPHEMERAL_AT + PUBLIC_KEY_LEN;
+    const ENTRIES_AT: usize = COUNT_AT + 2;
+
+    fn decryption_failed(result: Result<Vec<u8>, SealedBoxError>) -> bool {
+        matches!(
+            result,
+            Err(SealedBoxError::Crypto(CryptoError::DecryptionFailed) | SealedBoxError::Envelope(EnvelopeError::Crypto(CryptoError::DecryptionFailed)))
+        )
+    }
+
+    #[test]
+    fn every_recipient_opens_the_box() {
+        let (alice, bob) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+
+        let sealed = seal(b"project key", &[alice.public_key(), bob.public_key()], b"aad").unwrap();
+        assert!(is_sealed_box(&sealed));
+        assert_eq!(recipients(&sealed).unwrap(), vec![alice.fingerprint(), bob.fingerprint()]);
+        assert_eq!(open(&sealed, &alice, b"aad").unwrap(), b"project key");
+        assert_eq!(open(&sealed, &bob, b"aad").unwrap(), b"project key");
+    }
+
+    #[test]
+    fn others_are_not_recipients() {
+        let (alice, eve) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+
+        let sealed = seal(b"project key", &[alice.public_key()], b"aad").unwrap();
+        assert!(matches!(open(&sealed, &eve, b"aad"), Err(SealedBoxError::NotARecipient(fingerprint)) if fingerprint == eve.fingerprint()));
+    }
+
+    #[test]
+    fn wrong_aad_is_rejected() {
+        let alice = KeyPair::generate().unwrap();
+
+        let sealed = seal(b"project key", &[alice.public_key()], b"aad").unwrap();
+        assert!(decryption_failed(open(&sealed, &alice, b"other")));
+        assert!(decryption_failed(open(&sealed, &alice, b"")));
+    }
+
+    #[test]
+    fn seal_needs_a_recipient() {
+        assert!(matches!(seal(b"project key", &[], b"aad"), Err(SealedBoxError::InvalidRecipientCount)));
+    }
+
+    #[test]
+    fn header_is_checked_before_decrypting() {
+        let alice = KeyPair::generate().unwrap();
+        let sealed = seal(b"project key", &[alice.public_key()], b"aad").unwrap();
+
+        assert!(matches!(open(b"NVEN", &alice, b"aad"), Err(SealedBoxError::NotASealedBox)));
+
+        let mut newer = sealed.clone();
+        newer[MAGIC.len()] = SEALED_BOX_VERSION + 1;
+        assert!(matches!(open(&newer, &alice, b"aad"), Err(SealedBoxError::UnsupportedVersion(version)) if version == SEALED_BOX_VERSION + 1));
+
+        let mut unversioned = sealed.clone();
+        unversioned[MAGIC.len()] = 0;
+        assert!(matches!(recipients(&unversioned), Err(SealedBoxError::UnsupportedVersion(0))));
+    }
+
+    #[test]
+    fn truncated_header_is_rejected() {
+        let alice = KeyPair::generate().unwrap();
+        let sealed = seal(b"project key", &[alice.public_key()], b"aad").unwrap();
+
+        for len in [MAGIC.len() + 1, COUNT_AT + 1, ENTRIES_AT, ENTRIES_AT + RECIPIENT_LEN - 1] {
+            assert!(matches!(open(&sealed[..len], &alice, b"aad"), Err(SealedBoxError::Truncated)), "{len} bytes");
+            assert!(matches!(recipients(&sealed[..len]), Err(SealedBoxError::Truncated)), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn truncated_payload_is_rejected() {
+        let alice = KeyPair::generate().unwrap();
+        let sealed = seal(b"project key", &[alice.public_key()], b"aad").unwrap();
+
+        assert!(open(&sealed[..ENTRIES_AT + RECIPIENT_LEN], &alice, b"aad").is_err());
+        assert!(open(&sealed[..sealed.len() - 1], &alice, b"aad").is_err());
+    }
+
+    #[test]
+    fn dropping_a_recipient_breaks_the_payload_for_everyone_else() {
+        let (alice, bob) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+        let mut sealed = seal(b"project key", &[alice.public_key(), bob.public_key()], b"aad").unwrap();
+
+        sealed.drain(ENTRIES_AT + RECIPIENT_LEN..ENTRIES_AT + 2 * RECIPIENT_LEN);
+        sealed[COUNT_AT..ENTRIES_AT].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(recipients(&sealed).unwrap(), vec![alice.fingerprint()]);
+        assert!(decryption_failed(open(&sealed, &alice, b"aad")));
+    }
+
+    #[test]
+    fn tampered_recipient_count_is_rejected() {
+        let (alice, bob) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+        let sealed = seal(b"project key", &[alice.public_key(), bob.public_key()], b"aad").unwrap();
+
+        let mut none = sealed.clone();
+        none[COUNT_AT..ENTRIES_AT].copy_from_slice(&0u16.to_le_bytes());
+        assert!(matches!(open(&none, &alice, b"aad"), Err(SealedBoxError::InvalidRecipientCount)));
+
+        // bob's entry is now read as part of the payload
+        let mut fewer = sealed.clone();
+        fewer[COUNT_AT..ENTRIES_AT].copy_from_slice(&1u16.to_le_bytes());
+        assert!(open(&fewer, &alice, b"aad").is_err());
+
+        let mut more = sealed;
+        more[COUNT_AT..ENTRIES_AT].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert!(matches!(open(&more, &alice, b"aad"), Err(SealedBoxError::Truncated)));
+    }
+
+    #[test]
+    fn tampered_keys_are_rejected() {
+        let alice = KeyPair::generate().unwrap();
+        let sealed = seal(b"project key", &[alice.public_key()], b"aad").unwrap();
+
+        let mut ephemeral = sealed.clone();
+        ephemeral[EPHEMERAL_AT] ^= 1;
+        assert!(decryption_failed(open(&ephemeral, &alice, b"aad")));
+
+        let mut wrapped = sealed.clone();
+        wrapped[ENTRIES_AT + RECIPIENT_LEN - 1] ^= 1;
+        assert!(decryption_failed(open(&wrapped, &alice, b"aad")));
+
+        let mut fingerprint = sealed;
+        fingerprint[ENTRIES_AT] ^= 1;
+        assert!(matches!(open(&fingerprint, &alice, b"aad"), Err(SealedBoxError::NotARecipient(_))));
+    }
+
+    #[test]
+    fn tampered_payload_is_rejected() {
+        let alice = KeyPair::generate().unwrap();
+        let mut sealed = seal(b"project key", &[alice.public_key()], b"aad").unwrap();
+
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert!(decryption_failed(open(&sealed, &alice, b"aad")));
+    }
+
+    #[test]
+    fn recipient_key_round_trips_through_base64() {
+        let bob = KeyPair::generate().unwrap();
+
+        let exported = RecipientKey::from_base64(&format!(" {}\n", bob.public_key().to_base64())).unwrap();
+        assert_eq!(exported, bob.public_key());
+        assert_eq!(exported.fingerprint(), bob.fingerprint());
+
+        assert!(matches!(RecipientKey::from_base64("not base64!"), Err(SealedBoxError::Base64DecodeFailed(_))));
+        assert!(matches!(RecipientKey::from_base64(&BASE64_STANDARD.encode([1u8; 16])), Err(SealedBoxError::InvalidPublicKey)));
+    }
+
+    #[test]
+    fn protected_key_pair_needs_its_secret() {
+        let bob = KeyPair::generate().unwrap();
+        let sealed = seal(b"project key", &[bob.public_key()], b"aad").unwrap();
+
+        let protected = bob.protect(Secret::Key(&[7u8; KEY_LENGTH])).unwrap();
+        let restored = KeyPair::unprotect(&protected, Secret::Key(&[7u8; KEY_LENGTH])).unwrap();
+        assert_eq!(restored.public_key(), bob.public_key());
+        assert_eq!(open(&sealed, &restored, b"aad").unwrap(), b"project key");
+
+        assert!(KeyPair::unprotect(&protected, Secret::Key(&[8u8; KEY_LENGTH])).is_err());
+    }
+}
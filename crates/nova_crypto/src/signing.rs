@@ -0,0 +1,529 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use base64::prelude::*;
+use p256::ecdsa::signature::{DigestSigner, DigestVerifier};
+use p256::ecdsa::{self, Signature};
+use p256::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding};
+use rand::rngs::OsRng;
+use rand::TryRngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+use crate::crypto::CryptoError;
+use crate::envelope::{self, EnvelopeError, SealOptions, Secret};
+
+/// Hashed ahead of every signed message, so a signature can't be passed off as one made for
+/// another purpose, or as a JWT made with the same key.
+const DOMAIN: &[u8] = b"nova-signature-v1";
+const KEY_AAD: &[u8] = b"nova-signing-key";
+const ALGORITHM: &str = "ES256";
+const KEY_ID_LEN: usize = 8;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SigningError {
+    #[error("signature doesn't match the data")]
+    InvalidSignature,
+
+    #[error("signature was made for {got:?}, expected {expected:?}")]
+    ContextMismatch { expected: String, got: String },
+
+    #[error("unsupported signature algorithm {0:?}")]
+    UnsupportedAlgorithm(String),
+
+    #[error("no trusted key with id {0:?}")]
+    UntrustedKey(String),
+
+    #[error("invalid key: {0}")]
+    InvalidKey(String),
+
+    #[error("base64 decode failed: {0}")]
+    Base64DecodeFailed(#[source] base64::DecodeError),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+
+    #[error(transparent)]
+    Envelope(#[from] EnvelopeError),
+}
+
+/// An ECDSA P-256 private key, the same kind the ES256 JWT keys are.
+pub struct SigningKey(ecdsa::SigningKey);
+
+impl SigningKey {
+    pub fn generate() -> Result<Self, CryptoError> {
+        loop {
+            let mut bytes = Zeroizing::new([0u8; 32]);
+            OsRng.try_fill_bytes(bytes.as_mut()).map_err(CryptoError::NonceGenerationFailed)?;
+
+            // fails only for the zero scalar or one above the curve order
+            if let Ok(key) = ecdsa::SigningKey::from_slice(bytes.as_ref()) {
+                return Ok(Self(key));
+            }
+        }
+    }
+
+    /// Reads a PKCS#8 PEM key, like the ones `es256_key_gen.py` writes.
+    pub fn from_pem(pem: &str) -> Result<Self, SigningError> {
+        ecdsa::SigningKey::from_pkcs8_pem(pem).map(Self).map_err(|err| SigningError::InvalidKey(err.to_string()))
+    }
+
+    pub fn to_pem(&self) -> Result<Zeroizing<String>, SigningError> {
+        self.0.to_pkcs8_pem(LineEnding::LF).map_err(|err| SigningError::InvalidKey(err.to_string()))
+    }
+
+    /// Seals the PEM into an envelope named after the key id.
+    pub fn protect(&self, secret: Secret) -> Result<Vec<u8>, SigningError> {
+        let key_id = self.verifying_key().key_id();
+        let options = SealOptions { key_id: Some(&key_id), ..SealOptions::default() };
+        Ok(envelope::seal(self.to_pem()?.as_bytes(), secret, KEY_AAD, options)?)
+    }
+
+    pub fn unprotect(protected: &[u8], secret: Secret) -> Result<Self, SigningError> {
+        let pem = Zeroizing::new(envelope::open(protected, secret, KEY_AAD)?);
+        Self::from_pem(std::str::from_utf8(&pem).map_err(|err| SigningError::InvalidKey(err.to_string()))?)
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(*self.0.verifying_key())
+    }
+
+    /// Signs everything `reader` yields without holding it in memory.
+    pub fn sign_reader(&self, mut reader: impl Read, context: &str) -> Result<DetachedSignature, SigningError> {
+        let mut hasher = SignatureHasher::new(context);
+        io::copy(&mut reader, &mut hasher)?;
+        self.sign_hasher(hasher)
+    }
+
+    pub fn sign_bytes(&self, data: &[u8], context: &str) -> Result<DetachedSignature, SigningError> {
+        let mut hasher = SignatureHasher::new(context);
+        hasher.update(data);
+        self.sign_hasher(hasher)
+    }
+
+    pub fn sign_file(&self, path: &Path, context: &str) -> Result<DetachedSignature, SigningError> {
+        self.sign_reader(std::fs::File::open(path)?, context)
+    }
+
+    /// Signs a manifest in its canonical JSON form, with object keys sorted.
+    pub fn sign_manifest<T: Serialize>(&self, manifest: &T, context: &str) -> Result<DetachedSignature, SigningError> {
+        self.sign_bytes(&canonical_json(manifest)?, context)
+    }
+
+    /// Signs what was fed to `hasher`, for data that arrives in pieces, e.g. from an async reader.
+    pub fn sign_hasher(&self, hasher: SignatureHasher) -> Result<DetachedSignature, SigningError> {
+        let signature: Signature = self.0.try_sign_digest(hasher.digest).map_err(|_| SigningError::InvalidSignature)?;
+        let signature = signature.normalize_s().unwrap_or(signature);
+
+        Ok(DetachedSignature {
+            algorithm: ALGORITHM.to_string(),
+            key_id: self.verifying_key().key_id(),
+            context: hasher.context,
+            signature: BASE64_STANDARD.encode(signature.to_bytes()),
+        })
+    }
+}
+
+/// The public half of a [`SigningKey`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyingKey(ecdsa::VerifyingKey);
+
+impl VerifyingKey {
+    pub fn from_pem(pem: &str) -> Result<Self, SigningError> {
+        ecdsa::VerifyingKey::from_public_key_pem(pem).map(Self).map_err(|err| SigningError::InvalidKey(err.to_string()))
+    }
+
+    pub fn to_pem(&self) -> Result<String, SigningError> {
+        self.0.to_public_key_pem(LineEnding::LF).map_err(|err| SigningError::InvalidKey(err.to_string()))
+    }
+
+    /// Hex of the first bytes of a hash of the compressed point. Signatures name their key by it.
+    pub fn key_id(&self) -> String {
+        let hash = blake3::hash(self.0.to_encoded_point(true).as_bytes());
+        hash.as_bytes()[..KEY_ID_LEN].iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    pub fn verify_reader(&self, mut reader: impl Read, signature: &DetachedSignature, context: &str) -> Result<(), SigningError> {
+        let mut hasher = SignatureHasher::new(context);
+        io::copy(&mut reader, &mut hasher)?;
+        self.verify_hasher(hasher, signature)
+    }
+
+    pub fn verify_bytes(&self, data: &[u8], signature: &DetachedSignature, context: &str) -> Result<(), SigningError> {
+        let mut hasher = SignatureHasher::new(context);
+        hasher.update(data);
+        self.verify_hasher(hasher, signature)
+    }
+
+    pub fn verify_file(&self, path: &Path, signature: &DetachedSignature, context: &str) -> Result<(), SigningError> {
+        self.verify_reader(std::fs::File::open(path)?, signature, context)
+    }
+
+    pub fn verify_manifest<T: Serialize>(&self, manifest: &T, signature: &DetachedSignature, context: &str) -> Result<(), SigningError> {
+        self.verify_bytes(&canonical_json(manifest)?, signature, context)
+    }
+
+    /// Checks the signature against what was fed to `hasher`. The context the hasher was created
+    /// with has to be the one the signature was made for.
+    pub fn verify_hasher(&self, hasher: SignatureHasher, signature: &DetachedSignature) -> Result<(), SigningError> {
+        if signature.algorithm != ALGORITHM {
+            return Err(SigningError::UnsupportedAlgorithm(signature.algorithm.clone()));
+        }
+        if signature.context != hasher.context {
+            return Err(SigningError::ContextMismatch { expected: hasher.context, got: signature.context.clone() });
+        }
+        if signature.key_id != self.key_id() {
+            return Err(SigningError::InvalidSignature);
+        }
+
+        let bytes = BASE64_STANDARD.decode(&signature.signature).map_err(SigningError::Base64DecodeFailed)?;
+        let parsed = Signature::from_slice(&bytes).map_err(|_| SigningError::InvalidSignature)?;
+        if parsed.normalize_s().is_some() {
+            return Err(SigningError::InvalidSignature);
+        }
+        self.0.verify_digest(hasher.digest, &parsed).map_err(|_| SigningError::InvalidSignature)
+    }
+}
+
+/// Hashes data that is signed or verified piece by piece. Also a [`Write`], so it can be the
+/// target of [`io::copy`].
+pub struct SignatureHasher {
+    digest: Sha256,
+    context: String,
+}
+
+impl SignatureHasher {
+    /// `context` names what is signed, e.g. `"project-export"`, and has to match on verification.
+    pub fn new(context: &str) -> Self {
+        let mut digest = Sha256::new();
+        digest.update(DOMAIN);
+        digest.update((context.len() as u64).to_le_bytes());
+        digest.update(context.as_bytes());
+        Self { digest, context: context.to_string() }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.digest.update(data);
+    }
+}
+
+impl Write for SignatureHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A signature kept apart from the data, usually in a `.sig` file next to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetachedSignature {
+    pub algorithm: String,
+    pub key_id: String,
+    pub context: String,
+    /// Base64 of the fixed size `r || s` encoding. `s` is always the low one of its two valid values,
+    /// so a signature can't be altered into another one that still verifies.
+    pub signature: String,
+}
+
+impl DetachedSignature {
+    pub fn to_json(&self) -> Result<String, SigningError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SigningError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SigningError> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SigningError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// A public key the user has chosen to trust, under a name they recognize.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedKey {
+    pub name: String,
+    pub public_key: String,
+}
+
+/// Known public keys by key id. Verifying through the store answers both whether the data is
+/// unaltered and who signed it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrustStore {
+    keys: BTreeMap<String, TrustedKey>,
+}
+
+impl TrustStore {
+    /// An empty store if the file doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self, SigningError> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SigningError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Adds or renames a key. Returns its key id.
+    pub fn trust(&mut self, name: &str, key: &VerifyingKey) -> Result<String, SigningError> {
+        let key_id = key.key_id();
+        self.keys.insert(key_id.clone(), TrustedKey { name: name.to_string(), public_key: key.to_pem()? });
+        Ok(key_id)
+    }
+
+    pub fn revoke(&mut self, key_id: &str) -> Option<TrustedKey> {
+        self.keys.remove(key_id)
+    }
+
+    pub fn get(&self, key_id: &str) -> Option<&TrustedKey> {
+        self.keys.get(key_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TrustedKey)> {
+        self.keys.iter().map(|(key_id, key)| (key_id.as_str(), key))
+    }
+
+    /// Verifies with the trusted key the signature names and returns who it belongs to.
+    pub fn verify_reader(&self, reader: impl Read, signature: &DetachedSignature, context: &str) -> Result<&TrustedKey, SigningError> {
+        let (trusted, key) = self.key(&signature.key_id)?;
+        key.verify_reader(reader, signature, context)?;
+        Ok(trusted)
+    }
+
+    pub fn verify_file(&self, path: &Path, signature: &DetachedSignature, context: &str) -> Result<&TrustedKey, SigningError> {
+        self.verify_reader(std::fs::File::open(path)?, signature, context)
+    }
+
+    pub fn verify_manifest<T: Serialize>(&self, manifest: &T, signature: &DetachedSignature, context: &str) -> Result<&TrustedKey, SigningError> {
+        let (trusted, key) = self.key(&signature.key_id)?;
+        key.verify_manifest(manifest, signature, context)?;
+        Ok(trusted)
+    }
+
+    pub fn verify_hasher(&self, hasher: SignatureHasher, signature: &DetachedSignature) -> Result<&TrustedKey, SigningError> {
+        let (trusted, key) = self.key(&signature.key_id)?;
+        key.verify_hasher(hasher, signature)?;
+        Ok(trusted)
+    }
+
+    fn key(&self, key_id: &str) -> Result<(&TrustedKey, VerifyingKey), SigningError> {
+        let trusted = self.keys.get(key_id).ok_or_else(|| SigningError::UntrustedKey(key_id.to_string()))?;
+        Ok((trusted, VerifyingKey::from_pem(&trusted.public_key)?))
+    }
+}
+
+/// Objects go through `serde_json::Value`, whose maps keep their keys sorted.
+fn canonical_json<T: Serialize>(value: &T) -> Result<Vec<u8>, SigningError> {
+    Ok(serde_json::to_vec(&serde_json::to_value(value)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const CONTEXT: &str = "project-export";
+
+    fn data() -> Vec<u8> {
+        vec![42u8; 200_000]
+    }
+
+    fn with_signature(signature: &DetachedSignature, bytes: &[u8]) -> DetachedSignature {
+        DetachedSignature { signature: BASE64_STANDARD.encode(bytes), ..signature.clone() }
+    }
+
+    #[test]
+    fn signature_and_keys_round_trip() {
+        let key = SigningKey::generate().unwrap();
+        let signature = key.sign_reader(data().as_slice(), CONTEXT).unwrap();
+
+        let signature = DetachedSignature::from_json(&signature.to_json().unwrap()).unwrap();
+        let public = VerifyingKey::from_pem(&key.verifying_key().to_pem().unwrap()).unwrap();
+        assert_eq!(public, key.verifying_key());
+        assert_eq!(signature.key_id, public.key_id());
+        public.verify_bytes(&data(), &signature, CONTEXT).unwrap();
+
+        let reloaded = SigningKey::from_pem(&key.to_pem().unwrap()).unwrap();
+        assert_eq!(reloaded.verifying_key(), key.verifying_key());
+    }
+
+    #[test]
+    fn streamed_and_buffered_signatures_agree() {
+        let key = SigningKey::generate().unwrap();
+        let public = key.verifying_key();
+
+        let mut hasher = SignatureHasher::new(CONTEXT);
+        for chunk in data().chunks(4096) {
+            hasher.update(chunk);
+        }
+        let streamed = key.sign_hasher(hasher).unwrap();
+        public.verify_bytes(&data(), &streamed, CONTEXT).unwrap();
+
+        let buffered = key.sign_bytes(&data(), CONTEXT).unwrap();
+        public.verify_reader(data().as_slice(), &buffered, CONTEXT).unwrap();
+    }
+
+    #[test]
+    fn altered_data_is_rejected() {
+        let key = SigningKey::generate().unwrap();
+        let signature = key.sign_bytes(&data(), CONTEXT).unwrap();
+
+        let mut altered = data();
+        altered[100_000] = 0;
+        let result = key.verifying_key().verify_reader(altered.as_slice(), &signature, CONTEXT);
+        assert!(matches!(result, Err(SigningError::InvalidSignature)));
+
+        let result = key.verifying_key().verify_bytes(&data()[1..], &signature, CONTEXT);
+        assert!(matches!(result, Err(SigningError::InvalidSignature)));
+    }
+
+    #[test]
+    fn signature_for_another_context_is_rejected() {
+        let key = SigningKey::generate().unwrap();
+        let signature = key.sign_bytes(&data(), CONTEXT).unwrap();
+
+        let result = key.verifying_key().verify_bytes(&data(), &signature, "audit-log");
+        assert!(matches!(result, Err(SigningError::ContextMismatch { expected, got }) if expected == "audit-log" && got == CONTEXT));
+
+        // Claiming the other context doesn't help, it is part of what was signed.
+        let relabeled = DetachedSignature { context: "audit-log".to_string(), ..signature };
+        let result = key.verifying_key().verify_bytes(&data(), &relabeled, "audit-log");
+        assert!(matches!(result, Err(SigningError::InvalidSignature)));
+    }
+
+    #[test]
+    fn high_s_signature_is_rejected() {
+        let key = SigningKey::generate().unwrap();
+        let signature = key.sign_bytes(&data(), CONTEXT).unwrap();
+
+        let parsed = Signature::from_slice(&BASE64_STANDARD.decode(&signature.signature).unwrap()).unwrap();
+        assert!(parsed.normalize_s().is_none(), "signatures are made with a low s");
+
+        let (r, s) = parsed.split_scalars();
+        let high = Signature::from_scalars(r.to_bytes(), (-*s).to_bytes()).unwrap();
+        let result = key.verifying_key().verify_bytes(&data(), &with_signature(&signature, &high.to_bytes()), CONTEXT);
+        assert!(matches!(result, Err(SigningError::InvalidSignature)));
+    }
+
+    #[test]
+    fn malformed_signatures_are_rejected() {
+        let key = SigningKey::generate().unwrap();
+        let public = key.verifying_key();
+        let signature = key.sign_bytes(&data(), CONTEXT).unwrap();
+
+        let not_base64 = DetachedSignature { signature: "not base64!".to_string(), ..signature.clone() };
+        assert!(matches!(public.verify_bytes(&data(), &not_base64, CONTEXT), Err(SigningError::Base64DecodeFailed(_))));
+
+        let short = with_signature(&signature, &[1u8; 32]);
+        assert!(matches!(public.verify_bytes(&data(), &short, CONTEXT), Err(SigningError::InvalidSignature)));
+
+        let zero = with_signature(&signature, &[0u8; 64]);
+        assert!(matches!(public.verify_bytes(&data(), &zero, CONTEXT), Err(SigningError::InvalidSignature)));
+
+        let mut flipped = BASE64_STANDARD.decode(&signature.signature).unwrap();
+        flipped[10] ^= 1;
+        let flipped = with_signature(&signature, &flipped);
+        assert!(matches!(public.verify_bytes(&data(), &flipped, CONTEXT), Err(SigningError::InvalidSignature)));
+
+        let other_algorithm = DetachedSignature { algorithm: "ES384".to_string(), ..signature };
+        let result = public.verify_bytes(&data(), &other_algorithm, CONTEXT);
+        assert!(matches!(result, Err(SigningError::UnsupportedAlgorithm(algorithm)) if algorithm == "ES384"));
+    }
+
+    #[test]
+    fn signature_of_another_key_is_rejected() {
+        let (key, other) = (SigningKey::generate().unwrap(), SigningKey::generate().unwrap());
+        let signature = other.sign_bytes(&data(), CONTEXT).unwrap();
+
+        assert!(matches!(key.verifying_key().verify_bytes(&data(), &signature, CONTEXT), Err(SigningError::InvalidSignature)));
+
+        let claimed = DetachedSignature { key_id: key.verifying_key().key_id(), ..signature };
+        assert!(matches!(key.verifying_key().verify_bytes(&data(), &claimed, CONTEXT), Err(SigningError::InvalidSignature)));
+    }
+
+    #[test]
+    fn trust_store_names_the_signer() {
+        let key = SigningKey::generate().unwrap();
+        let signature = key.sign_bytes(&data(), CONTEXT).unwrap();
+
+        let mut store = TrustStore::default();
+        let key_id = store.trust("Dr. Example", &key.verifying_key()).unwrap();
+        assert_eq!(key_id, signature.key_id);
+        assert_eq!(store.verify_reader(data().as_slice(), &signature, CONTEXT).unwrap().name, "Dr. Example");
+
+        store.trust("Dr. Renamed", &key.verifying_key()).unwrap();
+        assert_eq!(store.iter().count(), 1);
+        assert_eq!(store.verify_reader(data().as_slice(), &signature, CONTEXT).unwrap().name, "Dr. Renamed");
+    }
+
+    #[test]
+    fn untrusted_and_revoked_keys_are_rejected() {
+        let key = SigningKey::generate().unwrap();
+        let signature = key.sign_bytes(&data(), CONTEXT).unwrap();
+
+        let mut store = TrustStore::default();
+        let result = store.verify_reader(data().as_slice(), &signature, CONTEXT);
+        assert!(matches!(result, Err(SigningError::UntrustedKey(key_id)) if key_id == signature.key_id));
+
+        let key_id = store.trust("Dr. Example", &key.verifying_key()).unwrap();
+        assert_eq!(store.revoke(&key_id).unwrap().name, "Dr. Example");
+        assert!(matches!(store.verify_reader(data().as_slice(), &signature, CONTEXT), Err(SigningError::UntrustedKey(_))));
+    }
+
+    #[test]
+    fn trust_store_still_checks_the_signature() {
+        let (key, other) = (SigningKey::generate().unwrap(), SigningKey::generate().unwrap());
+        let mut store = TrustStore::default();
+        store.trust("Dr. Example", &key.verifying_key()).unwrap();
+
+        let forged = DetachedSignature { key_id: key.verifying_key().key_id(), ..other.sign_bytes(&data(), CONTEXT).unwrap() };
+        assert!(matches!(store.verify_reader(data().as_slice(), &forged, CONTEXT), Err(SigningError::InvalidSignature)));
+    }
+
+    #[test]
+    fn manifest_signature_ignores_key_order() {
+        let key = SigningKey::generate().unwrap();
+        let mut store = TrustStore::default();
+        store.trust("Dr. Example", &key.verifying_key()).unwrap();
+
+        let manifest = HashMap::from([("b.dcm", "2"), ("a.dcm", "1")]);
+        let reordered: BTreeMap<_, _> = manifest.iter().collect();
+        let signature = key.sign_manifest(&manifest, "manifest").unwrap();
+        store.verify_manifest(&reordered, &signature, "manifest").unwrap();
+
+        let changed = BTreeMap::from([("a.dcm", "1"), ("b.dcm", "3")]);
+        assert!(matches!(store.verify_manifest(&changed, &signature, "manifest"), Err(SigningError::InvalidSignature)));
+    }
+
+    #[test]
+    fn protected_key_needs_its_secret() {
+        let key = SigningKey::generate().unwrap();
+
+        let protected = key.protect(Secret::Key(&[3u8; 32])).unwrap();
+        let restored = SigningKey::unprotect(&protected, Secret::Key(&[3u8; 32])).unwrap();
+        assert_eq!(restored.verifying_key(), key.verifying_key());
+        assert!(SigningKey::unprotect(&protected, Secret::Key(&[4u8; 32])).is_err());
+    }
+}
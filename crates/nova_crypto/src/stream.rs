@@ -0,0 +1,691 @@
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use aead::{Nonce, Payload};
+use aes_gcm::Aes256Gcm;
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
+use rand::rngs::OsRng;
+use rand::TryRngCore;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use zeroize::Zeroizing;
+use crate::crypto::CryptoAlgo;
+use crate::envelope::{Algorithm, EnvelopeError};
+use crate::key_derivation::KEY_LENGTH;
+
+/// First bytes of every stream.
+pub const STREAM_MAGIC: [u8; 4] = *b"NOVS";
+
+pub const STREAM_VERSION: u8 = 1;
+
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Upper bound for the chunk size a stream header may ask for, so a reader never allocates more than that.
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Poly1305 and GCM tags are both 16 bytes.
+const TAG_SIZE: usize = 16;
+
+/// The nonce ends with a 32 bit chunk counter and a one byte last-chunk flag.
+const NONCE_SUFFIX_SIZE: usize = 5;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StreamError {
+    #[error("not an encrypted stream (magic bytes missing)")]
+    NotAStream,
+
+    #[error("unsupported stream version {0} (newest supported is {STREAM_VERSION})")]
+    UnsupportedVersion(u8),
+
+    #[error("chunk size must be between 1 and {MAX_CHUNK_SIZE} bytes, got {0}")]
+    InvalidChunkSize(usize),
+
+    #[error("invalid key length")]
+    InvalidKeyLength,
+
+    #[error("nonce generation failed: {0}")]
+    NonceGenerationFailed(#[source] rand::rand_core::OsError),
+
+    #[error("stream ends before its last chunk")]
+    Truncated,
+
+    #[error("chunk {0} failed to authenticate (tampered, reordered or followed by more data)")]
+    ChunkAuthenticationFailed(u32),
+
+    #[error("stream exceeds the maximum number of chunks")]
+    TooManyChunks,
+
+    #[error("encryption failed")]
+    EncryptionFailed,
+
+    #[error("stream was already finished")]
+    Finished,
+
+    #[error(transparent)]
+    Envelope(#[from] EnvelopeError),
+}
+
+impl From<StreamError> for io::Error {
+    fn from(err: StreamError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+enum Cipher {
+    XChaCha20Poly1305(XChaCha20Poly1305),
+    ChaCha20Poly1305(ChaCha20Poly1305),
+    /// Boxed since the expanded AES key schedule is much larger than the ChaCha keys.
+    Aes256Gcm(Box<Aes256Gcm>),
+}
+
+impl Cipher {
+    fn new(algorithm: Algorithm, key: &[u8]) -> Result<Self, StreamError> {
+        if key.len() != KEY_LENGTH {
+            return Err(StreamError::InvalidKeyLength);
+        }
+
+        let cipher = match algorithm {
+            Algorithm::XChaCha20Poly1305 => Cipher::XChaCha20Poly1305(Self::init(key)?),
+            Algorithm::ChaCha20Poly1305 => Cipher::ChaCha20Poly1305(Self::init(key)?),
+            Algorithm::Aes256Gcm => Cipher::Aes256Gcm(Box::new(Self::init(key)?)),
+        };
+        Ok(cipher)
+    }
+
+    fn init<Algo: CryptoAlgo>(key: &[u8]) -> Result<Algo, StreamError> {
+        Algo::new_from_slice(key).map_err(|_| StreamError::InvalidKeyLength)
+    }
+
+    fn seal(&self, nonce: &[u8], msg: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Cipher::XChaCha20Poly1305(cipher) => Self::seal_with(cipher, nonce, msg, aad),
+            Cipher::ChaCha20Poly1305(cipher) => Self::seal_with(cipher, nonce, msg, aad),
+            Cipher::Aes256Gcm(cipher) => Self::seal_with(cipher.as_ref(), nonce, msg, aad),
+        }
+    }
+
+    fn open(&self, nonce: &[u8], msg: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Cipher::XChaCha20Poly1305(cipher) => Self::open_with(cipher, nonce, msg, aad),
+            Cipher::ChaCha20Poly1305(cipher) => Self::open_with(cipher, nonce, msg, aad),
+            Cipher::Aes256Gcm(cipher) => Self::open_with(cipher.as_ref(), nonce, msg, aad),
+        }
+    }
+
+    fn seal_with<Algo: CryptoAlgo>(cipher: &Algo, nonce: &[u8], msg: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        #[allow(deprecated)]
+        let nonce = Nonce::<Algo>::from_slice(nonce);
+        cipher.encrypt(nonce, Payload { msg, aad }).ok()
+    }
+
+    fn open_with<Algo: CryptoAlgo>(cipher: &Algo, nonce: &[u8], msg: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        #[allow(deprecated)]
+        let nonce = Nonce::<Algo>::from_slice(nonce);
+        cipher.decrypt(nonce, Payload { msg, aad }).ok()
+    }
+}
+
+/// The STREAM construction: every chunk is sealed under `prefix ‖ counter ‖ last flag`, with the stream
+/// header and the caller's AAD as associated data. Swapping chunks breaks the counter, cutting the stream
+/// short leaves it without a chunk flagged as last.
+struct ChunkCipher {
+    cipher: Cipher,
+    header: Vec<u8>,
+    prefix: Vec<u8>,
+    aad: Vec<u8>,
+    chunk_size: usize,
+    counter: u32,
+    finished: bool,
+}
+
+impl ChunkCipher {
+    fn for_encryption(algorithm: Algorithm, key: &[u8], aad: &[u8], chunk_size: usize) -> Result<Self, StreamError> {
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(StreamError::InvalidChunkSize(chunk_size));
+        }
+
+        let mut prefix = vec![0u8; algorithm.nonce_size() - NONCE_SUFFIX_SIZE];
+        OsRng.try_fill_bytes(&mut prefix).map_err(StreamError::NonceGenerationFailed)?;
+
+        let mut header = Vec::with_capacity(Self::header_len(algorithm));
+        header.extend_from_slice(&STREAM_MAGIC);
+        header.push(STREAM_VERSION);
+        header.push(algorithm.id());
+        header.extend_from_slice(&(chunk_size as u32).to_le_bytes());
+        header.extend_from_slice(&prefix);
+
+        Self::new(algorithm, key, header, prefix, aad, chunk_size)
+    }
+
+    /// Parses a header of [`ChunkCipher::header_len`] bytes.
+    fn for_decryption(header: Vec<u8>, key: &[u8], aad: &[u8]) -> Result<Self, StreamError> {
+        let algorithm = Self::algorithm(&header)?;
+        let chunk_size = u32::from_le_bytes([header[6], header[7], header[8], header[9]]) as usize;
+
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(StreamError::InvalidChunkSize(chunk_size));
+        }
+
+        let prefix = header[Self::FIXED_HEADER_LEN..].to_vec();
+        Self::new(algorithm, key, header, prefix, aad, chunk_size)
+    }
+
+    fn new(algorithm: Algorithm, key: &[u8], header: Vec<u8>, prefix: Vec<u8>, aad: &[u8], chunk_size: usize) -> Result<Self, StreamError> {
+        let mut associated = header.clone();
+        associated.extend_from_slice(aad);
+
+        Ok(Self {
+            cipher: Cipher::new(algorithm, key)?,
+            header,
+            prefix,
+            aad: associated,
+            chunk_size,
+            counter: 0,
+            finished: false,
+        })
+    }
+
+    /// Magic, version, algorithm id and chunk size. The nonce prefix follows.
+    const FIXED_HEADER_LEN: usize = 10;
+    /// Magic, version and algorithm id, which tell how long the rest of the header is.
+    const PREAMBLE_LEN: usize = 6;
+
+    fn header_len(algorithm: Algorithm) -> usize {
+        Self::FIXED_HEADER_LEN + algorithm.nonce_size() - NONCE_SUFFIX_SIZE
+    }
+
+    /// Reads the algorithm from the first [`ChunkCipher::PREAMBLE_LEN`] bytes of a header.
+    fn algorithm(header: &[u8]) -> Result<Algorithm, StreamError> {
+        if !header.starts_with(&STREAM_MAGIC) {
+            return Err(StreamError::NotAStream);
+        }
+
+        if header[4] == 0 || header[4] > STREAM_VERSION {
+            return Err(StreamError::UnsupportedVersion(header[4]));
+        }
+
+        Ok(Algorithm::from_id(header[5])?)
+    }
+
+    fn sealed_chunk_len(&self) -> usize {
+        self.chunk_size + TAG_SIZE
+    }
+
+    fn nonce(&self, last: bool) -> Vec<u8> {
+        let mut nonce = self.prefix.clone();
+        nonce.extend_from_slice(&self.counter.to_be_bytes());
+        nonce.push(last as u8);
+        nonce
+    }
+
+    fn advance(&mut self, last: bool) -> Result<(), StreamError> {
+        if last {
+            self.finished = true;
+            return Ok(());
+        }
+
+        self.counter = self.counter.checked_add(1).ok_or(StreamError::TooManyChunks)?;
+        Ok(())
+    }
+
+    fn seal(&mut self, plain: &[u8], last: bool) -> Result<Vec<u8>, StreamError> {
+        if self.finished {
+            return Err(StreamError::Finished);
+        }
+
+        let sealed = self.cipher.seal(&self.nonce(last), plain, &self.aad).ok_or(StreamError::EncryptionFailed)?;
+        self.advance(last)?;
+        Ok(sealed)
+    }
+
+    fn open(&mut self, sealed: &[u8], last: bool) -> Result<Vec<u8>, StreamError> {
+        if self.finished {
+            return Err(StreamError::Finished);
+        }
+
+        if sealed.len() < TAG_SIZE {
+            return Err(StreamError::Truncated);
+        }
+
+        let plain = self
+            .cipher
+            .open(&self.nonce(last), sealed, &self.aad)
+            .ok_or(StreamError::ChunkAuthenticationFailed(self.counter))?;
+        self.advance(last)?;
+        Ok(plain)
+    }
+}
+
+/// Collects ciphertext until a chunk can be opened. Holds at most one sealed chunk plus one byte,
+/// the byte telling whether more chunks follow.
+struct ChunkReader {
+    key: Zeroizing<Vec<u8>>,
+    aad: Vec<u8>,
+    cipher: Option<ChunkCipher>,
+    buffer: Vec<u8>,
+    plain: Vec<u8>,
+    position: usize,
+}
+
+impl ChunkReader {
+    fn new(key: &[u8], aad: &[u8]) -> Self {
+        Self {
+            key: Zeroizing::new(key.to_vec()),
+            aad: aad.to_vec(),
+            cipher: None,
+            buffer: Vec::new(),
+            plain: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// How many bytes the buffer should hold before [`ChunkReader::process`] can make progress.
+    fn wanted(&self) -> usize {
+        match &self.cipher {
+            Some(cipher) => cipher.sealed_chunk_len() + 1,
+            None if self.buffer.len() < ChunkCipher::PREAMBLE_LEN => ChunkCipher::PREAMBLE_LEN,
+            None => ChunkCipher::algorithm(&self.buffer).map(ChunkCipher::header_len).unwrap_or(self.buffer.len()),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.cipher.as_ref().is_some_and(|cipher| cipher.finished)
+    }
+
+    /// Copies decrypted bytes into `out`.
+    fn drain(&mut self, out: &mut [u8]) -> usize {
+        let available = &self.plain[self.position..];
+        let len = available.len().min(out.len());
+        out[..len].copy_from_slice(&available[..len]);
+        self.position += len;
+        len
+    }
+
+    fn has_plain(&self) -> bool {
+        self.position < self.plain.len()
+    }
+
+    /// Parses the header or opens the next chunk once enough bytes are buffered. `eof` is set when the
+    /// inner reader has no more bytes, which makes the buffered chunk the last one.
+    fn process(&mut self, eof: bool) -> Result<(), StreamError> {
+        let Some(cipher) = self.cipher.as_mut() else {
+            if self.buffer.len() >= ChunkCipher::PREAMBLE_LEN {
+                ChunkCipher::algorithm(&self.buffer)?;
+            }
+
+            let wanted = self.wanted();
+            if self.buffer.len() < wanted {
+                return if eof { Err(StreamError::Truncated) } else { Ok(()) };
+            }
+
+            let header: Vec<u8> = self.buffer.drain(..wanted).collect();
+            self.cipher = Some(ChunkCipher::for_decryption(header, &self.key, &self.aad)?);
+            return Ok(());
+        };
+
+        let sealed_len = cipher.sealed_chunk_len();
+
+        if self.buffer.len() > sealed_len {
+            let sealed: Vec<u8> = self.buffer.drain(..sealed_len).collect();
+            self.plain = cipher.open(&sealed, false)?;
+        } else if eof {
+            let sealed = std::mem::take(&mut self.buffer);
+            self.plain = cipher.open(&sealed, true)?;
+        } else {
+            return Ok(());
+        }
+
+        self.position = 0;
+        Ok(())
+    }
+}
+
+/// Encrypts everything written to it in chunks. [`EncryptingWriter::finish`] seals the last chunk,
+/// without it the stream reads as truncated.
+pub struct EncryptingWriter<W: Write> {
+    inner: W,
+    cipher: ChunkCipher,
+    buffer: Vec<u8>,
+    header_written: bool,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    pub fn new(inner: W, algorithm: Algorithm, key: &[u8], aad: &[u8]) -> Result<Self, StreamError> {
+        Self::with_chunk_size(inner, algorithm, key, aad, DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(inner: W, algorithm: Algorithm, key: &[u8], aad: &[u8], chunk_size: usize) -> Result<Self, StreamError> {
+        Ok(Self {
+            inner,
+            cipher: ChunkCipher::for_encryption(algorithm, key, aad, chunk_size)?,
+            buffer: Vec::with_capacity(chunk_size),
+            header_written: false,
+        })
+    }
+
+    /// Seals the last chunk and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_header()?;
+
+        let sealed = self.cipher.seal(&self.buffer, true)?;
+        self.inner.write_all(&sealed)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.header_written {
+            self.inner.write_all(&self.cipher.header)?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // A full chunk is only sealed once more data arrives, since the last chunk has to be sealed differently.
+        if self.buffer.len() == self.cipher.chunk_size {
+            self.write_header()?;
+            let sealed = self.cipher.seal(&self.buffer, false)?;
+            self.inner.write_all(&sealed)?;
+            self.buffer.clear();
+        }
+
+        let len = buf.len().min(self.cipher.chunk_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    /// Flushes the chunks sealed so far. A partly filled chunk stays buffered until it is full or the stream is finished.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts a stream written by [`EncryptingWriter`] or [`AsyncEncryptingWriter`]. Fails with
+/// [`io::ErrorKind::InvalidData`] as soon as a chunk doesn't authenticate or the stream ends early.
+/// Bytes are only returned after their chunk was authenticated.
+pub struct DecryptingReader<R: Read> {
+    inner: R,
+    reader: ChunkReader,
+}
+
+impl<R: Read> DecryptingReader<R> {
+    pub fn new(inner: R, key: &[u8], aad: &[u8]) -> Self {
+        Self { inner, reader: ChunkReader::new(key, aad) }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.reader.has_plain() || buf.is_empty() {
+                return Ok(self.reader.drain(buf));
+            }
+
+            if self.reader.is_done() {
+                return Ok(0);
+            }
+
+            let mut eof = false;
+            while self.reader.buffer.len() < self.reader.wanted() {
+                let start = self.reader.buffer.len();
+                self.reader.buffer.resize(self.reader.wanted(), 0);
+
+                let result = self.inner.read(&mut self.reader.buffer[start..]);
+                self.reader.buffer.truncate(start + *result.as_ref().unwrap_or(&0));
+
+                let read = match result {
+                    Ok(read) => read,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err),
+                };
+
+                if read == 0 {
+                    eof = true;
+                    break;
+                }
+            }
+
+            self.reader.process(eof)?;
+        }
+    }
+}
+
+/// [`EncryptingWriter`] for tokio. `shutdown` seals the last chunk.
+pub struct AsyncEncryptingWriter<W: AsyncWrite + Unpin> {
+    inner: W,
+    cipher: ChunkCipher,
+    buffer: Vec<u8>,
+    /// Sealed bytes not yet accepted by `inner`, starting with the header.
+    pending: Vec<u8>,
+    written: usize,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncEncryptingWriter<W> {
+    pub fn new(inner: W, algorithm: Algorithm, key: &[u8], aad: &[u8]) -> Result<Self, StreamError> {
+        Self::with_chunk_size(inner, algorithm, key, aad, DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(inner: W, algorithm: Algorithm, key: &[u8], aad: &[u8], chunk_size: usize) -> Result<Self, StreamError> {
+        let cipher = ChunkCipher::for_encryption(algorithm, key, aad, chunk_size)?;
+
+        Ok(Self {
+            inner,
+            pending: cipher.header.clone(),
+            cipher,
+            buffer: Vec::with_capacity(chunk_size),
+            written: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.pending.len() {
+            let written = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.pending[self.written..]))?;
+
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += written;
+        }
+
+        self.pending.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    fn seal_buffer(&mut self, last: bool) -> Result<(), StreamError> {
+        let sealed = self.cipher.seal(&self.buffer, last)?;
+        self.pending.extend_from_slice(&sealed);
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncEncryptingWriter<W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        if this.cipher.finished {
+            return Poll::Ready(Err(StreamError::Finished.into()));
+        }
+
+        if this.buffer.len() == this.cipher.chunk_size {
+            this.seal_buffer(false)?;
+            ready!(this.poll_pending(cx))?;
+        }
+
+        let len = buf.len().min(this.cipher.chunk_size - this.buffer.len());
+        this.buffer.extend_from_slice(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if !this.cipher.finished {
+            ready!(this.poll_pending(cx))?;
+            this.seal_buffer(true)?;
+        }
+
+        ready!(this.poll_pending(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// [`DecryptingReader`] for tokio.
+pub struct AsyncDecryptingReader<R: AsyncRead + Unpin> {
+    inner: R,
+    reader: ChunkReader,
+}
+
+impl<R: AsyncRead + Unpin> AsyncDecryptingReader<R> {
+    pub fn new(inner: R, key: &[u8], aad: &[u8]) -> Self {
+        Self { inner, reader: ChunkReader::new(key, aad) }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncDecryptingReader<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.reader.has_plain() || buf.remaining() == 0 {
+                let len = this.reader.drain(buf.initialize_unfilled());
+                buf.advance(len);
+                return Poll::Ready(Ok(()));
+            }
+
+            if this.reader.is_done() {
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut eof = false;
+            while this.reader.buffer.len() < this.reader.wanted() {
+                let start = this.reader.buffer.len();
+                this.reader.buffer.resize(this.reader.wanted(), 0);
+
+                let mut read_buf = ReadBuf::new(&mut this.reader.buffer[start..]);
+                let poll = Pin::new(&mut this.inner).poll_read(cx, &mut read_buf);
+                let read = read_buf.filled().len();
+                this.reader.buffer.truncate(start + read);
+
+                ready!(poll)?;
+                if read == 0 {
+                    eof = true;
+                    break;
+                }
+            }
+
+            this.reader.process(eof)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const KEY: [u8; KEY_LENGTH] = [3u8; KEY_LENGTH];
+
+    fn encrypt(plain: &[u8], chunk_size: usize) -> Vec<u8> {
+        let mut writer = EncryptingWriter::with_chunk_size(Vec::new(), Algorithm::XChaCha20Poly1305, &KEY, b"aad", chunk_size).unwrap();
+        writer.write_all(plain).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn decrypt(sealed: &[u8]) -> io::Result<Vec<u8>> {
+        let mut plain = Vec::new();
+        DecryptingReader::new(sealed, &KEY, b"aad").read_to_end(&mut plain)?;
+        Ok(plain)
+    }
+
+    fn header_len() -> usize {
+        ChunkCipher::header_len(Algorithm::XChaCha20Poly1305)
+    }
+
+    #[test]
+    fn roundtrip_at_chunk_boundaries() {
+        for len in [0, 1, 15, 16, 17, 32, 100] {
+            let plain: Vec<u8> = (0..len).map(|value| value as u8).collect();
+            let sealed = encrypt(&plain, 16);
+
+            assert_eq!(decrypt(&sealed).unwrap(), plain, "length {len}");
+        }
+    }
+
+    #[test]
+    fn truncation_reordering_and_appending_are_detected() {
+        let plain = [7u8; 40];
+        let sealed = encrypt(&plain, 16);
+        let chunk = 16 + TAG_SIZE;
+        let (header, chunks) = sealed.split_at(header_len());
+
+        // Cut off after the first two chunks, which were sealed as not last.
+        let truncated = &sealed[..header_len() + 2 * chunk];
+        assert_eq!(decrypt(truncated).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut reordered = header.to_vec();
+        reordered.extend_from_slice(&chunks[chunk..2 * chunk]);
+        reordered.extend_from_slice(&chunks[..chunk]);
+        reordered.extend_from_slice(&chunks[2 * chunk..]);
+        assert!(decrypt(&reordered).is_err());
+
+        let mut appended = sealed.clone();
+        appended.extend_from_slice(&sealed[header_len()..header_len() + chunk]);
+        assert!(decrypt(&appended).is_err());
+
+        assert!(decrypt(&sealed[..header_len() - 1]).is_err());
+        assert!(DecryptingReader::new(&sealed[..], &KEY, b"other").read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn async_adapters_match_sync_format() {
+        let plain: Vec<u8> = (0..1000u32).map(|value| value as u8).collect();
+
+        let mut writer = AsyncEncryptingWriter::with_chunk_size(Vec::new(), Algorithm::Aes256Gcm, &KEY, b"aad", 64).unwrap();
+        writer.write_all(&plain).await.unwrap();
+        writer.shutdown().await.unwrap();
+        let sealed = writer.into_inner();
+
+        let mut sync_plain = Vec::new();
+        DecryptingReader::new(&sealed[..], &KEY, b"aad").read_to_end(&mut sync_plain).unwrap();
+        assert_eq!(sync_plain, plain);
+
+        let mut async_plain = Vec::new();
+        AsyncDecryptingReader::new(&sealed[..], &KEY, b"aad").read_to_end(&mut async_plain).await.unwrap();
+        assert_eq!(async_plain, plain);
+
+        let mut truncated = Vec::new();
+        let result = AsyncDecryptingReader::new(&sealed[..sealed.len() - 1], &KEY, b"aad").read_to_end(&mut truncated).await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
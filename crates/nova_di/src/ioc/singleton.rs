@@ -75,6 +75,19 @@ impl Container {
             }
         }
     }
+
+    /// Resolves an instance of type `T` if it has been registered.
+    ///
+    /// - Same as [`Container::resolve`], but returns `None` instead of panicking for unregistered types.
+    pub fn try_resolve<T: 'static + Send + Sync>(&self) -> Option<Arc<T>> {
+        let type_id = TypeId::of::<T>();
+
+        if !self.instances.contains_key(&type_id) && !self.factories.contains_key(&type_id) {
+            return None;
+        }
+
+        Some(self.resolve::<T>())
+    }
 }
 
 static INSTANCE: Lazy<Container> = Lazy::new(Container::new);
@@ -146,6 +159,19 @@ mod tests {
         assert_eq!(COUNTER.load(Ordering::SeqCst), 1);
     }
 
+    #[test]
+    fn test_try_resolve() {
+        #[derive(Debug)]
+        struct Optional {
+            id: usize,
+        }
+
+        assert!(ioc().try_resolve::<Optional>().is_none());
+
+        ioc().register(|| Optional { id: 7 });
+        assert_eq!(ioc().try_resolve::<Optional>().map(|optional| optional.id), Some(7));
+    }
+
     #[test]
     #[should_panic(expected = "Type not registered")]
     fn test_resolve_unregistered_type_panics() {
@@ -0,0 +1,329 @@
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::NamedTempFile;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use nova_crypto::data_key::DataKey;
+use nova_crypto::envelope::{self, Algorithm, SealOptions, Secret};
+use nova_crypto::stream::{AsyncEncryptingWriter, DecryptingReader, EncryptingWriter, STREAM_MAGIC};
+
+/// Starts the associated data of every sealed file. The path of the file below the root follows,
+/// so a sealed file only opens at the place it was written to.
+const AAD_PREFIX: &str = "nova-fs-v1";
+
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// What [`EncryptedFs::copy_into_async`] copied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopiedFile {
+    pub len: u64,
+    /// BLAKE3 of the plaintext, as hex.
+    pub hash: String,
+}
+
+/// Reads and writes the files below a root, either as plaintext or sealed with a data key.
+///
+/// Without a key, files are read and written as they are, so a plaintext instance behaves exactly
+/// like `std::fs`. With a key, every write is sealed and reads only accept sealed files, so content
+/// swapped in from outside is never taken for the real thing. A sealed file is bound to its path
+/// relative to the root and won't open after being moved or renamed.
+#[derive(Clone, Default)]
+pub struct EncryptedFs {
+    key: Option<Arc<DataKey>>,
+    root: PathBuf,
+}
+
+impl EncryptedFs {
+    pub fn plaintext() -> Self {
+        Self { key: None, root: PathBuf::new() }
+    }
+
+    /// Seals the files below `root`. Paths passed in have to start with `root` as given here.
+    pub fn new(key: DataKey, root: impl Into<PathBuf>) -> Self {
+        Self { key: Some(Arc::new(key)), root: root.into() }
+    }
+
+    /// The same key for the files below another root, e.g. those of a copy.
+    pub fn rebased(&self, root: impl Into<PathBuf>) -> Self {
+        Self { key: self.key.clone(), root: root.into() }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Whether `path` was written sealed, judging by its header.
+    pub fn is_sealed_file(path: &Path) -> io::Result<bool> {
+        let mut magic = [0u8; STREAM_MAGIC.len()];
+        let mut file = std::fs::File::open(path)?;
+
+        match file.read_exact(&mut magic) {
+            Ok(()) => Ok(magic == STREAM_MAGIC),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Streams the plaintext of `path`. Only authenticated bytes are returned.
+    pub fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        let mut file = std::fs::File::open(path)?;
+
+        let mut magic = Vec::with_capacity(STREAM_MAGIC.len());
+        (&mut file).take(STREAM_MAGIC.len() as u64).read_to_end(&mut magic)?;
+
+        let sealed = magic == STREAM_MAGIC;
+        let content = Cursor::new(magic).chain(file);
+
+        match (&self.key, sealed) {
+            (Some(key), true) => Ok(Box::new(DecryptingReader::new(content, key.as_bytes(), self.aad(path)?.as_bytes()))),
+            (Some(_), false) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{:?} is not sealed", path))),
+            (None, true) => Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{:?} is encrypted", path))),
+            (None, false) => Ok(Box::new(content)),
+        }
+    }
+
+    pub fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut contents = Vec::new();
+        self.open(path)?.read_to_end(&mut contents)?;
+        Ok(contents)
+    }
+
+    pub fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// BLAKE3 of the plaintext of `path`, as hex.
+    pub fn hash(&self, path: &Path) -> io::Result<String> {
+        let mut hasher = blake3::Hasher::new();
+        io::copy(&mut self.open(path)?, &mut hasher)?;
+        Ok(hasher.finalize().to_hex().to_string())
+    }
+
+    pub fn write(&self, path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+        self.write_sealed_for(path, path, contents.as_ref())
+    }
+
+    /// Writes to a temporary file next to `path` first, so a crash never leaves a half written file behind.
+    pub fn write_atomic(&self, path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+        let mut tmp_name = path.file_name().ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?.to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+
+        self.write_sealed_for(&tmp_path, path, contents.as_ref())?;
+        std::fs::rename(&tmp_path, path)
+    }
+
+    /// Writes `contents` to `target`, sealed to open at `path`.
+    fn write_sealed_for(&self, target: &Path, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let Some(key) = &self.key else {
+            return std::fs::write(target, contents);
+        };
+
+        let aad = self.aad(path)?;
+        let mut writer = EncryptingWriter::new(std::fs::File::create(target)?, Algorithm::XChaCha20Poly1305, key.as_bytes(), aad.as_bytes())?;
+        writer.write_all(contents)?;
+        writer.finish()?.sync_all()
+    }
+
+    /// Seals a single line of `path`, for files that are appended to instead of rewritten.
+    /// The sealed line is base64, so it never contains a line break.
+    pub fn seal_line(&self, path: &Path, line: &str) -> io::Result<String> {
+        let Some(key) = &self.key else {
+            return Ok(line.to_string());
+        };
+
+        envelope::seal_str(line, Secret::Key(key.as_bytes()), &self.aad(path)?, SealOptions::default())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Opens a line written by [`EncryptedFs::seal_line`]. With a key, lines that aren't sealed are rejected.
+    pub fn open_line(&self, path: &Path, line: &str) -> io::Result<String> {
+        let Some(key) = &self.key else {
+            return Ok(line.to_string());
+        };
+
+        envelope::open_str(line, Secret::Key(key.as_bytes()), &self.aad(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("line of {:?} is not sealed or damaged: {err}", path)))
+    }
+
+    /// Copies a plaintext file from outside the root to `dst`, sealing it on the way.
+    /// The returned hash is that of the plaintext, so it can be checked against `src`.
+    pub async fn copy_into_async(&self, src: &Path, dst: &Path) -> io::Result<CopiedFile> {
+        let mut source = tokio::fs::File::open(src).await?;
+        let mut target = tokio::fs::File::create(dst).await?;
+
+        let Some(key) = &self.key else {
+            let copied = copy_hashed(&mut source, &mut target).await?;
+            target.flush().await?;
+            return Ok(copied);
+        };
+
+        let mut writer = AsyncEncryptingWriter::new(target, Algorithm::XChaCha20Poly1305, key.as_bytes(), self.aad(dst)?.as_bytes())?;
+        let copied = copy_hashed(&mut source, &mut writer).await?;
+        writer.shutdown().await?;
+        writer.into_inner().sync_all().await?;
+        Ok(copied)
+    }
+
+    /// Writes the plaintext of `path` to a temporary file in `dir`, for readers that only take a path.
+    /// `dir` has to be as well protected as the data itself, so never the system temp dir.
+    /// The file is deleted when the handle is dropped.
+    pub fn decrypted_copy(&self, path: &Path, dir: &Path) -> io::Result<NamedTempFile> {
+        std::fs::create_dir_all(dir)?;
+
+        let mut copy = NamedTempFile::new_in(dir)?;
+        io::copy(&mut self.open(path)?, &mut copy)?;
+        copy.flush()?;
+        Ok(copy)
+    }
+
+    /// `nova-fs-v1/` followed by the components of `path` below the root, joined with `/` on every platform.
+    fn aad(&self, path: &Path) -> io::Result<String> {
+        let relative = path.strip_prefix(&self.root).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} lies outside of {:?}", path, self.root))
+        })?;
+
+        let mut aad = AAD_PREFIX.to_string();
+        for component in relative.components() {
+            aad.push('/');
+            aad.push_str(&component.as_os_str().to_string_lossy());
+        }
+        Ok(aad)
+    }
+}
+
+async fn copy_hashed(reader: &mut (impl AsyncRead + Unpin), writer: &mut (impl AsyncWrite + Unpin)) -> io::Result<CopiedFile> {
+    use tokio::io::AsyncReadExt;
+
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0u8; COPY_BUFFER_SIZE];
+    let mut len = 0;
+
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..read]);
+        writer.write_all(&buffer[..read]).await?;
+        len += read as u64;
+    }
+
+    Ok(CopiedFile { len, hash: hasher.finalize().to_hex().to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_reads_are_transparent() {
+        let tmp = tempdir().unwrap();
+        let (plain, sealed, copied) = (tmp.path().join("plain"), tmp.path().join("sealed"), tmp.path().join("copied"));
+
+        let fs = EncryptedFs::new(DataKey::generate().unwrap(), tmp.path());
+        std::fs::write(&plain, b"plaintext").unwrap();
+        fs.write(&sealed, b"secret").unwrap();
+        let copy = fs.copy_into_async(&plain, &copied).await.unwrap();
+
+        assert!(EncryptedFs::is_sealed_file(&sealed).unwrap());
+        assert!(!std::fs::read(&sealed).unwrap().windows(6).any(|window| window == b"secret"));
+
+        assert_eq!(fs.read(&sealed).unwrap(), b"secret");
+        assert_eq!(fs.read_to_string(&copied).unwrap(), "plaintext");
+        assert_eq!(copy, CopiedFile { len: 9, hash: blake3::hash(b"plaintext").to_hex().to_string() });
+        assert_eq!(fs.hash(&copied).unwrap(), copy.hash);
+
+        assert_eq!(EncryptedFs::plaintext().read(&sealed).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert!(EncryptedFs::new(DataKey::generate().unwrap(), tmp.path()).read(&sealed).is_err());
+    }
+
+    #[test]
+    fn test_unsealed_files_are_rejected_with_a_key() {
+        let tmp = tempdir().unwrap();
+        let plain = tmp.path().join("plain");
+        std::fs::write(&plain, b"swapped in").unwrap();
+
+        let fs = EncryptedFs::new(DataKey::generate().unwrap(), tmp.path());
+        assert_eq!(fs.read(&plain).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(EncryptedFs::plaintext().read(&plain).unwrap(), b"swapped in");
+    }
+
+    #[test]
+    fn test_sealed_files_are_bound_to_their_path() {
+        let tmp = tempdir().unwrap();
+        let (first, second) = (tmp.path().join("a").join("IM0001"), tmp.path().join("b").join("IM0001"));
+        std::fs::create_dir_all(first.parent().unwrap()).unwrap();
+        std::fs::create_dir_all(second.parent().unwrap()).unwrap();
+
+        let fs = EncryptedFs::new(DataKey::generate().unwrap(), tmp.path());
+        fs.write(&first, b"first").unwrap();
+        fs.write(&second, b"second").unwrap();
+
+        let swapped = tmp.path().join("swapped");
+        std::fs::rename(&first, &swapped).unwrap();
+        std::fs::copy(&second, &first).unwrap();
+
+        assert!(fs.read(&first).is_err());
+        assert!(fs.read(&swapped).is_err());
+        assert_eq!(fs.read(&second).unwrap(), b"second");
+        assert_eq!(fs.write(&std::env::temp_dir().join("outside"), b"x").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_rebased_copy_opens_at_the_same_relative_path() {
+        let (original, copy) = (tempdir().unwrap(), tempdir().unwrap());
+        let fs = EncryptedFs::new(DataKey::generate().unwrap(), original.path());
+
+        fs.write(&original.path().join("index.json"), b"{}").unwrap();
+        std::fs::copy(original.path().join("index.json"), copy.path().join("index.json")).unwrap();
+
+        assert_eq!(fs.rebased(copy.path()).read(&copy.path().join("index.json")).unwrap(), b"{}");
+    }
+
+    #[test]
+    fn test_write_atomic_seals_for_the_final_path() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("journal.json");
+
+        let fs = EncryptedFs::new(DataKey::generate().unwrap(), tmp.path());
+        fs.write_atomic(&path, b"{\"saved\":true}").unwrap();
+
+        assert_eq!(fs.read(&path).unwrap(), b"{\"saved\":true}");
+        assert!(!tmp.path().join("journal.json.tmp").exists());
+    }
+
+    #[test]
+    fn test_lines_are_sealed_one_by_one() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("audit.jsonl");
+        let fs = EncryptedFs::new(DataKey::generate().unwrap(), tmp.path());
+
+        let sealed = fs.seal_line(&path, "{\"source\":\"/scans/Doe^John\"}").unwrap();
+        assert!(!sealed.contains('\n') && !sealed.contains("Doe"));
+        assert_eq!(fs.open_line(&path, &sealed).unwrap(), "{\"source\":\"/scans/Doe^John\"}");
+
+        assert!(fs.open_line(&path, "{\"source\":\"forged\"}").is_err());
+        assert!(fs.open_line(&tmp.path().join("other.jsonl"), &sealed).is_err());
+        assert_eq!(EncryptedFs::plaintext().seal_line(&path, "plain").unwrap(), "plain");
+    }
+
+    #[test]
+    fn test_decrypted_copy_is_written_to_the_given_dir() {
+        let tmp = tempdir().unwrap();
+        let (sealed, scratch) = (tmp.path().join("sealed"), tmp.path().join("private").join("staging"));
+
+        let fs = EncryptedFs::new(DataKey::generate().unwrap(), tmp.path());
+        fs.write(&sealed, b"secret").unwrap();
+
+        let copy = fs.decrypted_copy(&sealed, &scratch).unwrap();
+        assert!(copy.path().starts_with(&scratch));
+        assert_eq!(std::fs::read(copy.path()).unwrap(), b"secret");
+
+        let path = copy.path().to_path_buf();
+        drop(copy);
+        assert!(!path.exists());
+    }
+}
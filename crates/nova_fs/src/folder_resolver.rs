@@ -45,6 +45,34 @@ static LOG_DIR: LazyLock<Result<PathBuf, std::io::Error>> = LazyLock::new(|| {
     Ok(base)
 });
 
+static SETTINGS_DIR: LazyLock<Result<PathBuf, std::io::Error>> = LazyLock::new(|| {
+    let mut base = dirs::data_dir()
+        .ok_or_else(|| std::io::Error::other("Failed to locate data directory"))?;
+
+    base.push("nova");
+    base.push("settings");
+
+    std::fs::create_dir_all(&base)?;
+
+    debug!("Settings directory: {:?}", base);
+
+    Ok(base)
+});
+
+static SEARCH_DIR: LazyLock<Result<PathBuf, std::io::Error>> = LazyLock::new(|| {
+    let mut base = dirs::data_dir()
+        .ok_or_else(|| std::io::Error::other("Failed to locate data directory"))?;
+
+    base.push("nova");
+    base.push("search");
+
+    std::fs::create_dir_all(&base)?;
+
+    debug!("Search directory: {:?}", base);
+
+    Ok(base)
+});
+
 impl FolderResolver {
     pub fn resolve_assets_directory() -> PathBuf {
         match &*ASSETS_DIR {
@@ -75,4 +103,23 @@ impl FolderResolver {
             }
         }
     }
+    pub fn resolve_settings_dir() -> PathBuf {
+        match &*SETTINGS_DIR {
+            Ok(path) => path.clone(),
+            Err(err) => {
+                error!("Failed to resolve settings directory: {:?}", err);
+                panic!("Failed to resolve settings directory");
+            }
+        }
+    }
+
+    pub fn resolve_search_dir() -> PathBuf {
+        match &*SEARCH_DIR {
+            Ok(path) => path.clone(),
+            Err(err) => {
+                error!("Failed to resolve search directory: {:?}", err);
+                panic!("Failed to resolve search directory");
+            }
+        }
+    }
 }
@@ -1,2 +1,3 @@
 pub mod folder_resolver;
-pub mod file_system;
\ No newline at end of file
+pub mod file_system;
+pub mod encrypted_fs;
\ No newline at end of file
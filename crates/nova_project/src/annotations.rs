@@ -0,0 +1,362 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use nova_fs::encrypted_fs::EncryptedFs;
+
+/// Bump whenever the persisted layout changes in a way that older versions can't read.
+pub const ANNOTATIONS_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum AnnotationError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to (de)serialize annotations: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    #[error("Annotation {0} does not exist")]
+    NotFound(u64),
+
+    #[error("Annotation {id} was modified concurrently (expected revision {expected}, found {actual})")]
+    RevisionConflict {
+        id: u64,
+        expected: u32,
+        actual: u32,
+    },
+
+    #[error("Unsupported annotations version {0} (newest supported is {ANNOTATIONS_VERSION})")]
+    UnsupportedVersion(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnnotationKind {
+    Distance {
+        start: Point,
+        end: Point,
+        length_mm: Option<f64>,
+    },
+    Roi {
+        points: Vec<Point>,
+        label: Option<String>,
+    },
+    Note {
+        text: String,
+        position: Option<Point>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Windowing {
+    pub center: f64,
+    pub width: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub id: u64,
+    pub sop_instance_uid: String,
+    pub kind: AnnotationKind,
+    /// Incremented on every edit. Edits must name the revision they are based on.
+    pub revision: u32,
+    pub created_at_ms: u64,
+    pub updated_at_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Keeps existing annotations and adds the imported ones with new ids.
+    Merge,
+    /// Drops every existing annotation first.
+    Replace,
+}
+
+/// Everything a user draws or adjusts in the viewer.
+/// Annotations are keyed by the SOPInstanceUID they belong to, windowing by SeriesInstanceUID.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnnotationStore {
+    version: u32,
+    next_id: u64,
+    /// Incremented on every change of the store.
+    revision: u64,
+    annotations: BTreeMap<String, Vec<Annotation>>,
+    #[serde(default)]
+    series_windowing: BTreeMap<String, Windowing>,
+}
+
+impl Default for AnnotationStore {
+    fn default() -> Self {
+        Self {
+            version: ANNOTATIONS_VERSION,
+            next_id: 1,
+            revision: 0,
+            annotations: BTreeMap::new(),
+            series_windowing: BTreeMap::new(),
+        }
+    }
+}
+
+impl AnnotationStore {
+    pub fn load(path: &Path, fs: &EncryptedFs) -> Result<Self, AnnotationError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        Self::from_json(&fs.read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path, fs: &EncryptedFs) -> Result<(), AnnotationError> {
+        fs.write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String, AnnotationError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, AnnotationError> {
+        let store: Self = serde_json::from_str(json)?;
+
+        if store.version > ANNOTATIONS_VERSION {
+            return Err(AnnotationError::UnsupportedVersion(store.version));
+        }
+
+        Ok(store)
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn len(&self) -> usize {
+        self.annotations.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn for_instance(&self, sop_instance_uid: &str) -> &[Annotation] {
+        self.annotations.get(sop_instance_uid).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = &Annotation> {
+        self.annotations.values().flatten()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Annotation> {
+        self.all().find(|annotation| annotation.id == id)
+    }
+
+    pub fn add(&mut self, sop_instance_uid: &str, kind: AnnotationKind) -> Annotation {
+        let now = now_ms();
+        let annotation = Annotation {
+            id: self.next_id,
+            sop_instance_uid: sop_instance_uid.to_string(),
+            kind,
+            revision: 1,
+            created_at_ms: now,
+            updated_at_ms: now,
+        };
+
+        self.next_id += 1;
+        self.revision += 1;
+        self.annotations.entry(sop_instance_uid.to_string()).or_default().push(annotation.clone());
+
+        annotation
+    }
+
+    /// Replaces the content of annotation `id`, if nobody else changed it since `expected_revision`.
+    pub fn update(&mut self, id: u64, expected_revision: u32, kind: AnnotationKind) -> Result<Annotation, AnnotationError> {
+        let annotation = self.get_mut(id)?;
+
+        if annotation.revision != expected_revision {
+            return Err(AnnotationError::RevisionConflict {
+                id,
+                expected: expected_revision,
+                actual: annotation.revision,
+            });
+        }
+
+        annotation.kind = kind;
+        annotation.revision += 1;
+        annotation.updated_at_ms = now_ms();
+
+        let updated = annotation.clone();
+        self.revision += 1;
+
+        Ok(updated)
+    }
+
+    pub fn remove(&mut self, id: u64) -> Result<Annotation, AnnotationError> {
+        let sop_instance_uid = self.get(id).ok_or(AnnotationError::NotFound(id))?.sop_instance_uid.clone();
+        let annotations = self.annotations.get_mut(&sop_instance_uid).ok_or(AnnotationError::NotFound(id))?;
+
+        let position = annotations
+            .iter()
+            .position(|annotation| annotation.id == id)
+            .ok_or(AnnotationError::NotFound(id))?;
+
+        let removed = annotations.remove(position);
+
+        if annotations.is_empty() {
+            self.annotations.remove(&sop_instance_uid);
+        }
+
+        self.revision += 1;
+        Ok(removed)
+    }
+
+    /// Puts a previously removed annotation back, keeping its id.
+    pub fn restore(&mut self, annotation: Annotation) {
+        self.next_id = self.next_id.max(annotation.id + 1);
+        self.revision += 1;
+        self.annotations.entry(annotation.sop_instance_uid.clone()).or_default().push(annotation);
+    }
+
+    pub fn windowing(&self, series_instance_uid: &str) -> Option<Windowing> {
+        self.series_windowing.get(series_instance_uid).copied()
+    }
+
+    pub fn set_windowing(&mut self, series_instance_uid: &str, windowing: Option<Windowing>) {
+        match windowing {
+            Some(windowing) => self.series_windowing.insert(series_instance_uid.to_string(), windowing),
+            None => self.series_windowing.remove(series_instance_uid),
+        };
+        self.revision += 1;
+    }
+
+    /// Imports an exported store. Imported annotations always get fresh ids, so they can't clash.
+    pub fn import(&mut self, other: AnnotationStore, mode: ImportMode) {
+        if mode == ImportMode::Replace {
+            self.annotations.clear();
+            self.series_windowing.clear();
+        }
+
+        for annotation in other.annotations.into_values().flatten() {
+            let imported = Annotation {
+                id: self.next_id,
+                ..annotation
+            };
+
+            self.next_id += 1;
+            self.annotations.entry(imported.sop_instance_uid.clone()).or_default().push(imported);
+        }
+
+        self.series_windowing.extend(other.series_windowing);
+        self.revision += 1;
+    }
+
+    /// Rewrites every SOPInstanceUID and SeriesInstanceUID key, e.g. after the UIDs were replaced on export.
+    pub fn remap_uids(&mut self, mut remap: impl FnMut(&str) -> String) {
+        let annotations = std::mem::take(&mut self.annotations);
+
+        for (sop_instance_uid, annotations) in annotations {
+            let remapped = remap(&sop_instance_uid);
+            let entry = self.annotations.entry(remapped.clone()).or_default();
+
+            entry.extend(annotations.into_iter().map(|annotation| Annotation {
+                sop_instance_uid: remapped.clone(),
+                ..annotation
+            }));
+        }
+
+        self.series_windowing = std::mem::take(&mut self.series_windowing)
+            .into_iter()
+            .map(|(series_instance_uid, windowing)| (remap(&series_instance_uid), windowing))
+            .collect();
+
+        self.revision += 1;
+    }
+
+    fn get_mut(&mut self, id: u64) -> Result<&mut Annotation, AnnotationError> {
+        self.annotations
+            .values_mut()
+            .flatten()
+            .find(|annotation| annotation.id == id)
+            .ok_or(AnnotationError::NotFound(id))
+    }
+}
+
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn note(text: &str) -> AnnotationKind {
+        AnnotationKind::Note { text: text.to_string(), position: None }
+    }
+
+    #[test]
+    fn test_add_update_remove() {
+        let mut store = AnnotationStore::default();
+
+        let added = store.add("1.2.3.1", note("first"));
+        assert_eq!(added.revision, 1);
+        assert_eq!(store.for_instance("1.2.3.1").len(), 1);
+
+        let updated = store.update(added.id, 1, note("second")).unwrap();
+        assert_eq!(updated.revision, 2);
+        assert_eq!(store.get(added.id).unwrap().kind, note("second"));
+
+        store.remove(added.id).unwrap();
+        assert!(store.is_empty());
+        assert!(matches!(store.remove(added.id), Err(AnnotationError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_stale_update_is_rejected() {
+        let mut store = AnnotationStore::default();
+        let added = store.add("1.2.3.1", note("first"));
+
+        store.update(added.id, 1, note("second")).unwrap();
+
+        let result = store.update(added.id, 1, note("stale"));
+        assert!(matches!(result, Err(AnnotationError::RevisionConflict { expected: 1, actual: 2, .. })));
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let tmp = tempdir().unwrap();
+        let mut store = AnnotationStore::default();
+
+        store.add("1.2.3.1", AnnotationKind::Distance {
+            start: Point { x: 0.0, y: 0.0 },
+            end: Point { x: 3.0, y: 4.0 },
+            length_mm: Some(5.0),
+        });
+        store.set_windowing("1.2.3", Some(Windowing { center: 40.0, width: 400.0 }));
+
+        let path = tmp.path().join("annotations.json");
+        store.save(&path, &EncryptedFs::plaintext()).unwrap();
+        assert_eq!(AnnotationStore::load(&path, &EncryptedFs::plaintext()).unwrap(), store);
+
+        let mut other = AnnotationStore::default();
+        other.add("1.2.3.1", note("keep me"));
+        other.import(AnnotationStore::from_json(&store.to_json().unwrap()).unwrap(), ImportMode::Merge);
+
+        assert_eq!(other.for_instance("1.2.3.1").len(), 2);
+        assert_ne!(other.for_instance("1.2.3.1")[0].id, other.for_instance("1.2.3.1")[1].id);
+        assert_eq!(other.windowing("1.2.3"), Some(Windowing { center: 40.0, width: 400.0 }));
+
+        other.import(store, ImportMode::Replace);
+        assert_eq!(other.len(), 1);
+    }
+}
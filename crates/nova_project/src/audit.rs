@@ -0,0 +1,327 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use nova_fs::encrypted_fs::EncryptedFs;
+use crate::annotations::now_ms;
+
+/// Bump whenever the hashed content of an entry changes. Entries keep the version they were written with.
+pub const AUDIT_LOG_VERSION: u32 = 1;
+
+/// Who is recorded when nobody is logged in, e.g. in tests or tools that work on a project directly.
+pub const UNKNOWN_ACTOR: &str = "unknown";
+
+/// `previous` of the first entry.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Error, Debug)]
+pub enum AuditError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to (de)serialize audit log: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    #[error("Unsupported audit log version {0} (newest supported is {AUDIT_LOG_VERSION})")]
+    UnsupportedVersion(u32),
+
+    #[error("Audit log was tampered with at entry {sequence}: {reason}")]
+    Tampered { sequence: u64, reason: String },
+}
+
+/// What happened to the data of a project.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// `source` was copied to `file`, relative to `projectFiles`. `hash` is the BLAKE3 of the copy.
+    Imported { source: PathBuf, file: PathBuf, hash: String },
+    /// `source` was imported again, but `file` already held the same content, so nothing was copied.
+    AlreadyImported { source: PathBuf, file: PathBuf, hash: String },
+    /// The content of an import was moved out of `projectFiles`.
+    Removed { source: PathBuf, files: Vec<PathBuf> },
+    /// A removal was undone.
+    Restored { source: PathBuf, files: Vec<PathBuf> },
+    /// The trash holding removed content was emptied for good.
+    Deleted { files: Vec<PathBuf> },
+    Exported { destination: PathBuf, files: usize, deidentified: bool },
+}
+
+/// One line of the audit log. `hash` covers every other field, including the hash of the entry before,
+/// so changing, dropping or reordering entries breaks the chain from there on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub version: u32,
+    pub sequence: u64,
+    pub timestamp_ms: u64,
+    /// The JWT subject of the user.
+    pub actor: String,
+    pub event: AuditEvent,
+    pub previous: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn compute_hash(&self) -> Result<String, AuditError> {
+        let content = serde_json::to_vec(&(self.version, self.sequence, self.timestamp_ms, &self.actor, &self.event))?;
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.previous.as_bytes());
+        hasher.update(&content);
+        Ok(hasher.finalize().to_hex().to_string())
+    }
+}
+
+/// Append-only, hash-chained JSON lines. Nothing ever rewrites or shortens the file.
+/// In an encrypted project, every line is sealed on its own.
+///
+/// Cutting entries off the end leaves a valid chain, so keep [`AuditLog::head`] elsewhere
+/// if that has to be detected as well.
+pub struct AuditLog {
+    path: PathBuf,
+    fs: EncryptedFs,
+    /// Sequence and hash of the last entry, read on the first append.
+    head: Option<(u64, String)>,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>, fs: EncryptedFs) -> Self {
+        Self { path: path.into(), fs, head: None }
+    }
+
+    pub fn append(&mut self, actor: &str, event: AuditEvent) -> Result<AuditEntry, AuditError> {
+        let (sequence, previous) = match self.head.take() {
+            Some((sequence, hash)) => (sequence + 1, hash),
+            None => match self.read()?.pop() {
+                Some(last) => (last.sequence + 1, last.hash),
+                None => (0, GENESIS_HASH.to_string()),
+            },
+        };
+
+        let mut entry = AuditEntry {
+            version: AUDIT_LOG_VERSION,
+            sequence,
+            timestamp_ms: now_ms(),
+            actor: actor.to_string(),
+            event,
+            previous,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash()?;
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut line = self.fs.seal_line(&self.path, &serde_json::to_string(&entry)?)?;
+        line.push('\n');
+
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+
+        self.head = Some((entry.sequence, entry.hash.clone()));
+        Ok(entry)
+    }
+
+    /// Every entry, oldest first. Does not check the chain, see [`AuditLog::verify`].
+    pub fn entries(&self) -> Result<Vec<AuditEntry>, AuditError> {
+        self.read()
+    }
+
+    /// Sequence and hash of the last entry, if any.
+    pub fn head(&self) -> Result<Option<(u64, String)>, AuditError> {
+        Ok(self.read()?.pop().map(|last| (last.sequence, last.hash)))
+    }
+
+    /// Walks the chain and returns the number of entries, or where it breaks.
+    pub fn verify(&self) -> Result<usize, AuditError> {
+        let entries = self.read()?;
+        let mut previous = GENESIS_HASH.to_string();
+
+        for (position, entry) in entries.iter().enumerate() {
+            let tampered = |reason: &str| AuditError::Tampered { sequence: entry.sequence, reason: reason.to_string() };
+
+            if entry.sequence != position as u64 {
+                return Err(tampered("entries are missing or out of order"));
+            }
+
+            if entry.previous != previous {
+                return Err(tampered("does not follow the entry before"));
+            }
+
+            if entry.compute_hash()? != entry.hash {
+                return Err(tampered("content does not match its hash"));
+            }
+
+            previous = entry.hash.clone();
+        }
+
+        Ok(entries.len())
+    }
+
+    fn read(&self) -> Result<Vec<AuditEntry>, AuditError> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry: AuditEntry = serde_json::from_str(&self.fs.open_line(&self.path, line.trim())?)?;
+            if entry.version > AUDIT_LOG_VERSION {
+                return Err(AuditError::UnsupportedVersion(entry.version));
+            }
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn export(files: usize) -> AuditEvent {
+        AuditEvent::Exported { destination: PathBuf::from("/exports/a"), files, deidentified: true }
+    }
+
+    fn import() -> AuditEvent {
+        AuditEvent::Imported { source: PathBuf::from("/scans/knee.dcm"), file: PathBuf::from("knee.dcm"), hash: "ab".repeat(32) }
+    }
+
+    /// Writes a log of three entries and returns its lines.
+    fn write_log(path: &Path) -> Vec<String> {
+        let mut log = AuditLog::new(path, EncryptedFs::plaintext());
+        log.append("alice", import()).unwrap();
+        log.append("alice", export(1)).unwrap();
+        log.append("bob", export(2)).unwrap();
+
+        std::fs::read_to_string(path).unwrap().lines().map(str::to_string).collect()
+    }
+
+    fn verify(path: &Path) -> Result<usize, AuditError> {
+        AuditLog::new(path, EncryptedFs::plaintext()).verify()
+    }
+
+    #[test]
+    fn test_new_instance_continues_the_chain() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("private").join("audit.jsonl");
+
+        let mut log = AuditLog::new(&path, EncryptedFs::plaintext());
+        log.append("alice", import()).unwrap();
+        let second = log.append("alice", export(1)).unwrap();
+
+        let third = AuditLog::new(&path, EncryptedFs::plaintext()).append("bob", export(2)).unwrap();
+        assert_eq!(third.sequence, 2);
+        assert_eq!(third.previous, second.hash);
+        assert_eq!(AuditLog::new(&path, EncryptedFs::plaintext()).head().unwrap(), Some((2, third.hash)));
+        assert_eq!(verify(&path).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_edited_entry_breaks_its_hash() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("audit.jsonl");
+        let lines = write_log(&path);
+
+        let edited = lines.join("\n").replace("\"files\":1", "\"files\":0");
+        std::fs::write(&path, edited).unwrap();
+        assert!(matches!(verify(&path), Err(AuditError::Tampered { sequence: 1, .. })));
+    }
+
+    #[test]
+    fn test_rehashed_entry_breaks_the_chain_after_it() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("audit.jsonl");
+        let lines = write_log(&path);
+
+        let mut entry: AuditEntry = serde_json::from_str(&lines[1]).unwrap();
+        entry.actor = "mallory".to_string();
+        entry.hash = entry.compute_hash().unwrap();
+        let forged = [lines[0].clone(), serde_json::to_string(&entry).unwrap(), lines[2].clone()];
+        std::fs::write(&path, forged.join("\n")).unwrap();
+
+        assert!(matches!(verify(&path), Err(AuditError::Tampered { sequence: 2, .. })));
+    }
+
+    #[test]
+    fn test_reordered_entries_are_detected() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("audit.jsonl");
+        let mut lines = write_log(&path);
+
+        lines.swap(0, 1);
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        assert!(matches!(verify(&path), Err(AuditError::Tampered { sequence: 1, .. })));
+    }
+
+    #[test]
+    fn test_dropped_entry_is_detected() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("audit.jsonl");
+        let mut lines = write_log(&path);
+
+        lines.remove(1);
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        assert!(matches!(verify(&path), Err(AuditError::Tampered { sequence: 2, .. })));
+    }
+
+    #[test]
+    fn test_truncated_log_still_verifies() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("audit.jsonl");
+        let lines = write_log(&path);
+
+        // Only a head kept elsewhere catches this, see `AuditLog`.
+        std::fs::write(&path, lines[..2].join("\n")).unwrap();
+        assert_eq!(verify(&path).unwrap(), 2);
+        assert_eq!(AuditLog::new(&path, EncryptedFs::plaintext()).head().unwrap().map(|(sequence, _)| sequence), Some(1));
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("audit.jsonl");
+        let lines = write_log(&path);
+
+        let mut entry: AuditEntry = serde_json::from_str(&lines[0]).unwrap();
+        entry.version = AUDIT_LOG_VERSION + 1;
+        std::fs::write(&path, serde_json::to_string(&entry).unwrap()).unwrap();
+        assert!(matches!(verify(&path), Err(AuditError::UnsupportedVersion(version)) if version == AUDIT_LOG_VERSION + 1));
+    }
+
+    #[test]
+    fn test_encrypted_log_seals_every_line() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("private").join("audit.jsonl");
+        let fs = EncryptedFs::new(nova_crypto::data_key::DataKey::generate().unwrap(), tmp.path());
+
+        let mut log = AuditLog::new(&path, fs.clone());
+        log.append("alice", AuditEvent::Imported {
+            source: PathBuf::from("/scans/Doe^John/IM0001"),
+            file: PathBuf::from("IM0001"),
+            hash: "ab".repeat(32),
+        }).unwrap();
+        AuditLog::new(&path, fs.clone()).append("alice", export(1)).unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.lines().count(), 2);
+        assert!(!written.contains("Doe") && !written.contains("alice"));
+        assert_eq!(AuditLog::new(&path, fs.clone()).verify().unwrap(), 2);
+
+        // A plaintext line slipped in is rejected rather than trusted.
+        let forged = serde_json::to_string(&log.entries().unwrap()[1]).unwrap();
+        std::fs::write(&path, format!("{written}{forged}\n")).unwrap();
+        assert!(AuditLog::new(&path, fs).entries().is_err());
+    }
+}
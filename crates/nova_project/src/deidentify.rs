@@ -0,0 +1,528 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+use nova_fs::encrypted_fs::EncryptedFs;
+use crate::dicom::{tags, DataElement, Dicom, DicomError, DicomObject, Tag, TransferSyntax};
+
+/// Bump whenever the persisted layout changes in a way that older versions can't read.
+pub const DEIDENTIFICATION_MAP_VERSION: u32 = 1;
+
+/// UIDs derived from a UUID (PS3.5 B.2) don't need a registered organization root.
+const UUID_UID_ROOT: &str = "2.25";
+const PSEUDONYM_PREFIX: &str = "ANON";
+
+const PATIENT_IDENTITY_REMOVED: Tag = Tag(0x0012, 0x0062);
+const DEIDENTIFICATION_METHOD: Tag = Tag(0x0012, 0x0063);
+const DEIDENTIFICATION_METHOD_VALUE: &str = "PS3.15 Basic Application Confidentiality Profile";
+
+/// The attributes of the PS3.15 E.1 basic application level confidentiality profile nova knows about,
+/// plus the ones it keeps. Anything else is removed, see [`DeidentificationProfile::default_action`].
+/// The VR is used to build dummy values for implicit VR files, where the element doesn't carry one.
+const BASIC_PROFILE: &[(Tag, [u8; 2], DeidentificationAction)] = {
+    use DeidentificationAction::*;
+
+    &[
+        // Kept: what it takes to tell what an image shows and where it sits in its series.
+        (Tag(0x0008, 0x0005), *b"CS", Keep),
+        (Tag(0x0008, 0x0008), *b"CS", Keep),
+        (Tag(0x0008, 0x0016), *b"UI", Keep),
+        (tags::MODALITY, *b"CS", Keep),
+        (Tag(0x0008, 0x0064), *b"CS", Keep),
+        (Tag(0x0008, 0x1140), *b"SQ", Keep),
+        (Tag(0x0008, 0x1150), *b"UI", Keep),
+        (tags::BODY_PART_EXAMINED, *b"CS", Keep),
+        (Tag(0x0018, 0x0050), *b"DS", Keep),
+        (Tag(0x0018, 0x0060), *b"DS", Keep),
+        (Tag(0x0018, 0x0088), *b"DS", Keep),
+        (Tag(0x0018, 0x1150), *b"IS", Keep),
+        (Tag(0x0018, 0x1151), *b"IS", Keep),
+        (Tag(0x0018, 0x5100), *b"CS", Keep),
+        (Tag(0x0020, 0x0011), *b"IS", Keep),
+        (Tag(0x0020, 0x0012), *b"IS", Keep),
+        (tags::INSTANCE_NUMBER, *b"IS", Keep),
+        (Tag(0x0020, 0x0020), *b"CS", Keep),
+        (Tag(0x0020, 0x0032), *b"DS", Keep),
+        (Tag(0x0020, 0x0037), *b"DS", Keep),
+        (Tag(0x0020, 0x0060), *b"CS", Keep),
+        (Tag(0x0020, 0x1041), *b"DS", Keep),
+        // Table E.1-1.
+        (tags::MEDIA_STORAGE_SOP_INSTANCE_UID, *b"UI", RemapUid),
+        (Tag(0x0008, 0x0014), *b"UI", RemapUid),
+        (tags::SOP_INSTANCE_UID, *b"UI", RemapUid),
+        (tags::STUDY_DATE, *b"DA", Empty),
+        (Tag(0x0008, 0x0021), *b"DA", Remove),
+        (Tag(0x0008, 0x0022), *b"DA", Remove),
+        (Tag(0x0008, 0x0023), *b"DA", Empty),
+        (Tag(0x0008, 0x0030), *b"TM", Empty),
+        (Tag(0x0008, 0x0031), *b"TM", Remove),
+        (Tag(0x0008, 0x0032), *b"TM", Remove),
+        (Tag(0x0008, 0x0033), *b"TM", Empty),
+        (Tag(0x0008, 0x0050), *b"SH", Empty),
+        (Tag(0x0008, 0x0080), *b"LO", Remove),
+        (Tag(0x0008, 0x0081), *b"ST", Remove),
+        (Tag(0x0008, 0x0090), *b"PN", Empty),
+        (Tag(0x0008, 0x0092), *b"ST", Remove),
+        (Tag(0x0008, 0x0094), *b"SH", Remove),
+        (Tag(0x0008, 0x0096), *b"SQ", Remove),
+        (Tag(0x0008, 0x1010), *b"SH", Remove),
+        (tags::STUDY_DESCRIPTION, *b"LO", Remove),
+        (tags::SERIES_DESCRIPTION, *b"LO", Remove),
+        (Tag(0x0008, 0x1040), *b"LO", Remove),
+        (Tag(0x0008, 0x1048), *b"PN", Remove),
+        (Tag(0x0008, 0x1050), *b"PN", Remove),
+        (Tag(0x0008, 0x1060), *b"PN", Remove),
+        (Tag(0x0008, 0x1070), *b"PN", Remove),
+        (Tag(0x0008, 0x1080), *b"LO", Remove),
+        (Tag(0x0008, 0x1155), *b"UI", RemapUid),
+        (Tag(0x0008, 0x2111), *b"ST", Remove),
+        (tags::PATIENT_NAME, *b"PN", Empty),
+        (tags::PATIENT_ID, *b"LO", Empty),
+        (Tag(0x0010, 0x0030), *b"DA", Empty),
+        (Tag(0x0010, 0x0032), *b"TM", Remove),
+        (Tag(0x0010, 0x0040), *b"CS", Empty),
+        (Tag(0x0010, 0x1000), *b"LO", Remove),
+        (Tag(0x0010, 0x1001), *b"PN", Remove),
+        (Tag(0x0010, 0x1002), *b"SQ", Remove),
+        (Tag(0x0010, 0x1010), *b"AS", Remove),
+        (Tag(0x0010, 0x1020), *b"DS", Remove),
+        (Tag(0x0010, 0x1030), *b"DS", Remove),
+        (Tag(0x0010, 0x1040), *b"LO", Remove),
+        (Tag(0x0010, 0x2154), *b"SH", Remove),
+        (Tag(0x0010, 0x2160), *b"SH", Remove),
+        (Tag(0x0010, 0x2180), *b"SH", Remove),
+        (Tag(0x0010, 0x21B0), *b"LT", Remove),
+        (Tag(0x0010, 0x4000), *b"LT", Remove),
+        (Tag(0x0018, 0x1000), *b"LO", Remove),
+        (Tag(0x0018, 0x1030), *b"LO", Remove),
+        (tags::STUDY_INSTANCE_UID, *b"UI", RemapUid),
+        (tags::SERIES_INSTANCE_UID, *b"UI", RemapUid),
+        (Tag(0x0020, 0x0010), *b"SH", Empty),
+        (Tag(0x0020, 0x0052), *b"UI", RemapUid),
+        (Tag(0x0020, 0x0200), *b"UI", RemapUid),
+        (Tag(0x0020, 0x4000), *b"LT", Remove),
+        (Tag(0x0032, 0x1032), *b"PN", Remove),
+        (Tag(0x0028, 0x4000), *b"LT", Remove),
+        (Tag(0x0032, 0x1060), *b"LO", Remove),
+        (Tag(0x0038, 0x0010), *b"LO", Remove),
+        (Tag(0x0038, 0x0300), *b"LO", Remove),
+        (Tag(0x0040, 0x0244), *b"DA", Remove),
+        (Tag(0x0040, 0x0253), *b"SH", Remove),
+        (Tag(0x0040, 0x0254), *b"LO", Remove),
+        (Tag(0x0040, 0x0275), *b"SQ", Remove),
+        (Tag(0x0040, 0xA124), *b"UI", RemapUid),
+        (Tag(0x0040, 0xA730), *b"SQ", Remove),
+        (Tag(0x0088, 0x0140), *b"UI", RemapUid),
+        (Tag(0x3006, 0x0024), *b"UI", RemapUid),
+    ]
+};
+
+#[derive(Error, Debug)]
+pub enum DeidentificationError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to (de)serialize de-identification map: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    #[error("Dicom error: {0}")]
+    Dicom(#[from] DicomError),
+
+    #[error("Unsupported de-identification map version {0} (newest supported is {DEIDENTIFICATION_MAP_VERSION})")]
+    UnsupportedVersion(u32),
+}
+
+/// The action codes of PS3.15 table E.1-1, minus the conditional ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeidentificationAction {
+    /// K
+    Keep,
+    /// X
+    Remove,
+    /// Z: replace with a zero length value.
+    Empty,
+    /// D: replace with a non-identifying dummy value of the same VR.
+    Dummy,
+    /// U: replace with a new UID, consistently across all exported files.
+    RemapUid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileRule {
+    pub tag: Tag,
+    pub action: DeidentificationAction,
+}
+
+/// Decides what happens to each attribute on de-identification. Attributes without a rule get the
+/// `default_action`, unless they are private, curves or overlays. File meta information, the image
+/// pixel description and pixel data are always kept, or there would be nothing left to look at.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeidentificationProfile {
+    /// Later rules win, so overrides can simply be appended to the basic profile.
+    pub rules: Vec<ProfileRule>,
+    /// For attributes without a rule. The basic profile removes them, since no list of identifying attributes is ever complete.
+    pub default_action: DeidentificationAction,
+    pub remove_private_tags: bool,
+    pub remove_curves_and_overlays: bool,
+    /// Replaces patient name and id with a stable pseudonym instead of emptying them,
+    /// so the recipient can still tell patients apart.
+    pub pseudonymize_patients: bool,
+}
+
+impl Default for DeidentificationProfile {
+    fn default() -> Self {
+        Self::basic()
+    }
+}
+
+impl DeidentificationProfile {
+    pub fn basic() -> Self {
+        Self {
+            rules: BASIC_PROFILE.iter().map(|&(tag, _, action)| ProfileRule { tag, action }).collect(),
+            default_action: DeidentificationAction::Remove,
+            remove_private_tags: true,
+            remove_curves_and_overlays: true,
+            pseudonymize_patients: true,
+        }
+    }
+
+    pub fn with_rule(mut self, tag: Tag, action: DeidentificationAction) -> Self {
+        self.rules.push(ProfileRule { tag, action });
+        self
+    }
+
+    pub fn action(&self, tag: Tag) -> DeidentificationAction {
+        if let Some(rule) = self.rules.iter().rev().find(|rule| rule.tag == tag) {
+            return rule.action;
+        }
+
+        let group = tag.group();
+
+        // Group lengths outside of the file meta information are retired and would be wrong after editing.
+        if tag.element() == 0x0000 && group != 0x0002 {
+            return DeidentificationAction::Remove;
+        }
+
+        if self.remove_private_tags && group % 2 == 1 {
+            return DeidentificationAction::Remove;
+        }
+
+        let is_curve = (0x5000..=0x50FF).contains(&group);
+        let is_overlay_data = (0x6000..=0x60FF).contains(&group) && matches!(tag.element(), 0x3000 | 0x4000);
+
+        if self.remove_curves_and_overlays && (is_curve || is_overlay_data) {
+            return DeidentificationAction::Remove;
+        }
+
+        if matches!(group, 0x0002 | 0x0028) || tag == tags::PIXEL_DATA {
+            return DeidentificationAction::Keep;
+        }
+
+        self.default_action
+    }
+}
+
+/// Original → replacement values of every export so far. Stays inside the project, so exported data
+/// can be traced back by us, but not by the recipient. Reusing it keeps repeated exports consistent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeidentificationMap {
+    version: u32,
+    uids: BTreeMap<String, String>,
+    patients: BTreeMap<String, String>,
+}
+
+impl Default for DeidentificationMap {
+    fn default() -> Self {
+        Self {
+            version: DEIDENTIFICATION_MAP_VERSION,
+            uids: BTreeMap::new(),
+            patients: BTreeMap::new(),
+        }
+    }
+}
+
+impl DeidentificationMap {
+    pub fn load(path: &Path, fs: &EncryptedFs) -> Result<Self, DeidentificationError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let map: Self = serde_json::from_str(&fs.read_to_string(path)?)?;
+
+        if map.version > DEIDENTIFICATION_MAP_VERSION {
+            return Err(DeidentificationError::UnsupportedVersion(map.version));
+        }
+
+        Ok(map)
+    }
+
+    pub fn save(&self, path: &Path, fs: &EncryptedFs) -> Result<(), DeidentificationError> {
+        fs.write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Returns the replacement of `original`, creating a new UID on first use.
+    pub fn uid(&mut self, original: &str) -> String {
+        self.uids
+            .entry(original.to_string())
+            .or_insert_with(|| format!("{UUID_UID_ROOT}.{}", Uuid::new_v4().as_u128()))
+            .clone()
+    }
+
+    /// Returns the pseudonym of the patient identified by `original`, creating a new one on first use.
+    pub fn patient(&mut self, original: &str) -> String {
+        let next = self.patients.len() + 1;
+
+        self.patients
+            .entry(original.to_string())
+            .or_insert_with(|| format!("{PSEUDONYM_PREFIX}{next:05}"))
+            .clone()
+    }
+
+    pub fn original_uid(&self, replacement: &str) -> Option<&str> {
+        self.uids
+            .iter()
+            .find(|(_, uid)| uid.as_str() == replacement)
+            .map(|(original, _)| original.as_str())
+    }
+}
+
+pub struct Deidentifier<'a> {
+    profile: &'a DeidentificationProfile,
+    map: &'a mut DeidentificationMap,
+}
+
+impl<'a> Deidentifier<'a> {
+    pub fn new(profile: &'a DeidentificationProfile, map: &'a mut DeidentificationMap) -> Self {
+        Self { profile, map }
+    }
+
+    /// Applies the profile to the file meta information and the data set, including nested sequences.
+    pub fn deidentify(&mut self, object: &mut DicomObject) -> Result<(), DicomError> {
+        let patient = object
+            .string(tags::PATIENT_ID)
+            .or_else(|| object.string(tags::PATIENT_NAME))
+            .unwrap_or_default();
+
+        object.meta = self.apply(std::mem::take(&mut object.meta), TransferSyntax::ExplicitVrLittleEndian)?;
+        object.dataset = self.apply(std::mem::take(&mut object.dataset), object.transfer_syntax)?;
+
+        if self.profile.pseudonymize_patients {
+            let pseudonym = self.map.patient(&patient);
+
+            object.insert(DataElement::string(tags::PATIENT_NAME, Some(*b"PN"), &pseudonym));
+            object.insert(DataElement::string(tags::PATIENT_ID, Some(*b"LO"), &pseudonym));
+        }
+
+        object.insert(DataElement::string(PATIENT_IDENTITY_REMOVED, Some(*b"CS"), "YES"));
+        object.insert(DataElement::string(DEIDENTIFICATION_METHOD, Some(*b"LO"), DEIDENTIFICATION_METHOD_VALUE));
+
+        Ok(())
+    }
+
+    fn apply(&mut self, elements: Vec<DataElement>, syntax: TransferSyntax) -> Result<Vec<DataElement>, DicomError> {
+        let mut result = Vec::with_capacity(elements.len());
+
+        for mut element in elements {
+            match self.profile.action(element.tag) {
+                DeidentificationAction::Remove => continue,
+                DeidentificationAction::Empty => {
+                    element.value.clear();
+                    element.undefined_length = false;
+                }
+                DeidentificationAction::Dummy => {
+                    let vr = element.vr.or_else(|| default_vr(element.tag));
+                    element = DataElement::string(element.tag, element.vr, dummy_value(vr));
+                }
+                DeidentificationAction::RemapUid => {
+                    // UIDs may be multi-valued.
+                    let remapped = element
+                        .as_string()
+                        .split('\\')
+                        .map(|uid| match uid.trim() {
+                            "" => String::new(),
+                            uid => self.map.uid(uid),
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\\");
+
+                    element = DataElement::string(element.tag, element.vr, &remapped);
+                }
+                DeidentificationAction::Keep if element.is_sequence() => {
+                    let items = Dicom::parse_items(&element.value, syntax)?
+                        .into_iter()
+                        .map(|item| self.apply(item, syntax))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    element.value = Dicom::encode_items(&items, syntax);
+                    element.undefined_length = true;
+                }
+                DeidentificationAction::Keep => {}
+            }
+
+            result.push(element);
+        }
+
+        Ok(result)
+    }
+}
+
+fn default_vr(tag: Tag) -> Option<[u8; 2]> {
+    BASIC_PROFILE.iter().find(|(known, _, _)| *known == tag).map(|&(_, vr, _)| vr)
+}
+
+fn dummy_value(vr: Option<[u8; 2]>) -> &'static str {
+    match vr.as_ref() {
+        Some(b"PN") => "ANONYMOUS",
+        Some(b"LO" | b"SH" | b"ST" | b"LT" | b"UT" | b"UC") => "ANONYMIZED",
+        Some(b"DA") => "19000101",
+        Some(b"TM") => "000000",
+        Some(b"DT") => "19000101000000",
+        Some(b"AS") => "000Y",
+        Some(b"IS" | b"DS") => "0",
+        Some(b"CS") => "ANON",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dicom::tests::encode_test_file;
+
+    fn identified_file() -> DicomObject {
+        let data = encode_test_file(&[
+            (tags::SOP_INSTANCE_UID, b"UI", "1.2.3.1.1"),
+            (tags::STUDY_DATE, b"DA", "20240102"),
+            (Tag(0x0008, 0x0080), b"LO", "St. Elsewhere"),
+            (tags::MODALITY, b"CS", "CT"),
+            (tags::PATIENT_NAME, b"PN", "Doe^John"),
+            (tags::PATIENT_ID, b"LO", "12345"),
+            (Tag(0x0010, 0x0030), b"DA", "19700101"),
+            (Tag(0x0029, 0x0010), b"LO", "VENDOR PRIVATE"),
+            (tags::STUDY_INSTANCE_UID, b"UI", "1.2.3"),
+            (tags::SERIES_INSTANCE_UID, b"UI", "1.2.3.1"),
+        ]);
+
+        let mut object = Dicom::parse(&data, true, None).unwrap();
+        object.insert(DataElement::string(tags::MEDIA_STORAGE_SOP_INSTANCE_UID, Some(*b"UI"), "1.2.3.1.1"));
+
+        // A referenced image sequence holding another instance's UID.
+        let items = vec![vec![DataElement::string(Tag(0x0008, 0x1155), Some(*b"UI"), "1.2.3.1.2")]];
+        object.insert(DataElement {
+            tag: Tag(0x0008, 0x1140),
+            vr: Some(*b"SQ"),
+            value: Dicom::encode_items(&items, TransferSyntax::ExplicitVrLittleEndian),
+            undefined_length: true,
+        });
+
+        object
+    }
+
+    #[test]
+    fn test_basic_profile_removes_identifying_attributes() {
+        let profile = DeidentificationProfile::basic();
+        let mut map = DeidentificationMap::default();
+        let mut object = identified_file();
+
+        Deidentifier::new(&profile, &mut map).deidentify(&mut object).unwrap();
+        let object = Dicom::parse(&object.encode(), true, None).unwrap();
+
+        assert_eq!(object.string(tags::PATIENT_NAME).as_deref(), Some("ANON00001"));
+        assert_eq!(object.string(tags::PATIENT_ID).as_deref(), Some("ANON00001"));
+        assert_eq!(object.string(Tag(0x0010, 0x0030)), None);
+        assert_eq!(object.string(tags::STUDY_DATE), None);
+        assert!(object.element(Tag(0x0008, 0x0080)).is_none());
+        assert!(object.element(Tag(0x0029, 0x0010)).is_none());
+        assert_eq!(object.string(tags::MODALITY).as_deref(), Some("CT"));
+        assert_eq!(object.string(PATIENT_IDENTITY_REMOVED).as_deref(), Some("YES"));
+        assert!(object.element(tags::PIXEL_DATA).is_some());
+        assert!(object.element(tags::TRANSFER_SYNTAX_UID).is_some());
+
+        let sop_instance_uid = object.string(tags::SOP_INSTANCE_UID).unwrap();
+        assert!(sop_instance_uid.starts_with("2.25."));
+        assert_eq!(object.string(tags::MEDIA_STORAGE_SOP_INSTANCE_UID), Some(sop_instance_uid.clone()));
+        assert_eq!(map.original_uid(&sop_instance_uid), Some("1.2.3.1.1"));
+    }
+
+    #[test]
+    fn test_basic_profile_removes_unlisted_attributes() {
+        let profile = DeidentificationProfile::basic();
+        let mut map = DeidentificationMap::default();
+
+        let data = encode_test_file(&[
+            (tags::SOP_INSTANCE_UID, b"UI", "1.2.3.1.1"),
+            (tags::MODALITY, b"CS", "MR"),
+            (Tag(0x0010, 0x2180), b"SH", "Miner"),
+            (Tag(0x0010, 0x21B0), b"LT", "Fell down a shaft"),
+            (Tag(0x0028, 0x0010), b"US", "\x00\x02"),
+            (Tag(0x0038, 0x0010), b"LO", "ADM-77"),
+            (Tag(0x0038, 0x0300), b"LO", "Ward 3, bed 12"),
+            // Not in table E.1-1 either, but still names the patient.
+            (Tag(0x0040, 0xA123), b"PN", "Doe^John"),
+        ]);
+        let mut object = Dicom::parse(&data, true, None).unwrap();
+
+        let items = vec![vec![DataElement::string(Tag(0x0010, 0x0020), Some(*b"LO"), "OTHER-1")]];
+        object.insert(DataElement {
+            tag: Tag(0x0010, 0x1002),
+            vr: Some(*b"SQ"),
+            value: Dicom::encode_items(&items, TransferSyntax::ExplicitVrLittleEndian),
+            undefined_length: true,
+        });
+
+        Deidentifier::new(&profile, &mut map).deidentify(&mut object).unwrap();
+        let encoded = object.encode();
+        let object = Dicom::parse(&encoded, true, None).unwrap();
+
+        for tag in [Tag(0x0010, 0x1002), Tag(0x0010, 0x2180), Tag(0x0010, 0x21B0), Tag(0x0038, 0x0010), Tag(0x0038, 0x0300), Tag(0x0040, 0xA123)] {
+            assert!(object.element(tag).is_none(), "{tag:?}");
+        }
+        assert!(!encoded.windows(5).any(|window| window == b"OTHER"));
+        assert_eq!(object.string(tags::MODALITY).as_deref(), Some("MR"));
+        assert!(object.element(Tag(0x0028, 0x0010)).is_some());
+    }
+
+    #[test]
+    fn test_uids_are_remapped_consistently() {
+        let profile = DeidentificationProfile::basic();
+        let mut map = DeidentificationMap::default();
+
+        let mut first = identified_file();
+        let mut second = identified_file();
+        Deidentifier::new(&profile, &mut map).deidentify(&mut first).unwrap();
+        Deidentifier::new(&profile, &mut map).deidentify(&mut second).unwrap();
+
+        assert_eq!(first.string(tags::STUDY_INSTANCE_UID), second.string(tags::STUDY_INSTANCE_UID));
+        assert_eq!(first.string(tags::STUDY_INSTANCE_UID), Some(map.uid("1.2.3")));
+
+        // UIDs inside sequences use the same mapping.
+        let sequence = first.element(Tag(0x0008, 0x1140)).unwrap();
+        let items = Dicom::parse_items(&sequence.value, first.transfer_syntax).unwrap();
+        assert_eq!(items[0][0].as_string(), map.uid("1.2.3.1.2"));
+    }
+
+    #[test]
+    fn test_profile_overrides() {
+        let profile = DeidentificationProfile {
+            pseudonymize_patients: false,
+            default_action: DeidentificationAction::Keep,
+            ..DeidentificationProfile::basic()
+        }
+        .with_rule(tags::STUDY_DATE, DeidentificationAction::Keep)
+        .with_rule(Tag(0x0010, 0x0030), DeidentificationAction::Dummy);
+
+        let mut map = DeidentificationMap::default();
+        let mut object = identified_file();
+        Deidentifier::new(&profile, &mut map).deidentify(&mut object).unwrap();
+
+        assert_eq!(object.string(tags::STUDY_DATE).as_deref(), Some("20240102"));
+        assert_eq!(object.string(Tag(0x0010, 0x0030)).as_deref(), Some("19000101"));
+        assert_eq!(object.string(tags::PATIENT_NAME), None);
+        assert_eq!(object.string(Tag(0x0008, 0x0080)), None);
+    }
+}
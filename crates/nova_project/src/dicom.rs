@@ -0,0 +1,642 @@
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Every DICOM Part 10 file starts with a 128 byte preamble followed by the `DICM` magic.
+pub const DICOM_PREAMBLE_LEN: usize = 128;
+pub const DICOM_MAGIC: &[u8; 4] = b"DICM";
+
+const UNDEFINED_LENGTH: u32 = 0xFFFF_FFFF;
+
+/// `(FFFE,E000)` and `(FFFE,E0DD)` as they appear at the start of an implicit VR little endian sequence value.
+const ITEM_PREFIX: [u8; 4] = [0xFE, 0xFF, 0x00, 0xE0];
+const SEQUENCE_DELIMITATION_PREFIX: [u8; 4] = [0xFE, 0xFF, 0xDD, 0xE0];
+
+/// Most headers fit in the first few kilobytes. Only files with huge headers are read completely.
+const HEADER_PROBE_SIZE: u64 = 64 * 1024;
+
+const IMPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2";
+const EXPLICIT_VR_BIG_ENDIAN: &str = "1.2.840.10008.1.2.2";
+const DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1.99";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Tag(pub u16, pub u16);
+
+impl Tag {
+    pub fn group(&self) -> u16 {
+        self.0
+    }
+
+    pub fn element(&self) -> u16 {
+        self.1
+    }
+}
+
+pub mod tags {
+    use super::Tag;
+
+    pub const FILE_META_INFORMATION_GROUP_LENGTH: Tag = Tag(0x0002, 0x0000);
+    pub const MEDIA_STORAGE_SOP_INSTANCE_UID: Tag = Tag(0x0002, 0x0003);
+    pub const TRANSFER_SYNTAX_UID: Tag = Tag(0x0002, 0x0010);
+    pub const SOP_INSTANCE_UID: Tag = Tag(0x0008, 0x0018);
+    pub const STUDY_DATE: Tag = Tag(0x0008, 0x0020);
+    pub const MODALITY: Tag = Tag(0x0008, 0x0060);
+    pub const STUDY_DESCRIPTION: Tag = Tag(0x0008, 0x1030);
+    pub const SERIES_DESCRIPTION: Tag = Tag(0x0008, 0x103E);
+    pub const PATIENT_NAME: Tag = Tag(0x0010, 0x0010);
+    pub const PATIENT_ID: Tag = Tag(0x0010, 0x0020);
+    pub const BODY_PART_EXAMINED: Tag = Tag(0x0018, 0x0015);
+    pub const STUDY_INSTANCE_UID: Tag = Tag(0x0020, 0x000D);
+    pub const SERIES_INSTANCE_UID: Tag = Tag(0x0020, 0x000E);
+    pub const INSTANCE_NUMBER: Tag = Tag(0x0020, 0x0013);
+    pub const PIXEL_DATA: Tag = Tag(0x7FE0, 0x0010);
+
+    pub(crate) const ITEM: Tag = Tag(0xFFFE, 0xE000);
+    pub(crate) const ITEM_DELIMITATION: Tag = Tag(0xFFFE, 0xE00D);
+    pub(crate) const SEQUENCE_DELIMITATION: Tag = Tag(0xFFFE, 0xE0DD);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferSyntax {
+    ExplicitVrLittleEndian,
+    ImplicitVrLittleEndian,
+    ExplicitVrBigEndian,
+}
+
+impl TransferSyntax {
+    fn from_uid(uid: &str) -> Result<Self, DicomError> {
+        match uid {
+            IMPLICIT_VR_LITTLE_ENDIAN => Ok(TransferSyntax::ImplicitVrLittleEndian),
+            EXPLICIT_VR_BIG_ENDIAN => Ok(TransferSyntax::ExplicitVrBigEndian),
+            DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN => Err(DicomError::UnsupportedTransferSyntax(uid.to_string())),
+            // Every other (including all compressed) transfer syntax encodes the header as explicit VR little endian.
+            _ => Ok(TransferSyntax::ExplicitVrLittleEndian),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DicomError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("File is not a DICOM part 10 file")]
+    NotDicom,
+
+    #[error("DICOM data is truncated")]
+    Truncated,
+
+    #[error("Unsupported transfer syntax: {0}")]
+    UnsupportedTransferSyntax(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct DataElement {
+    pub tag: Tag,
+    /// `None` for implicit VR encoded elements and sequence items.
+    pub vr: Option<[u8; 2]>,
+    /// The raw value. For undefined length elements this includes the encoded items and the delimiter.
+    pub value: Vec<u8>,
+    pub undefined_length: bool,
+}
+
+impl DataElement {
+    /// Creates a string element, padded to an even length as the standard requires.
+    pub fn string(tag: Tag, vr: Option<[u8; 2]>, value: &str) -> Self {
+        let mut value = value.as_bytes().to_vec();
+
+        if !value.len().is_multiple_of(2) {
+            value.push(if vr == Some(*b"UI") { 0 } else { b' ' });
+        }
+
+        Self {
+            tag,
+            vr,
+            value,
+            undefined_length: false,
+        }
+    }
+
+    /// Whether the value holds sequence items. Implicit VR elements are recognized by their first item.
+    pub fn is_sequence(&self) -> bool {
+        match self.vr {
+            Some(vr) => &vr == b"SQ",
+            None => {
+                self.tag.group() != 0xFFFE
+                    && self.tag != tags::PIXEL_DATA
+                    && (self.value.starts_with(&ITEM_PREFIX) || self.value.starts_with(&SEQUENCE_DELIMITATION_PREFIX))
+            }
+        }
+    }
+
+    /// Decodes the value as a DICOM string, stripping the space/NUL padding.
+    pub fn as_string(&self) -> String {
+        String::from_utf8_lossy(&self.value)
+            .trim_end_matches(['\0', ' '])
+            .trim_start()
+            .to_string()
+    }
+}
+
+pub struct DicomObject {
+    pub preamble: Vec<u8>,
+    pub meta: Vec<DataElement>,
+    pub dataset: Vec<DataElement>,
+    pub transfer_syntax: TransferSyntax,
+}
+
+impl DicomObject {
+    pub fn element(&self, tag: Tag) -> Option<&DataElement> {
+        let elements = match tag.group() {
+            0x0002 => &self.meta,
+            _ => &self.dataset,
+        };
+
+        elements.iter().find(|element| element.tag == tag)
+    }
+
+    /// Returns the string value of `tag`, or `None` if the tag is missing or empty.
+    pub fn string(&self, tag: Tag) -> Option<String> {
+        self.element(tag)
+            .map(DataElement::as_string)
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Replaces the element with the same tag, or inserts it in ascending tag order.
+    pub fn insert(&mut self, element: DataElement) {
+        let elements = match element.tag.group() {
+            0x0002 => &mut self.meta,
+            _ => &mut self.dataset,
+        };
+
+        match elements.binary_search_by_key(&element.tag, |existing| existing.tag) {
+            Ok(position) => elements[position] = element,
+            Err(position) => elements.insert(position, element),
+        }
+    }
+
+    /// Encodes the object as a part 10 file. The file meta information group length is recomputed.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut meta = Vec::new();
+        for element in self.meta.iter().filter(|element| element.tag != tags::FILE_META_INFORMATION_GROUP_LENGTH) {
+            encode_element(&mut meta, element, TransferSyntax::ExplicitVrLittleEndian);
+        }
+
+        let mut out = Vec::with_capacity(DICOM_PREAMBLE_LEN + meta.len() + self.dataset.iter().map(|element| element.value.len() + 12).sum::<usize>());
+        out.extend_from_slice(&self.preamble);
+        out.resize(DICOM_PREAMBLE_LEN, 0);
+        out.extend_from_slice(DICOM_MAGIC);
+
+        let group_length = DataElement {
+            tag: tags::FILE_META_INFORMATION_GROUP_LENGTH,
+            vr: Some(*b"UL"),
+            value: (meta.len() as u32).to_le_bytes().to_vec(),
+            undefined_length: false,
+        };
+        encode_element(&mut out, &group_length, TransferSyntax::ExplicitVrLittleEndian);
+        out.extend_from_slice(&meta);
+
+        for element in &self.dataset {
+            encode_element(&mut out, element, self.transfer_syntax);
+        }
+
+        out
+    }
+}
+
+pub struct Dicom {}
+
+impl Dicom {
+    /// Checks for the `DICM` magic at offset 128.
+    /// Works for files without any (or with a misleading) file extension.
+    pub fn has_dicom_preamble(path: &Path) -> bool {
+        let Ok(mut file) = File::open(path) else {
+            return false;
+        };
+
+        let mut header = [0u8; DICOM_PREAMBLE_LEN + DICOM_MAGIC.len()];
+        match file.read_exact(&mut header) {
+            Ok(()) => &header[DICOM_PREAMBLE_LEN..] == DICOM_MAGIC,
+            Err(_) => false,
+        }
+    }
+
+    /// Reads the header of a DICOM file, i.e. every top level element in front of the pixel data.
+    pub fn read_header(path: &Path) -> Result<DicomObject, DicomError> {
+        Self::read_header_from(File::open(path)?)
+    }
+
+    /// Like [`Dicom::read_header`], for content that doesn't come straight from a file, e.g. a decrypting reader.
+    pub fn read_header_from(mut reader: impl Read) -> Result<DicomObject, DicomError> {
+        let mut probe = Vec::new();
+        (&mut reader).take(HEADER_PROBE_SIZE).read_to_end(&mut probe)?;
+
+        let complete = (probe.len() as u64) < HEADER_PROBE_SIZE;
+
+        match Self::parse(&probe, complete, Some(tags::PIXEL_DATA)) {
+            Err(DicomError::Truncated) if !complete => {
+                reader.read_to_end(&mut probe)?;
+                Self::parse(&probe, true, Some(tags::PIXEL_DATA))
+            }
+            result => result,
+        }
+    }
+
+    /// Reads a complete DICOM file, including the pixel data.
+    pub fn read(path: &Path) -> Result<DicomObject, DicomError> {
+        Self::read_from(File::open(path)?)
+    }
+
+    pub fn read_from(mut reader: impl Read) -> Result<DicomObject, DicomError> {
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents)?;
+        Self::parse(&contents, true, None)
+    }
+
+    /// Splits the value of a sequence element into its items.
+    pub fn parse_items(value: &[u8], syntax: TransferSyntax) -> Result<Vec<Vec<DataElement>>, DicomError> {
+        let mut parser = Parser { data: value, pos: 0, syntax };
+        let mut items = Vec::new();
+
+        while parser.pos < value.len() {
+            let item = parser.element()?;
+
+            if item.tag == tags::SEQUENCE_DELIMITATION {
+                break;
+            }
+
+            let mut item_parser = Parser { data: &item.value, pos: 0, syntax };
+            let mut elements = Vec::new();
+
+            while item_parser.pos < item.value.len() {
+                let element = item_parser.element()?;
+
+                if element.tag == tags::ITEM_DELIMITATION {
+                    break;
+                }
+                elements.push(element);
+            }
+
+            items.push(elements);
+        }
+
+        Ok(items)
+    }
+
+    /// Encodes `items` as the value of an undefined length sequence, including the sequence delimiter.
+    pub fn encode_items(items: &[Vec<DataElement>], syntax: TransferSyntax) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        for item in items {
+            let mut value = Vec::new();
+            for element in item {
+                encode_element(&mut value, element, syntax);
+            }
+
+            encode_element(&mut out, &DataElement { tag: tags::ITEM, vr: None, value, undefined_length: false }, syntax);
+        }
+
+        encode_element(&mut out, &DataElement { tag: tags::SEQUENCE_DELIMITATION, vr: None, value: Vec::new(), undefined_length: false }, syntax);
+        out
+    }
+
+    /// Parses `data` until `stop_at` (exclusive) is reached or all elements were read.
+    /// If `complete` is false, `data` is only the beginning of a file and running out of data is an error.
+    pub fn parse(data: &[u8], complete: bool, stop_at: Option<Tag>) -> Result<DicomObject, DicomError> {
+        let magic_end = DICOM_PREAMBLE_LEN + DICOM_MAGIC.len();
+
+        if data.len() < magic_end || &data[DICOM_PREAMBLE_LEN..magic_end] != DICOM_MAGIC {
+            return Err(DicomError::NotDicom);
+        }
+
+        let mut parser = Parser {
+            data,
+            pos: magic_end,
+            syntax: TransferSyntax::ExplicitVrLittleEndian,
+        };
+
+        // The file meta information is always explicit VR little endian.
+        let mut meta = Vec::new();
+        while parser.pos < data.len() && parser.peek_tag()?.group() == 0x0002 {
+            meta.push(parser.element()?);
+        }
+
+        let transfer_syntax = match meta.iter().find(|element| element.tag == tags::TRANSFER_SYNTAX_UID) {
+            Some(element) => TransferSyntax::from_uid(&element.as_string())?,
+            None => TransferSyntax::ExplicitVrLittleEndian,
+        };
+
+        parser.syntax = transfer_syntax;
+
+        let mut dataset = Vec::new();
+        loop {
+            if parser.pos >= data.len() {
+                if !complete {
+                    return Err(DicomError::Truncated);
+                }
+                break;
+            }
+
+            if let Some(stop_at) = stop_at && parser.peek_tag()? >= stop_at {
+                break;
+            }
+
+            dataset.push(parser.element()?);
+        }
+
+        Ok(DicomObject {
+            preamble: data[..DICOM_PREAMBLE_LEN].to_vec(),
+            meta,
+            dataset,
+            transfer_syntax,
+        })
+    }
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    syntax: TransferSyntax,
+}
+
+impl<'a> Parser<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DicomError> {
+        let end = self.pos.checked_add(len).ok_or(DicomError::Truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or(DicomError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, DicomError> {
+        let bytes = [self.take(1)?[0], self.take(1)?[0]];
+        Ok(match self.syntax {
+            TransferSyntax::ExplicitVrBigEndian => u16::from_be_bytes(bytes),
+            _ => u16::from_le_bytes(bytes),
+        })
+    }
+
+    fn u32(&mut self) -> Result<u32, DicomError> {
+        let bytes: [u8; 4] = self.take(4)?.try_into().map_err(|_| DicomError::Truncated)?;
+        Ok(match self.syntax {
+            TransferSyntax::ExplicitVrBigEndian => u32::from_be_bytes(bytes),
+            _ => u32::from_le_bytes(bytes),
+        })
+    }
+
+    fn peek_tag(&mut self) -> Result<Tag, DicomError> {
+        let pos = self.pos;
+        let tag = Tag(self.u16()?, self.u16()?);
+        self.pos = pos;
+        Ok(tag)
+    }
+
+    fn element(&mut self) -> Result<DataElement, DicomError> {
+        let tag = Tag(self.u16()?, self.u16()?);
+
+        // Items and delimiters never carry a VR, regardless of the transfer syntax.
+        let (vr, len) = if tag.group() == 0xFFFE || self.syntax == TransferSyntax::ImplicitVrLittleEndian {
+            (None, self.u32()?)
+        } else {
+            let vr: [u8; 2] = self.take(2)?.try_into().map_err(|_| DicomError::Truncated)?;
+
+            let len = if has_long_length(&vr) {
+                self.take(2)?;
+                self.u32()?
+            } else {
+                self.u16()? as u32
+            };
+
+            (Some(vr), len)
+        };
+
+        let start = self.pos;
+        let undefined_length = len == UNDEFINED_LENGTH;
+
+        if undefined_length {
+            self.skip_until_delimiter()?;
+        } else {
+            self.take(len as usize)?;
+        }
+
+        Ok(DataElement {
+            tag,
+            vr,
+            value: self.data[start..self.pos].to_vec(),
+            undefined_length,
+        })
+    }
+
+    /// Skips the content of an undefined length sequence or item, including its delimiter.
+    fn skip_until_delimiter(&mut self) -> Result<(), DicomError> {
+        loop {
+            let element = self.element()?;
+
+            if element.tag == tags::SEQUENCE_DELIMITATION || element.tag == tags::ITEM_DELIMITATION {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn encode_element(out: &mut Vec<u8>, element: &DataElement, syntax: TransferSyntax) {
+    let big_endian = syntax == TransferSyntax::ExplicitVrBigEndian;
+    let u16_bytes = |value: u16| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+    let u32_bytes = |value: u32| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+
+    // Undefined length values already end with their delimiter.
+    let len = match element.undefined_length {
+        true => UNDEFINED_LENGTH,
+        false => element.value.len() as u32,
+    };
+
+    out.extend_from_slice(&u16_bytes(element.tag.group()));
+    out.extend_from_slice(&u16_bytes(element.tag.element()));
+
+    match element.vr {
+        Some(vr) if element.tag.group() != 0xFFFE && syntax != TransferSyntax::ImplicitVrLittleEndian => {
+            out.extend_from_slice(&vr);
+
+            if has_long_length(&vr) {
+                out.extend_from_slice(&[0, 0]);
+                out.extend_from_slice(&u32_bytes(len));
+            } else {
+                out.extend_from_slice(&u16_bytes(len as u16));
+            }
+        }
+        _ => out.extend_from_slice(&u32_bytes(len)),
+    }
+
+    out.extend_from_slice(&element.value);
+}
+
+fn has_long_length(vr: &[u8; 2]) -> bool {
+    matches!(
+        vr,
+        b"OB" | b"OD" | b"OF" | b"OL" | b"OV" | b"OW" | b"SQ" | b"SV" | b"UC" | b"UN" | b"UR" | b"UT" | b"UV"
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// Encodes a minimal explicit VR little endian part 10 file.
+    pub(crate) fn encode_test_file(elements: &[(Tag, &[u8; 2], &str)]) -> Vec<u8> {
+        fn push_element(out: &mut Vec<u8>, tag: Tag, vr: &[u8; 2], value: &[u8]) {
+            out.extend_from_slice(&tag.group().to_le_bytes());
+            out.extend_from_slice(&tag.element().to_le_bytes());
+            out.extend_from_slice(vr);
+
+            if has_long_length(vr) {
+                out.extend_from_slice(&[0, 0]);
+                out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            } else {
+                out.extend_from_slice(&(value.len() as u16).to_le_bytes());
+            }
+            out.extend_from_slice(value);
+        }
+
+        let mut out = vec![0u8; DICOM_PREAMBLE_LEN];
+        out.extend_from_slice(DICOM_MAGIC);
+        push_element(&mut out, tags::TRANSFER_SYNTAX_UID, b"UI", b"1.2.840.10008.1.2.1\0");
+
+        for (tag, vr, value) in elements {
+            let mut value = value.as_bytes().to_vec();
+            if value.len() % 2 != 0 {
+                value.push(if *vr == b"UI" { 0 } else { b' ' });
+            }
+            push_element(&mut out, *tag, vr, &value);
+        }
+
+        push_element(&mut out, tags::PIXEL_DATA, b"OW", &[0u8; 8]);
+        out
+    }
+
+    #[test]
+    fn test_detects_dicom_preamble_without_extension() {
+        let tmp = tempdir().unwrap();
+        let file = tmp.path().join("IM0001");
+
+        let mut contents = vec![0u8; DICOM_PREAMBLE_LEN];
+        contents.extend_from_slice(DICOM_MAGIC);
+        contents.extend_from_slice(&[0u8; 16]);
+        std::fs::write(&file, contents).unwrap();
+
+        assert!(Dicom::has_dicom_preamble(&file));
+    }
+
+    #[test]
+    fn test_rejects_non_dicom_files() {
+        let tmp = tempdir().unwrap();
+        let short = tmp.path().join("short");
+        let text = tmp.path().join("notes.txt");
+
+        std::fs::write(&short, b"DICM").unwrap();
+        std::fs::write(&text, vec![b'a'; 512]).unwrap();
+
+        assert!(!Dicom::has_dicom_preamble(&short));
+        assert!(!Dicom::has_dicom_preamble(&text));
+        assert!(!Dicom::has_dicom_preamble(&tmp.path().join("missing")));
+    }
+
+    #[test]
+    fn test_read_header_stops_at_pixel_data() {
+        let tmp = tempdir().unwrap();
+        let file = tmp.path().join("IM0001");
+
+        std::fs::write(&file, encode_test_file(&[
+            (tags::MODALITY, b"CS", "CT"),
+            (tags::PATIENT_NAME, b"PN", "Doe^John"),
+            (tags::STUDY_INSTANCE_UID, b"UI", "1.2.3"),
+            (tags::INSTANCE_NUMBER, b"IS", "7"),
+        ])).unwrap();
+
+        let header = Dicom::read_header(&file).unwrap();
+
+        assert_eq!(header.transfer_syntax, TransferSyntax::ExplicitVrLittleEndian);
+        assert_eq!(header.string(tags::MODALITY).as_deref(), Some("CT"));
+        assert_eq!(header.string(tags::PATIENT_NAME).as_deref(), Some("Doe^John"));
+        assert_eq!(header.string(tags::STUDY_INSTANCE_UID).as_deref(), Some("1.2.3"));
+        assert_eq!(header.string(tags::INSTANCE_NUMBER).as_deref(), Some("7"));
+        assert!(header.element(tags::PIXEL_DATA).is_none());
+    }
+
+    #[test]
+    fn test_parse_skips_undefined_length_sequences() {
+        let mut data = encode_test_file(&[(tags::MODALITY, b"CS", "MR")]);
+        let pixel_data_pos = data.len() - 20;
+        let pixel_data = data.split_off(pixel_data_pos);
+
+        // (0008,1115) SQ, undefined length, one undefined length item holding a single element.
+        data.extend_from_slice(&[0x08, 0x00, 0x15, 0x11, b'S', b'Q', 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+        data.extend_from_slice(&[0xFE, 0xFF, 0x00, 0xE0, 0xFF, 0xFF, 0xFF, 0xFF]);
+        data.extend_from_slice(&[0x08, 0x00, 0x50, 0x11, b'U', b'I', 2, 0, b'1', 0]);
+        data.extend_from_slice(&[0xFE, 0xFF, 0x0D, 0xE0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0xFE, 0xFF, 0xDD, 0xE0, 0, 0, 0, 0]);
+        data.extend_from_slice(&pixel_data);
+
+        let object = Dicom::parse(&data, true, None).unwrap();
+
+        let sequence = object.element(Tag(0x0008, 0x1115)).unwrap();
+        assert!(sequence.undefined_length);
+        assert!(object.element(tags::PIXEL_DATA).is_some());
+        assert_eq!(object.string(tags::MODALITY).as_deref(), Some("MR"));
+    }
+
+    #[test]
+    fn test_encode_roundtrip_with_edited_elements() {
+        let data = encode_test_file(&[
+            (tags::SOP_INSTANCE_UID, b"UI", "1.2.3.1"),
+            (tags::PATIENT_NAME, b"PN", "Doe^John"),
+        ]);
+
+        let mut object = Dicom::parse(&data, true, None).unwrap();
+        object.insert(DataElement::string(tags::PATIENT_NAME, Some(*b"PN"), "Roe^Jane"));
+        object.insert(DataElement::string(tags::MODALITY, Some(*b"CS"), "CT"));
+
+        let reparsed = Dicom::parse(&object.encode(), true, None).unwrap();
+
+        assert_eq!(reparsed.string(tags::PATIENT_NAME).as_deref(), Some("Roe^Jane"));
+        assert_eq!(reparsed.string(tags::MODALITY).as_deref(), Some("CT"));
+        assert!(reparsed.element(tags::FILE_META_INFORMATION_GROUP_LENGTH).is_some());
+        assert_eq!(reparsed.element(tags::PIXEL_DATA).unwrap().value, vec![0u8; 8]);
+
+        let tags: Vec<Tag> = reparsed.dataset.iter().map(|element| element.tag).collect();
+        assert!(tags.is_sorted());
+    }
+
+    #[test]
+    fn test_sequence_items_roundtrip() {
+        let items = vec![
+            vec![DataElement::string(Tag(0x0008, 0x1150), Some(*b"UI"), "1.2.840.10008.5.1.4.1.1.2")],
+            vec![DataElement::string(Tag(0x0008, 0x1155), Some(*b"UI"), "1.2.3.4")],
+        ];
+
+        for syntax in [TransferSyntax::ExplicitVrLittleEndian, TransferSyntax::ExplicitVrBigEndian] {
+            let parsed = Dicom::parse_items(&Dicom::encode_items(&items, syntax), syntax).unwrap();
+
+            assert_eq!(parsed.len(), 2);
+            assert_eq!(parsed[1][0].as_string(), "1.2.3.4");
+        }
+
+        let implicit = DataElement {
+            tag: Tag(0x0008, 0x1115),
+            vr: None,
+            value: Dicom::encode_items(&items, TransferSyntax::ImplicitVrLittleEndian),
+            undefined_length: true,
+        };
+        assert!(implicit.is_sequence());
+    }
+
+    #[test]
+    fn test_parse_reports_truncated_input() {
+        let data = encode_test_file(&[(tags::MODALITY, b"CS", "CT")]);
+
+        let result = Dicom::parse(&data[..data.len() - 4], true, None);
+        assert!(matches!(result, Err(DicomError::Truncated)));
+    }
+}
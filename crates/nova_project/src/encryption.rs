@@ -0,0 +1,118 @@
+use std::io;
+use std::path::Path;
+use keyring::Entry;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::debug;
+use uuid::Uuid;
+use nova_crypto::crypto::CryptoError;
+use nova_crypto::data_key::DataKey;
+use nova_crypto::envelope::{EnvelopeError, Secret};
+use nova_crypto::key_derivation::PASSPHRASE_LENGTH;
+use nova_crypto::password_generator::PasswordGenerator;
+
+/// Keyring entries are named after the key id of the project.
+const KEYRING_SERVICE: &str = "com.nova.project";
+
+#[derive(Error, Debug)]
+pub enum EncryptionError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("The project is encrypted and needs its passphrase to open")]
+    PassphraseRequired,
+
+    #[error("Wrong passphrase, or the data key of the project is damaged")]
+    WrongSecret,
+
+    #[error("Data key error: {0}")]
+    Envelope(#[from] EnvelopeError),
+
+    #[error("Crypto error: {0}")]
+    Crypto(#[from] CryptoError),
+
+    #[error("Keyring error: {0}")]
+    Keyring(#[from] keyring::Error),
+
+    #[error("Failed to generate a passphrase for the keyring")]
+    PassphraseGeneration,
+}
+
+/// What the data key of a project is wrapped with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyProtection {
+    /// A passphrase the user enters on every open.
+    Passphrase,
+    /// A random passphrase kept in the OS keyring, like the login session.
+    Keyring,
+}
+
+/// Stored in the manifest, so a project can tell what it needs to be opened before touching any encrypted file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncryptionInfo {
+    pub protection: KeyProtection,
+    /// Names the keyring entry and is sealed into the wrapped key.
+    pub key_id: String,
+}
+
+/// Requested when a project is created. Not `Debug`, since it holds the passphrase.
+#[derive(Clone, Deserialize)]
+#[serde(tag = "protection", rename_all = "snake_case")]
+pub enum ProjectEncryption {
+    Passphrase { passphrase: String },
+    Keyring,
+}
+
+impl ProjectEncryption {
+    pub fn protection(&self) -> KeyProtection {
+        match self {
+            Self::Passphrase { .. } => KeyProtection::Passphrase,
+            Self::Keyring => KeyProtection::Keyring,
+        }
+    }
+
+    /// Generates the data key of a new project and writes it, wrapped, to `data_key_file`.
+    pub fn create(&self, data_key_file: &Path) -> Result<(EncryptionInfo, DataKey), EncryptionError> {
+        let key = DataKey::generate()?;
+        let key_id = Uuid::new_v4().to_string();
+
+        let wrapped = match self {
+            Self::Passphrase { passphrase } => key.wrap(Secret::Passphrase { passphrase, pepper: None }, &key_id)?,
+            Self::Keyring => {
+                let passphrase = PasswordGenerator::default()
+                    .generate(PASSPHRASE_LENGTH)
+                    .ok_or(EncryptionError::PassphraseGeneration)?;
+
+                let wrapped = key.wrap(Secret::Passphrase { passphrase: &passphrase, pepper: None }, &key_id)?;
+                Entry::new(KEYRING_SERVICE, &key_id)?.set_password(&passphrase)?;
+                debug!("Stored project key passphrase to keyring entry. service={KEYRING_SERVICE}, user={key_id}");
+                wrapped
+            }
+        };
+
+        if let Some(parent) = data_key_file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(data_key_file, wrapped)?;
+
+        Ok((EncryptionInfo { protection: self.protection(), key_id }, key))
+    }
+}
+
+impl EncryptionInfo {
+    /// Unwraps the data key in `data_key_file`. `passphrase` is ignored for keyring protection.
+    pub fn unlock(&self, data_key_file: &Path, passphrase: Option<&str>) -> Result<DataKey, EncryptionError> {
+        let wrapped = std::fs::read(data_key_file)?;
+
+        let passphrase = match self.protection {
+            KeyProtection::Passphrase => passphrase.ok_or(EncryptionError::PassphraseRequired)?.to_string(),
+            KeyProtection::Keyring => Entry::new(KEYRING_SERVICE, &self.key_id)?.get_password()?,
+        };
+
+        DataKey::unwrap(&wrapped, Secret::Passphrase { passphrase: &passphrase, pepper: None }).map_err(|err| match err {
+            EnvelopeError::Crypto(_) => EncryptionError::WrongSecret,
+            err => err.into(),
+        })
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::VecDeque;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use nova_fs::encrypted_fs::EncryptedFs;
+use crate::annotations::{Annotation, Windowing};
+use crate::metadata::ProjectMetadata;
+
+/// Bump whenever the persisted layout changes in a way that older versions can't read.
+pub const HISTORY_VERSION: u32 = 1;
+
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to (de)serialize history: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    #[error("Nothing to undo")]
+    NothingToUndo,
+
+    #[error("Nothing to redo")]
+    NothingToRedo,
+
+    #[error("No group is open")]
+    NoOpenGroup,
+}
+
+/// A single reversible edit. Each variant stores enough state to apply it in both directions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProjectCommand {
+    Rename {
+        from: String,
+        to: String,
+    },
+    SetMetadata {
+        before: ProjectMetadata,
+        after: ProjectMetadata,
+    },
+    RemoveImport {
+        file: PathBuf,
+        /// Position in the manifest's import list, so undo puts it back where it was.
+        position: usize,
+        /// Directory below `trash` that holds the removed content until the command leaves the history.
+        trash: String,
+        /// Removed entries, relative to `projectFiles`.
+        moved: Vec<PathBuf>,
+    },
+    AddAnnotation {
+        annotation: Annotation,
+    },
+    UpdateAnnotation {
+        before: Annotation,
+        after: Annotation,
+    },
+    RemoveAnnotation {
+        annotation: Annotation,
+    },
+    SetWindowing {
+        series_instance_uid: String,
+        before: Option<Windowing>,
+        after: Option<Windowing>,
+    },
+}
+
+impl ProjectCommand {
+    /// The trash directory this command keeps content in, if any.
+    pub fn trash(&self) -> Option<&str> {
+        match self {
+            ProjectCommand::RemoveImport { trash, .. } => Some(trash),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProjectCommand::Rename { .. } => "Rename project",
+            ProjectCommand::SetMetadata { .. } => "Edit project details",
+            ProjectCommand::RemoveImport { .. } => "Remove import",
+            ProjectCommand::AddAnnotation { .. } => "Add annotation",
+            ProjectCommand::UpdateAnnotation { .. } => "Edit annotation",
+            ProjectCommand::RemoveAnnotation { .. } => "Delete annotation",
+            ProjectCommand::SetWindowing { .. } => "Change windowing",
+        }
+    }
+}
+
+/// One undo step: either a single command or a group of related ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub label: String,
+    pub commands: Vec<ProjectCommand>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryState {
+    /// Labels of the undo stack, most recent last.
+    pub undo: Vec<String>,
+    /// Labels of the redo stack, next redo last.
+    pub redo: Vec<String>,
+}
+
+/// Bounded undo/redo stacks. Recording a new entry clears the redo stack.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandHistory {
+    version: u32,
+    limit: usize,
+    undo: VecDeque<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+    /// Commands recorded since the outermost `begin_group`, only in memory.
+    #[serde(skip)]
+    group: Option<HistoryEntry>,
+    #[serde(skip)]
+    group_depth: usize,
+}
+
+impl Default for CommandHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+impl CommandHistory {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            version: HISTORY_VERSION,
+            limit,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            group: None,
+            group_depth: 0,
+        }
+    }
+
+    /// A missing, outdated or unreadable history file simply means "no history".
+    pub fn load(path: &Path, fs: &EncryptedFs) -> Result<Option<Self>, HistoryError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let history: Self = serde_json::from_str(&fs.read_to_string(path)?)?;
+
+        if history.version != HISTORY_VERSION {
+            return Ok(None);
+        }
+
+        Ok(Some(history))
+    }
+
+    pub fn save(&self, path: &Path, fs: &EncryptedFs) -> Result<(), HistoryError> {
+        fs.write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Changes the limit. Returns the entries that no longer fit, oldest first.
+    pub fn set_limit(&mut self, limit: usize) -> Vec<HistoryEntry> {
+        self.limit = limit;
+        self.trim()
+    }
+
+    /// Every command on the undo stack, oldest first.
+    pub fn undo_commands(&self) -> impl Iterator<Item = &ProjectCommand> {
+        self.undo.iter().flat_map(|entry| &entry.commands)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn state(&self) -> HistoryState {
+        HistoryState {
+            undo: self.undo.iter().map(|entry| entry.label.clone()).collect(),
+            redo: self.redo.iter().map(|entry| entry.label.clone()).collect(),
+        }
+    }
+
+    /// Starts collecting commands into a single undo step. Nested groups are merged into the outermost one.
+    pub fn begin_group(&mut self, label: &str) {
+        if self.group.is_none() {
+            self.group = Some(HistoryEntry { label: label.to_string(), commands: Vec::new() });
+        }
+        self.group_depth += 1;
+    }
+
+    /// Closes the current group. Returns the entries that left the history, see [`CommandHistory::record`].
+    pub fn end_group(&mut self) -> Result<Vec<HistoryEntry>, HistoryError> {
+        if self.group_depth == 0 {
+            return Err(HistoryError::NoOpenGroup);
+        }
+
+        self.group_depth -= 1;
+        if self.group_depth > 0 {
+            return Ok(Vec::new());
+        }
+
+        match self.group.take() {
+            Some(group) if !group.commands.is_empty() => Ok(self.push(group)),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Records an already applied command. Returns every entry that left the history (the redo stack,
+    /// and the oldest undo entries beyond the limit), so the caller can release what they hold on to.
+    pub fn record(&mut self, command: ProjectCommand) -> Vec<HistoryEntry> {
+        if let Some(group) = &mut self.group {
+            group.commands.push(command);
+            return Vec::new();
+        }
+
+        let label = command.label().to_string();
+        self.push(HistoryEntry { label, commands: vec![command] })
+    }
+
+    /// Takes the most recent entry off the undo stack. The caller reverts it and hands it to [`CommandHistory::undone`].
+    pub fn pop_undo(&mut self) -> Result<HistoryEntry, HistoryError> {
+        self.undo.pop_back().ok_or(HistoryError::NothingToUndo)
+    }
+
+    pub fn undone(&mut self, entry: HistoryEntry) {
+        self.redo.push(entry);
+    }
+
+    /// Puts back an entry that failed to undo, so it can be tried again. Unlike a new entry, it leaves the redo stack alone.
+    pub fn restore_undo(&mut self, entry: HistoryEntry) {
+        self.undo.push_back(entry);
+    }
+
+    /// Puts back an entry that failed to redo.
+    pub fn restore_redo(&mut self, entry: HistoryEntry) {
+        self.redo.push(entry);
+    }
+
+    pub fn pop_redo(&mut self) -> Result<HistoryEntry, HistoryError> {
+        self.redo.pop().ok_or(HistoryError::NothingToRedo)
+    }
+
+    pub fn redone(&mut self, entry: HistoryEntry) -> Vec<HistoryEntry> {
+        self.undo.push_back(entry);
+        self.trim()
+    }
+
+    /// Drops everything. Returns the dropped entries.
+    pub fn clear(&mut self) -> Vec<HistoryEntry> {
+        self.group = None;
+        self.group_depth = 0;
+        self.undo.drain(..).chain(self.redo.drain(..)).collect()
+    }
+
+    fn push(&mut self, entry: HistoryEntry) -> Vec<HistoryEntry> {
+        let mut dropped: Vec<HistoryEntry> = self.redo.drain(..).collect();
+
+        self.undo.push_back(entry);
+        dropped.extend(self.trim());
+        dropped
+    }
+
+    fn trim(&mut self) -> Vec<HistoryEntry> {
+        let excess = self.undo.len().saturating_sub(self.limit);
+        self.undo.drain(..excess).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn rename(from: &str, to: &str) -> ProjectCommand {
+        ProjectCommand::Rename { from: from.to_string(), to: to.to_string() }
+    }
+
+    #[test]
+    fn test_undo_stack_is_bounded() {
+        let mut history = CommandHistory::new(2);
+
+        assert!(history.record(rename("a", "b")).is_empty());
+        assert!(history.record(rename("b", "c")).is_empty());
+
+        let dropped = history.record(rename("c", "d"));
+        assert_eq!(dropped[0].commands, vec![rename("a", "b")]);
+        assert_eq!(history.state().undo.len(), 2);
+
+        assert_eq!(history.set_limit(1).len(), 1);
+        assert_eq!(history.pop_undo().unwrap().commands, vec![rename("c", "d")]);
+        assert!(matches!(history.pop_undo(), Err(HistoryError::NothingToUndo)));
+    }
+
+    #[test]
+    fn test_new_command_clears_redo() {
+        let mut history = CommandHistory::default();
+        history.record(rename("a", "b"));
+
+        let entry = history.pop_undo().unwrap();
+        history.undone(entry);
+        assert!(history.can_redo());
+
+        let dropped = history.record(rename("a", "c"));
+        assert_eq!(dropped.len(), 1);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn test_grouped_commands_are_one_step() {
+        let mut history = CommandHistory::default();
+
+        history.begin_group("Rename twice");
+        history.record(rename("a", "b"));
+        history.begin_group("ignored, nested");
+        history.record(rename("b", "c"));
+        history.end_group().unwrap();
+        assert!(history.state().undo.is_empty());
+        history.end_group().unwrap();
+
+        assert!(matches!(history.end_group(), Err(HistoryError::NoOpenGroup)));
+        assert_eq!(history.state().undo, vec!["Rename twice".to_string()]);
+        assert_eq!(history.pop_undo().unwrap().commands.len(), 2);
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("history.json");
+
+        let mut history = CommandHistory::new(10);
+        history.record(rename("a", "b"));
+        history.save(&path, &EncryptedFs::plaintext()).unwrap();
+
+        assert_eq!(CommandHistory::load(&path, &EncryptedFs::plaintext()).unwrap(), Some(history));
+        assert_eq!(CommandHistory::load(&tmp.path().join("missing.json"), &EncryptedFs::plaintext()).unwrap(), None);
+    }
+}
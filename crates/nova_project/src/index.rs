@@ -0,0 +1,470 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, warn};
+use nova_fs::encrypted_fs::EncryptedFs;
+use crate::dicom::{tags, Dicom, DicomObject};
+
+/// Bump whenever the persisted layout changes. Older indices are discarded and rebuilt.
+pub const INDEX_VERSION: u32 = 3;
+
+#[derive(Error, Debug)]
+pub enum IndexError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to (de)serialize index: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstanceRecord {
+    pub study_instance_uid: String,
+    pub series_instance_uid: String,
+    pub sop_instance_uid: String,
+    pub instance_number: Option<i32>,
+    pub modality: Option<String>,
+    pub patient_name: Option<String>,
+    pub patient_id: Option<String>,
+    /// `YYYYMMDD`, as stored in the file.
+    pub study_date: Option<String>,
+    pub study_description: Option<String>,
+    pub series_description: Option<String>,
+    pub body_part_examined: Option<String>,
+}
+
+impl InstanceRecord {
+    /// Returns `None` if one of the UIDs needed to place the instance in the hierarchy is missing.
+    pub fn from_header(header: &DicomObject) -> Option<Self> {
+        Some(Self {
+            study_instance_uid: header.string(tags::STUDY_INSTANCE_UID)?,
+            series_instance_uid: header.string(tags::SERIES_INSTANCE_UID)?,
+            sop_instance_uid: header.string(tags::SOP_INSTANCE_UID)?,
+            instance_number: header.string(tags::INSTANCE_NUMBER).and_then(|number| number.parse().ok()),
+            modality: header.string(tags::MODALITY),
+            patient_name: header.string(tags::PATIENT_NAME),
+            patient_id: header.string(tags::PATIENT_ID),
+            study_date: header.string(tags::STUDY_DATE),
+            study_description: header.string(tags::STUDY_DESCRIPTION),
+            series_description: header.string(tags::SERIES_DESCRIPTION),
+            body_part_examined: header.string(tags::BODY_PART_EXAMINED),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedFile {
+    size: u64,
+    modified_ms: u64,
+    /// BLAKE3 of the content when the file was first indexed. Kept on refresh, so
+    /// [`ProjectIndex::verify`] can tell that a file was changed behind nova's back.
+    hash: String,
+    instance: InstanceRecord,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Instance {
+    pub sop_instance_uid: String,
+    pub instance_number: Option<i32>,
+    /// Path relative to the `projectFiles` directory.
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Series {
+    pub series_instance_uid: String,
+    pub modality: Option<String>,
+    pub series_description: Option<String>,
+    pub body_part_examined: Option<String>,
+    pub instances: Vec<Instance>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Study {
+    pub study_instance_uid: String,
+    pub patient_name: Option<String>,
+    pub patient_id: Option<String>,
+    pub study_date: Option<String>,
+    pub study_description: Option<String>,
+    pub series: Vec<Series>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct IndexUpdate {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+/// Differences between the index and the files on disk.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IndexVerification {
+    /// Indexed, but gone from disk.
+    pub missing: Vec<PathBuf>,
+    /// Content differs from when the file was indexed.
+    pub modified: Vec<PathBuf>,
+    /// On disk, but not indexed.
+    pub orphaned: Vec<PathBuf>,
+}
+
+/// Study → series → instance index of every DICOM file inside `projectFiles`.
+///
+/// Files are keyed by their path relative to `projectFiles`. Size and modification time are
+/// stored alongside, so [`ProjectIndex::refresh`] only has to re-read files that actually changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectIndex {
+    version: u32,
+    files: BTreeMap<PathBuf, IndexedFile>,
+}
+
+impl Default for ProjectIndex {
+    fn default() -> Self {
+        Self {
+            version: INDEX_VERSION,
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+impl ProjectIndex {
+    pub fn load(path: &Path, fs: &EncryptedFs) -> Result<Self, IndexError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let index: Self = serde_json::from_str(&fs.read_to_string(path)?)?;
+
+        if index.version != INDEX_VERSION {
+            warn!("Discarding project index with version {} (expected {})", index.version, INDEX_VERSION);
+            return Ok(Self::default());
+        }
+
+        Ok(index)
+    }
+
+    pub fn save(&self, path: &Path, fs: &EncryptedFs) -> Result<(), IndexError> {
+        fs.write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Brings the index in sync with the files in `project_files_dir`.
+    /// Unchanged files are not read again, deleted files are dropped.
+    pub fn refresh(&mut self, project_files_dir: &Path, fs: &EncryptedFs) -> IndexUpdate {
+        let mut update = IndexUpdate::default();
+        let mut found = Vec::new();
+
+        if let Err(err) = Self::collect_files(project_files_dir, project_files_dir, &mut found) {
+            warn!("Failed to scan {:?} for dicom files: {}", project_files_dir, err);
+            return update;
+        }
+
+        let found_paths: HashSet<&PathBuf> = found.iter().map(|(path, _, _)| path).collect();
+        let before = self.files.len();
+        self.files.retain(|relative, _| found_paths.contains(relative));
+        update.removed = before - self.files.len();
+
+        for (relative, size, modified_ms) in found {
+            if let Some(existing) = self.files.get(&relative)
+                && existing.size == size
+                && existing.modified_ms == modified_ms
+            {
+                continue;
+            }
+
+            let full_path = project_files_dir.join(&relative);
+            let record = fs
+                .open(&full_path)
+                .ok()
+                .and_then(|file| Dicom::read_header_from(file).ok())
+                .and_then(|header| InstanceRecord::from_header(&header));
+
+            let Some(instance) = record else {
+                debug!("Not indexing {:?}: no readable dicom header", full_path);
+                update.removed += self.files.remove(&relative).is_some() as usize;
+                continue;
+            };
+
+            let hash = match self.files.get(&relative) {
+                Some(existing) => existing.hash.clone(),
+                None => match hash_file(&full_path) {
+                    Ok(hash) => hash,
+                    Err(err) => {
+                        warn!("Not indexing {:?}: {}", full_path, err);
+                        continue;
+                    }
+                },
+            };
+
+            let previous = self.files.insert(relative, IndexedFile { size, modified_ms, hash, instance });
+
+            match previous {
+                Some(_) => update.updated += 1,
+                None => update.added += 1,
+            }
+        }
+
+        debug!("Project index refreshed: {:?}", update);
+        update
+    }
+
+    /// Drops `relative` and everything below it, so the next [`ProjectIndex::refresh`] reads it again
+    /// even if size and modification time did not change.
+    pub fn forget(&mut self, relative: &Path) {
+        self.files.retain(|path, _| !path.starts_with(relative));
+    }
+
+    /// Drops everything and indexes `project_files_dir` from scratch, accepting the current content of every file.
+    pub fn rebuild(&mut self, project_files_dir: &Path, fs: &EncryptedFs) -> IndexUpdate {
+        let previous = std::mem::take(&mut self.files);
+        let mut update = self.refresh(project_files_dir, fs);
+
+        update.updated = self.files.keys().filter(|relative| previous.contains_key(*relative)).count();
+        update.added -= update.updated;
+        update.removed = previous.keys().filter(|relative| !self.files.contains_key(*relative)).count();
+        update
+    }
+
+    /// Compares every indexed file with the disk, including its content hash. Reads every file completely.
+    pub fn verify(&self, project_files_dir: &Path) -> Result<IndexVerification, IndexError> {
+        let mut verification = IndexVerification::default();
+        let mut found = Vec::new();
+
+        if project_files_dir.exists() {
+            Self::collect_files(project_files_dir, project_files_dir, &mut found)?;
+        }
+
+        let found: BTreeMap<PathBuf, u64> = found.into_iter().map(|(relative, size, _)| (relative, size)).collect();
+
+        for (relative, file) in &self.files {
+            match found.get(relative) {
+                None => verification.missing.push(relative.clone()),
+                Some(size) if *size != file.size || hash_file(&project_files_dir.join(relative))? != file.hash => {
+                    verification.modified.push(relative.clone());
+                }
+                Some(_) => {}
+            }
+        }
+
+        verification.orphaned = found.into_keys().filter(|relative| !self.files.contains_key(relative)).collect();
+        Ok(verification)
+    }
+
+    pub fn instance(&self, sop_instance_uid: &str) -> Option<(&Path, &InstanceRecord)> {
+        self.files
+            .iter()
+            .find(|(_, file)| file.instance.sop_instance_uid == sop_instance_uid)
+            .map(|(path, file)| (path.as_path(), &file.instance))
+    }
+
+    pub fn instances(&self) -> impl Iterator<Item = (&Path, &InstanceRecord)> {
+        self.files.iter().map(|(path, file)| (path.as_path(), &file.instance))
+    }
+
+    /// Builds the study → series → instance hierarchy. Instances are ordered by their instance number.
+    pub fn studies(&self) -> Vec<Study> {
+        let mut studies: BTreeMap<&str, Study> = BTreeMap::new();
+        let mut series_by_study: BTreeMap<&str, BTreeMap<&str, Series>> = BTreeMap::new();
+
+        for (path, file) in &self.files {
+            let record = &file.instance;
+
+            studies.entry(&record.study_instance_uid).or_insert_with(|| Study {
+                study_instance_uid: record.study_instance_uid.clone(),
+                patient_name: record.patient_name.clone(),
+                patient_id: record.patient_id.clone(),
+                study_date: record.study_date.clone(),
+                study_description: record.study_description.clone(),
+                series: Vec::new(),
+            });
+
+            series_by_study
+                .entry(&record.study_instance_uid)
+                .or_default()
+                .entry(&record.series_instance_uid)
+                .or_insert_with(|| Series {
+                    series_instance_uid: record.series_instance_uid.clone(),
+                    modality: record.modality.clone(),
+                    series_description: record.series_description.clone(),
+                    body_part_examined: record.body_part_examined.clone(),
+                    instances: Vec::new(),
+                })
+                .instances
+                .push(Instance {
+                    sop_instance_uid: record.sop_instance_uid.clone(),
+                    instance_number: record.instance_number,
+                    file: path.clone(),
+                });
+        }
+
+        studies
+            .into_iter()
+            .map(|(study_uid, mut study)| {
+                study.series = series_by_study
+                    .remove(study_uid)
+                    .unwrap_or_default()
+                    .into_values()
+                    .map(|mut series| {
+                        series.instances.sort_by_key(|instance| instance.instance_number.unwrap_or(i32::MAX));
+                        series
+                    })
+                    .collect();
+                study
+            })
+            .collect()
+    }
+
+    fn collect_files(root: &Path, dir: &Path, found: &mut Vec<(PathBuf, u64, u64)>) -> io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+
+            if metadata.is_dir() {
+                Self::collect_files(root, &path, found)?;
+                continue;
+            }
+
+            let modified_ms = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_millis() as u64)
+                .unwrap_or_default();
+
+            if let Ok(relative) = path.strip_prefix(root) {
+                found.push((relative.to_path_buf(), metadata.len(), modified_ms));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nova_crypto::data_key::DataKey;
+    use crate::dicom::tests::encode_test_file;
+    use tempfile::tempdir;
+
+    fn write_instance(dir: &Path, name: &str, series: &str, sop: &str, number: &str) {
+        std::fs::write(dir.join(name), encode_test_file(&[
+            (tags::SOP_INSTANCE_UID, b"UI", sop),
+            (tags::MODALITY, b"CS", "CT"),
+            (tags::PATIENT_NAME, b"PN", "Doe^John"),
+            (tags::STUDY_INSTANCE_UID, b"UI", "1.2.3"),
+            (tags::SERIES_INSTANCE_UID, b"UI", series),
+            (tags::INSTANCE_NUMBER, b"IS", number),
+        ])).unwrap();
+    }
+
+    #[test]
+    fn test_builds_study_series_instance_hierarchy() {
+        let tmp = tempdir().unwrap();
+        write_instance(tmp.path(), "a.dcm", "1.2.3.1", "1.2.3.1.2", "2");
+        write_instance(tmp.path(), "b.dcm", "1.2.3.1", "1.2.3.1.1", "1");
+        write_instance(tmp.path(), "c.dcm", "1.2.3.2", "1.2.3.2.1", "1");
+        std::fs::write(tmp.path().join("notes.txt"), "not dicom").unwrap();
+
+        let mut index = ProjectIndex::default();
+        let update = index.refresh(tmp.path(), &EncryptedFs::plaintext());
+
+        assert_eq!(update, IndexUpdate { added: 3, updated: 0, removed: 0 });
+
+        let studies = index.studies();
+        assert_eq!(studies.len(), 1);
+        assert_eq!(studies[0].patient_name.as_deref(), Some("Doe^John"));
+        assert_eq!(studies[0].series.len(), 2);
+
+        let first_series = &studies[0].series[0];
+        assert_eq!(first_series.modality.as_deref(), Some("CT"));
+        assert_eq!(first_series.instances[0].sop_instance_uid, "1.2.3.1.1");
+        assert_eq!(first_series.instances[1].sop_instance_uid, "1.2.3.1.2");
+    }
+
+    #[test]
+    fn test_refresh_is_incremental() {
+        let tmp = tempdir().unwrap();
+        write_instance(tmp.path(), "a.dcm", "1.2.3.1", "1.2.3.1.1", "1");
+        write_instance(tmp.path(), "b.dcm", "1.2.3.1", "1.2.3.1.2", "2");
+
+        let mut index = ProjectIndex::default();
+        index.refresh(tmp.path(), &EncryptedFs::plaintext());
+
+        assert_eq!(index.refresh(tmp.path(), &EncryptedFs::plaintext()), IndexUpdate::default());
+
+        std::fs::remove_file(tmp.path().join("b.dcm")).unwrap();
+        write_instance(tmp.path(), "c.dcm", "1.2.3.1", "1.2.3.1.3", "3");
+
+        assert_eq!(index.refresh(tmp.path(), &EncryptedFs::plaintext()), IndexUpdate { added: 1, updated: 0, removed: 1 });
+        assert!(index.instance("1.2.3.1.3").is_some());
+        assert!(index.instance("1.2.3.1.2").is_none());
+    }
+
+    #[test]
+    fn test_verify_reports_missing_modified_and_orphaned_files() {
+        let tmp = tempdir().unwrap();
+        write_instance(tmp.path(), "a.dcm", "1.2.3.1", "1.2.3.1.1", "1");
+        write_instance(tmp.path(), "b.dcm", "1.2.3.1", "1.2.3.1.2", "2");
+
+        let mut index = ProjectIndex::default();
+        index.refresh(tmp.path(), &EncryptedFs::plaintext());
+        assert_eq!(index.verify(tmp.path()).unwrap(), IndexVerification::default());
+
+        std::fs::remove_file(tmp.path().join("a.dcm")).unwrap();
+        write_instance(tmp.path(), "b.dcm", "1.2.3.1", "1.2.3.1.9", "2");
+        std::fs::write(tmp.path().join("notes.txt"), "not dicom").unwrap();
+
+        // A refresh picks up the new header, but keeps the hash from the first import.
+        index.refresh(tmp.path(), &EncryptedFs::plaintext());
+
+        let verification = index.verify(tmp.path()).unwrap();
+        assert!(verification.missing.is_empty());
+        assert_eq!(verification.modified, vec![PathBuf::from("b.dcm")]);
+        assert_eq!(verification.orphaned, vec![PathBuf::from("notes.txt")]);
+
+        assert_eq!(index.rebuild(tmp.path(), &EncryptedFs::plaintext()), IndexUpdate { added: 0, updated: 1, removed: 0 });
+        assert!(index.verify(tmp.path()).unwrap().modified.is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let tmp = tempdir().unwrap();
+        let files = tmp.path().join("files");
+        std::fs::create_dir(&files).unwrap();
+        write_instance(&files, "a.dcm", "1.2.3.1", "1.2.3.1.1", "1");
+
+        // Sealed in place, the way an encrypted project stores its files.
+        let fs = EncryptedFs::new(DataKey::generate().unwrap(), tmp.path());
+        let file = files.join("a.dcm");
+        fs.write(&file, std::fs::read(&file).unwrap()).unwrap();
+
+        let mut index = ProjectIndex::default();
+        index.refresh(&files, &fs);
+
+        let index_path = tmp.path().join("index.json");
+        index.save(&index_path, &fs).unwrap();
+        assert!(ProjectIndex::load(&index_path, &EncryptedFs::plaintext()).is_err());
+
+        let loaded = ProjectIndex::load(&index_path, &fs).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.instance("1.2.3.1.1").unwrap().0, Path::new("a.dcm"));
+    }
+}
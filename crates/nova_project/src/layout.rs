@@ -0,0 +1,201 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+const PROJECT_FILES_DIR: &str = "projectFiles";
+const CACHE_DIR: &str = "cache";
+const EXPORTS_DIR: &str = "exports";
+const ANNOTATIONS_DIR: &str = "annotations";
+const PRIVATE_DIR: &str = "private";
+const RECOVERY_DIR: &str = "recovery";
+const TRASH_DIR: &str = "trash";
+const THUMBNAILS_DIR: &str = "thumbnails";
+const STAGING_DIR: &str = "staging";
+
+const MANIFEST_FILE: &str = "project.json";
+const SEALED_MANIFEST_FILE: &str = "manifest.json";
+const HISTORY_FILE: &str = "history.json";
+const INDEX_FILE: &str = "projectIndex.json";
+const ANNOTATIONS_FILE: &str = "annotations.json";
+const DEIDENTIFICATION_MAP_FILE: &str = "deidentificationMap.json";
+const RECOVERY_JOURNAL_FILE: &str = "journal.json";
+const SESSION_LOCK_FILE: &str = "session.lock";
+const THUMBNAIL_CACHE_FILE: &str = "thumbnails.json";
+const AUDIT_LOG_FILE: &str = "audit.jsonl";
+const DATA_KEY_FILE: &str = "dataKey.bin";
+
+/// Knows where every well-known file and directory of a project lives.
+/// Nothing outside of this type should join paths onto the working directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectLayout {
+    root: PathBuf,
+}
+
+impl ProjectLayout {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Imported DICOM data, copied (and unzipped) from the import sources.
+    pub fn project_files_dir(&self) -> PathBuf {
+        self.root.join(PROJECT_FILES_DIR)
+    }
+
+    /// Derived data that can always be rebuilt from `projectFiles`.
+    pub fn cache_dir(&self) -> PathBuf {
+        self.root.join(CACHE_DIR)
+    }
+
+    pub fn exports_dir(&self) -> PathBuf {
+        self.root.join(EXPORTS_DIR)
+    }
+
+    pub fn annotations_dir(&self) -> PathBuf {
+        self.root.join(ANNOTATIONS_DIR)
+    }
+
+    /// Data that must never leave the machine, not even with an export.
+    pub fn private_dir(&self) -> PathBuf {
+        self.root.join(PRIVATE_DIR)
+    }
+
+    /// Autosave state. Only meaningful after an unclean shutdown.
+    pub fn recovery_dir(&self) -> PathBuf {
+        self.root.join(RECOVERY_DIR)
+    }
+
+    /// Content of removed imports, kept for as long as the removal can be undone.
+    pub fn trash_dir(&self) -> PathBuf {
+        self.root.join(TRASH_DIR)
+    }
+
+    /// One PNG per series, created on demand.
+    pub fn thumbnails_dir(&self) -> PathBuf {
+        self.cache_dir().join(THUMBNAILS_DIR)
+    }
+
+    /// Plaintext an encrypted project needs on disk for a moment, e.g. for readers that only take a path.
+    /// Emptied whenever the project is opened.
+    pub fn staging_dir(&self) -> PathBuf {
+        self.private_dir().join(STAGING_DIR)
+    }
+
+    pub fn manifest_file(&self) -> PathBuf {
+        self.root.join(MANIFEST_FILE)
+    }
+
+    /// Everything of the manifest of an encrypted project but what is needed to unlock it.
+    pub fn sealed_manifest_file(&self) -> PathBuf {
+        self.private_dir().join(SEALED_MANIFEST_FILE)
+    }
+
+    pub fn history_file(&self) -> PathBuf {
+        self.root.join(HISTORY_FILE)
+    }
+
+    pub fn index_file(&self) -> PathBuf {
+        self.cache_dir().join(INDEX_FILE)
+    }
+
+    pub fn thumbnail_cache_file(&self) -> PathBuf {
+        self.thumbnails_dir().join(THUMBNAIL_CACHE_FILE)
+    }
+
+    pub fn annotations_file(&self) -> PathBuf {
+        self.annotations_dir().join(ANNOTATIONS_FILE)
+    }
+
+    pub fn deidentification_map_file(&self) -> PathBuf {
+        self.private_dir().join(DEIDENTIFICATION_MAP_FILE)
+    }
+
+    /// Who imported, removed and exported what. Kept private since import paths tend to contain patient names.
+    pub fn audit_log_file(&self) -> PathBuf {
+        self.private_dir().join(AUDIT_LOG_FILE)
+    }
+
+    /// The data key of an encrypted project, wrapped with its passphrase.
+    pub fn data_key_file(&self) -> PathBuf {
+        self.private_dir().join(DATA_KEY_FILE)
+    }
+
+    pub fn recovery_journal_file(&self) -> PathBuf {
+        self.recovery_dir().join(RECOVERY_JOURNAL_FILE)
+    }
+
+    /// Exists while the project is open. Finding it on open means the last session did not close the project.
+    pub fn session_lock_file(&self) -> PathBuf {
+        self.recovery_dir().join(SESSION_LOCK_FILE)
+    }
+
+    pub fn directories(&self) -> [PathBuf; 7] {
+        [
+            self.project_files_dir(),
+            self.cache_dir(),
+            self.exports_dir(),
+            self.annotations_dir(),
+            self.private_dir(),
+            self.recovery_dir(),
+            self.trash_dir(),
+        ]
+    }
+
+    pub fn create_directories(&self) -> io::Result<()> {
+        for dir in self.directories() {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Resolves what the user picked when opening a project: either the manifest itself or the project directory.
+    pub fn from_selection(selection: &Path) -> Self {
+        match selection.file_name() {
+            Some(file_name) if file_name == MANIFEST_FILE => {
+                Self::new(selection.parent().unwrap_or(Path::new("")))
+            }
+            _ => Self::new(selection),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_well_known_paths_are_below_root() {
+        let layout = ProjectLayout::new("/data/projects/knee");
+
+        assert_eq!(layout.project_files_dir(), Path::new("/data/projects/knee/projectFiles"));
+        assert_eq!(layout.index_file(), Path::new("/data/projects/knee/cache/projectIndex.json"));
+
+        for dir in layout.directories() {
+            assert!(dir.starts_with(layout.root()));
+            assert!(!dir.to_string_lossy().contains('\\'));
+        }
+    }
+
+    #[test]
+    fn test_create_directories() {
+        let tmp = tempdir().unwrap();
+        let layout = ProjectLayout::new(tmp.path());
+
+        layout.create_directories().unwrap();
+
+        for dir in layout.directories() {
+            assert!(dir.is_dir());
+        }
+    }
+
+    #[test]
+    fn test_from_selection() {
+        let root = Path::new("/data/projects/knee");
+
+        assert_eq!(ProjectLayout::from_selection(root).root(), root);
+        assert_eq!(ProjectLayout::from_selection(&root.join("project.json")).root(), root);
+    }
+}
@@ -1 +1,16 @@
-pub mod project;
\ No newline at end of file
+pub mod project;
+pub mod dicom;
+pub mod index;
+pub mod layout;
+pub mod manifest;
+pub mod annotations;
+pub mod deidentify;
+pub mod recovery;
+pub mod session_lock;
+pub mod history;
+pub mod registry;
+pub mod thumbnails;
+pub mod search;
+pub mod metadata;
+pub mod audit;
+pub mod encryption;
\ No newline at end of file
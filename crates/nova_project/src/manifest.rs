@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use nova_fs::encrypted_fs::EncryptedFs;
+use nova_settings::settings_store::SettingsOverrides;
+use crate::encryption::EncryptionInfo;
+use crate::layout::ProjectLayout;
+use crate::metadata::ProjectMetadata;
+
+/// Bump whenever the manifest layout changes in a way that older versions can't read.
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum ManifestError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to (de)serialize manifest: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    #[error("Unsupported manifest version {0} (newest supported is {MANIFEST_VERSION})")]
+    UnsupportedVersion(u32),
+}
+
+/// What `project.json` of an encrypted project holds: just enough to tell how to unlock the rest,
+/// which is sealed in [`ProjectLayout::sealed_manifest_file`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestHeader {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<EncryptionInfo>,
+}
+
+impl ManifestHeader {
+    /// Reads the header of any manifest, encrypted or not.
+    pub fn load(path: &Path) -> Result<Self, ManifestError> {
+        let header: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+        if header.version > MANIFEST_VERSION {
+            return Err(ManifestError::UnsupportedVersion(header.version));
+        }
+
+        Ok(header)
+    }
+}
+
+/// The persisted description of a project. Everything else in the working directory is either
+/// imported data or can be rebuilt from it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectManifest {
+    pub version: u32,
+    pub project_name: String,
+    #[serde(default)]
+    pub imported_files: Vec<PathBuf>,
+    /// Where imports went inside `projectFiles` when their file name was already taken by another one.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub import_destinations: BTreeMap<PathBuf, PathBuf>,
+    #[serde(flatten)]
+    pub metadata: ProjectMetadata,
+    /// Settings that apply to this project only, on top of the global settings.
+    #[serde(default, skip_serializing_if = "SettingsOverrides::is_empty")]
+    pub settings: SettingsOverrides,
+    /// Set for projects that are encrypted at rest. Only this stays readable, the rest of the manifest is sealed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<EncryptionInfo>,
+}
+
+impl ProjectManifest {
+    pub fn new(project_name: String, imported_files: Vec<PathBuf>) -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            project_name,
+            imported_files,
+            import_destinations: BTreeMap::new(),
+            metadata: ProjectMetadata::default(),
+            settings: SettingsOverrides::new(),
+            encryption: None,
+        }
+    }
+
+    /// Reads the manifest of the project at `layout`. `fs` is only used for an encrypted project,
+    /// whose header names what unlocks it, see [`ManifestHeader::load`].
+    pub fn load(layout: &ProjectLayout, fs: &EncryptedFs) -> Result<Self, ManifestError> {
+        let header = ManifestHeader::load(&layout.manifest_file())?;
+
+        let manifest: Self = match &header.encryption {
+            Some(_) => serde_json::from_str(&fs.read_to_string(&layout.sealed_manifest_file())?)?,
+            None => serde_json::from_str(&std::fs::read_to_string(layout.manifest_file())?)?,
+        };
+
+        if manifest.version > MANIFEST_VERSION {
+            return Err(ManifestError::UnsupportedVersion(manifest.version));
+        }
+
+        Ok(Self { encryption: header.encryption, ..manifest })
+    }
+
+    /// Writes to temporary files first, so a crash never leaves a half written manifest behind.
+    /// An encrypted project gets its manifest sealed through `fs`, next to a header in the clear.
+    pub fn save(&self, layout: &ProjectLayout, fs: &EncryptedFs) -> Result<(), ManifestError> {
+        let clear = match &self.encryption {
+            Some(encryption) => {
+                fs.write_atomic(&layout.sealed_manifest_file(), serde_json::to_string_pretty(self)?)?;
+                serde_json::to_string_pretty(&ManifestHeader { version: self.version, encryption: Some(encryption.clone()) })?
+            }
+            None => serde_json::to_string_pretty(self)?,
+        };
+
+        EncryptedFs::plaintext().write_atomic(&layout.manifest_file(), clear)?;
+        Ok(())
+    }
+}
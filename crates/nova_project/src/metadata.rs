@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub const MAX_TAGS: usize = 32;
+pub const MAX_TAG_LEN: usize = 40;
+pub const MAX_DESCRIPTION_LEN: usize = 10_000;
+pub const MAX_FIELDS: usize = 64;
+pub const MAX_FIELD_KEY_LEN: usize = 64;
+pub const MAX_FIELD_TEXT_LEN: usize = 1_000;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum MetadataError {
+    #[error("tags must not be empty")]
+    EmptyTag,
+
+    #[error("tag \"{0}\" is longer than {MAX_TAG_LEN} characters")]
+    TagTooLong(String),
+
+    #[error("tag \"{0}\" is given more than once")]
+    DuplicateTag(String),
+
+    #[error("a project can have at most {MAX_TAGS} tags")]
+    TooManyTags,
+
+    #[error("description is longer than {MAX_DESCRIPTION_LEN} characters")]
+    DescriptionTooLong,
+
+    #[error("a project can have at most {MAX_FIELDS} custom fields")]
+    TooManyFields,
+
+    #[error("field name \"{0}\" must be 1 to {MAX_FIELD_KEY_LEN} letters, digits, '_' or '-'")]
+    InvalidFieldKey(String),
+
+    #[error("field \"{key}\" is invalid: {reason}")]
+    InvalidFieldValue { key: String, reason: String },
+}
+
+/// A typed value of a custom field.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum FieldValue {
+    Text(String),
+    Number(f64),
+    Boolean(bool),
+    /// `YYYY-MM-DD`
+    Date(String),
+}
+
+impl FieldValue {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            FieldValue::Text(text) if text.chars().count() > MAX_FIELD_TEXT_LEN => {
+                Err(format!("text is longer than {MAX_FIELD_TEXT_LEN} characters"))
+            }
+            FieldValue::Number(number) if !number.is_finite() => Err("number must be finite".to_string()),
+            FieldValue::Date(date) if !is_valid_date(date) => Err(format!("\"{date}\" is not a YYYY-MM-DD date")),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What the user records about a project besides its name: tags, a description and custom fields.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectMetadata {
+    /// User-defined labels, e.g. to find the project again through search.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, FieldValue>,
+}
+
+impl ProjectMetadata {
+    /// Trims tags and the description, then checks every limit.
+    pub fn normalized(mut self) -> Result<Self, MetadataError> {
+        self.tags = self.tags.iter().map(|tag| tag.trim().to_string()).collect();
+        self.description = self.description.trim().to_string();
+        self.validate()?;
+        Ok(self)
+    }
+
+    pub fn validate(&self) -> Result<(), MetadataError> {
+        if self.tags.len() > MAX_TAGS {
+            return Err(MetadataError::TooManyTags);
+        }
+
+        for (position, tag) in self.tags.iter().enumerate() {
+            if tag.trim().is_empty() {
+                return Err(MetadataError::EmptyTag);
+            }
+
+            if tag.chars().count() > MAX_TAG_LEN {
+                return Err(MetadataError::TagTooLong(tag.clone()));
+            }
+
+            if self.tags[..position].iter().any(|other| other.to_lowercase() == tag.to_lowercase()) {
+                return Err(MetadataError::DuplicateTag(tag.clone()));
+            }
+        }
+
+        if self.description.chars().count() > MAX_DESCRIPTION_LEN {
+            return Err(MetadataError::DescriptionTooLong);
+        }
+
+        if self.fields.len() > MAX_FIELDS {
+            return Err(MetadataError::TooManyFields);
+        }
+
+        for (key, value) in &self.fields {
+            let valid_key = (1..=MAX_FIELD_KEY_LEN).contains(&key.chars().count())
+                && key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+
+            if !valid_key {
+                return Err(MetadataError::InvalidFieldKey(key.clone()));
+            }
+
+            value
+                .validate()
+                .map_err(|reason| MetadataError::InvalidFieldValue { key: key.clone(), reason })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own.to_lowercase() == tag.to_lowercase())
+    }
+}
+
+/// Narrows a project listing. Every condition that is set has to hold.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ProjectFilter {
+    /// Projects must carry all of these tags (case-insensitive).
+    pub tags: Vec<String>,
+    /// Projects must have these fields with exactly these values.
+    pub fields: BTreeMap<String, FieldValue>,
+    /// Case-insensitive substring of the name or the description.
+    pub text: Option<String>,
+}
+
+impl ProjectFilter {
+    pub fn matches(&self, project_name: &str, metadata: &ProjectMetadata) -> bool {
+        let tags = self.tags.iter().all(|tag| metadata.has_tag(tag));
+        let fields = self.fields.iter().all(|(key, value)| metadata.fields.get(key) == Some(value));
+        let text = self.text.as_deref().is_none_or(|text| {
+            let text = text.to_lowercase();
+            project_name.to_lowercase().contains(&text) || metadata.description.to_lowercase().contains(&text)
+        });
+
+        tags && fields && text
+    }
+}
+
+fn is_valid_date(date: &str) -> bool {
+    let parts: Vec<&str> = date.split('-').collect();
+
+    let [year, month, day] = parts.as_slice() else {
+        return false;
+    };
+
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return false;
+    }
+
+    let (Ok(year), Ok(month), Ok(day)) = (year.parse::<u32>(), month.parse::<u32>(), day.parse::<u32>()) else {
+        return false;
+    };
+
+    let leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+
+    (1..=days).contains(&day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(tags: &[&str], fields: &[(&str, FieldValue)]) -> ProjectMetadata {
+        ProjectMetadata {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            description: String::new(),
+            fields: fields.iter().map(|(key, value)| (key.to_string(), value.clone())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(metadata(&[" knee ", "ortho"], &[]).normalized().unwrap().tags, vec!["knee", "ortho"]);
+        assert_eq!(metadata(&["knee", "Knee"], &[]).validate(), Err(MetadataError::DuplicateTag("Knee".to_string())));
+        assert_eq!(metadata(&["  "], &[]).normalized(), Err(MetadataError::EmptyTag));
+
+        assert!(metadata(&[], &[("due", FieldValue::Date("2024-02-29".to_string()))]).validate().is_ok());
+        assert!(matches!(
+            metadata(&[], &[("due", FieldValue::Date("2023-02-29".to_string()))]).validate(),
+            Err(MetadataError::InvalidFieldValue { .. })
+        ));
+        assert!(matches!(
+            metadata(&[], &[("score", FieldValue::Number(f64::NAN))]).validate(),
+            Err(MetadataError::InvalidFieldValue { .. })
+        ));
+        assert_eq!(
+            metadata(&[], &[("has space", FieldValue::Boolean(true))]).validate(),
+            Err(MetadataError::InvalidFieldKey("has space".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_fields_roundtrip_with_their_type() {
+        let metadata = metadata(&[], &[("reviewed", FieldValue::Boolean(true)), ("slices", FieldValue::Number(120.0))]);
+        let json = serde_json::to_value(&metadata).unwrap();
+
+        assert_eq!(json["fields"]["reviewed"], serde_json::json!({ "type": "boolean", "value": true }));
+        assert_eq!(serde_json::from_value::<ProjectMetadata>(json).unwrap(), metadata);
+    }
+
+    #[test]
+    fn test_filter() {
+        let metadata = ProjectMetadata {
+            description: "Follow-up after ACL surgery".to_string(),
+            ..metadata(&["Knee", "ortho"], &[("reviewed", FieldValue::Boolean(true))])
+        };
+
+        assert!(ProjectFilter::default().matches("Study", &metadata));
+        assert!(ProjectFilter { tags: vec!["knee".to_string()], ..Default::default() }.matches("Study", &metadata));
+        assert!(!ProjectFilter { tags: vec!["knee".to_string(), "spine".to_string()], ..Default::default() }.matches("Study", &metadata));
+        assert!(ProjectFilter { text: Some("acl".to_string()), ..Default::default() }.matches("Study", &metadata));
+
+        let unreviewed = ProjectFilter {
+            fields: BTreeMap::from([("reviewed".to_string(), FieldValue::Boolean(false))]),
+            ..Default::default()
+        };
+        assert!(!unreviewed.matches("Study", &metadata));
+    }
+}
@@ -1,27 +1,149 @@
+use std::collections::{BTreeMap, HashSet};
 use std::io;
-use std::path::{Path};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use anyhow::anyhow;
 use arc_swap::ArcSwap;
-use serde::Deserialize;
-use tempfile::tempdir;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 use nova_compression::zip::{UnzipAppError, Zip};
+use nova_fs::encrypted_fs::EncryptedFs;
 use nova_fs::file_system::FileSystem;
-
-const ALLOWED_FILE_EXTENSIONS: [&str; 3] = ["zip", "dcm", "dicom"];
+use nova_settings::settings_store::SettingsOverrides;
+use crate::audit::{AuditEntry, AuditError, AuditEvent, AuditLog, UNKNOWN_ACTOR};
+use crate::annotations::{Annotation, AnnotationError, AnnotationKind, AnnotationStore, Windowing};
+use crate::deidentify::{DeidentificationError, DeidentificationMap, DeidentificationProfile, Deidentifier};
+use crate::dicom::{Dicom, DicomError};
+use crate::encryption::{EncryptionError, EncryptionInfo, ProjectEncryption};
+use crate::history::{CommandHistory, HistoryEntry, HistoryError, HistoryState, ProjectCommand};
+use crate::index::{hash_file, IndexError, IndexUpdate, IndexVerification, ProjectIndex, Study};
+use crate::layout::ProjectLayout;
+use crate::manifest::{ManifestError, ManifestHeader, ProjectManifest};
+use crate::metadata::{MetadataError, ProjectMetadata};
+use crate::recovery::{RecoveryError, RecoveryInfo, RecoveryJournal};
+use crate::session_lock::{SessionLock, SessionState};
+use crate::thumbnails::{ThumbnailCache, ThumbnailEntry, ThumbnailError, ThumbnailRenderer, ThumbnailSummary, THUMBNAIL_SIZE};
 
 #[derive(Deserialize)]
 pub struct ProjectParams {
     pub project_name: String,
-    pub working_directory: String,
-    pub imported_files: Vec<String>,
+    pub working_directory: PathBuf,
+    pub imported_files: Vec<PathBuf>,
+    #[serde(default)]
+    pub metadata: ProjectMetadata,
+    /// Who creates the project, for the audit log. Filled in by the backend from the session, never by the frontend.
+    #[serde(skip)]
+    pub actor: Option<String>,
+    /// Encrypts everything the project stores about its data. Can't be changed later.
+    #[serde(default)]
+    pub encryption: Option<ProjectEncryption>,
+}
+
+#[derive(Deserialize)]
+pub struct DuplicateParams {
+    pub project_name: String,
+    pub working_directory: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExportOptions {
+    /// `None` exports the files unchanged.
+    #[serde(default)]
+    pub deidentification: Option<DeidentificationProfile>,
+    #[serde(default = "default_include_annotations")]
+    pub include_annotations: bool,
+}
+
+fn default_include_annotations() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportSummary {
+    pub exported: usize,
+    /// Indexed files that could not be read (and therefore not de-identified) during the export.
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct VerifyReport {
+    #[serde(flatten)]
+    pub files: IndexVerification,
+    /// Manifest entries whose copy inside `projectFiles` is gone.
+    pub missing_imports: Vec<PathBuf>,
+    /// Annotations on instances that are no longer part of the project.
+    pub dangling_annotations: Vec<u64>,
+    /// Set if the problems were repaired after the report was taken.
+    pub repaired: bool,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.files.missing.is_empty()
+            && self.files.modified.is_empty()
+            && self.files.orphaned.is_empty()
+            && self.missing_imports.is_empty()
+            && self.dangling_annotations.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImportKind {
+    Zip,
+    Dicom,
+}
+
+struct ImportSource {
+    path: PathBuf,
+    /// Destination relative to the `projectFiles` directory.
+    destination: PathBuf,
+    kind: ImportKind,
+}
+
+/// What [`Project::copy_into_project`] did with an import source. Both carry the hash of its content.
+enum CopyOutcome {
+    Copied(String),
+    /// The destination already held the same content, so nothing was copied.
+    AlreadyPresent(String),
 }
 
 pub struct Project {
     pub project_name: ArcSwap<String>,
-    pub working_directory: ArcSwap<String>,
-    pub imported_files: ArcSwap<Vec<String>>,
+    pub working_directory: ArcSwap<PathBuf>,
+    pub imported_files: ArcSwap<Vec<PathBuf>>,
+    /// See [`ProjectManifest::import_destinations`]. May still hold removed imports, so undo can put them back.
+    import_destinations: ArcSwap<BTreeMap<PathBuf, PathBuf>>,
+    pub index: ArcSwap<ProjectIndex>,
+    pub annotations: ArcSwap<AnnotationStore>,
+    pub metadata: ArcSwap<ProjectMetadata>,
+    pub settings: ArcSwap<SettingsOverrides>,
+    /// Serializes read-modify-write cycles of the `ArcSwap` fields. Readers never take it.
+    write_lock: Mutex<()>,
+    /// Held for a whole export, so concurrent exports can't hand out different UIDs for the same original.
+    export_lock: Mutex<()>,
+    /// Bumped on every in-memory edit that is not saved right away. The project is dirty while
+    /// it differs from `saved_generation`, and needs an autosave while it differs from `journaled_generation`.
+    generation: AtomicU64,
+    saved_generation: AtomicU64,
+    journaled_generation: AtomicU64,
+    /// The journal left behind by a session that didn't close the project, until it is restored or discarded.
+    recovery: Mutex<Option<RecoveryJournal>>,
+    /// Held from open until close. Dropping the project without closing it leaves the lock file behind.
+    session: Mutex<Option<SessionLock>>,
+    history: Mutex<CommandHistory>,
+    /// Whether the history is written on save, so undo survives a reopen.
+    persist_history: AtomicBool,
+    thumbnails: Mutex<ThumbnailCache>,
+    audit: Mutex<AuditLog>,
+    /// The JWT subject recorded in the audit log.
+    actor: ArcSwap<String>,
+    encryption: Option<EncryptionInfo>,
+    /// Every read and write of imported data and project state goes through here.
+    fs: EncryptedFs,
 }
 
 #[derive(Error, Debug)]
@@ -34,106 +156,2368 @@ pub enum ProjectError {
 
     #[error("Unzip error: {0}")]
     UnzipAppError(#[from] UnzipAppError),
+
+    #[error("Index error: {0}")]
+    Index(#[from] IndexError),
+
+    #[error("Manifest error: {0}")]
+    Manifest(#[from] ManifestError),
+
+    #[error("Annotation error: {0}")]
+    Annotation(#[from] AnnotationError),
+
+    #[error("De-identification error: {0}")]
+    Deidentification(#[from] DeidentificationError),
+
+    #[error("Destination {0:?} is not empty")]
+    DestinationNotEmpty(PathBuf),
+
+    #[error("Destination {0:?} lies inside the project")]
+    DestinationInsideProject(PathBuf),
+
+    #[error("Recovery error: {0}")]
+    Recovery(#[from] RecoveryError),
+
+    #[error("History error: {0}")]
+    History(#[from] HistoryError),
+
+    #[error("{0:?} is not an import of this project")]
+    UnknownImport(PathBuf),
+
+    #[error("{0:?} is already taken by another import")]
+    ImportConflict(PathBuf),
+
+    #[error("Thumbnail error: {0}")]
+    Thumbnail(#[from] ThumbnailError),
+
+    #[error("Invalid metadata: {0}")]
+    Metadata(#[from] MetadataError),
+
+    #[error("Audit log error: {0}")]
+    Audit(#[from] AuditError),
+
+    #[error("Encryption error: {0}")]
+    Encryption(#[from] EncryptionError),
+
+    #[error("Project is open in another instance (process {0})")]
+    OpenElsewhere(u32),
+
+    #[error("Project is already open")]
+    AlreadyOpen,
 }
 
 impl Project {
 
     pub async fn new_project(project_params: ProjectParams) -> Result<Self, ProjectError> {
+        let layout = ProjectLayout::new(&project_params.working_directory);
+        let metadata = project_params.metadata.normalized()?;
+
         // The UI has already shown a big yellow warning that the contents of the
         // selected folder will be overwritten or deleted, and the user explicitly
         // confirmed (otherwise we wouldn’t be here).
         // At this point, data loss is the user’s decision, not a bug.
         // It’s called informed consent.
-        FileSystem::clear_dir_par(&project_params.working_directory)?;
-
-        if project_params.imported_files.is_empty() {
-            return Ok(
-                Self {
-                    project_name: ArcSwap::from_pointee(project_params.project_name),
-                    working_directory: ArcSwap::from_pointee(project_params.working_directory),
-                    imported_files: ArcSwap::from_pointee(project_params.imported_files)
+        // Still, never pull the project out from under a session that has it open.
+        match SessionLock::state(&layout.session_lock_file())? {
+            SessionState::OpenElsewhere(pid) => return Err(ProjectError::OpenElsewhere(pid)),
+            SessionState::OpenHere => return Err(ProjectError::AlreadyOpen),
+            SessionState::Abandoned | SessionState::Closed => {}
+        }
+
+        if layout.root().exists() {
+            FileSystem::clear_dir_par(layout.root())?;
+        }
+
+        layout.create_directories()?;
+
+        let (encryption, fs) = match &project_params.encryption {
+            Some(encryption) => {
+                let (info, key) = encryption.create(&layout.data_key_file())?;
+                (Some(info), EncryptedFs::new(key, layout.root()))
+            }
+            None => (None, EncryptedFs::plaintext()),
+        };
+
+        let mut import_destinations = BTreeMap::new();
+        let sources = Self::load_imported_files(&project_params.imported_files, &[], &mut import_destinations, &layout, &fs).await?;
+
+        let project = Self::from_parts(
+            layout,
+            ProjectManifest {
+                metadata,
+                encryption,
+                import_destinations,
+                ..ProjectManifest::new(project_params.project_name, project_params.imported_files)
+            },
+            ProjectIndex::default(),
+            AnnotationStore::default(),
+            fs,
+        );
+
+        if let Some(actor) = project_params.actor {
+            project.set_actor(&actor);
+        }
+
+        project.audit_imports(sources)?;
+        project.save_manifest()?;
+        project.start_session()?;
+        project.refresh_index().await?;
+        Ok(project)
+    }
+
+    /// Opens an existing project. `selection` is either the project directory or its manifest file.
+    pub async fn open(selection: &Path) -> Result<Self, ProjectError> {
+        Self::open_with_passphrase(selection, None).await
+    }
+
+    /// Opens a project that may be encrypted. The passphrase is only needed if its data key is protected
+    /// by one, otherwise opening fails with [`EncryptionError::PassphraseRequired`].
+    pub async fn open_with_passphrase(selection: &Path, passphrase: Option<&str>) -> Result<Self, ProjectError> {
+        let layout = ProjectLayout::from_selection(selection);
+        let header = ManifestHeader::load(&layout.manifest_file())?;
+
+        let fs = match &header.encryption {
+            Some(encryption) => EncryptedFs::new(encryption.unlock(&layout.data_key_file(), passphrase)?, layout.root()),
+            None => EncryptedFs::plaintext(),
+        };
+
+        let manifest = ProjectManifest::load(&layout, &fs)?;
+        info!("Opening project \"{}\" from {:?}", manifest.project_name, layout.root());
+
+        // Directories added in later versions are simply created on open.
+        layout.create_directories()?;
+
+        let index = ProjectIndex::load(&layout.index_file(), &fs)?;
+        let annotations = AnnotationStore::load(&layout.annotations_file(), &fs)?;
+        let project = Self::from_parts(layout, manifest, index, annotations, fs);
+
+        project.start_session()?;
+        project.refresh_index().await?;
+        Ok(project)
+    }
+
+    /// Imports additional files into an already existing project.
+    pub async fn import(&self, files: Vec<PathBuf>) -> Result<IndexUpdate, ProjectError> {
+        let layout = self.layout();
+
+        let imported = self.imported_files.load_full();
+        let mut import_destinations = BTreeMap::clone(&self.import_destinations.load());
+        let sources = Self::load_imported_files(&files, &imported, &mut import_destinations, &layout, &self.fs).await?;
+        let replaced: Vec<PathBuf> = sources
+            .iter()
+            .filter(|(_, outcome)| matches!(outcome, CopyOutcome::Copied(_)))
+            .flat_map(|(source, _)| match source.kind {
+                ImportKind::Zip => vec![source.destination.clone(), source.destination.with_extension("")],
+                ImportKind::Dicom => vec![source.destination.clone()],
+            })
+            .collect();
+        self.audit_imports(sources)?;
+
+        let guard = self.write_lock.lock();
+        self.import_destinations.store(Arc::new(import_destinations));
+
+        // A copy can replace a file of the same size within the same millisecond.
+        let mut index = ProjectIndex::clone(&self.index.load());
+        for destination in &replaced {
+            index.forget(destination);
+        }
+        self.index.store(Arc::new(index));
+
+        let mut imported_files = Vec::clone(&self.imported_files.load());
+        for file in files {
+            if !imported_files.contains(&file) {
+                imported_files.push(file);
+            }
+        }
+        self.imported_files.store(Arc::new(imported_files));
+        self.save_manifest()?;
+        drop(guard);
+
+        self.refresh_index().await
+    }
+
+    fn from_parts(layout: ProjectLayout, manifest: ProjectManifest, index: ProjectIndex, annotations: AnnotationStore, fs: EncryptedFs) -> Self {
+        Self {
+            project_name: ArcSwap::from_pointee(manifest.project_name),
+            working_directory: ArcSwap::from_pointee(layout.root().to_path_buf()),
+            imported_files: ArcSwap::from_pointee(manifest.imported_files),
+            import_destinations: ArcSwap::from_pointee(manifest.import_destinations),
+            index: ArcSwap::from_pointee(index),
+            annotations: ArcSwap::from_pointee(annotations),
+            metadata: ArcSwap::from_pointee(manifest.metadata),
+            settings: ArcSwap::from_pointee(manifest.settings),
+            write_lock: Mutex::new(()),
+            export_lock: Mutex::new(()),
+            generation: AtomicU64::new(0),
+            saved_generation: AtomicU64::new(0),
+            journaled_generation: AtomicU64::new(0),
+            recovery: Mutex::new(None),
+            session: Mutex::new(None),
+            history: Mutex::new(CommandHistory::default()),
+            persist_history: AtomicBool::new(true),
+            thumbnails: Mutex::new(ThumbnailCache::default()),
+            audit: Mutex::new(AuditLog::new(layout.audit_log_file(), fs.clone())),
+            actor: ArcSwap::from_pointee(UNKNOWN_ACTOR.to_string()),
+            encryption: manifest.encryption,
+            fs,
+        }
+    }
+
+    /// Claims the session lock. If the previous session never released it, its journal is kept for [`Project::restore_recovery`].
+    fn start_session(&self) -> Result<(), ProjectError> {
+        let layout = self.layout();
+        let lock_file = layout.session_lock_file();
+
+        match SessionLock::state(&lock_file)? {
+            SessionState::OpenElsewhere(pid) => return Err(ProjectError::OpenElsewhere(pid)),
+            SessionState::OpenHere => return Err(ProjectError::AlreadyOpen),
+            SessionState::Abandoned => {
+                warn!("Project {:?} was not closed cleanly", layout.root());
+
+                if let Some(journal) = RecoveryJournal::load(&layout.recovery_journal_file(), &self.fs)? {
+                    info!("Found recovery journal written at {}", journal.written_at_ms);
+                    *self.recovery.lock() = Some(journal);
                 }
-            );
+            }
+            SessionState::Closed => RecoveryJournal::remove(&layout.recovery_journal_file())?,
+        }
+
+        *self.session.lock() = Some(SessionLock::acquire(&lock_file)?);
+
+        // Plaintext a crashed session didn't get to delete.
+        let staging_dir = layout.staging_dir();
+        if staging_dir.exists() {
+            std::fs::remove_dir_all(&staging_dir)?;
+        }
+
+        if let Some(history) = CommandHistory::load(&layout.history_file(), &self.fs)? {
+            *self.history.lock() = history;
+        }
+        self.purge_trash()?;
+
+        // Only derived data, so a broken cache is simply rebuilt.
+        match ThumbnailCache::load(&layout.thumbnail_cache_file(), &self.fs) {
+            Ok(thumbnails) => *self.thumbnails.lock() = thumbnails,
+            Err(err) => warn!("Discarding thumbnail cache: {err}"),
+        }
+
+        Ok(())
+    }
+
+    /// Releases the session lock and drops the recovery journal. Unsaved changes are discarded.
+    pub fn close(&self) -> Result<(), ProjectError> {
+        let layout = self.layout();
+
+        RecoveryJournal::remove(&layout.recovery_journal_file())?;
+        if let Some(session) = self.session.lock().take() {
+            session.release()?;
+        }
+
+        if !self.persist_history.load(Ordering::Acquire) {
+            self.release(self.history.lock().clear());
+        }
+
+        info!("Closed project \"{}\"", self.project_name.load());
+        Ok(())
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.generation.load(Ordering::Acquire) != self.saved_generation.load(Ordering::Acquire)
+    }
+
+    fn mark_dirty(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Persists every in-memory edit (name and annotations). Imports and settings are saved right away.
+    pub fn save(&self) -> Result<(), ProjectError> {
+        let _guard = self.write_lock.lock();
+        let generation = self.generation.load(Ordering::Acquire);
+        let layout = self.layout();
+
+        self.save_manifest()?;
+        self.annotations.load().save(&layout.annotations_file(), &self.fs)?;
+        self.save_history()?;
+        RecoveryJournal::remove(&layout.recovery_journal_file())?;
+
+        self.saved_generation.store(generation, Ordering::Release);
+        self.journaled_generation.store(generation, Ordering::Release);
+
+        info!("Saved project \"{}\"", self.project_name.load());
+        Ok(())
+    }
+
+    /// Writes the unsaved state to the recovery journal, if it changed since the last save or autosave.
+    /// Returns whether a journal was written.
+    pub fn autosave(&self) -> Result<bool, ProjectError> {
+        let _guard = self.write_lock.lock();
+        let generation = self.generation.load(Ordering::Acquire);
+
+        if generation == self.saved_generation.load(Ordering::Acquire) || generation == self.journaled_generation.load(Ordering::Acquire) {
+            return Ok(false);
+        }
+
+        let journal = RecoveryJournal::new(self.manifest(), AnnotationStore::clone(&self.annotations.load()));
+        journal.save(&self.layout().recovery_journal_file(), &self.fs)?;
+        self.journaled_generation.store(generation, Ordering::Release);
+
+        debug!("Autosaved project \"{}\"", self.project_name.load());
+        Ok(true)
+    }
+
+    /// Describes the journal of an unclean shutdown, if there is one waiting to be restored.
+    pub fn recovery(&self) -> Option<RecoveryInfo> {
+        self.recovery.lock().as_ref().map(RecoveryJournal::info)
+    }
+
+    /// Replaces the in-memory state with the journal of the unclean shutdown. The project stays dirty until saved.
+    pub fn restore_recovery(&self) -> Result<bool, ProjectError> {
+        let Some(journal) = self.recovery.lock().take() else {
+            return Ok(false);
+        };
+
+        let _guard = self.write_lock.lock();
+
+        // Imports and settings are never journaled, they are saved right away.
+        self.project_name.store(Arc::new(journal.manifest.project_name));
+        self.metadata.store(Arc::new(journal.manifest.metadata));
+        self.annotations.store(Arc::new(journal.annotations));
+        self.mark_dirty();
+
+        info!("Restored project \"{}\" from recovery journal", self.project_name.load());
+        Ok(true)
+    }
+
+    pub fn discard_recovery(&self) -> Result<(), ProjectError> {
+        if self.recovery.lock().take().is_some() {
+            RecoveryJournal::remove(&self.layout().recovery_journal_file())?;
+            info!("Discarded recovery journal");
+        }
+        Ok(())
+    }
+
+    pub fn rename(&self, project_name: String) {
+        let from = self.project_name.load().to_string();
+
+        self.set_name(project_name.clone());
+        self.record(ProjectCommand::Rename { from, to: project_name });
+    }
+
+    fn set_name(&self, project_name: String) {
+        let _guard = self.write_lock.lock();
+
+        self.project_name.store(Arc::new(project_name));
+        self.mark_dirty();
+    }
+
+    /// Replaces tags, description and custom fields. Like a rename, this is an in-memory edit until [`Project::save`].
+    pub fn set_metadata(&self, metadata: ProjectMetadata) -> Result<ProjectMetadata, ProjectError> {
+        let after = metadata.normalized()?;
+        let before = ProjectMetadata::clone(&self.metadata.load());
+
+        if before == after {
+            return Ok(after);
+        }
+
+        self.store_metadata(after.clone());
+        self.record(ProjectCommand::SetMetadata { before, after: after.clone() });
+        Ok(after)
+    }
+
+    fn store_metadata(&self, metadata: ProjectMetadata) {
+        let _guard = self.write_lock.lock();
+
+        self.metadata.store(Arc::new(metadata));
+        self.mark_dirty();
+    }
+
+    pub fn add_annotation(&self, sop_instance_uid: &str, kind: AnnotationKind) -> Result<Annotation, ProjectError> {
+        let annotation = self.edit_annotations(|store| Ok(store.add(sop_instance_uid, kind)))?;
+
+        self.record(ProjectCommand::AddAnnotation { annotation: annotation.clone() });
+        Ok(annotation)
+    }
+
+    pub fn update_annotation(&self, id: u64, expected_revision: u32, kind: AnnotationKind) -> Result<Annotation, ProjectError> {
+        let (before, after) = self.edit_annotations(|store| {
+            let before = store.get(id).cloned().ok_or(AnnotationError::NotFound(id))?;
+            Ok((before, store.update(id, expected_revision, kind)?))
+        })?;
+
+        self.record(ProjectCommand::UpdateAnnotation { before, after: after.clone() });
+        Ok(after)
+    }
+
+    pub fn remove_annotation(&self, id: u64) -> Result<Annotation, ProjectError> {
+        let annotation = self.edit_annotations(|store| store.remove(id))?;
+
+        self.record(ProjectCommand::RemoveAnnotation { annotation: annotation.clone() });
+        Ok(annotation)
+    }
+
+    pub fn set_windowing(&self, series_instance_uid: &str, windowing: Option<Windowing>) -> Result<(), ProjectError> {
+        let before = self.edit_annotations(|store| {
+            let before = store.windowing(series_instance_uid);
+            store.set_windowing(series_instance_uid, windowing);
+            Ok(before)
+        })?;
+
+        self.record(ProjectCommand::SetWindowing {
+            series_instance_uid: series_instance_uid.to_string(),
+            before,
+            after: windowing,
+        });
+        Ok(())
+    }
+
+    /// Removes an import from the manifest and moves its content out of `projectFiles`.
+    /// The content is kept in the trash for as long as the removal can be undone.
+    pub async fn remove_import(&self, file: &Path) -> Result<IndexUpdate, ProjectError> {
+        let position = self
+            .imported_files
+            .load()
+            .iter()
+            .position(|imported| imported == file)
+            .ok_or_else(|| ProjectError::UnknownImport(file.to_path_buf()))?;
+
+        let layout = self.layout();
+        let destination = self.import_destination(file).ok_or_else(|| ProjectError::UnknownImport(file.to_path_buf()))?;
+
+        // A zip archive is kept next to the directory it was extracted to.
+        let mut moved = vec![destination.clone()];
+        if Self::check_file_extension(file, "zip") {
+            moved.push(destination.with_extension(""));
+        }
+        moved.retain(|relative| layout.project_files_dir().join(relative).exists());
+
+        let command = ProjectCommand::RemoveImport {
+            file: file.to_path_buf(),
+            position,
+            trash: Uuid::new_v4().to_string(),
+            moved,
+        };
+
+        let update = self.apply(&command, true).await?;
+        self.record(command);
+
+        // The manifest is already saved, so the history has to be as well, or a crash would lose the way back.
+        self.save_history()?;
+        Ok(update)
+    }
+
+    pub fn history(&self) -> HistoryState {
+        self.history.lock().state()
+    }
+
+    /// Limits the undo stack to `limit` steps. With `persist`, the history is saved alongside the project.
+    pub fn configure_history(&self, limit: usize, persist: bool) {
+        self.persist_history.store(persist, Ordering::Release);
+
+        let dropped = self.history.lock().set_limit(limit);
+        self.release(dropped);
+    }
+
+    /// Collects every edit until the matching [`Project::end_group`] into a single undo step.
+    pub fn begin_group(&self, label: &str) {
+        self.history.lock().begin_group(label);
+    }
+
+    pub fn end_group(&self) -> Result<(), ProjectError> {
+        let dropped = self.history.lock().end_group()?;
+        self.release(dropped);
+        Ok(())
+    }
+
+    pub async fn undo(&self) -> Result<HistoryState, ProjectError> {
+        let entry = self.history.lock().pop_undo()?;
+
+        if let Err(err) = self.apply_entry(&entry, false).await {
+            warn!("Failed to undo \"{}\": {err}", entry.label);
+            // Its trash still holds what the entry removed, so it stays undoable.
+            self.history.lock().restore_undo(entry);
+            return Err(err);
         }
 
-        let project_files_dir = format!("{}\\{}", project_params.working_directory, "projectFiles");
-        if !FileSystem::create_dir_recursive_async(&project_files_dir).await {
-            return Err(ProjectError::Anyhow(anyhow!("")))
+        info!("Undid \"{}\"", entry.label);
+        self.history.lock().undone(entry);
+        Ok(self.history())
+    }
+
+    pub async fn redo(&self) -> Result<HistoryState, ProjectError> {
+        let entry = self.history.lock().pop_redo()?;
+
+        if let Err(err) = self.apply_entry(&entry, true).await {
+            warn!("Failed to redo \"{}\": {err}", entry.label);
+            self.history.lock().restore_redo(entry);
+            return Err(err);
         }
 
-        Self::load_imported_files(&project_params.imported_files, &project_files_dir).await?;
+        info!("Redid \"{}\"", entry.label);
+        let dropped = self.history.lock().redone(entry);
+        self.release(dropped);
+        Ok(self.history())
+    }
+
+    fn record(&self, command: ProjectCommand) {
+        let dropped = self.history.lock().record(command);
+        self.release(dropped);
+    }
+
+    /// Applies the commands of `entry` (`forward`) or reverts them in reverse order. If one fails, those
+    /// before it are rolled back as far as possible, so the entry can stay where it was in the history.
+    async fn apply_entry(&self, entry: &HistoryEntry, forward: bool) -> Result<(), ProjectError> {
+        let commands: Vec<&ProjectCommand> = match forward {
+            true => entry.commands.iter().collect(),
+            false => entry.commands.iter().rev().collect(),
+        };
 
-        Ok(
-            Self {
-                project_name: ArcSwap::from_pointee(project_params.project_name),
-                working_directory: ArcSwap::from_pointee(project_params.working_directory),
-                imported_files: ArcSwap::from_pointee(project_params.imported_files)
+        for (position, command) in commands.iter().enumerate() {
+            if let Err(err) = self.apply(command, forward).await {
+                for applied in commands[..position].iter().rev() {
+                    if let Err(rollback) = self.apply(applied, !forward).await {
+                        warn!("Failed to roll back \"{}\": {rollback}", applied.label());
+                    }
+                }
+                return Err(err);
             }
-        )
+        }
+
+        Ok(())
     }
 
-    pub fn open(file_name: &str) {
-        let path = Path::new(file_name);
+    /// Applies `command` (`forward`) or reverts it. Returns the index update of commands that touch `projectFiles`.
+    async fn apply(&self, command: &ProjectCommand, forward: bool) -> Result<IndexUpdate, ProjectError> {
+        match command {
+            ProjectCommand::Rename { from, to } => {
+                self.set_name(if forward { to } else { from }.clone());
+            }
+            ProjectCommand::SetMetadata { before, after } => {
+                self.store_metadata(if forward { after } else { before }.clone());
+            }
+            ProjectCommand::AddAnnotation { annotation } | ProjectCommand::RemoveAnnotation { annotation } => {
+                let add = forward == matches!(command, ProjectCommand::AddAnnotation { .. });
+
+                self.edit_annotations(|store| match add {
+                    true => {
+                        store.restore(annotation.clone());
+                        Ok(())
+                    }
+                    false => store.remove(annotation.id).map(|_| ()),
+                })?;
+            }
+            ProjectCommand::UpdateAnnotation { before, after } => {
+                let target = if forward { after } else { before };
 
-        if let Some(extension) = path.extension() && let Some(ext) = extension.to_str() {
-            if !ALLOWED_FILE_EXTENSIONS.contains(&ext) {
-                return;
+                self.edit_annotations(|store| {
+                    let revision = store.get(target.id).ok_or(AnnotationError::NotFound(target.id))?.revision;
+                    store.update(target.id, revision, target.kind.clone()).map(|_| ())
+                })?;
             }
+            ProjectCommand::SetWindowing { series_instance_uid, before, after } => {
+                let windowing = if forward { *after } else { *before };
 
-            if ext == "zip" {
-                let result = Zip::unzip(file_name, "");
-                match result {
-                    Ok(_zipfile) => info!("successfully unzipped file!"),
-                    Err(e) => error!("failed to unzipped file: {}", e)
+                self.edit_annotations(|store| {
+                    store.set_windowing(series_instance_uid, windowing);
+                    Ok(())
+                })?;
+            }
+            ProjectCommand::RemoveImport { file, position, trash, moved } => {
+                let layout = self.layout();
+                let trash_dir = layout.trash_dir().join(trash);
+                let (from, to) = match forward {
+                    true => (layout.project_files_dir(), trash_dir.clone()),
+                    false => (trash_dir.clone(), layout.project_files_dir()),
                 };
+
+                // Never overwrite what was imported under the same name in the meantime.
+                if let Some(taken) = moved.iter().map(|relative| to.join(relative)).find(|target| target.exists()) {
+                    return Err(ProjectError::ImportConflict(taken));
+                }
+
+                std::fs::create_dir_all(&to)?;
+                for relative in moved {
+                    std::fs::rename(from.join(relative), to.join(relative))?;
+                }
+
+                if !forward {
+                    std::fs::remove_dir_all(&trash_dir)?;
+                }
+
+                let (source, files) = (file.clone(), moved.clone());
+                self.audit(match forward {
+                    true => AuditEvent::Removed { source, files },
+                    false => AuditEvent::Restored { source, files },
+                })?;
+
+                let guard = self.write_lock.lock();
+                // After a reopen only the moved content tells where a renamed import went.
+                if let Some(destination) = moved.first().filter(|destination| Some(destination.as_os_str()) != file.file_name()) {
+                    let mut import_destinations = BTreeMap::clone(&self.import_destinations.load());
+                    import_destinations.insert(file.clone(), destination.clone());
+                    self.import_destinations.store(Arc::new(import_destinations));
+                }
+
+                let mut imported_files = Vec::clone(&self.imported_files.load());
+                match forward {
+                    true => imported_files.retain(|imported| imported != file),
+                    false => imported_files.insert((*position).min(imported_files.len()), file.clone()),
+                }
+                self.imported_files.store(Arc::new(imported_files));
+                self.save_manifest()?;
+                drop(guard);
+
+                return self.refresh_index().await;
             }
         }
-    }
 
-    async fn load_imported_files(files: &[String], project_files_dir: &str) -> anyhow::Result<()> {
-        let project_files_dir = Path::new(project_files_dir);
+        Ok(IndexUpdate::default())
+    }
 
-        for file in files {
-            let path = Path::new(file);
+    /// Deletes the trash of commands that left the history for good.
+    fn release(&self, dropped: Vec<HistoryEntry>) {
+        let trash_dir = self.layout().trash_dir();
 
-            if ! Self::check_file_extension(path, "zip") {
-                debug!("File is not a zip file. continue: {:?}", path);
+        for trash in dropped.iter().flat_map(|entry| &entry.commands).filter_map(ProjectCommand::trash) {
+            let dir = trash_dir.join(trash);
+            if !dir.exists() {
                 continue;
             }
 
-            info!("unzipping...");
+            if let Err(err) = self.delete_trash(&dir) {
+                warn!("Failed to empty trash {:?}: {err}", dir);
+            }
+        }
+    }
+
+    /// Deletes the removed content for good and records what was deleted.
+    fn delete_trash(&self, dir: &Path) -> Result<(), ProjectError> {
+        let files = std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| PathBuf::from(entry.file_name())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        std::fs::remove_dir_all(dir)?;
+        self.audit(AuditEvent::Deleted { files })
+    }
 
-            let file_name = path.file_name().ok_or_else(|| anyhow::anyhow!("Missing file name in path: {:?}", file))?;
+    /// Deletes every trash directory the history doesn't know about, e.g. after a crash.
+    fn purge_trash(&self) -> Result<(), ProjectError> {
+        let trash_dir = self.layout().trash_dir();
+        if !trash_dir.exists() {
+            return Ok(());
+        }
 
-            let dst_file_path = project_files_dir.join(file_name);
+        let history = self.history.lock();
+        let referenced: HashSet<&str> = history.undo_commands().filter_map(ProjectCommand::trash).collect();
 
-            debug!("Copying {:?} to {:?}", file, dst_file_path);
+        for entry in std::fs::read_dir(&trash_dir)? {
+            let entry = entry?;
 
-            let sized_copied = tokio::fs::copy(file, &dst_file_path).await?;
+            if !referenced.contains(entry.file_name().to_string_lossy().as_ref()) {
+                debug!("Purging unreferenced trash {:?}", entry.path());
+                self.delete_trash(&entry.path())?;
+            }
+        }
 
-            debug!("Successfully copied {} bytes to {:?}", sized_copied, dst_file_path);
+        Ok(())
+    }
 
-            let temp_dir = tempdir()?;
+    fn save_history(&self) -> Result<(), ProjectError> {
+        let history_file = self.layout().history_file();
 
-            if let Some(temp_path) = temp_dir.path().to_str() {
-                match Zip::unzip(file, temp_path) {
-                    Ok(()) => debug!("successfully unzipped file to {:?}", temp_path),
-                    _ => { error!("Failed to unzip file to {:?}", temp_path) }
+        match self.persist_history.load(Ordering::Acquire) {
+            true => self.history.lock().save(&history_file, &self.fs)?,
+            false => {
+                if history_file.exists() {
+                    std::fs::remove_file(&history_file)?;
                 }
-                // load dicom file(s)
-            }
-            else {
-                error!("Temp dir path {:?} is not valid UTF-8", temp_dir.path());
             }
         }
 
         Ok(())
     }
 
-    fn check_file_extension(file_name: &Path, ext: &str) -> bool {
-        match file_name.extension() {
-            Some(extension) => extension == ext,
-            None => false,
+    /// Sets who the audit log attributes changes to, usually the subject of the logged in user.
+    pub fn set_actor(&self, actor: &str) {
+        self.actor.store(Arc::new(actor.to_string()));
+    }
+
+    /// Every audit log entry, oldest first.
+    pub fn audit_log(&self) -> Result<Vec<AuditEntry>, ProjectError> {
+        Ok(self.audit.lock().entries()?)
+    }
+
+    /// Checks the hash chain of the audit log. Returns the number of entries.
+    pub fn verify_audit_log(&self) -> Result<usize, ProjectError> {
+        Ok(self.audit.lock().verify()?)
+    }
+
+    fn audit(&self, event: AuditEvent) -> Result<(), ProjectError> {
+        let entry = self.audit.lock().append(&self.actor.load(), event)?;
+        debug!("Audit log entry {} written", entry.sequence);
+        Ok(())
+    }
+
+    /// Records each import source together with the hash of its content. Sources whose content was
+    /// already in the project are recorded apart from the ones that were copied.
+    fn audit_imports(&self, sources: Vec<(ImportSource, CopyOutcome)>) -> Result<(), ProjectError> {
+        for (source, outcome) in sources {
+            let (source, file) = (source.path, source.destination);
+            self.audit(match outcome {
+                CopyOutcome::Copied(hash) => AuditEvent::Imported { source, file, hash },
+                CopyOutcome::AlreadyPresent(hash) => AuditEvent::AlreadyImported { source, file, hash },
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
+    }
+
+    pub fn encryption(&self) -> Option<&EncryptionInfo> {
+        self.encryption.as_ref()
+    }
+
+    pub fn layout(&self) -> ProjectLayout {
+        ProjectLayout::new(self.working_directory.load().as_path())
+    }
+
+    pub fn manifest(&self) -> ProjectManifest {
+        let imported_files = self.imported_files.load();
+
+        ProjectManifest {
+            import_destinations: self
+                .import_destinations
+                .load()
+                .iter()
+                .filter(|(file, _)| imported_files.contains(file))
+                .map(|(file, destination)| (file.clone(), destination.clone()))
+                .collect(),
+            metadata: ProjectMetadata::clone(&self.metadata.load()),
+            settings: SettingsOverrides::clone(&self.settings.load()),
+            encryption: self.encryption.clone(),
+            ..ProjectManifest::new(
+                self.project_name.load().to_string(),
+                Vec::clone(&imported_files),
+            )
+        }
+    }
+
+    /// Where `file` was imported to, relative to `projectFiles`.
+    fn import_destination(&self, file: &Path) -> Option<PathBuf> {
+        match self.import_destinations.load().get(file) {
+            Some(destination) => Some(destination.clone()),
+            None => file.file_name().map(PathBuf::from),
+        }
+    }
+
+    pub fn save_manifest(&self) -> Result<(), ProjectError> {
+        self.manifest().save(&self.layout(), &self.fs)?;
+        Ok(())
+    }
+
+    /// Replaces the project's settings overrides and persists them in the manifest.
+    /// Validation is up to the settings store, which resolves them against the global settings.
+    pub fn set_settings(&self, settings: SettingsOverrides) -> Result<(), ProjectError> {
+        let _guard = self.write_lock.lock();
+
+        let previous = self.settings.swap(Arc::new(settings));
+        if let Err(err) = self.save_manifest() {
+            self.settings.store(previous);
+            return Err(err);
         }
+
+        Ok(())
+    }
+
+    /// Re-reads the DICOM headers of new or modified files and persists the updated index.
+    pub async fn refresh_index(&self) -> Result<IndexUpdate, ProjectError> {
+        let layout = self.layout();
+        let fs = self.fs.clone();
+        let mut index = ProjectIndex::clone(&self.index.load());
+
+        let (index, update) = tokio::task::spawn_blocking(move || {
+            let update = index.refresh(&layout.project_files_dir(), &fs);
+            index.save(&layout.index_file(), &fs)?;
+            Ok::<_, IndexError>((index, update))
+        })
+        .await
+        .map_err(|err| anyhow!("Index refresh task failed: {err}"))??;
+
+        info!("Project index updated. added: {}, updated: {}, removed: {}", update.added, update.updated, update.removed);
+
+        self.index.store(Arc::new(index));
+        Ok(update)
+    }
+
+    /// Cross-checks manifest, index, annotations and the files on disk, hashing every imported file.
+    /// With `repair`, the index is rebuilt from disk (accepting modified files) and manifest entries
+    /// and annotations that point to data which is gone are dropped.
+    pub async fn verify(&self, repair: bool) -> Result<VerifyReport, ProjectError> {
+        let layout = self.layout();
+        let index = self.index.load_full();
+
+        let files_dir = layout.project_files_dir();
+        let mut files = tokio::task::spawn_blocking(move || index.verify(&files_dir))
+            .await
+            .map_err(|err| anyhow!("Verify task failed: {err}"))??;
+
+        // Imported archives are kept next to their extracted content, but never indexed.
+        let imported_copies: Vec<PathBuf> = self
+            .imported_files
+            .load()
+            .iter()
+            .filter_map(|file| self.import_destination(file))
+            .collect();
+
+        files.orphaned.retain(|relative| !imported_copies.contains(relative));
+
+        let missing_imports = self
+            .imported_files
+            .load()
+            .iter()
+            .filter(|file| self.import_destination(file).is_none_or(|destination| !layout.project_files_dir().join(destination).exists()))
+            .cloned()
+            .collect();
+
+        let mut report = VerifyReport {
+            files,
+            missing_imports,
+            ..VerifyReport::default()
+        };
+
+        let missing: HashSet<&PathBuf> = report.files.missing.iter().collect();
+        let index = self.index.load();
+        let present: HashSet<&str> = index
+            .instances()
+            .filter(|(relative, _)| !missing.contains(&relative.to_path_buf()))
+            .map(|(_, instance)| instance.sop_instance_uid.as_str())
+            .collect();
+
+        report.dangling_annotations = self
+            .annotations
+            .load()
+            .all()
+            .filter(|annotation| !present.contains(annotation.sop_instance_uid.as_str()))
+            .map(|annotation| annotation.id)
+            .collect();
+
+        info!(
+            "Project verified. missing: {}, modified: {}, orphaned: {}, missing imports: {}, dangling annotations: {}",
+            report.files.missing.len(),
+            report.files.modified.len(),
+            report.files.orphaned.len(),
+            report.missing_imports.len(),
+            report.dangling_annotations.len(),
+        );
+
+        if repair && !report.is_ok() {
+            self.repair(&report).await?;
+            report.repaired = true;
+        }
+
+        Ok(report)
+    }
+
+    async fn repair(&self, report: &VerifyReport) -> Result<(), ProjectError> {
+        let layout = self.layout();
+        let fs = self.fs.clone();
+        let mut index = ProjectIndex::clone(&self.index.load());
+
+        let (index, update) = tokio::task::spawn_blocking(move || {
+            let update = index.rebuild(&layout.project_files_dir(), &fs);
+            index.save(&layout.index_file(), &fs)?;
+            Ok::<_, IndexError>((index, update))
+        })
+        .await
+        .map_err(|err| anyhow!("Reindex task failed: {err}"))??;
+
+        info!("Project reindexed. added: {}, updated: {}, removed: {}", update.added, update.updated, update.removed);
+        self.index.store(Arc::new(index));
+
+        if !report.missing_imports.is_empty() {
+            let _guard = self.write_lock.lock();
+
+            let imported_files = self
+                .imported_files
+                .load()
+                .iter()
+                .filter(|file| !report.missing_imports.contains(file))
+                .cloned()
+                .collect();
+
+            self.imported_files.store(Arc::new(imported_files));
+            self.save_manifest()?;
+        }
+
+        if !report.dangling_annotations.is_empty() {
+            self.edit_annotations(|store| {
+                for id in &report.dangling_annotations {
+                    store.remove(*id)?;
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn studies(&self) -> Vec<Study> {
+        self.index.load().studies()
+    }
+
+    /// Returns the PNG of the series' thumbnail, rendering it first if there is none or the rendered
+    /// instance changed since. The middle instance of the series is rendered. Blocking.
+    pub fn thumbnail(&self, series_instance_uid: &str, renderer: &dyn ThumbnailRenderer) -> Result<Vec<u8>, ProjectError> {
+        let (thumbnail, _) = self.ensure_thumbnail(series_instance_uid, renderer)?;
+        Ok(self.fs.read(&thumbnail)?)
+    }
+
+    /// Renders the missing or outdated thumbnails of every series and deletes those of series that
+    /// are gone. A series that fails to render doesn't stop the others. Blocking.
+    pub fn generate_thumbnails(&self, renderer: &dyn ThumbnailRenderer) -> Result<ThumbnailSummary, ProjectError> {
+        let layout = self.layout();
+        let series: HashSet<String> = self
+            .studies()
+            .into_iter()
+            .flat_map(|study| study.series)
+            .map(|series| series.series_instance_uid)
+            .collect();
+
+        let mut summary = ThumbnailSummary::default();
+
+        {
+            let mut thumbnails = self.thumbnails.lock();
+            let dropped = thumbnails.retain(|uid| series.contains(uid));
+            summary.removed = dropped.len();
+
+            for entry in dropped {
+                Self::remove_thumbnail_file(&layout, &entry);
+            }
+            thumbnails.save(&layout.thumbnail_cache_file(), &self.fs)?;
+        }
+
+        for uid in &series {
+            match self.ensure_thumbnail(uid, renderer) {
+                Ok((_, true)) => summary.generated += 1,
+                Ok((_, false)) => summary.cached += 1,
+                Err(err) => {
+                    warn!("No thumbnail for series {uid}: {err}");
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        info!(
+            "Thumbnails: {} generated, {} cached, {} failed, {} removed",
+            summary.generated, summary.cached, summary.failed, summary.removed
+        );
+        Ok(summary)
+    }
+
+    /// Returns the thumbnail path and whether it had to be rendered.
+    fn ensure_thumbnail(&self, series_instance_uid: &str, renderer: &dyn ThumbnailRenderer) -> Result<(PathBuf, bool), ProjectError> {
+        let layout = self.layout();
+
+        let source = self
+            .studies()
+            .into_iter()
+            .flat_map(|study| study.series)
+            .find(|series| series.series_instance_uid == series_instance_uid)
+            .and_then(|series| series.instances.get(series.instances.len() / 2).map(|instance| instance.file.clone()))
+            .ok_or_else(|| ThumbnailError::UnknownSeries(series_instance_uid.to_string()))?;
+
+        let source_file = layout.project_files_dir().join(&source);
+        let hash = hash_file(&source_file)?;
+        let thumbnail = layout.thumbnails_dir().join(ThumbnailCache::file_name(&hash));
+
+        // Rendering happens under the lock, so the same series is never rendered twice at once.
+        let mut thumbnails = self.thumbnails.lock();
+
+        if thumbnails.get(series_instance_uid).is_some_and(|entry| entry.hash == hash) && thumbnail.is_file() {
+            return Ok((thumbnail, false));
+        }
+
+        debug!("Rendering thumbnail of series {series_instance_uid} from {:?}", source);
+
+        // The renderer only takes paths, so an encrypted source is decrypted to a temporary file for the duration.
+        // It stays inside the project, never in the system temp dir.
+        let decrypted = match self.fs.is_encrypted() {
+            true => Some(self.fs.decrypted_copy(&source_file, &layout.staging_dir())?),
+            false => None,
+        };
+
+        let png = renderer
+            .render(decrypted.as_ref().map_or(source_file.as_path(), |copy| copy.path()), THUMBNAIL_SIZE)
+            .map_err(|err| ThumbnailError::Render { series: series_instance_uid.to_string(), reason: format!("{err:#}") })?;
+        drop(decrypted);
+
+        std::fs::create_dir_all(layout.thumbnails_dir())?;
+        self.fs.write(&thumbnail, png)?;
+
+        let entry = ThumbnailEntry { hash, source };
+        if let Some(replaced) = thumbnails.insert(series_instance_uid, entry.clone()) && replaced.hash != entry.hash {
+            Self::remove_thumbnail_file(&layout, &replaced);
+        }
+        thumbnails.save(&layout.thumbnail_cache_file(), &self.fs)?;
+
+        Ok((thumbnail, true))
+    }
+
+    fn remove_thumbnail_file(layout: &ProjectLayout, entry: &ThumbnailEntry) {
+        let file = layout.thumbnails_dir().join(ThumbnailCache::file_name(&entry.hash));
+
+        if let Err(err) = std::fs::remove_file(&file) && err.kind() != io::ErrorKind::NotFound {
+            warn!("Failed to delete thumbnail {:?}: {err}", file);
+        }
+    }
+
+    /// Applies `edit` to a copy of the annotation store. The in-memory store is only replaced if the edit
+    /// succeeded. The change is persisted by [`Project::save`], and journaled by [`Project::autosave`] until then.
+    pub fn edit_annotations<T>(&self, edit: impl FnOnce(&mut AnnotationStore) -> Result<T, AnnotationError>) -> Result<T, ProjectError> {
+        let _guard = self.write_lock.lock();
+
+        let mut annotations = AnnotationStore::clone(&self.annotations.load());
+        let result = edit(&mut annotations)?;
+
+        self.annotations.store(Arc::new(annotations));
+        self.mark_dirty();
+
+        Ok(result)
+    }
+
+    /// Creates an independent copy of the project under a new name and working directory.
+    /// Manifest, annotations and index are copied, the imported files are hardlinked where the
+    /// file system allows it. That is safe because nova never modifies imported files in place.
+    /// The copy of an encrypted project shares its data key and passphrase.
+    pub async fn duplicate(&self, params: DuplicateParams) -> Result<Self, ProjectError> {
+        let layout = self.layout();
+        let target = ProjectLayout::new(&params.working_directory);
+
+        Self::check_destination(&layout, target.root())?;
+
+        info!("Duplicating project \"{}\" to {:?}", self.project_name.load(), target.root());
+        target.create_directories()?;
+
+        let (source_files, target_files) = (layout.project_files_dir(), target.project_files_dir());
+        let linked = tokio::task::spawn_blocking(move || Self::link_tree(&source_files, &target_files))
+            .await
+            .map_err(|err| anyhow!("Duplicate task failed: {err}"))??;
+
+        debug!("Linked {} imported files into {:?}", linked, target.project_files_dir());
+
+        // The copy keeps the provenance of the data it was made from.
+        for private_file in [layout.deidentification_map_file(), layout.audit_log_file(), layout.data_key_file()] {
+            if private_file.exists() {
+                std::fs::copy(&private_file, target.root().join(private_file.strip_prefix(layout.root()).unwrap_or(&private_file)))?;
+            }
+        }
+
+        let manifest = ProjectManifest {
+            project_name: params.project_name,
+            ..self.manifest()
+        };
+        let index = ProjectIndex::clone(&self.index.load());
+        let annotations = AnnotationStore::clone(&self.annotations.load());
+
+        let fs = self.fs.rebased(target.root());
+        annotations.save(&target.annotations_file(), &fs)?;
+
+        let project = Self::from_parts(target, manifest, index, annotations, fs);
+        project.set_actor(&self.actor.load());
+
+        project.save_manifest()?;
+        project.start_session()?;
+        project.refresh_index().await?;
+        Ok(project)
+    }
+
+    /// Writes a copy of the project to `destination`, which can be opened like any other project.
+    /// With de-identification, every file is rewritten and named after its new UIDs, and the source
+    /// paths of the imports are dropped, since folder names tend to contain patient names.
+    /// Exports are never encrypted, they are meant to leave the machine.
+    ///
+    /// Blocks until all files are written.
+    pub fn export(&self, destination: &Path, options: &ExportOptions) -> Result<ExportSummary, ProjectError> {
+        let layout = self.layout();
+        Self::check_destination(&layout, destination)?;
+
+        let _guard = self.export_lock.lock();
+
+        let target = ProjectLayout::new(destination);
+        target.create_directories()?;
+
+        let mut map = match options.deidentification {
+            Some(_) => Some(DeidentificationMap::load(&layout.deidentification_map_file(), &self.fs)?),
+            None => None,
+        };
+
+        let index = self.index.load();
+        let mut summary = ExportSummary { exported: 0, skipped: 0 };
+
+        for (relative, instance) in index.instances() {
+            let source = layout.project_files_dir().join(relative);
+
+            let (destination_file, contents) = match (&options.deidentification, map.as_mut()) {
+                (Some(profile), Some(map)) => {
+                    let mut object = match self.fs.open(&source).map_err(DicomError::from).and_then(Dicom::read_from) {
+                        Ok(object) => object,
+                        Err(err) => {
+                            warn!("Skipping {:?} on export: {err}", source);
+                            summary.skipped += 1;
+                            continue;
+                        }
+                    };
+
+                    Deidentifier::new(profile, map).deidentify(&mut object).map_err(DeidentificationError::from)?;
+
+                    let file = PathBuf::from(map.uid(&instance.study_instance_uid))
+                        .join(map.uid(&instance.series_instance_uid))
+                        .join(format!("{}.dcm", map.uid(&instance.sop_instance_uid)));
+
+                    (file, object.encode())
+                }
+                _ => (relative.to_path_buf(), self.fs.read(&source)?),
+            };
+
+            let destination_file = target.project_files_dir().join(destination_file);
+            if let Some(parent) = destination_file.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            std::fs::write(&destination_file, contents)?;
+            summary.exported += 1;
+        }
+
+        if options.include_annotations {
+            let mut annotations = AnnotationStore::clone(&self.annotations.load());
+
+            if let Some(map) = map.as_mut() {
+                annotations.remap_uids(|uid| map.uid(uid));
+            }
+
+            annotations.save(&target.annotations_file(), &EncryptedFs::plaintext())?;
+        }
+
+        let mut manifest = ProjectManifest { encryption: None, ..self.manifest() };
+        if let Some(map) = &map {
+            manifest.imported_files.clear();
+            manifest.import_destinations.clear();
+            manifest.metadata = ProjectMetadata::default();
+            map.save(&layout.deidentification_map_file(), &self.fs)?;
+        }
+        manifest.save(&target, &EncryptedFs::plaintext())?;
+
+        self.audit(AuditEvent::Exported {
+            destination: destination.to_path_buf(),
+            files: summary.exported,
+            deidentified: options.deidentification.is_some(),
+        })?;
+
+        info!("Exported {} files to {:?} ({} skipped)", summary.exported, destination, summary.skipped);
+        Ok(summary)
+    }
+
+    /// A new project location must be empty and must not end up inside the project it is copied from.
+    fn check_destination(layout: &ProjectLayout, destination: &Path) -> Result<(), ProjectError> {
+        let root = std::fs::canonicalize(layout.root()).unwrap_or_else(|_| layout.root().to_path_buf());
+        let canonical = std::fs::canonicalize(destination).unwrap_or_else(|_| destination.to_path_buf());
+
+        if canonical.starts_with(&root) {
+            return Err(ProjectError::DestinationInsideProject(destination.to_path_buf()));
+        }
+
+        if destination.exists() && std::fs::read_dir(destination)?.next().is_some() {
+            return Err(ProjectError::DestinationNotEmpty(destination.to_path_buf()));
+        }
+
+        Ok(())
+    }
+
+    /// Hardlinks every file below `src` into `dst`, falling back to a copy across file systems.
+    fn link_tree(src: &Path, dst: &Path) -> io::Result<usize> {
+        let mut linked = 0;
+        std::fs::create_dir_all(dst)?;
+
+        for entry in std::fs::read_dir(src)? {
+            let entry = entry?;
+            let target = dst.join(entry.file_name());
+
+            if entry.file_type()?.is_dir() {
+                linked += Self::link_tree(&entry.path(), &target)?;
+                continue;
+            }
+
+            if let Err(err) = std::fs::hard_link(entry.path(), &target) {
+                debug!("Hardlinking {:?} failed ({err}), copying instead", entry.path());
+                std::fs::copy(entry.path(), &target)?;
+            }
+            linked += 1;
+        }
+
+        Ok(linked)
+    }
+
+    /// Copies the import sources into `projectFiles`, next to the `imported` ones. `import_destinations` gets
+    /// the new imports whose file name was taken. Returns the sources with what was done with each of them.
+    async fn load_imported_files(
+        files: &[PathBuf],
+        imported: &[PathBuf],
+        import_destinations: &mut BTreeMap<PathBuf, PathBuf>,
+        layout: &ProjectLayout,
+        fs: &EncryptedFs,
+    ) -> anyhow::Result<Vec<(ImportSource, CopyOutcome)>> {
+        let project_files_dir = layout.project_files_dir();
+        let roots = Self::import_roots(files, imported, import_destinations, &project_files_dir)?;
+
+        for (file, root) in &roots {
+            match Some(root.as_os_str()) == file.file_name() {
+                true => import_destinations.remove(file),
+                false => import_destinations.insert(file.clone(), root.clone()),
+            };
+        }
+
+        let sources = Self::collect_import_sources(&roots)?;
+        let total = sources.len();
+        let mut loaded = Vec::with_capacity(total);
+
+        // The same file can be selected twice (e.g. a loose file and the folder containing it).
+        let mut imported_sources: HashSet<PathBuf> = HashSet::new();
+
+        for (index, source) in sources.into_iter().enumerate() {
+            let canonical = std::fs::canonicalize(&source.path).unwrap_or_else(|_| source.path.clone());
+
+            if !imported_sources.insert(canonical) {
+                debug!("Skipping duplicate import source: {:?}", source.path);
+                continue;
+            }
+
+            let dst_file_path = project_files_dir.join(&source.destination);
+            let outcome = Self::copy_into_project(&source.path, &dst_file_path, fs).await?;
+
+            if source.kind == ImportKind::Zip {
+                info!("unzipping...");
+                Self::unzip_into_project(&source.path, &dst_file_path, &layout.staging_dir(), fs).await?;
+            }
+
+            info!("Imported {}/{} files", index + 1, total);
+            loaded.push((source, outcome));
+        }
+
+        Ok(loaded)
+    }
+
+    /// Picks where each of `files` goes inside `projectFiles`. An import that is already `imported` keeps its
+    /// place, a new one gets its file name, or `name-1.ext`, `name-2.ext`, ... if another import has it.
+    fn import_roots(
+        files: &[PathBuf],
+        imported: &[PathBuf],
+        import_destinations: &BTreeMap<PathBuf, PathBuf>,
+        project_files_dir: &Path,
+    ) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+        let destination_of = |file: &Path| import_destinations.get(file).cloned().or_else(|| file.file_name().map(PathBuf::from));
+        let mut taken: Vec<PathBuf> = imported.iter().filter_map(|file| destination_of(file)).collect();
+        let mut roots: Vec<(PathBuf, PathBuf)> = Vec::with_capacity(files.len());
+
+        for file in files {
+            let file_name = file.file_name().ok_or_else(|| anyhow::anyhow!("Missing file name in path: {:?}", file))?;
+
+            if roots.iter().any(|(root_file, _)| root_file == file) {
+                continue;
+            }
+
+            let root = match imported.contains(file) {
+                true => destination_of(file).unwrap_or_else(|| PathBuf::from(file_name)),
+                false => (0..)
+                    .map(|suffix| Self::numbered(file_name, suffix))
+                    .find(|candidate| !Self::is_taken(candidate, &taken, project_files_dir))
+                    .expect("some suffix is free"),
+            };
+
+            taken.push(root.clone());
+            roots.push((file.clone(), root));
+        }
+
+        Ok(roots)
+    }
+
+    /// `name` itself for `0`, otherwise `name-<suffix>` with the extension kept.
+    fn numbered(name: &std::ffi::OsStr, suffix: usize) -> PathBuf {
+        let name = Path::new(name);
+        if suffix == 0 {
+            return name.to_path_buf();
+        }
+
+        let mut numbered = name.file_stem().unwrap_or(name.as_os_str()).to_os_string();
+        numbered.push(format!("-{suffix}"));
+        if let Some(extension) = name.extension() {
+            numbered.push(".");
+            numbered.push(extension);
+        }
+        PathBuf::from(numbered)
+    }
+
+    /// Whether `candidate` or, for an archive, the directory it is extracted to, is used by another import or already exists.
+    fn is_taken(candidate: &Path, taken: &[PathBuf], project_files_dir: &Path) -> bool {
+        let extracted = |path: &Path| Self::check_file_extension(path, "zip").then(|| path.with_extension(""));
+        let occupies = |path: &Path| [Some(path.to_path_buf()), extracted(path)].into_iter().flatten();
+
+        occupies(candidate).any(|path| {
+            project_files_dir.join(&path).exists() || taken.iter().any(|other| occupies(other).any(|used| used == path))
+        })
+    }
+
+    /// Lists what to copy for each `(file, root)`: the file itself to `root`, or every file below a directory to the same place below `root`.
+    fn collect_import_sources(roots: &[(PathBuf, PathBuf)]) -> anyhow::Result<Vec<ImportSource>> {
+        let mut sources = Vec::new();
+
+        for (path, root) in roots {
+            if path.is_dir() {
+                Self::collect_directory_sources(path, root, &mut sources)?;
+                continue;
+            }
+
+            match Self::import_kind(path) {
+                Some(kind) => sources.push(ImportSource {
+                    path: path.clone(),
+                    destination: root.clone(),
+                    kind,
+                }),
+                None => debug!("File is neither a zip nor a dicom file. continue: {:?}", path),
+            }
+        }
+
+        Ok(sources)
+    }
+
+    fn collect_directory_sources(dir: &Path, destination: &Path, sources: &mut Vec<ImportSource>) -> io::Result<()> {
+        let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let path = entry.path();
+            let entry_destination = destination.join(entry.file_name());
+
+            if path.is_dir() {
+                Self::collect_directory_sources(&path, &entry_destination, sources)?;
+                continue;
+            }
+
+            match Self::import_kind(&path) {
+                Some(kind) => sources.push(ImportSource { path, destination: entry_destination, kind }),
+                None => debug!("Skipping non dicom file in imported folder: {:?}", path),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn import_kind(path: &Path) -> Option<ImportKind> {
+        if Self::check_file_extension(path, "zip") {
+            return Some(ImportKind::Zip);
+        }
+
+        if Self::check_file_extension(path, "dcm") || Self::check_file_extension(path, "dicom") || Dicom::has_dicom_preamble(path) {
+            return Some(ImportKind::Dicom);
+        }
+
+        None
+    }
+
+    /// A copy that already holds the same content as `src` is kept.
+    async fn copy_into_project(src: &Path, dst_file_path: &Path, fs: &EncryptedFs) -> anyhow::Result<CopyOutcome> {
+        if tokio::fs::try_exists(dst_file_path).await? {
+            let hash = hash_file(src)?;
+            if fs.hash(dst_file_path).is_ok_and(|existing| existing == hash) {
+                debug!("{:?} was already imported. Skipping copy", dst_file_path);
+                return Ok(CopyOutcome::AlreadyPresent(hash));
+            }
+        }
+
+        if let Some(parent) = dst_file_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        debug!("Copying {:?} to {:?}", src, dst_file_path);
+
+        let copied = fs.copy_into_async(src, dst_file_path).await?;
+
+        debug!("Successfully copied {} bytes to {:?}", copied.len, dst_file_path);
+        Ok(CopyOutcome::Copied(copied.hash))
+    }
+
+    /// Extracts the archive next to its copy inside `projectFiles`, so its contents get indexed.
+    /// Encrypted projects never hold plaintext in `projectFiles`, so there the original `source` is
+    /// extracted to a temporary directory below `staging_dir` and every file is sealed on its way in.
+    async fn unzip_into_project(source: &Path, archive: &Path, staging_dir: &Path, fs: &EncryptedFs) -> anyhow::Result<()> {
+        let output_dir = archive.with_extension("");
+        let staging = match fs.is_encrypted() {
+            true => {
+                tokio::fs::create_dir_all(staging_dir).await?;
+                Some(tempfile::tempdir_in(staging_dir)?)
+            }
+            false => None,
+        };
+        let (archive, unzip_dir) = match &staging {
+            Some(staging) => (source, staging.path().to_path_buf()),
+            None => (archive, output_dir.clone()),
+        };
+
+        // nova_compression still works on UTF-8 strings.
+        match (archive.to_str(), unzip_dir.to_str()) {
+            (Some(archive), Some(output)) => {
+                match Zip::unzip(archive, output) {
+                    Ok(()) => debug!("successfully unzipped file to {:?}", output),
+                    _ => { error!("Failed to unzip file to {:?}", output) }
+                }
+            }
+            _ => error!("Zip file {:?} or output dir {:?} is not valid UTF-8", archive, unzip_dir),
+        }
+
+        if let Some(staging) = staging {
+            for relative in Self::files_below(staging.path())? {
+                let destination = output_dir.join(&relative);
+                if let Some(parent) = destination.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                fs.copy_into_async(&staging.path().join(&relative), &destination).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Paths of every file below `root`, relative to it.
+    fn files_below(root: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        let mut pending = vec![root.to_path_buf()];
+
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+
+                match path.is_dir() {
+                    true => pending.push(path),
+                    false => files.extend(path.strip_prefix(root).map(Path::to_path_buf)),
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn check_file_extension(file_name: &Path, ext: &str) -> bool {
+        match file_name.extension() {
+            Some(extension) => extension == ext,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::*;
+    use crate::annotations::AnnotationKind;
+    use crate::dicom::tags;
+    use crate::dicom::tests::encode_test_file;
+    use crate::metadata::FieldValue;
+    use tempfile::tempdir;
+
+    fn write_instance(path: &Path, series: &str, sop: &str) {
+        std::fs::write(path, encode_test_file(&[
+            (tags::SOP_INSTANCE_UID, b"UI", sop),
+            (tags::STUDY_INSTANCE_UID, b"UI", "1.2.3"),
+            (tags::SERIES_INSTANCE_UID, b"UI", series),
+        ])).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_open_import_cycle() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+
+        // A loose file with extension, and a folder holding an extensionless file plus noise.
+        let loose = sources.path().join("loose.dcm");
+        write_instance(&loose, "1.2.3.1", "1.2.3.1.1");
+
+        let folder = sources.path().join("series");
+        std::fs::create_dir(&folder).unwrap();
+        write_instance(&folder.join("IM0001"), "1.2.3.2", "1.2.3.2.1");
+        std::fs::write(folder.join("readme.txt"), "not dicom").unwrap();
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "knee".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![loose.clone(), folder.clone()],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        let layout = project.layout();
+        assert!(layout.project_files_dir().join("loose.dcm").is_file());
+        assert!(layout.project_files_dir().join("series").join("IM0001").is_file());
+        assert!(!layout.project_files_dir().join("series").join("readme.txt").exists());
+        assert!(layout.manifest_file().is_file());
+        assert_eq!(project.index.load().len(), 2);
+
+        drop(project);
+
+        let reopened = Project::open(&layout.manifest_file()).await.unwrap();
+        assert_eq!(reopened.project_name.load().as_str(), "knee");
+        assert_eq!(reopened.imported_files.load().as_slice(), &[loose.clone(), folder]);
+        assert_eq!(reopened.studies()[0].series.len(), 2);
+
+        let late = sources.path().join("late.dcm");
+        write_instance(&late, "1.2.3.2", "1.2.3.2.2");
+
+        let update = reopened.import(vec![late, loose]).await.unwrap();
+        assert_eq!(update, IndexUpdate { added: 1, updated: 0, removed: 0 });
+        assert_eq!(reopened.imported_files.load().len(), 3);
+
+        let manifest = ProjectManifest::load(&layout, &EncryptedFs::plaintext()).unwrap();
+        assert_eq!(manifest.imported_files.len(), 3);
+    }
+
+    #[test]
+    fn test_collect_import_sources() {
+        let sources = tempdir().unwrap();
+
+        let loose = sources.path().join("loose.dcm");
+        write_instance(&loose, "1.2.3.1", "1.2.3.1.1");
+        let archive = sources.path().join("series.zip");
+        std::fs::write(&archive, b"PK").unwrap();
+        let noise = sources.path().join("notes.txt");
+        std::fs::write(&noise, "not dicom").unwrap();
+
+        let folder = sources.path().join("study");
+        std::fs::create_dir_all(folder.join("series")).unwrap();
+        write_instance(&folder.join("series").join("IM0001"), "1.2.3.2", "1.2.3.2.1");
+        write_instance(&folder.join("IM0002.dcm"), "1.2.3.2", "1.2.3.2.2");
+        std::fs::write(folder.join("readme.txt"), "not dicom").unwrap();
+
+        let roots = vec![
+            (loose.clone(), PathBuf::from("loose.dcm")),
+            (archive.clone(), PathBuf::from("series-1.zip")),
+            (noise, PathBuf::from("notes.txt")),
+            (folder.clone(), PathBuf::from("study")),
+        ];
+
+        let collected: Vec<(PathBuf, PathBuf, ImportKind)> = Project::collect_import_sources(&roots)
+            .unwrap()
+            .into_iter()
+            .map(|source| (source.path, source.destination, source.kind))
+            .collect();
+
+        assert_eq!(collected, vec![
+            (loose, PathBuf::from("loose.dcm"), ImportKind::Dicom),
+            (archive, PathBuf::from("series-1.zip"), ImportKind::Zip),
+            (folder.join("IM0002.dcm"), PathBuf::from("study/IM0002.dcm"), ImportKind::Dicom),
+            (folder.join("series").join("IM0001"), PathBuf::from("study/series/IM0001"), ImportKind::Dicom),
+        ]);
+    }
+
+    #[test]
+    fn test_import_roots_avoid_taken_names() {
+        let project_files = tempdir().unwrap();
+        std::fs::create_dir(project_files.path().join("leftover")).unwrap();
+
+        let imported = vec![PathBuf::from("/a/IM0001.dcm"), PathBuf::from("/a/scan.zip")];
+        let files = vec![
+            PathBuf::from("/a/IM0001.dcm"),
+            PathBuf::from("/b/IM0001.dcm"),
+            PathBuf::from("/c/IM0001.dcm"),
+            PathBuf::from("/b/scan"),
+            PathBuf::from("/b/leftover.zip"),
+            PathBuf::from("/b/IM0001.dcm"),
+        ];
+
+        let roots = Project::import_roots(&files, &imported, &BTreeMap::new(), project_files.path()).unwrap();
+
+        assert_eq!(roots, vec![
+            (PathBuf::from("/a/IM0001.dcm"), PathBuf::from("IM0001.dcm")),
+            (PathBuf::from("/b/IM0001.dcm"), PathBuf::from("IM0001-1.dcm")),
+            (PathBuf::from("/c/IM0001.dcm"), PathBuf::from("IM0001-2.dcm")),
+            // Taken by the directory `scan.zip` is extracted to.
+            (PathBuf::from("/b/scan"), PathBuf::from("scan-1")),
+            // Would be extracted to the existing `leftover`.
+            (PathBuf::from("/b/leftover.zip"), PathBuf::from("leftover-1.zip")),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_loose_files_with_the_same_name_are_kept_apart() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+
+        let first = sources.path().join("a").join("IM0001.dcm");
+        let second = sources.path().join("b").join("IM0001.dcm");
+        std::fs::create_dir_all(first.parent().unwrap()).unwrap();
+        std::fs::create_dir_all(second.parent().unwrap()).unwrap();
+        write_instance(&first, "1.2.3.1", "1.2.3.1.1");
+        write_instance(&second, "1.2.3.2", "1.2.3.2.1");
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "collisions".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![first.clone(), second.clone()],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        let files_dir = project.layout().project_files_dir();
+        assert_eq!(std::fs::read(files_dir.join("IM0001.dcm")).unwrap(), std::fs::read(&first).unwrap());
+        assert_eq!(std::fs::read(files_dir.join("IM0001-1.dcm")).unwrap(), std::fs::read(&second).unwrap());
+        assert_eq!(project.index.load().instances().count(), 2);
+        drop(project);
+
+        // Re-importing keeps each source at its own copy, also after a reopen.
+        let reopened = Project::open(working_directory.path()).await.unwrap();
+        reopened.import(vec![second.clone(), first.clone()]).await.unwrap();
+        assert!(!files_dir.join("IM0001-2.dcm").exists());
+        assert_eq!(reopened.index.load().instances().count(), 2);
+
+        reopened.remove_import(&second).await.unwrap();
+        assert!(files_dir.join("IM0001.dcm").is_file());
+        assert!(!files_dir.join("IM0001-1.dcm").exists());
+        drop(reopened);
+
+        let reopened = Project::open(working_directory.path()).await.unwrap();
+        reopened.undo().await.unwrap();
+        assert!(files_dir.join("IM0001-1.dcm").is_file());
+        assert_eq!(reopened.manifest().import_destinations, BTreeMap::from([(second, PathBuf::from("IM0001-1.dcm"))]));
+    }
+
+    #[tokio::test]
+    async fn test_reimport_compares_content_not_size() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+
+        let file = sources.path().join("IM0001.dcm");
+        write_instance(&file, "1.2.3.1", "1.2.3.1.1");
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "reimport".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![file.clone()],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        // Same size, different content.
+        write_instance(&file, "1.2.3.1", "1.2.3.1.2");
+        project.import(vec![file.clone()]).await.unwrap();
+
+        let copy = project.layout().project_files_dir().join("IM0001.dcm");
+        assert_eq!(std::fs::read(&copy).unwrap(), std::fs::read(&file).unwrap());
+        assert!(project.index.load().instances().any(|(_, instance)| instance.sop_instance_uid == "1.2.3.1.2"));
+        assert!(project.verify(false).await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_reimport_is_audited_apart_from_copies() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+
+        let file = sources.path().join("IM0001.dcm");
+        write_instance(&file, "1.2.3.1", "1.2.3.1.1");
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "reimport".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![file.clone()],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        let unchanged = hash_file(&file).unwrap();
+        project.import(vec![file.clone()]).await.unwrap();
+        write_instance(&file, "1.2.3.1", "1.2.3.1.2");
+        let changed = hash_file(&file).unwrap();
+        project.import(vec![file.clone()]).await.unwrap();
+
+        let events: Vec<AuditEvent> = project.audit_log().unwrap().into_iter().map(|entry| entry.event).collect();
+        let destination = PathBuf::from("IM0001.dcm");
+        assert_eq!(events, vec![
+            AuditEvent::Imported { source: file.clone(), file: destination.clone(), hash: unchanged.clone() },
+            AuditEvent::AlreadyImported { source: file.clone(), file: destination.clone(), hash: unchanged },
+            AuditEvent::Imported { source: file, file: destination, hash: changed },
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_annotations_survive_reopen() {
+        let working_directory = tempdir().unwrap();
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "annotated".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        let note = AnnotationKind::Note { text: "lesion".to_string(), position: None };
+        let added = project.edit_annotations(|store| Ok(store.add("1.2.3.1", note.clone()))).unwrap();
+
+        let result = project.edit_annotations(|store| store.update(added.id, 7, note.clone()));
+        assert!(matches!(result, Err(ProjectError::Annotation(AnnotationError::RevisionConflict { .. }))));
+
+        assert!(project.is_dirty());
+        project.save().unwrap();
+        assert!(!project.is_dirty());
+        project.close().unwrap();
+        drop(project);
+
+        let reopened = Project::open(working_directory.path()).await.unwrap();
+        assert_eq!(reopened.annotations.load().get(added.id), Some(&added));
+        assert!(reopened.recovery().is_none());
+    }
+
+    struct CountingRenderer(std::sync::atomic::AtomicUsize);
+
+    impl ThumbnailRenderer for CountingRenderer {
+        fn render(&self, _file: &Path, _max_size: u32) -> anyhow::Result<Vec<u8>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(b"\x89PNG".to_vec())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_thumbnails_are_cached_by_content() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+
+        let first = sources.path().join("IM0001.dcm");
+        let second = sources.path().join("IM0002.dcm");
+        write_instance(&first, "1.2.3.1", "1.2.3.1.1");
+        write_instance(&second, "1.2.3.2", "1.2.3.2.1");
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "thumbnails".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![first, second.clone()],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        let renderer = CountingRenderer(std::sync::atomic::AtomicUsize::new(0));
+
+        let summary = project.generate_thumbnails(&renderer).unwrap();
+        assert_eq!((summary.generated, summary.cached), (2, 0));
+
+        assert_eq!(project.thumbnail("1.2.3.1", &renderer).unwrap(), b"\x89PNG");
+        assert_eq!(renderer.0.load(Ordering::SeqCst), 2);
+        assert!(matches!(project.thumbnail("9.9.9", &renderer), Err(ProjectError::Thumbnail(ThumbnailError::UnknownSeries(_)))));
+
+        // Same series, different content: the old PNG is replaced.
+        let copy = project.layout().project_files_dir().join("IM0001.dcm");
+        write_instance(&copy, "1.2.3.1", "1.2.3.1.9");
+        project.refresh_index().await.unwrap();
+
+        project.thumbnail("1.2.3.1", &renderer).unwrap();
+        let pngs = std::fs::read_dir(project.layout().thumbnails_dir())
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path() != project.layout().thumbnail_cache_file())
+            .count();
+        assert_eq!(pngs, 2);
+        assert_eq!(renderer.0.load(Ordering::SeqCst), 3);
+
+        project.remove_import(&second).await.unwrap();
+        let summary = project.generate_thumbnails(&renderer).unwrap();
+        assert_eq!((summary.generated, summary.cached, summary.removed), (0, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_metadata_is_validated_undoable_and_saved() {
+        let working_directory = tempdir().unwrap();
+
+        let invalid = Project::new_project(ProjectParams {
+            project_name: "invalid".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![],
+            metadata: ProjectMetadata { tags: vec!["".to_string()], ..Default::default() },
+            actor: None,
+            encryption: None,
+        }).await;
+        assert!(matches!(invalid, Err(ProjectError::Metadata(MetadataError::EmptyTag))));
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "metadata".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![],
+            metadata: ProjectMetadata { tags: vec!["knee".to_string()], ..Default::default() },
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        let edited = project.set_metadata(ProjectMetadata {
+            tags: vec!["knee".to_string(), " ortho ".to_string()],
+            description: "ACL follow-up".to_string(),
+            fields: BTreeMap::from([("slices".to_string(), FieldValue::Number(120.0))]),
+        }).unwrap();
+        assert_eq!(edited.tags, vec!["knee", "ortho"]);
+        assert!(project.is_dirty());
+
+        project.undo().await.unwrap();
+        assert_eq!(project.metadata.load().tags, vec!["knee"]);
+        project.redo().await.unwrap();
+
+        project.save().unwrap();
+        project.close().unwrap();
+        drop(project);
+
+        let reopened = Project::open(working_directory.path()).await.unwrap();
+        assert_eq!(*reopened.metadata.load().as_ref(), edited);
+    }
+
+    #[tokio::test]
+    async fn test_undo_redo_survives_reopen() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+
+        let file = sources.path().join("IM0001.dcm");
+        write_instance(&file, "1.2.3.1", "1.2.3.1.1");
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "history".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![file.clone()],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        let note = |text: &str| AnnotationKind::Note { text: text.to_string(), position: None };
+
+        project.begin_group("Annotate");
+        let added = project.add_annotation("1.2.3.1.1", note("first")).unwrap();
+        project.update_annotation(added.id, added.revision, note("second")).unwrap();
+        project.end_group().unwrap();
+        project.rename("renamed".to_string());
+
+        let update = project.remove_import(&file).await.unwrap();
+        assert_eq!(update.removed, 1);
+        assert!(project.imported_files.load().is_empty());
+        assert_eq!(project.history().undo, vec!["Annotate", "Rename project", "Remove import"]);
+
+        project.undo().await.unwrap();
+        assert_eq!(project.imported_files.load().as_slice(), std::slice::from_ref(&file));
+        assert_eq!(project.index.load().len(), 1);
+
+        project.undo().await.unwrap();
+        assert_eq!(project.project_name.load().as_str(), "history");
+
+        project.undo().await.unwrap();
+        assert!(project.annotations.load().is_empty());
+        assert!(matches!(project.undo().await, Err(ProjectError::History(HistoryError::NothingToUndo))));
+
+        project.redo().await.unwrap();
+        assert_eq!(project.annotations.load().get(added.id).unwrap().kind, note("second"));
+
+        project.save().unwrap();
+        project.close().unwrap();
+        drop(project);
+
+        let reopened = Project::open(working_directory.path()).await.unwrap();
+        assert_eq!(reopened.history().undo, vec!["Annotate"]);
+        assert_eq!(reopened.history().redo, vec!["Remove import", "Rename project"]);
+
+        reopened.undo().await.unwrap();
+        assert!(reopened.annotations.load().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_undo_keeps_the_entry_and_its_trash() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+
+        let file = sources.path().join("IM0001.dcm");
+        write_instance(&file, "1.2.3.1", "1.2.3.1.1");
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "conflict".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![file.clone()],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        project.begin_group("Remove and rename");
+        project.remove_import(&file).await.unwrap();
+        project.rename("renamed".to_string());
+        project.end_group().unwrap();
+
+        // A new import takes the name the removed one would be restored to.
+        project.import(vec![file.clone()]).await.unwrap();
+        let trash = std::fs::read_dir(project.layout().trash_dir()).unwrap().next().unwrap().unwrap().path();
+
+        // The rename is undone first, then rolled back when restoring the import fails.
+        assert!(matches!(project.undo().await, Err(ProjectError::ImportConflict(_))));
+        assert_eq!(project.history().undo, vec!["Remove and rename"]);
+        assert_eq!(project.project_name.load().as_str(), "renamed");
+        assert!(trash.join("IM0001.dcm").is_file());
+        assert!(!project.audit_log().unwrap().iter().any(|entry| matches!(entry.event, AuditEvent::Deleted { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_unclean_shutdown_offers_recovery() {
+        let working_directory = tempdir().unwrap();
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "crashed".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        assert!(!project.autosave().unwrap());
+
+        let note = AnnotationKind::Note { text: "unsaved".to_string(), position: None };
+        project.edit_annotations(|store| Ok(store.add("1.2.3.1", note))).unwrap();
+        project.rename("crashed (edited)".to_string());
+
+        assert!(project.autosave().unwrap());
+        assert!(!project.autosave().unwrap());
+
+        // Simulate a crash: the project is never saved or closed.
+        drop(project);
+
+        let reopened = Project::open(working_directory.path()).await.unwrap();
+        assert_eq!(reopened.project_name.load().as_str(), "crashed");
+        assert!(reopened.annotations.load().is_empty());
+
+        let recovery = reopened.recovery().unwrap();
+        assert_eq!(recovery.project_name, "crashed (edited)");
+        assert_eq!(recovery.annotations, 1);
+
+        assert!(reopened.restore_recovery().unwrap());
+        assert_eq!(reopened.project_name.load().as_str(), "crashed (edited)");
+        assert_eq!(reopened.annotations.load().len(), 1);
+        assert!(reopened.is_dirty());
+
+        reopened.save().unwrap();
+        reopened.close().unwrap();
+        drop(reopened);
+
+        let clean = Project::open(working_directory.path()).await.unwrap();
+        assert!(clean.recovery().is_none());
+        assert_eq!(clean.annotations.load().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_project_open_in_another_instance_is_refused() {
+        let working_directory = tempdir().unwrap();
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "busy".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+        drop(project);
+
+        let mut other = std::process::Command::new("sleep").arg("10").spawn().unwrap();
+        let lock_file = ProjectLayout::new(working_directory.path()).session_lock_file();
+        std::fs::write(&lock_file, other.id().to_string()).unwrap();
+
+        assert!(matches!(Project::open(working_directory.path()).await, Err(ProjectError::OpenElsewhere(pid)) if pid == other.id()));
+
+        let overwrite = ProjectParams {
+            project_name: "overwrite".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        };
+        assert!(matches!(Project::new_project(overwrite).await, Err(ProjectError::OpenElsewhere(_))));
+        assert!(ProjectLayout::new(working_directory.path()).manifest_file().is_file());
+
+        // Once the other instance is gone, its lock only means it crashed.
+        other.kill().unwrap();
+        other.wait().unwrap();
+        let reopened = Project::open(working_directory.path()).await.unwrap();
+        assert_eq!(reopened.project_name.load().as_str(), "busy");
+        reopened.close().unwrap();
+        assert!(!lock_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_project_open_in_this_process_is_not_overwritten() {
+        let working_directory = tempdir().unwrap();
+        let params = |name: &str| ProjectParams {
+            project_name: name.to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        };
+
+        let project = Project::new_project(params("open")).await.unwrap();
+        let manifest = project.layout().manifest_file();
+
+        assert!(matches!(Project::new_project(params("overwrite")).await, Err(ProjectError::AlreadyOpen)));
+        assert!(matches!(Project::open(working_directory.path()).await, Err(ProjectError::AlreadyOpen)));
+        assert!(manifest.is_file());
+        assert!(project.layout().session_lock_file().is_file());
+
+        project.close().unwrap();
+        let overwritten = Project::new_project(params("overwrite")).await.unwrap();
+
+        // Closing the old project again must not release the lock of the new one.
+        project.close().unwrap();
+        assert!(matches!(Project::open(working_directory.path()).await, Err(ProjectError::AlreadyOpen)));
+        overwritten.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_settings_survive_reopen() {
+        let working_directory = tempdir().unwrap();
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "configured".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        let settings = SettingsOverrides::from([("autosave_interval_secs".to_string(), serde_json::json!(30))]);
+        project.set_settings(settings.clone()).unwrap();
+        drop(project);
+
+        let reopened = Project::open(working_directory.path()).await.unwrap();
+        assert_eq!(*reopened.settings.load_full(), settings);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_is_independent_of_original() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+        let duplicate_directory = tempdir().unwrap();
+
+        let file = sources.path().join("IM0001.dcm");
+        write_instance(&file, "1.2.3.1", "1.2.3.1.1");
+
+        let original = Project::new_project(ProjectParams {
+            project_name: "original".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![file],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        let note = AnnotationKind::Note { text: "first reading".to_string(), position: None };
+        let added = original.edit_annotations(|store| Ok(store.add("1.2.3.1.1", note))).unwrap();
+
+        let inside = original.duplicate(DuplicateParams {
+            project_name: "nested".to_string(),
+            working_directory: working_directory.path().join("copy"),
+        }).await;
+        assert!(matches!(inside, Err(ProjectError::DestinationInsideProject(_))));
+
+        let duplicate = original.duplicate(DuplicateParams {
+            project_name: "branch".to_string(),
+            working_directory: duplicate_directory.path().to_path_buf(),
+        }).await.unwrap();
+
+        assert_eq!(duplicate.project_name.load().as_str(), "branch");
+        assert_eq!(duplicate.index.load().len(), 1);
+        assert!(duplicate.layout().project_files_dir().join("IM0001.dcm").is_file());
+
+        duplicate.edit_annotations(|store| store.remove(added.id)).unwrap();
+        duplicate.save().unwrap();
+        assert_eq!(original.annotations.load().len(), 1);
+        duplicate.close().unwrap();
+
+        let reopened = Project::open(duplicate_directory.path()).await.unwrap();
+        assert_eq!(reopened.project_name.load().as_str(), "branch");
+        assert!(reopened.annotations.load().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_verify_and_repair() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+
+        let kept = sources.path().join("kept.dcm");
+        let deleted = sources.path().join("deleted.dcm");
+        write_instance(&kept, "1.2.3.1", "1.2.3.1.1");
+        write_instance(&deleted, "1.2.3.1", "1.2.3.1.2");
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "damaged".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![kept.clone(), deleted.clone()],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        let note = AnnotationKind::Note { text: "gone".to_string(), position: None };
+        let dangling = project.edit_annotations(|store| Ok(store.add("1.2.3.1.2", note))).unwrap();
+
+        assert!(project.verify(false).await.unwrap().is_ok());
+
+        let files_dir = project.layout().project_files_dir();
+        std::fs::remove_file(files_dir.join("deleted.dcm")).unwrap();
+        std::fs::write(files_dir.join("kept.dcm"), encode_test_file(&[
+            (tags::SOP_INSTANCE_UID, b"UI", "1.2.3.1.1"),
+            (tags::STUDY_INSTANCE_UID, b"UI", "1.2.3"),
+            (tags::SERIES_INSTANCE_UID, b"UI", "1.2.3.9"),
+        ])).unwrap();
+        std::fs::write(files_dir.join("stray.txt"), "left behind").unwrap();
+
+        let report = project.verify(false).await.unwrap();
+        assert_eq!(report.files.missing, vec![PathBuf::from("deleted.dcm")]);
+        assert_eq!(report.files.modified, vec![PathBuf::from("kept.dcm")]);
+        assert_eq!(report.files.orphaned, vec![PathBuf::from("stray.txt")]);
+        assert_eq!(report.missing_imports, vec![deleted]);
+        assert_eq!(report.dangling_annotations, vec![dangling.id]);
+        assert!(!report.repaired);
+
+        let repaired = project.verify(true).await.unwrap();
+        assert!(repaired.repaired);
+        assert_eq!(project.imported_files.load().as_slice(), &[kept]);
+        assert!(project.annotations.load().is_empty());
+
+        let report = project.verify(false).await.unwrap();
+        assert!(report.files.missing.is_empty() && report.files.modified.is_empty());
+        assert_eq!(report.files.orphaned, vec![PathBuf::from("stray.txt")]);
+    }
+
+    #[tokio::test]
+    async fn test_deidentified_export_opens_as_project() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+        let export_directory = tempdir().unwrap();
+
+        let file = sources.path().join("Doe^John").join("IM0001");
+        std::fs::create_dir(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, encode_test_file(&[
+            (tags::SOP_INSTANCE_UID, b"UI", "1.2.3.1.1"),
+            (tags::PATIENT_NAME, b"PN", "Doe^John"),
+            (tags::STUDY_INSTANCE_UID, b"UI", "1.2.3"),
+            (tags::SERIES_INSTANCE_UID, b"UI", "1.2.3.1"),
+        ])).unwrap();
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "shared".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![file.parent().unwrap().to_path_buf()],
+            metadata: ProjectMetadata { description: "Knee of John Doe".to_string(), ..ProjectMetadata::default() },
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        let note = AnnotationKind::Note { text: "lesion".to_string(), position: None };
+        project.edit_annotations(|store| Ok(store.add("1.2.3.1.1", note))).unwrap();
+
+        let options = ExportOptions { deidentification: Some(DeidentificationProfile::basic()), include_annotations: true };
+        let summary = project.export(export_directory.path(), &options).unwrap();
+        assert_eq!(summary, ExportSummary { exported: 1, skipped: 0 });
+
+        assert!(matches!(project.export(export_directory.path(), &options), Err(ProjectError::DestinationNotEmpty(_))));
+
+        let exported = Project::open(export_directory.path()).await.unwrap();
+        let studies = exported.studies();
+        let instance = &studies[0].series[0].instances[0];
+
+        assert_eq!(studies[0].patient_name.as_deref(), Some("ANON00001"));
+        assert_ne!(studies[0].study_instance_uid, "1.2.3");
+        assert!(!instance.file.to_string_lossy().contains("Doe"));
+        assert!(exported.imported_files.load().is_empty());
+        assert_eq!(**exported.metadata.load(), ProjectMetadata::default());
+        assert_eq!(exported.annotations.load().for_instance(&instance.sop_instance_uid).len(), 1);
+        assert!(!export_directory.path().join("private").join("deidentificationMap.json").exists());
+
+        let map = DeidentificationMap::load(&project.layout().deidentification_map_file(), &EncryptedFs::plaintext()).unwrap();
+        assert_eq!(map.original_uid(&instance.sop_instance_uid), Some("1.2.3.1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_audit_log_records_provenance() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+        let export_directory = tempdir().unwrap();
+
+        let file = sources.path().join("IM0001.dcm");
+        write_instance(&file, "1.2.3.1", "1.2.3.1.1");
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "audited".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![file.clone()],
+            metadata: ProjectMetadata::default(),
+            actor: Some("alice".to_string()),
+            encryption: None,
+        }).await.unwrap();
+
+        project.export(export_directory.path(), &ExportOptions { deidentification: None, include_annotations: false }).unwrap();
+        project.set_actor("bob");
+        project.remove_import(&file).await.unwrap();
+        project.undo().await.unwrap();
+
+        let entries = project.audit_log().unwrap();
+        let actors: Vec<&str> = entries.iter().map(|entry| entry.actor.as_str()).collect();
+        assert_eq!(actors, vec!["alice", "alice", "bob", "bob"]);
+
+        assert_eq!(entries[0].event, AuditEvent::Imported {
+            source: file.clone(),
+            file: PathBuf::from("IM0001.dcm"),
+            hash: hash_file(&file).unwrap(),
+        });
+        assert!(matches!(entries[1].event, AuditEvent::Exported { files: 1, deidentified: false, .. }));
+        assert!(matches!(entries[2].event, AuditEvent::Removed { .. }));
+        assert!(matches!(entries[3].event, AuditEvent::Restored { .. }));
+        assert_eq!(project.verify_audit_log().unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_project_needs_its_passphrase() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+        let export_directory = tempdir().unwrap();
+        let passphrase = "correct horse battery staple";
+
+        let file = sources.path().join("Doe^John").join("IM0001.dcm");
+        std::fs::create_dir(file.parent().unwrap()).unwrap();
+        write_instance(&file, "1.2.3.1", "1.2.3.1.1");
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "Knee of John Doe".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![file.clone()],
+            metadata: ProjectMetadata { description: "Doe, follow-up".to_string(), ..Default::default() },
+            actor: None,
+            encryption: Some(ProjectEncryption::Passphrase { passphrase: passphrase.to_string() }),
+        }).await.unwrap();
+
+        project.add_annotation("1.2.3.1.1", AnnotationKind::Note { text: "lesion".to_string(), position: None }).unwrap();
+        project.save().unwrap();
+        project.close().unwrap();
+
+        let layout = project.layout();
+        for sealed in [layout.project_files_dir().join("IM0001.dcm"), layout.index_file(), layout.annotations_file(), layout.sealed_manifest_file()] {
+            assert!(EncryptedFs::is_sealed_file(&sealed).unwrap(), "{sealed:?} is plaintext");
+        }
+
+        // Only what it takes to unlock the project stays readable.
+        for clear in [layout.manifest_file(), layout.audit_log_file()] {
+            assert!(!std::fs::read_to_string(&clear).unwrap().contains("Doe"), "{clear:?} reveals the patient");
+        }
+        assert_eq!(project.audit_log().unwrap()[0].event, AuditEvent::Imported {
+            source: file,
+            file: PathBuf::from("IM0001.dcm"),
+            hash: hash_file(&sources.path().join("Doe^John").join("IM0001.dcm")).unwrap(),
+        });
+
+        let locked = Project::open(working_directory.path()).await;
+        assert!(matches!(locked, Err(ProjectError::Encryption(EncryptionError::PassphraseRequired))));
+        let wrong = Project::open_with_passphrase(working_directory.path(), Some("wrong")).await;
+        assert!(matches!(wrong, Err(ProjectError::Encryption(EncryptionError::WrongSecret))));
+
+        let reopened = Project::open_with_passphrase(working_directory.path(), Some(passphrase)).await.unwrap();
+        assert!(reopened.is_encrypted());
+        assert_eq!(reopened.project_name.load().as_str(), "Knee of John Doe");
+        assert_eq!(reopened.studies().len(), 1);
+        assert_eq!(reopened.annotations.load().len(), 1);
+
+        // Exports leave the machine in the clear.
+        let options = ExportOptions { deidentification: None, include_annotations: true };
+        assert_eq!(reopened.export(export_directory.path(), &options).unwrap().exported, 1);
+
+        let exported = Project::open(export_directory.path()).await.unwrap();
+        assert!(!exported.is_encrypted());
+        assert_eq!(exported.studies().len(), 1);
+        assert_eq!(exported.annotations.load().len(), 1);
+    }
+
+    struct StagingRenderer(PathBuf);
+
+    impl ThumbnailRenderer for StagingRenderer {
+        fn render(&self, file: &Path, _max_size: u32) -> anyhow::Result<Vec<u8>> {
+            anyhow::ensure!(file.starts_with(&self.0), "{file:?} is outside of the project");
+            anyhow::ensure!(Dicom::has_dicom_preamble(file), "{file:?} is not decrypted");
+            Ok(b"\x89PNG".to_vec())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_project_rejects_swapped_files_and_renders_inside() {
+        let sources = tempdir().unwrap();
+        let working_directory = tempdir().unwrap();
+
+        let (first, second) = (sources.path().join("IM0001.dcm"), sources.path().join("IM0002.dcm"));
+        write_instance(&first, "1.2.3.1", "1.2.3.1.1");
+        write_instance(&second, "1.2.3.1", "1.2.3.1.2");
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "sealed".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![first, second],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: Some(ProjectEncryption::Passphrase { passphrase: "correct horse battery staple".to_string() }),
+        }).await.unwrap();
+
+        let layout = project.layout();
+        let summary = project.generate_thumbnails(&StagingRenderer(layout.staging_dir())).unwrap();
+        assert_eq!((summary.generated, summary.failed), (1, 0));
+        assert_eq!(std::fs::read_dir(layout.staging_dir()).unwrap().count(), 0);
+
+        // Neither plaintext nor a sealed file moved to another name is read as part of the project.
+        let files_dir = layout.project_files_dir();
+        std::fs::rename(files_dir.join("IM0002.dcm"), files_dir.join("IM0003.dcm")).unwrap();
+        write_instance(&files_dir.join("IM0001.dcm"), "6.6.6", "6.6.6.1");
+        project.verify(true).await.unwrap();
+        assert!(project.studies().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_new_project_clears_working_directory() {
+        let working_directory = tempdir().unwrap();
+        std::fs::write(working_directory.path().join("stale.txt"), "old").unwrap();
+
+        let project = Project::new_project(ProjectParams {
+            project_name: "empty".to_string(),
+            working_directory: working_directory.path().to_path_buf(),
+            imported_files: vec![],
+            metadata: ProjectMetadata::default(),
+            actor: None,
+            encryption: None,
+        }).await.unwrap();
+
+        assert!(!working_directory.path().join("stale.txt").exists());
+        assert!(project.index.load().is_empty());
+
+        for dir in project.layout().directories() {
+            assert!(dir.is_dir());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_open_fails_without_manifest() {
+        let working_directory = tempdir().unwrap();
+
+        let result = Project::open(working_directory.path()).await;
+        assert!(matches!(result, Err(ProjectError::Manifest(_))));
     }
 }
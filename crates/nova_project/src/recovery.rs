@@ -0,0 +1,85 @@
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use nova_fs::encrypted_fs::EncryptedFs;
+use crate::annotations::{now_ms, AnnotationStore};
+use crate::manifest::ProjectManifest;
+
+/// Bump whenever the persisted layout changes in a way that older versions can't read.
+pub const RECOVERY_JOURNAL_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum RecoveryError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to (de)serialize recovery journal: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    #[error("Unsupported recovery journal version {0} (newest supported is {RECOVERY_JOURNAL_VERSION})")]
+    UnsupportedVersion(u32),
+}
+
+/// A snapshot of the unsaved in-memory state of a project, written by autosave.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryJournal {
+    version: u32,
+    pub written_at_ms: u64,
+    pub manifest: ProjectManifest,
+    pub annotations: AnnotationStore,
+}
+
+/// What the UI needs to ask the user whether to restore.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecoveryInfo {
+    pub written_at_ms: u64,
+    pub project_name: String,
+    pub annotations: usize,
+}
+
+impl RecoveryJournal {
+    pub fn new(manifest: ProjectManifest, annotations: AnnotationStore) -> Self {
+        Self {
+            version: RECOVERY_JOURNAL_VERSION,
+            written_at_ms: now_ms(),
+            manifest,
+            annotations,
+        }
+    }
+
+    pub fn load(path: &Path, fs: &EncryptedFs) -> Result<Option<Self>, RecoveryError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let journal: Self = serde_json::from_str(&fs.read_to_string(path)?)?;
+
+        if journal.version > RECOVERY_JOURNAL_VERSION {
+            return Err(RecoveryError::UnsupportedVersion(journal.version));
+        }
+
+        Ok(Some(journal))
+    }
+
+    /// Writes to a temporary file first, so a crash during autosave never corrupts the previous journal.
+    pub fn save(&self, path: &Path, fs: &EncryptedFs) -> Result<(), RecoveryError> {
+        fs.write_atomic(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn remove(path: &Path) -> Result<(), RecoveryError> {
+        match std::fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn info(&self) -> RecoveryInfo {
+        RecoveryInfo {
+            written_at_ms: self.written_at_ms,
+            project_name: self.manifest.project_name.clone(),
+            annotations: self.annotations.len(),
+        }
+    }
+}
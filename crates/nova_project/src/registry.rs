@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use parking_lot::RwLock;
+use serde::Serialize;
+use thiserror::Error;
+use tracing::{info, warn};
+use uuid::Uuid;
+use crate::metadata::{ProjectFilter, ProjectMetadata};
+use crate::project::{Project, ProjectError};
+
+/// Identifies an open project for as long as it stays open. Not persisted.
+pub type ProjectId = Uuid;
+
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("No project is open")]
+    NoActiveProject,
+
+    #[error("Project {0} is not open")]
+    UnknownProject(ProjectId),
+
+    #[error("Failed to close project: {0}")]
+    Close(#[from] ProjectError),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OpenProject {
+    pub id: ProjectId,
+    pub project_name: String,
+    pub working_directory: PathBuf,
+    pub active: bool,
+    pub dirty: bool,
+    #[serde(flatten)]
+    pub metadata: ProjectMetadata,
+}
+
+/// Every project open in this session, and which one the user is working on.
+#[derive(Default)]
+pub struct ProjectRegistry {
+    projects: RwLock<HashMap<ProjectId, Arc<Project>>>,
+    /// Order in which projects were activated, most recent last. The active project is the last entry.
+    activation: RwLock<Vec<ProjectId>>,
+}
+
+impl ProjectRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a freshly opened project and makes it the active one.
+    pub fn insert(&self, project: Project) -> (ProjectId, Arc<Project>) {
+        let id = Uuid::new_v4();
+        let project = Arc::new(project);
+
+        self.projects.write().insert(id, Arc::clone(&project));
+        self.activation.write().push(id);
+
+        info!("Registered project \"{}\" as {id}", project.project_name.load());
+        (id, project)
+    }
+
+    pub fn get(&self, id: ProjectId) -> Result<Arc<Project>, RegistryError> {
+        self.projects
+            .read()
+            .get(&id)
+            .cloned()
+            .ok_or(RegistryError::UnknownProject(id))
+    }
+
+    /// `None` resolves to the active project.
+    pub fn resolve(&self, id: Option<ProjectId>) -> Result<Arc<Project>, RegistryError> {
+        match id {
+            Some(id) => self.get(id),
+            None => self.active().map(|(_, project)| project).ok_or(RegistryError::NoActiveProject),
+        }
+    }
+
+    pub fn active(&self) -> Option<(ProjectId, Arc<Project>)> {
+        let id = *self.activation.read().last()?;
+        self.get(id).ok().map(|project| (id, project))
+    }
+
+    pub fn set_active(&self, id: ProjectId) -> Result<Arc<Project>, RegistryError> {
+        let project = self.get(id)?;
+
+        let mut activation = self.activation.write();
+        activation.retain(|other| *other != id);
+        activation.push(id);
+
+        Ok(project)
+    }
+
+    /// The id of the open project stored in `working_directory`, if any. Paths are compared canonicalized,
+    /// so a relative path, a symlink or different casing on Windows still finds the project.
+    pub fn find(&self, working_directory: &Path) -> Option<ProjectId> {
+        let canonical = |path: &Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let working_directory = canonical(working_directory);
+
+        self.projects
+            .read()
+            .iter()
+            .find(|(_, project)| canonical(&project.working_directory.load()) == working_directory)
+            .map(|(id, _)| *id)
+    }
+
+    /// The open projects `filter` accepts, ordered by name.
+    pub fn list(&self, filter: &ProjectFilter) -> Vec<OpenProject> {
+        let active = self.active().map(|(id, _)| id);
+        let mut projects: Vec<OpenProject> = self
+            .projects
+            .read()
+            .iter()
+            .filter(|(_, project)| filter.matches(&project.project_name.load(), &project.metadata.load()))
+            .map(|(id, project)| OpenProject {
+                id: *id,
+                project_name: project.project_name.load().to_string(),
+                working_directory: project.working_directory.load().to_path_buf(),
+                active: Some(*id) == active,
+                dirty: project.is_dirty(),
+                metadata: ProjectMetadata::clone(&project.metadata.load()),
+            })
+            .collect();
+
+        projects.sort_by(|a, b| a.project_name.cmp(&b.project_name).then(a.id.cmp(&b.id)));
+        projects
+    }
+
+    pub fn ids(&self) -> Vec<ProjectId> {
+        self.projects.read().keys().copied().collect()
+    }
+
+    /// Closes the project and removes it. The most recently active of the remaining projects becomes active.
+    /// Unsaved changes are discarded.
+    pub fn close(&self, id: ProjectId) -> Result<Option<ProjectId>, RegistryError> {
+        let project = self.projects.write().remove(&id).ok_or(RegistryError::UnknownProject(id))?;
+
+        let mut activation = self.activation.write();
+        activation.retain(|other| *other != id);
+        let active = activation.last().copied();
+        drop(activation);
+
+        project.close()?;
+        Ok(active)
+    }
+
+    pub fn close_all(&self) {
+        for id in self.ids() {
+            if let Err(err) = self.close(id) {
+                warn!("Failed to close project {id}: {err}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use crate::layout::ProjectLayout;
+    use crate::project::ProjectParams;
+
+    async fn project(name: &str, working_directory: &Path) -> Project {
+        Project::new_project(ProjectParams {
+            project_name: name.to_string(),
+            working_directory: working_directory.to_path_buf(),
+            imported_files: Vec::new(),
+            metadata: ProjectMetadata { tags: vec![name.to_string()], ..Default::default() },
+            actor: None,
+            encryption: None,
+        }).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_projects_are_independent() {
+        let first_dir = tempdir().unwrap();
+        let second_dir = tempdir().unwrap();
+        let registry = ProjectRegistry::new();
+
+        let (first, _) = registry.insert(project("first", first_dir.path()).await);
+        let (second, _) = registry.insert(project("second", second_dir.path()).await);
+
+        assert_eq!(registry.resolve(None).unwrap().project_name.load().as_str(), "second");
+        assert_eq!(registry.resolve(Some(first)).unwrap().project_name.load().as_str(), "first");
+        assert_eq!(registry.find(first_dir.path()), Some(first));
+        assert_eq!(registry.find(&first_dir.path().join(".").join("projectFiles").join("..")), Some(first));
+
+        registry.set_active(first).unwrap();
+        assert_eq!(registry.list(&ProjectFilter::default()).iter().filter(|open| open.active).map(|open| open.id).collect::<Vec<_>>(), vec![first]);
+
+        let tagged = ProjectFilter { tags: vec!["SECOND".to_string()], ..Default::default() };
+        assert_eq!(registry.list(&tagged).iter().map(|open| open.id).collect::<Vec<_>>(), vec![second]);
+
+        assert_eq!(registry.close(first).unwrap(), Some(second));
+        assert!(matches!(registry.get(first), Err(RegistryError::UnknownProject(_))));
+        assert!(!ProjectLayout::new(first_dir.path()).session_lock_file().exists());
+
+        assert_eq!(registry.close(second).unwrap(), None);
+        assert!(matches!(registry.resolve(None), Err(RegistryError::NoActiveProject)));
+    }
+}
@@ -0,0 +1,478 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, warn};
+use crate::annotations::now_ms;
+use crate::layout::ProjectLayout;
+use crate::project::Project;
+
+/// Bump whenever the persisted layout changes. Older indices are discarded and rebuilt as projects are opened.
+pub const SEARCH_INDEX_VERSION: u32 = 1;
+
+pub const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+/// Words that carry no meaning in queries like "the CT of the knee from March".
+const STOPWORDS: &[&str] = &["a", "an", "and", "at", "from", "in", "of", "on", "the", "to", "with"];
+
+const MONTHS: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+
+#[derive(Error, Debug)]
+pub enum SearchError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to (de)serialize search index: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchField {
+    Name,
+    Tag,
+    Modality,
+    BodyPart,
+    StudyDate,
+    Description,
+}
+
+impl SearchField {
+    fn weight(self) -> f32 {
+        match self {
+            SearchField::Name => 3.0,
+            SearchField::Tag => 2.0,
+            SearchField::Modality | SearchField::BodyPart | SearchField::StudyDate => 1.5,
+            SearchField::Description => 1.0,
+        }
+    }
+}
+
+/// What is searchable about one project. Built from the manifest and the DICOM index.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchDocument {
+    pub project_name: String,
+    pub tags: Vec<String>,
+    pub modalities: BTreeSet<String>,
+    pub body_parts: BTreeSet<String>,
+    /// `YYYYMMDD`
+    pub study_dates: BTreeSet<String>,
+    pub descriptions: BTreeSet<String>,
+    pub indexed_at_ms: u64,
+}
+
+impl SearchDocument {
+    pub fn from_project(project: &Project) -> Self {
+        let mut document = Self {
+            project_name: String::new(),
+            tags: Vec::new(),
+            modalities: BTreeSet::new(),
+            body_parts: BTreeSet::new(),
+            study_dates: BTreeSet::new(),
+            descriptions: BTreeSet::new(),
+            indexed_at_ms: now_ms(),
+        };
+
+        // The search index is not encrypted, and an encrypted project doesn't reveal even its name on disk.
+        if project.is_encrypted() {
+            return document;
+        }
+
+        let metadata = project.metadata.load();
+        document.project_name = project.project_name.load().to_string();
+        document.tags = metadata.tags.clone();
+
+        if !metadata.description.is_empty() {
+            document.descriptions.insert(metadata.description.clone());
+        }
+
+        for study in project.studies() {
+            document.study_dates.extend(study.study_date);
+            document.descriptions.extend(study.study_description);
+
+            for series in study.series {
+                document.modalities.extend(series.modality);
+                document.body_parts.extend(series.body_part_examined);
+                document.descriptions.extend(series.series_description);
+            }
+        }
+
+        document
+    }
+
+    /// Every searchable term with the field it came from.
+    fn terms(&self) -> Vec<(SearchField, String)> {
+        let mut terms = Vec::new();
+
+        let mut add = |field: SearchField, text: &str| {
+            terms.extend(tokenize(text).into_iter().map(|term| (field, term)));
+        };
+
+        add(SearchField::Name, &self.project_name);
+        self.tags.iter().for_each(|tag| add(SearchField::Tag, tag));
+        self.modalities.iter().for_each(|modality| add(SearchField::Modality, modality));
+        self.body_parts.iter().for_each(|body_part| add(SearchField::BodyPart, body_part));
+        self.descriptions.iter().for_each(|description| add(SearchField::Description, description));
+
+        for date in &self.study_dates {
+            terms.extend(date_terms(date).into_iter().map(|term| (SearchField::StudyDate, term)));
+        }
+
+        terms
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchMatch {
+    pub field: SearchField,
+    /// The indexed term that matched, lowercased.
+    pub term: String,
+    /// Whether the match needed typo tolerance.
+    pub fuzzy: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchHit {
+    pub working_directory: PathBuf,
+    pub project_name: String,
+    pub score: f32,
+    pub matches: Vec<SearchMatch>,
+}
+
+/// Every project this installation has seen, keyed by working directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchIndex {
+    version: u32,
+    documents: BTreeMap<PathBuf, SearchDocument>,
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        Self {
+            version: SEARCH_INDEX_VERSION,
+            documents: BTreeMap::new(),
+        }
+    }
+}
+
+impl SearchIndex {
+    pub fn load(path: &Path) -> Result<Self, SearchError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let index: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+        if index.version != SEARCH_INDEX_VERSION {
+            warn!("Discarding search index with version {} (expected {})", index.version, SEARCH_INDEX_VERSION);
+            return Ok(Self::default());
+        }
+
+        Ok(index)
+    }
+
+    /// Writes to a temporary file first, so a crash never leaves a half written index behind.
+    pub fn save(&self, path: &Path) -> Result<(), SearchError> {
+        let tmp_path = path.with_extension("json.tmp");
+
+        std::fs::write(&tmp_path, serde_json::to_string(self)?)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    pub fn upsert(&mut self, working_directory: &Path, document: SearchDocument) {
+        self.documents.insert(working_directory.to_path_buf(), document);
+    }
+
+    pub fn remove(&mut self, working_directory: &Path) -> bool {
+        self.documents.remove(working_directory).is_some()
+    }
+
+    /// Drops projects whose manifest is gone. Returns how many were dropped.
+    pub fn prune(&mut self) -> usize {
+        let before = self.documents.len();
+        self.documents.retain(|working_directory, _| ProjectLayout::new(working_directory).manifest_file().exists());
+        before - self.documents.len()
+    }
+
+    /// Every query term has to match a term of the project, exactly, as a prefix or with a typo or two.
+    /// Hits are ordered by score, best first.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query_terms: Vec<String> = tokenize(&normalize_dates(query))
+            .into_iter()
+            .filter(|term| !STOPWORDS.contains(&term.as_str()))
+            .collect();
+
+        if query_terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut hits: Vec<SearchHit> = self
+            .documents
+            .iter()
+            .filter_map(|(working_directory, document)| {
+                let terms = document.terms();
+                let mut score = 0.0;
+                let mut matches = Vec::new();
+
+                for query_term in &query_terms {
+                    let (term_score, field, term, fuzzy) = terms
+                        .iter()
+                        .filter_map(|(field, term)| {
+                            let (quality, fuzzy) = match_quality(query_term, term)?;
+                            Some((quality * field.weight(), *field, term, fuzzy))
+                        })
+                        .max_by(|a, b| a.0.total_cmp(&b.0))?;
+
+                    score += term_score;
+                    matches.push(SearchMatch { field, term: term.clone(), fuzzy });
+                }
+
+                Some(SearchHit {
+                    working_directory: working_directory.clone(),
+                    project_name: document.project_name.clone(),
+                    score,
+                    matches,
+                })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.project_name.cmp(&b.project_name)));
+        hits.truncate(limit);
+
+        debug!("Search for {:?} found {} projects", query, hits.len());
+        hits
+    }
+}
+
+/// The search index shared by every open project, persisted after each change.
+pub struct ProjectSearch {
+    path: PathBuf,
+    index: Mutex<SearchIndex>,
+}
+
+impl ProjectSearch {
+    /// Loads the index from `path`. Projects that were deleted in the meantime are dropped.
+    pub fn open(path: &Path) -> Result<Self, SearchError> {
+        let mut index = SearchIndex::load(path)?;
+
+        if index.prune() > 0 {
+            index.save(path)?;
+        }
+
+        Ok(Self { path: path.to_path_buf(), index: Mutex::new(index) })
+    }
+
+    /// An index that starts empty, e.g. because the persisted one could not be read. It is still saved to `path`.
+    pub fn empty(path: &Path) -> Self {
+        Self { path: path.to_path_buf(), index: Mutex::new(SearchIndex::default()) }
+    }
+
+    /// (Re-)indexes the project. Call whenever its name, metadata or files changed.
+    pub fn update(&self, project: &Project) -> Result<(), SearchError> {
+        let document = SearchDocument::from_project(project);
+        let mut index = self.index.lock();
+
+        index.upsert(&project.working_directory.load(), document);
+        index.save(&self.path)
+    }
+
+    pub fn remove(&self, working_directory: &Path) -> Result<(), SearchError> {
+        let mut index = self.index.lock();
+
+        if index.remove(working_directory) {
+            index.save(&self.path)?;
+        }
+        Ok(())
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        self.index.lock().search(query, limit)
+    }
+}
+
+/// Lowercase words and numbers.
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Joins digits separated by `-`, `.` or `/`, so "2024-03" and "2024.03.15" become single terms.
+fn normalize_dates(query: &str) -> String {
+    let chars: Vec<char> = query.chars().collect();
+
+    chars
+        .iter()
+        .enumerate()
+        .filter(|(position, c)| {
+            !(matches!(c, '-' | '.' | '/')
+                && *position > 0
+                && chars[*position - 1].is_ascii_digit()
+                && chars.get(position + 1).is_some_and(char::is_ascii_digit))
+        })
+        .map(|(_, c)| *c)
+        .collect()
+}
+
+/// A `YYYYMMDD` date is found by its year, year and month, the full date and the month's name.
+fn date_terms(date: &str) -> Vec<String> {
+    let mut terms = vec![date.to_string()];
+
+    if date.len() == 8 && date.chars().all(|c| c.is_ascii_digit()) {
+        terms.push(date[..4].to_string());
+        terms.push(date[..6].to_string());
+
+        if let Ok(month @ 1..=12) = date[4..6].parse::<usize>() {
+            terms.push(MONTHS[month - 1].to_string());
+        }
+    }
+
+    terms
+}
+
+/// `None` if `query` doesn't match `term`. Otherwise how well it matches (1.0 is exact) and whether it was fuzzy.
+fn match_quality(query: &str, term: &str) -> Option<(f32, bool)> {
+    if query == term {
+        return Some((1.0, false));
+    }
+
+    if term.starts_with(query) {
+        return Some((0.8, false));
+    }
+
+    // Typos only count for words long enough to be more than a coincidence. A "typo" in a number is a different number.
+    if query.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let allowed = match query.chars().count() {
+        0..=3 => return None,
+        4..=7 => 1,
+        _ => 2,
+    };
+
+    // Compare against the term's prefix too, so a misspelled prefix still finds the word.
+    let prefix: String = term.chars().take(query.chars().count()).collect();
+    let distance = edit_distance(query, term).min(edit_distance(query, &prefix) + 1);
+
+    (distance <= allowed).then_some((0.5, true))
+}
+
+/// Levenshtein distance.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn document(name: &str, tags: &[&str], modality: &str, body_part: &str, date: &str) -> SearchDocument {
+        SearchDocument {
+            project_name: name.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            modalities: BTreeSet::from([modality.to_string()]),
+            body_parts: BTreeSet::from([body_part.to_string()]),
+            study_dates: BTreeSet::from([date.to_string()]),
+            descriptions: BTreeSet::new(),
+            indexed_at_ms: 0,
+        }
+    }
+
+    fn index() -> SearchIndex {
+        let mut index = SearchIndex::default();
+        index.upsert(Path::new("/projects/knee"), document("Knee follow-up", &["ortho"], "CT", "KNEE", "20240312"));
+        index.upsert(Path::new("/projects/head"), document("Head trauma", &["emergency"], "CT", "HEAD", "20240105"));
+        index.upsert(Path::new("/projects/spine"), document("Lumbar spine", &[], "MR", "LSPINE", "20230320"));
+        index
+    }
+
+    fn names(hits: &[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|hit| hit.project_name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_natural_query_matches_attributes() {
+        let index = index();
+
+        assert_eq!(names(&index.search("the CT of the knee from March", 10)), vec!["Knee follow-up"]);
+        assert_eq!(names(&index.search("ct 2024", 10)), vec!["Head trauma", "Knee follow-up"]);
+        assert_eq!(names(&index.search("2024-01", 10)), vec!["Head trauma"]);
+        assert!(index.search("of the", 10).is_empty());
+    }
+
+    #[test]
+    fn test_prefix_and_fuzzy_matching() {
+        let index = index();
+
+        let hits = index.search("lumb", 10);
+        assert_eq!(names(&hits), vec!["Lumbar spine"]);
+        assert!(!hits[0].matches[0].fuzzy);
+
+        let hits = index.search("emergancy", 10);
+        assert_eq!(names(&hits), vec!["Head trauma"]);
+        assert_eq!(hits[0].matches[0], SearchMatch { field: SearchField::Tag, term: "emergency".to_string(), fuzzy: true });
+
+        // Short words must match exactly or as a prefix.
+        assert!(index.search("mx", 10).is_empty());
+    }
+
+    #[test]
+    fn test_name_outranks_description() {
+        let mut index = SearchIndex::default();
+        let mut described = document("Study A", &[], "CT", "CHEST", "20240101");
+        described.descriptions.insert("Knee".to_string());
+        index.upsert(Path::new("/a"), described);
+        index.upsert(Path::new("/b"), document("Knee B", &[], "CT", "CHEST", "20240101"));
+
+        assert_eq!(names(&index.search("knee", 10)), vec!["Knee B", "Study A"]);
+        assert_eq!(index.search("knee", 1).len(), 1);
+    }
+
+    #[test]
+    fn test_open_prunes_deleted_projects() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("searchIndex.json");
+        let project_dir = tmp.path().join("project");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        std::fs::write(ProjectLayout::new(&project_dir).manifest_file(), "{}").unwrap();
+
+        let mut index = index();
+        index.upsert(&project_dir, document("Kept", &[], "CT", "KNEE", "20240101"));
+        index.save(&path).unwrap();
+
+        let search = ProjectSearch::open(&path).unwrap();
+        assert_eq!(names(&search.search("kept", 10)), vec!["Kept"]);
+        assert_eq!(SearchIndex::load(&path).unwrap().len(), 1);
+    }
+}
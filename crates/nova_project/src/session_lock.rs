@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use parking_lot::Mutex;
+
+/// Lock files held by sessions of this process that are still alive.
+static HELD: LazyLock<Mutex<HashSet<PathBuf>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// What the session lock of a project says about the last session that opened it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    /// No lock: the project was closed cleanly, or never opened.
+    Closed,
+    /// A lock whose process is gone, so the session ended without closing the project.
+    Abandoned,
+    /// Open in another instance that is still running, with this process id.
+    OpenElsewhere(u32),
+    /// Open in this process, by a project that is still alive.
+    OpenHere,
+}
+
+/// The session lock holds the id of the process that has the project open. It is created on open and
+/// removed on close, so finding one whose process is gone means the last session crashed.
+///
+/// Dropping the lock without [`SessionLock::release`] leaves the file behind, like a crash would.
+pub struct SessionLock {
+    path: PathBuf,
+}
+
+impl SessionLock {
+    /// A lock of this process whose [`SessionLock`] was dropped without being released counts as abandoned.
+    pub fn state(path: &Path) -> io::Result<SessionState> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(SessionState::Closed),
+            Err(err) => return Err(err),
+        };
+
+        match content.trim().parse::<u32>() {
+            Ok(pid) if pid == std::process::id() && HELD.lock().contains(&held_key(path)) => Ok(SessionState::OpenHere),
+            Ok(pid) if pid != std::process::id() && is_running(pid) => Ok(SessionState::OpenElsewhere(pid)),
+            _ => Ok(SessionState::Abandoned),
+        }
+    }
+
+    pub fn acquire(path: &Path) -> io::Result<Self> {
+        std::fs::write(path, std::process::id().to_string())?;
+
+        let path = held_key(path);
+        HELD.lock().insert(path.clone());
+        Ok(Self { path })
+    }
+
+    pub fn release(self) -> io::Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for SessionLock {
+    fn drop(&mut self) {
+        HELD.lock().remove(&self.path);
+    }
+}
+
+/// The same project can be reached through different paths.
+fn held_key(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+
+    // Signal 0 only checks that the process exists. EPERM means it does, but belongs to another user.
+    let signalled = unsafe { libc::kill(pid, 0) } == 0;
+    signalled || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return false;
+        }
+
+        let mut exit_code = 0;
+        let running = GetExitCodeProcess(process, &mut exit_code) != 0 && exit_code == STILL_ACTIVE as u32;
+        CloseHandle(process);
+        running
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_running(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_lock_tells_closed_abandoned_and_open_elsewhere_apart() {
+        let tmp = tempdir().unwrap();
+        let lock = tmp.path().join("session.lock");
+
+        assert_eq!(SessionLock::state(&lock).unwrap(), SessionState::Closed);
+
+        let held = SessionLock::acquire(&lock).unwrap();
+        assert_eq!(SessionLock::state(&lock).unwrap(), SessionState::OpenHere);
+        drop(held);
+        assert_eq!(SessionLock::state(&lock).unwrap(), SessionState::Abandoned);
+
+        let mut other = std::process::Command::new(if cfg!(windows) { "cmd" } else { "sleep" })
+            .args(if cfg!(windows) { &["/C", "timeout", "/T", "10"][..] } else { &["10"][..] })
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        std::fs::write(&lock, other.id().to_string()).unwrap();
+        assert_eq!(SessionLock::state(&lock).unwrap(), SessionState::OpenElsewhere(other.id()));
+
+        other.kill().unwrap();
+        other.wait().unwrap();
+        assert_eq!(SessionLock::state(&lock).unwrap(), SessionState::Abandoned);
+
+        std::fs::write(&lock, "not a pid").unwrap();
+        assert_eq!(SessionLock::state(&lock).unwrap(), SessionState::Abandoned);
+
+        SessionLock::acquire(&lock).unwrap().release().unwrap();
+        assert_eq!(SessionLock::state(&lock).unwrap(), SessionState::Closed);
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use nova_fs::encrypted_fs::EncryptedFs;
+
+/// Bump whenever the persisted layout changes. Older caches are discarded and rebuilt.
+pub const THUMBNAIL_CACHE_VERSION: u32 = 1;
+
+/// Edge length of the square thumbnails are scaled into, in pixels.
+pub const THUMBNAIL_SIZE: u32 = 128;
+
+#[derive(Error, Debug)]
+pub enum ThumbnailError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to (de)serialize thumbnail cache: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    #[error("Series {0} is not part of the project")]
+    UnknownSeries(String),
+
+    #[error("Failed to render thumbnail of series {series}: {reason}")]
+    Render { series: String, reason: String },
+}
+
+/// Turns a DICOM file into PNG bytes. Implemented on top of the native DICOM bridge,
+/// which nova_project can't depend on.
+pub trait ThumbnailRenderer: Send + Sync {
+    /// Renders `file` as a PNG that fits into `max_size`×`max_size` pixels.
+    fn render(&self, file: &Path, max_size: u32) -> anyhow::Result<Vec<u8>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThumbnailEntry {
+    /// BLAKE3 of the rendered instance. The PNG is named after it, so a changed instance never hits a stale PNG.
+    pub hash: String,
+    /// The rendered instance, relative to `projectFiles`.
+    pub source: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ThumbnailSummary {
+    pub generated: usize,
+    pub cached: usize,
+    pub failed: usize,
+    pub removed: usize,
+}
+
+/// Series instance UID → thumbnail. The PNGs live next to the cache file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThumbnailCache {
+    version: u32,
+    entries: BTreeMap<String, ThumbnailEntry>,
+}
+
+impl Default for ThumbnailCache {
+    fn default() -> Self {
+        Self {
+            version: THUMBNAIL_CACHE_VERSION,
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl ThumbnailCache {
+    pub fn load(path: &Path, fs: &EncryptedFs) -> Result<Self, ThumbnailError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let cache: Self = serde_json::from_str(&fs.read_to_string(path)?)?;
+
+        if cache.version != THUMBNAIL_CACHE_VERSION {
+            return Ok(Self::default());
+        }
+
+        Ok(cache)
+    }
+
+    pub fn save(&self, path: &Path, fs: &EncryptedFs) -> Result<(), ThumbnailError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        fs.write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, series_instance_uid: &str) -> Option<&ThumbnailEntry> {
+        self.entries.get(series_instance_uid)
+    }
+
+    /// Returns the entry that was replaced, so the caller can delete its PNG.
+    pub fn insert(&mut self, series_instance_uid: &str, entry: ThumbnailEntry) -> Option<ThumbnailEntry> {
+        self.entries.insert(series_instance_uid.to_string(), entry)
+    }
+
+    /// Drops the entries of every series `keep` rejects. Returns the dropped entries.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) -> Vec<ThumbnailEntry> {
+        let (kept, dropped) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|(series, _)| keep(series));
+
+        self.entries = kept;
+        dropped.into_values().collect()
+    }
+
+    pub fn file_name(hash: &str) -> String {
+        format!("{hash}.png")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entry(hash: &str) -> ThumbnailEntry {
+        ThumbnailEntry { hash: hash.to_string(), source: PathBuf::from(format!("{hash}.dcm")) }
+    }
+
+    #[test]
+    fn test_retain_and_roundtrip() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("thumbnails").join("thumbnails.json");
+
+        let mut cache = ThumbnailCache::default();
+        assert_eq!(cache.insert("1.2.1", entry("a")), None);
+        assert_eq!(cache.insert("1.2.1", entry("b")), Some(entry("a")));
+        cache.insert("1.2.2", entry("c"));
+
+        assert_eq!(cache.retain(|series| series == "1.2.1"), vec![entry("c")]);
+        cache.save(&path, &EncryptedFs::plaintext()).unwrap();
+
+        let loaded = ThumbnailCache::load(&path, &EncryptedFs::plaintext()).unwrap();
+        assert_eq!(loaded.get("1.2.1"), Some(&entry("b")));
+        assert_eq!(loaded.get("1.2.2"), None);
+    }
+}
@@ -0,0 +1,2 @@
+pub mod settings;
+pub mod settings_store;
\ No newline at end of file
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Smallest autosave interval that is accepted. `0` disables autosave.
+pub const MIN_AUTOSAVE_INTERVAL_SECS: u64 = 5;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SettingsValidationError {
+    #[error("windowing preset name must not be empty")]
+    EmptyPresetName,
+
+    #[error("windowing preset \"{0}\" is defined more than once")]
+    DuplicatePreset(String),
+
+    #[error("windowing preset \"{0}\" must have a positive width")]
+    InvalidPresetWidth(String),
+
+    #[error("default windowing preset \"{0}\" does not exist")]
+    UnknownDefaultPreset(String),
+
+    #[error("autosave interval must be 0 (disabled) or at least {MIN_AUTOSAVE_INTERVAL_SECS} seconds, got {0}")]
+    AutosaveIntervalTooShort(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowingPreset {
+    pub name: String,
+    pub center: f64,
+    pub width: f64,
+}
+
+impl WindowingPreset {
+    fn new(name: &str, center: f64, width: f64) -> Self {
+        Self { name: name.to_string(), center, width }
+    }
+}
+
+/// The effective settings, after every layer has been applied.
+///
+/// Every field is a valid settings key. Adding a field here makes it configurable globally
+/// and per project, as long as it has a built-in default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub windowing_presets: Vec<WindowingPreset>,
+    pub default_windowing_preset: String,
+    pub autosave_interval_secs: u64,
+    /// Number of undo steps kept per project. `0` disables undo.
+    pub undo_limit: usize,
+    /// Whether the undo history is saved with the project and survives a reopen.
+    pub persist_undo_history: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            windowing_presets: vec![
+                WindowingPreset::new("Brain", 40.0, 80.0),
+                WindowingPreset::new("Lung", -600.0, 1500.0),
+                WindowingPreset::new("Mediastinum", 40.0, 400.0),
+                WindowingPreset::new("Bone", 300.0, 2000.0),
+                WindowingPreset::new("Liver", 60.0, 150.0),
+                WindowingPreset::new("Abdomen", 40.0, 400.0),
+                WindowingPreset::new("Spine", 60.0, 300.0),
+                WindowingPreset::new("Pelvis", 50.0, 400.0),
+                WindowingPreset::new("Kidneys", 35.0, 240.0),
+            ],
+            default_windowing_preset: "Abdomen".to_string(),
+            autosave_interval_secs: 60,
+            undo_limit: 100,
+            persist_undo_history: true,
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<(), SettingsValidationError> {
+        for (position, preset) in self.windowing_presets.iter().enumerate() {
+            if preset.name.trim().is_empty() {
+                return Err(SettingsValidationError::EmptyPresetName);
+            }
+
+            if preset.width <= 0.0 {
+                return Err(SettingsValidationError::InvalidPresetWidth(preset.name.clone()));
+            }
+
+            if self.windowing_presets[..position].iter().any(|other| other.name == preset.name) {
+                return Err(SettingsValidationError::DuplicatePreset(preset.name.clone()));
+            }
+        }
+
+        if self.windowing_preset(&self.default_windowing_preset).is_none() {
+            return Err(SettingsValidationError::UnknownDefaultPreset(self.default_windowing_preset.clone()));
+        }
+
+        if self.autosave_interval_secs != 0 && self.autosave_interval_secs < MIN_AUTOSAVE_INTERVAL_SECS {
+            return Err(SettingsValidationError::AutosaveIntervalTooShort(self.autosave_interval_secs));
+        }
+
+        Ok(())
+    }
+
+    pub fn windowing_preset(&self, name: &str) -> Option<&WindowingPreset> {
+        self.windowing_presets.iter().find(|preset| preset.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_are_valid() {
+        assert_eq!(Settings::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_unknown_default_preset() {
+        let settings = Settings {
+            default_windowing_preset: "Knee".to_string(),
+            ..Settings::default()
+        };
+
+        assert_eq!(settings.validate(), Err(SettingsValidationError::UnknownDefaultPreset("Knee".to_string())));
+    }
+
+    #[test]
+    fn test_rejects_invalid_presets() {
+        let mut settings = Settings::default();
+        settings.windowing_presets.push(WindowingPreset::new("Brain", 0.0, 10.0));
+        assert_eq!(settings.validate(), Err(SettingsValidationError::DuplicatePreset("Brain".to_string())));
+
+        let mut settings = Settings::default();
+        settings.windowing_presets[0].width = 0.0;
+        assert_eq!(settings.validate(), Err(SettingsValidationError::InvalidPresetWidth("Brain".to_string())));
+    }
+
+    #[test]
+    fn test_autosave_interval_bounds() {
+        let disabled = Settings { autosave_interval_secs: 0, ..Settings::default() };
+        let too_short = Settings { autosave_interval_secs: 1, ..Settings::default() };
+
+        assert_eq!(disabled.validate(), Ok(()));
+        assert_eq!(too_short.validate(), Err(SettingsValidationError::AutosaveIntervalTooShort(1)));
+    }
+}
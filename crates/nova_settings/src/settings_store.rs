@@ -0,0 +1,329 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use arc_swap::ArcSwap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::{debug, info};
+use crate::settings::{Settings, SettingsValidationError};
+
+/// A partial set of settings, keyed by the field names of [`Settings`].
+pub type SettingsOverrides = BTreeMap<String, Value>;
+
+pub type SubscriptionId = u64;
+
+type Subscriber = dyn Fn(&SettingsChange) + Send + Sync;
+
+#[derive(Error, Debug)]
+pub enum SettingsError {
+    #[error("unknown setting \"{0}\"")]
+    UnknownKey(String),
+
+    #[error("invalid value for setting \"{key}\": {reason}")]
+    InvalidValue {
+        key: String,
+        reason: String,
+    },
+
+    #[error("invalid settings: {0}")]
+    Validation(#[from] SettingsValidationError),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("failed to parse settings file: {0}")]
+    TomlDe(#[from] toml::de::Error),
+
+    #[error("failed to write settings file: {0}")]
+    TomlSer(#[from] toml::ser::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingsScope {
+    /// Applies to every project. Persisted as TOML in the user's data directory.
+    Global,
+    /// Applies to the open project only. Persisted in the project manifest.
+    Project,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SettingsChange {
+    pub key: String,
+    pub value: Value,
+}
+
+/// Layered settings: built-in defaults, overridden by the global settings file,
+/// overridden by the settings of the open project.
+///
+/// Every change is validated against the fully resolved settings before it is applied,
+/// and subscribers are notified about each key whose effective value changed.
+pub struct SettingsStore {
+    global_file: Option<PathBuf>,
+    global: ArcSwap<SettingsOverrides>,
+    project: ArcSwap<SettingsOverrides>,
+    effective: ArcSwap<Settings>,
+    subscribers: Mutex<Vec<(SubscriptionId, Arc<Subscriber>)>>,
+    next_subscription: AtomicU64,
+    write_lock: Mutex<()>,
+}
+
+impl Default for SettingsStore {
+    fn default() -> Self {
+        Self::with_global_overrides(None, SettingsOverrides::new())
+    }
+}
+
+impl SettingsStore {
+    /// Creates a store backed by the global settings file. A missing file means "all defaults".
+    pub fn load(global_file: impl Into<PathBuf>) -> Result<Self, SettingsError> {
+        let global_file = global_file.into();
+
+        let overrides = match global_file.exists() {
+            true => toml::from_str::<SettingsOverrides>(&std::fs::read_to_string(&global_file)?)?,
+            false => SettingsOverrides::new(),
+        };
+
+        Self::resolve(&overrides, &SettingsOverrides::new())?;
+
+        debug!("Loaded global settings from {:?}", global_file);
+        Ok(Self::with_global_overrides(Some(global_file), overrides))
+    }
+
+    fn with_global_overrides(global_file: Option<PathBuf>, global: SettingsOverrides) -> Self {
+        let effective = Self::resolve(&global, &SettingsOverrides::new()).unwrap_or_default();
+
+        Self {
+            global_file,
+            global: ArcSwap::from_pointee(global),
+            project: ArcSwap::from_pointee(SettingsOverrides::new()),
+            effective: ArcSwap::from_pointee(effective),
+            subscribers: Mutex::new(Vec::new()),
+            next_subscription: AtomicU64::new(0),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn settings(&self) -> Arc<Settings> {
+        self.effective.load_full()
+    }
+
+    pub fn get(&self, key: &str) -> Result<Value, SettingsError> {
+        Self::to_map(&self.effective.load())
+            .remove(key)
+            .ok_or_else(|| SettingsError::UnknownKey(key.to_string()))
+    }
+
+    pub fn overrides(&self, scope: SettingsScope) -> Arc<SettingsOverrides> {
+        match scope {
+            SettingsScope::Global => self.global.load_full(),
+            SettingsScope::Project => self.project.load_full(),
+        }
+    }
+
+    /// Overrides `key` in `scope`, or removes the override if `value` is `None`.
+    /// Returns the new overrides of `scope`. Global overrides are persisted right away,
+    /// project overrides have to be stored in the project manifest by the caller.
+    pub fn set(&self, scope: SettingsScope, key: &str, value: Option<Value>) -> Result<Arc<SettingsOverrides>, SettingsError> {
+        let _guard = self.write_lock.lock();
+
+        let mut overrides = SettingsOverrides::clone(&self.overrides(scope));
+        match value {
+            Some(value) => overrides.insert(key.to_string(), value),
+            None => overrides.remove(key),
+        };
+
+        let (global, project) = match scope {
+            SettingsScope::Global => (overrides, SettingsOverrides::clone(&self.project.load())),
+            SettingsScope::Project => (SettingsOverrides::clone(&self.global.load()), overrides),
+        };
+
+        let effective = Self::resolve(&global, &project)?;
+
+        if scope == SettingsScope::Global && let Some(global_file) = &self.global_file {
+            Self::save_global(global_file, &global)?;
+        }
+
+        info!("Setting \"{key}\" changed in {:?} scope", scope);
+        Ok(self.apply(global, project, effective, scope))
+    }
+
+    /// Replaces the project layer, e.g. when a project is opened (or with empty overrides when it is closed).
+    pub fn set_project_overrides(&self, project: SettingsOverrides) -> Result<(), SettingsError> {
+        let _guard = self.write_lock.lock();
+
+        let global = SettingsOverrides::clone(&self.global.load());
+        let effective = Self::resolve(&global, &project)?;
+
+        self.apply(global, project, effective, SettingsScope::Project);
+        Ok(())
+    }
+
+    /// Registers a callback that is invoked once for every setting whose effective value changed.
+    pub fn subscribe(&self, subscriber: impl Fn(&SettingsChange) + Send + Sync + 'static) -> SubscriptionId {
+        let id = self.next_subscription.fetch_add(1, Ordering::Relaxed);
+        self.subscribers.lock().push((id, Arc::new(subscriber)));
+        id
+    }
+
+    pub fn unsubscribe(&self, id: SubscriptionId) {
+        self.subscribers.lock().retain(|(subscription, _)| *subscription != id);
+    }
+
+    fn apply(&self, global: SettingsOverrides, project: SettingsOverrides, effective: Settings, scope: SettingsScope) -> Arc<SettingsOverrides> {
+        let previous = Self::to_map(&self.effective.load());
+        let current = Self::to_map(&effective);
+
+        let global = Arc::new(global);
+        let project = Arc::new(project);
+
+        self.global.store(Arc::clone(&global));
+        self.project.store(Arc::clone(&project));
+        self.effective.store(Arc::new(effective));
+
+        let changes: Vec<SettingsChange> = current
+            .into_iter()
+            .filter(|(key, value)| previous.get(key) != Some(value))
+            .map(|(key, value)| SettingsChange { key, value })
+            .collect();
+
+        // Call subscribers without holding the lock, so they may (un)subscribe themselves.
+        let subscribers: Vec<Arc<Subscriber>> = self.subscribers.lock().iter().map(|(_, subscriber)| Arc::clone(subscriber)).collect();
+
+        for change in &changes {
+            for subscriber in &subscribers {
+                subscriber(change);
+            }
+        }
+
+        match scope {
+            SettingsScope::Global => global,
+            SettingsScope::Project => project,
+        }
+    }
+
+    fn resolve(global: &SettingsOverrides, project: &SettingsOverrides) -> Result<Settings, SettingsError> {
+        let mut merged = Self::to_map(&Settings::default());
+
+        for (key, value) in global.iter().chain(project.iter()) {
+            if !merged.contains_key(key) {
+                return Err(SettingsError::UnknownKey(key.clone()));
+            }
+
+            // Check the type of every value on its own, so the error names the offending key.
+            let mut single = Self::to_map(&Settings::default());
+            single.insert(key.clone(), value.clone());
+            serde_json::from_value::<Settings>(Value::Object(single.into_iter().collect()))
+                .map_err(|err| SettingsError::InvalidValue { key: key.clone(), reason: err.to_string() })?;
+
+            merged.insert(key.clone(), value.clone());
+        }
+
+        let settings: Settings = serde_json::from_value(Value::Object(merged.into_iter().collect()))
+            .map_err(|err| SettingsError::InvalidValue { key: String::new(), reason: err.to_string() })?;
+
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn to_map(settings: &Settings) -> BTreeMap<String, Value> {
+        match serde_json::to_value(settings) {
+            Ok(Value::Object(map)) => map.into_iter().collect(),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    fn save_global(path: &Path, overrides: &SettingsOverrides) -> Result<(), SettingsError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(path, toml::to_string_pretty(overrides)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_project_overrides_global_overrides_defaults() {
+        let store = SettingsStore::default();
+        assert_eq!(store.get("default_windowing_preset").unwrap(), json!("Abdomen"));
+
+        store.set(SettingsScope::Global, "default_windowing_preset", Some(json!("Lung"))).unwrap();
+        assert_eq!(store.settings().default_windowing_preset, "Lung");
+
+        store.set(SettingsScope::Project, "default_windowing_preset", Some(json!("Bone"))).unwrap();
+        assert_eq!(store.settings().default_windowing_preset, "Bone");
+
+        store.set_project_overrides(SettingsOverrides::new()).unwrap();
+        assert_eq!(store.settings().default_windowing_preset, "Lung");
+
+        store.set(SettingsScope::Global, "default_windowing_preset", None).unwrap();
+        assert_eq!(store.settings().default_windowing_preset, "Abdomen");
+    }
+
+    #[test]
+    fn test_invalid_changes_are_rejected() {
+        let store = SettingsStore::default();
+
+        assert!(matches!(store.set(SettingsScope::Global, "colour", Some(json!(1))), Err(SettingsError::UnknownKey(_))));
+        assert!(matches!(store.set(SettingsScope::Global, "autosave_interval_secs", Some(json!("soon"))), Err(SettingsError::InvalidValue { .. })));
+        assert!(matches!(store.set(SettingsScope::Project, "autosave_interval_secs", Some(json!(1))), Err(SettingsError::Validation(_))));
+
+        assert_eq!(*store.settings(), Settings::default());
+        assert!(store.overrides(SettingsScope::Global).is_empty());
+        assert!(store.overrides(SettingsScope::Project).is_empty());
+    }
+
+    #[test]
+    fn test_subscribers_are_notified_about_effective_changes() {
+        let store = SettingsStore::default();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+
+        let received = Arc::clone(&changes);
+        let id = store.subscribe(move |change| received.lock().push(change.clone()));
+
+        store.set(SettingsScope::Global, "autosave_interval_secs", Some(json!(30))).unwrap();
+        // Overriding with the same effective value is not a change.
+        store.set(SettingsScope::Project, "autosave_interval_secs", Some(json!(30))).unwrap();
+
+        store.unsubscribe(id);
+        store.set(SettingsScope::Global, "autosave_interval_secs", Some(json!(90))).unwrap();
+
+        assert_eq!(*changes.lock(), vec![SettingsChange { key: "autosave_interval_secs".to_string(), value: json!(30) }]);
+    }
+
+    #[test]
+    fn test_global_settings_are_persisted_as_toml() {
+        let tmp = tempdir().unwrap();
+        let file = tmp.path().join("settings").join("settings.toml");
+
+        let store = SettingsStore::load(&file).unwrap();
+        store.set(SettingsScope::Global, "autosave_interval_secs", Some(json!(120))).unwrap();
+        store.set(SettingsScope::Project, "autosave_interval_secs", Some(json!(10))).unwrap();
+
+        let contents = std::fs::read_to_string(&file).unwrap();
+        assert!(contents.contains("autosave_interval_secs = 120"));
+
+        let reloaded = SettingsStore::load(&file).unwrap();
+        assert_eq!(reloaded.settings().autosave_interval_secs, 120);
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_global_file() {
+        let tmp = tempdir().unwrap();
+        let file = tmp.path().join("settings.toml");
+        std::fs::write(&file, "unknown_key = true").unwrap();
+
+        assert!(matches!(SettingsStore::load(&file), Err(SettingsError::UnknownKey(_))));
+    }
+}
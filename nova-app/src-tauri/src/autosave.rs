@@ -0,0 +1,36 @@
+use std::time::Duration;
+use tracing::{debug, warn};
+use nova_di::ioc;
+use nova_settings::settings::MIN_AUTOSAVE_INTERVAL_SECS;
+use nova_settings::settings_store::SettingsStore;
+use crate::commands::project::registry;
+
+/// Periodically journals unsaved edits of every open project, so they survive a crash.
+/// The interval is re-read after every tick, so changing the setting takes effect without a restart.
+pub(crate) fn spawn_autosave() {
+    tauri::async_runtime::spawn(async {
+        loop {
+            let interval = ioc::singleton::ioc().resolve::<SettingsStore>().settings().autosave_interval_secs;
+
+            // 0 disables autosave. Keep polling, so enabling it again works.
+            tokio::time::sleep(Duration::from_secs(interval.max(MIN_AUTOSAVE_INTERVAL_SECS))).await;
+
+            if interval == 0 {
+                continue;
+            }
+
+            let registry = registry();
+            for id in registry.ids() {
+                let Ok(project) = registry.get(id) else {
+                    continue;
+                };
+
+                match project.autosave() {
+                    Ok(true) => debug!("Autosaved project {id}"),
+                    Ok(false) => {}
+                    Err(err) => warn!("Autosave of project {id} failed: {err}"),
+                }
+            }
+        }
+    });
+}
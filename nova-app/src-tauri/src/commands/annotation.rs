@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+use authenticated_command::authenticated_command;
+use tracing::info;
+use nova_project::annotations::{Annotation, AnnotationKind, AnnotationStore, ImportMode, Windowing};
+use nova_project::registry::ProjectId;
+use crate::commands::project::resolve_project;
+
+#[authenticated_command]
+pub async fn list_annotations(project_id: Option<ProjectId>, sop_instance_uid: String) -> Result<Vec<Annotation>, String> {
+    let project = resolve_project(project_id)?;
+    Ok(project.annotations.load().for_instance(&sop_instance_uid).to_vec())
+}
+
+#[authenticated_command]
+pub async fn add_annotation(project_id: Option<ProjectId>, sop_instance_uid: String, kind: AnnotationKind) -> Result<Annotation, String> {
+    let project = resolve_project(project_id)?;
+
+    project
+        .add_annotation(&sop_instance_uid, kind)
+        .map_err(|err| format!("Failed to add annotation: {err}"))
+}
+
+#[authenticated_command]
+pub async fn update_annotation(project_id: Option<ProjectId>, id: u64, revision: u32, kind: AnnotationKind) -> Result<Annotation, String> {
+    let project = resolve_project(project_id)?;
+
+    project
+        .update_annotation(id, revision, kind)
+        .map_err(|err| format!("Failed to update annotation: {err}"))
+}
+
+#[authenticated_command]
+pub async fn delete_annotation(project_id: Option<ProjectId>, id: u64) -> Result<(), String> {
+    let project = resolve_project(project_id)?;
+
+    project
+        .remove_annotation(id)
+        .map(|_| ())
+        .map_err(|err| format!("Failed to delete annotation: {err}"))
+}
+
+#[authenticated_command]
+pub async fn get_series_windowing(project_id: Option<ProjectId>, series_instance_uid: String) -> Result<Option<Windowing>, String> {
+    let project = resolve_project(project_id)?;
+    Ok(project.annotations.load().windowing(&series_instance_uid))
+}
+
+#[authenticated_command]
+pub async fn set_series_windowing(project_id: Option<ProjectId>, series_instance_uid: String, windowing: Option<Windowing>) -> Result<(), String> {
+    let project = resolve_project(project_id)?;
+
+    project
+        .set_windowing(&series_instance_uid, windowing)
+        .map_err(|err| format!("Failed to store windowing: {err}"))
+}
+
+#[authenticated_command]
+pub async fn export_annotations(project_id: Option<ProjectId>, file: PathBuf) -> Result<(), String> {
+    let project = resolve_project(project_id)?;
+
+    info!("Exporting annotations to {:?}", file);
+    project.annotations.load().save(&file).map_err(|err| format!("Failed to export annotations: {err}"))
+}
+
+#[authenticated_command]
+pub async fn import_annotations(project_id: Option<ProjectId>, file: PathBuf, mode: ImportMode) -> Result<(), String> {
+    let project = resolve_project(project_id)?;
+
+    info!("Importing annotations from {:?}", file);
+
+    let imported = AnnotationStore::load(&file).map_err(|err| format!("Failed to read annotations: {err}"))?;
+
+    project
+        .edit_annotations(|store| {
+            store.import(imported, mode);
+            Ok(())
+        })
+        .map_err(|err| format!("Failed to import annotations: {err}"))
+}
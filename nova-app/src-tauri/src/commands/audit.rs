@@ -0,0 +1,16 @@
+use authenticated_command::authenticated_command;
+use nova_project::audit::AuditEntry;
+use nova_project::registry::ProjectId;
+use crate::commands::project::resolve_project;
+
+/// Who imported, removed and exported what, oldest first.
+#[authenticated_command]
+pub async fn get_audit_log(project_id: Option<ProjectId>) -> Result<Vec<AuditEntry>, String> {
+    resolve_project(project_id)?.audit_log().map_err(|err| format!("Failed to read audit log: {err}"))
+}
+
+/// Checks that no audit log entry was changed, dropped or reordered. Returns the number of entries.
+#[authenticated_command]
+pub async fn verify_audit_log(project_id: Option<ProjectId>) -> Result<usize, String> {
+    resolve_project(project_id)?.verify_audit_log().map_err(|err| err.to_string())
+}
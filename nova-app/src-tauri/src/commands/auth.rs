@@ -20,6 +20,8 @@ pub async fn signup(username: String, password: String) -> Result<(), String> {
             let error_message = match err {
                 LoginError::RateLimitReached => "Too many signup attempts. Please try again later.",
                 LoginError::UserAlreadyExists => "User already exists. Please try another username",
+                // only describes the password the user just typed, so it is safe to show
+                LoginError::WeakPassword(feedback) => return Err(feedback),
                 _ => "Failed to signup. Try again later."
             };
 
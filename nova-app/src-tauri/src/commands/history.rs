@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+use authenticated_command::authenticated_command;
+use tracing::info;
+use nova_project::history::HistoryState;
+use nova_project::index::IndexUpdate;
+use nova_project::registry::ProjectId;
+use crate::commands::project::resolve_project;
+use crate::commands::search::index_project;
+
+#[authenticated_command]
+pub async fn undo(project_id: Option<ProjectId>) -> Result<HistoryState, String> {
+    let project = resolve_project(project_id)?;
+
+    let state = project.undo().await.map_err(|err| format!("Failed to undo: {err}"))?;
+    index_project(&project);
+    Ok(state)
+}
+
+#[authenticated_command]
+pub async fn redo(project_id: Option<ProjectId>) -> Result<HistoryState, String> {
+    let project = resolve_project(project_id)?;
+
+    let state = project.redo().await.map_err(|err| format!("Failed to redo: {err}"))?;
+    index_project(&project);
+    Ok(state)
+}
+
+#[authenticated_command]
+pub async fn get_history(project_id: Option<ProjectId>) -> Result<HistoryState, String> {
+    Ok(resolve_project(project_id)?.history())
+}
+
+/// Every edit until the matching `end_history_group` is undone and redone as one step.
+#[authenticated_command]
+pub async fn begin_history_group(project_id: Option<ProjectId>, label: String) -> Result<(), String> {
+    resolve_project(project_id)?.begin_group(&label);
+    Ok(())
+}
+
+#[authenticated_command]
+pub async fn end_history_group(project_id: Option<ProjectId>) -> Result<(), String> {
+    resolve_project(project_id)?.end_group().map_err(|err| err.to_string())
+}
+
+#[authenticated_command]
+pub async fn remove_import(project_id: Option<ProjectId>, file: PathBuf) -> Result<IndexUpdate, String> {
+    info!("Removing import {:?}", file);
+
+    let project = resolve_project(project_id)?;
+
+    let update = project
+        .remove_import(&file)
+        .await
+        .map_err(|err| format!("Failed to remove import: {err}"))?;
+    index_project(&project);
+    Ok(update)
+}
@@ -0,0 +1,47 @@
+use authenticated_command::authenticated_command;
+use nova_project::metadata::{FieldValue, ProjectMetadata};
+use nova_project::registry::ProjectId;
+use crate::commands::project::resolve_project;
+use crate::commands::search::index_project;
+
+#[authenticated_command]
+pub async fn get_project_metadata(project_id: Option<ProjectId>) -> Result<ProjectMetadata, String> {
+    Ok(ProjectMetadata::clone(&resolve_project(project_id)?.metadata.load()))
+}
+
+/// Replaces tags, description and custom fields in one undoable step. Returns the normalized metadata.
+#[authenticated_command]
+pub async fn set_project_metadata(project_id: Option<ProjectId>, metadata: ProjectMetadata) -> Result<ProjectMetadata, String> {
+    update_metadata(project_id, |_| metadata)
+}
+
+#[authenticated_command]
+pub async fn set_project_tags(project_id: Option<ProjectId>, tags: Vec<String>) -> Result<ProjectMetadata, String> {
+    update_metadata(project_id, |metadata| ProjectMetadata { tags, ..metadata })
+}
+
+#[authenticated_command]
+pub async fn set_project_description(project_id: Option<ProjectId>, description: String) -> Result<ProjectMetadata, String> {
+    update_metadata(project_id, |metadata| ProjectMetadata { description, ..metadata })
+}
+
+/// Sets the custom field `key`, or removes it if no value is given.
+#[authenticated_command]
+pub async fn set_project_field(project_id: Option<ProjectId>, key: String, value: Option<FieldValue>) -> Result<ProjectMetadata, String> {
+    update_metadata(project_id, |mut metadata| {
+        match value {
+            Some(value) => metadata.fields.insert(key, value),
+            None => metadata.fields.remove(&key),
+        };
+        metadata
+    })
+}
+
+fn update_metadata(project_id: Option<ProjectId>, update: impl FnOnce(ProjectMetadata) -> ProjectMetadata) -> Result<ProjectMetadata, String> {
+    let project = resolve_project(project_id)?;
+
+    let metadata = update(ProjectMetadata::clone(&project.metadata.load()));
+    let metadata = project.set_metadata(metadata).map_err(|err| format!("Invalid project details: {err}"))?;
+    index_project(&project);
+    Ok(metadata)
+}
@@ -1,4 +1,11 @@
 pub mod file_system;
 pub mod project;
 pub mod log;
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+pub mod annotation;
+pub mod settings;
+pub mod history;
+pub mod thumbnail;
+pub mod search;
+pub mod metadata;
+pub mod audit;
\ No newline at end of file
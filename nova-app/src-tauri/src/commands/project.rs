@@ -1,30 +1,220 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 use authenticated_command::authenticated_command;
 use tracing::{debug, info};
 use nova_project::project::*;
+use nova_project::index::{IndexUpdate, Study};
+use nova_project::layout::ProjectLayout;
+use nova_project::metadata::ProjectFilter;
+use nova_project::recovery::RecoveryInfo;
+use nova_project::registry::{OpenProject, ProjectId, ProjectRegistry};
+use nova_auth::auth_service::AuthService;
 use nova_di::ioc;
+use crate::commands::search::index_project;
+use crate::commands::settings::apply_project_settings;
+use crate::commands::thumbnail::spawn_thumbnail_generation;
 
 
 #[authenticated_command]
-pub async fn create_new_project(params: ProjectParams) -> Result<(), String> {
+pub async fn create_new_project(params: ProjectParams) -> Result<ProjectId, String> {
     info!("Creating new project: {}", params.project_name);
-    debug!("Working directory: {}", params.working_directory);
+    debug!("Working directory: {:?}", params.working_directory);
     debug!("Imported files: {:?}", params.imported_files);
 
+    // Creating a project clears its directory, so it must not be one that is open.
+    if let Some(id) = registry().find(ProjectLayout::new(&params.working_directory).root()) {
+        return Err(format!("Project creation failed: the project in {:?} is open as {id}. Close it first", params.working_directory));
+    }
+
+    let params = ProjectParams { actor: current_actor(), ..params };
     let result = Project::new_project(params).await;
     if let Ok(project) = result {
-        let arc = Arc::new(project);
-        ioc::singleton::ioc().register(move || Arc::clone(&arc));
+        let id = register_project(project);
         info!("Project successfully created");
-        Ok(())
+        Ok(id)
     }
     else {
         Err(format!("Project creation failed: {:?}", result.err()))
     }
 }
 
+/// Opens the project and makes it the active one. A project that is already open is only activated.
+/// `passphrase` is only needed for encrypted projects whose key isn't kept in the OS keyring.
+#[authenticated_command]
+pub async fn open_project(file: PathBuf, passphrase: Option<String>) -> Result<ProjectId, String> {
+    info!("Opening project from file: {:?}", file);
+
+    if let Some(id) = registry().find(ProjectLayout::from_selection(&file).root()) {
+        info!("Project is already open as {id}");
+        activate(id)?;
+        return Ok(id);
+    }
+
+    match Project::open_with_passphrase(&file, passphrase.as_deref()).await {
+        Ok(project) => {
+            let id = register_project(project);
+            info!("Project successfully opened");
+            Ok(id)
+        }
+        Err(err) => Err(format!("Failed to open project: {err}")),
+    }
+}
+
+pub(crate) fn registry() -> Arc<ProjectRegistry> {
+    ioc::singleton::ioc().resolve::<ProjectRegistry>()
+}
+
+/// The open project with `project_id`, or the active project if no id is given.
+pub(crate) fn resolve_project(project_id: Option<ProjectId>) -> Result<Arc<Project>, String> {
+    registry().resolve(project_id).map_err(|err| err.to_string())
+}
+
+/// The subject of the logged in user, which the audit logs attribute project changes to.
+fn current_actor() -> Option<String> {
+    ioc::singleton::ioc().resolve::<AuthService>().subject()
+}
+
+/// Adds the project to the registry, activates it and brings its search entry and thumbnails up to date.
+fn register_project(project: Project) -> ProjectId {
+    if let Some(actor) = current_actor() {
+        project.set_actor(&actor);
+    }
+
+    let (id, project) = registry().insert(project);
+    apply_project_settings(&project);
+    index_project(&project);
+    spawn_thumbnail_generation(project);
+    id
+}
+
+fn activate(project_id: ProjectId) -> Result<(), String> {
+    let project = registry().set_active(project_id).map_err(|err| err.to_string())?;
+    apply_project_settings(&project);
+    Ok(())
+}
+
+/// Releases the session locks of every open project. Unsaved changes are discarded.
+pub(crate) fn close_all_projects() {
+    registry().close_all();
+}
+
+/// The open projects, ordered by name. `filter` narrows them down by tags, fields and text.
+#[authenticated_command]
+pub async fn list_open_projects(filter: Option<ProjectFilter>) -> Result<Vec<OpenProject>, String> {
+    Ok(registry().list(&filter.unwrap_or_default()))
+}
+
+#[authenticated_command]
+pub async fn get_active_project() -> Result<Option<ProjectId>, String> {
+    Ok(registry().active().map(|(id, _)| id))
+}
+
+/// Switches the project the UI works on. Project settings follow the active project.
+#[authenticated_command]
+pub async fn set_active_project(project_id: ProjectId) -> Result<(), String> {
+    activate(project_id)
+}
+
+/// Closes the project, discarding unsaved changes. Returns the project that became active instead, if any.
+#[authenticated_command]
+pub async fn close_project(project_id: Option<ProjectId>) -> Result<Option<ProjectId>, String> {
+    let registry = registry();
+    let id = match project_id {
+        Some(id) => id,
+        None => registry.active().map(|(id, _)| id).ok_or_else(|| "No project is open".to_string())?,
+    };
+
+    let active = registry.close(id).map_err(|err| err.to_string())?;
+    if let Some(active) = active {
+        activate(active)?;
+    }
+
+    info!("Project {id} closed");
+    Ok(active)
+}
+
+#[authenticated_command]
+pub async fn save_project(project_id: Option<ProjectId>) -> Result<(), String> {
+    let project = resolve_project(project_id)?;
+
+    project.save().map_err(|err| format!("Failed to save project: {err}"))?;
+    index_project(&project);
+    Ok(())
+}
+
 #[authenticated_command]
-pub async fn open_project(file: String) -> Result<(), String> {
-    info!("Opening project from file: {}", file);
+pub async fn rename_project(project_id: Option<ProjectId>, project_name: String) -> Result<(), String> {
+    resolve_project(project_id)?.rename(project_name);
     Ok(())
 }
+
+#[authenticated_command]
+pub async fn is_project_dirty(project_id: Option<ProjectId>) -> Result<bool, String> {
+    Ok(resolve_project(project_id)?.is_dirty())
+}
+
+/// Set if the project was not closed cleanly last time and a journal is waiting to be restored.
+#[authenticated_command]
+pub async fn get_project_recovery(project_id: Option<ProjectId>) -> Result<Option<RecoveryInfo>, String> {
+    Ok(resolve_project(project_id)?.recovery())
+}
+
+#[authenticated_command]
+pub async fn restore_project_recovery(project_id: Option<ProjectId>) -> Result<bool, String> {
+    resolve_project(project_id)?.restore_recovery().map_err(|err| format!("Failed to restore project: {err}"))
+}
+
+#[authenticated_command]
+pub async fn discard_project_recovery(project_id: Option<ProjectId>) -> Result<(), String> {
+    resolve_project(project_id)?.discard_recovery().map_err(|err| format!("Failed to discard recovery journal: {err}"))
+}
+
+#[authenticated_command]
+pub async fn get_project_studies(project_id: Option<ProjectId>) -> Result<Vec<Study>, String> {
+    Ok(resolve_project(project_id)?.studies())
+}
+
+#[authenticated_command]
+pub async fn refresh_project_index(project_id: Option<ProjectId>) -> Result<IndexUpdate, String> {
+    let project = resolve_project(project_id)?;
+
+    let update = project.refresh_index().await.map_err(|err| format!("Failed to refresh project index: {err}"))?;
+    index_project(&project);
+    Ok(update)
+}
+
+/// Checks the project for missing, modified and orphaned files. `repair` fixes what can be fixed.
+#[authenticated_command]
+pub async fn verify_project(project_id: Option<ProjectId>, repair: bool) -> Result<VerifyReport, String> {
+    let project = resolve_project(project_id)?;
+
+    project.verify(repair).await.map_err(|err| format!("Failed to verify project: {err}"))
+}
+
+/// "Save as": copies the project to a new working directory and opens the copy next to it as the active project.
+#[authenticated_command]
+pub async fn duplicate_project(project_id: Option<ProjectId>, params: DuplicateParams) -> Result<ProjectId, String> {
+    let project = resolve_project(project_id)?;
+    info!("Duplicating project as {} in {:?}", params.project_name, params.working_directory);
+
+    match project.duplicate(params).await {
+        Ok(duplicate) => {
+            let id = register_project(duplicate);
+            info!("Project successfully duplicated");
+            Ok(id)
+        }
+        Err(err) => Err(format!("Failed to duplicate project: {err}")),
+    }
+}
+
+/// Exports the project, optionally de-identified, e.g. to share it with an external collaborator.
+#[authenticated_command]
+pub async fn export_project(project_id: Option<ProjectId>, destination: PathBuf, options: ExportOptions) -> Result<ExportSummary, String> {
+    let project = resolve_project(project_id)?;
+    info!("Exporting project to {:?} (de-identified: {})", destination, options.deidentification.is_some());
+
+    tauri::async_runtime::spawn_blocking(move || project.export(&destination, &options))
+        .await
+        .map_err(|err| format!("Export task failed: {err}"))?
+        .map_err(|err| format!("Failed to export project: {err}"))
+}
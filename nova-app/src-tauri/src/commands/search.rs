@@ -0,0 +1,29 @@
+use authenticated_command::authenticated_command;
+use tracing::warn;
+use nova_di::ioc;
+use nova_fs::folder_resolver::FolderResolver;
+use nova_project::project::Project;
+use nova_project::search::{ProjectSearch, SearchHit, DEFAULT_SEARCH_LIMIT};
+
+/// Finds projects by name, tag and DICOM attributes, e.g. "CT knee march". Closed projects are found too.
+#[authenticated_command]
+pub async fn search_projects(query: String, limit: Option<usize>) -> Result<Vec<SearchHit>, String> {
+    let search = ioc::singleton::ioc().resolve::<ProjectSearch>();
+    Ok(search.search(&query, limit.unwrap_or(DEFAULT_SEARCH_LIMIT)))
+}
+
+pub(crate) fn load_project_search() -> ProjectSearch {
+    let index_file = FolderResolver::resolve_search_dir().join("searchIndex.json");
+
+    ProjectSearch::open(&index_file).unwrap_or_else(|err| {
+        warn!("Rebuilding search index {:?}: {err}", index_file);
+        ProjectSearch::empty(&index_file)
+    })
+}
+
+/// Brings the project's search entry up to date. A failure only makes search results stale.
+pub(crate) fn index_project(project: &Project) {
+    if let Err(err) = ioc::singleton::ioc().resolve::<ProjectSearch>().update(project) {
+        warn!("Failed to update search index: {err}");
+    }
+}
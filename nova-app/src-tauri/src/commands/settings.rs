@@ -0,0 +1,74 @@
+use authenticated_command::authenticated_command;
+use serde_json::Value;
+use tracing::{info, warn};
+use nova_di::ioc;
+use nova_project::project::Project;
+use nova_settings::settings::Settings;
+use nova_settings::settings_store::{SettingsOverrides, SettingsScope, SettingsStore};
+use crate::commands::project::resolve_project;
+
+#[authenticated_command]
+pub async fn get_settings() -> Result<Settings, String> {
+    Ok(Settings::clone(&ioc::singleton::ioc().resolve::<SettingsStore>().settings()))
+}
+
+#[authenticated_command]
+pub async fn get_setting(key: String) -> Result<Value, String> {
+    ioc::singleton::ioc()
+        .resolve::<SettingsStore>()
+        .get(&key)
+        .map_err(|err| err.to_string())
+}
+
+#[authenticated_command]
+pub async fn get_setting_overrides(scope: SettingsScope) -> Result<SettingsOverrides, String> {
+    Ok(SettingsOverrides::clone(&ioc::singleton::ioc().resolve::<SettingsStore>().overrides(scope)))
+}
+
+/// Overrides `key` in `scope`. A `null` value removes the override again.
+/// The project scope always refers to the active project, whose settings are the effective ones.
+#[authenticated_command]
+pub async fn set_setting(scope: SettingsScope, key: String, value: Option<Value>) -> Result<(), String> {
+    let store = ioc::singleton::ioc().resolve::<SettingsStore>();
+
+    // Fail before touching the store, so a project override can't outlive its project.
+    let project = match scope {
+        SettingsScope::Project => Some(resolve_project(None)?),
+        SettingsScope::Global => None,
+    };
+
+    let previous = store.overrides(scope);
+    let overrides = store
+        .set(scope, &key, value)
+        .map_err(|err| format!("Failed to change setting: {err}"))?;
+
+    if let Some(project) = project && let Err(err) = project.set_settings(SettingsOverrides::clone(&overrides)) {
+        // Keep the store in line with what is persisted.
+        if let Err(rollback_err) = store.set_project_overrides(SettingsOverrides::clone(&previous)) {
+            warn!("Failed to roll back project settings: {rollback_err}");
+        }
+        return Err(format!("Failed to save project settings: {err}"));
+    }
+
+    info!("Setting \"{key}\" updated");
+    Ok(())
+}
+
+/// Puts the settings of a newly activated project on top of the global settings.
+pub(crate) fn apply_project_settings(project: &Project) {
+    let store = ioc::singleton::ioc().resolve::<SettingsStore>();
+
+    if let Err(err) = store.set_project_overrides(SettingsOverrides::clone(&project.settings.load())) {
+        warn!("Ignoring invalid project settings: {err}");
+        if let Err(err) = store.set_project_overrides(SettingsOverrides::new()) {
+            warn!("Failed to reset project settings: {err}");
+        }
+    }
+
+    apply_history_settings(project);
+}
+
+pub(crate) fn apply_history_settings(project: &Project) {
+    let settings = ioc::singleton::ioc().resolve::<SettingsStore>().settings();
+    project.configure_history(settings.undo_limit, settings.persist_undo_history);
+}
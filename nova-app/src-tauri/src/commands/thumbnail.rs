@@ -0,0 +1,49 @@
+use std::sync::Arc;
+use authenticated_command::authenticated_command;
+use tauri::ipc::Response;
+use tracing::warn;
+use ::nova::dicom::thumbnail::DicomThumbnailRenderer;
+use nova_di::ioc;
+use nova_project::project::{Project, ProjectError};
+use nova_project::registry::ProjectId;
+use nova_project::thumbnails::ThumbnailSummary;
+use crate::commands::project::resolve_project;
+
+/// The PNG thumbnail of a series as raw bytes. Rendered on first use and whenever the series changed.
+#[authenticated_command]
+pub async fn get_series_thumbnail(project_id: Option<ProjectId>, series_instance_uid: String) -> Result<Response, String> {
+    let project = resolve_project(project_id)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let renderer = ioc::singleton::ioc().resolve::<DicomThumbnailRenderer>();
+        project.thumbnail(&series_instance_uid, renderer.as_ref())
+    })
+        .await
+        .map_err(|err| format!("Thumbnail task failed: {err}"))?
+        .map(Response::new)
+        .map_err(|err| format!("Failed to load thumbnail: {err}"))
+}
+
+#[authenticated_command]
+pub async fn generate_thumbnails(project_id: Option<ProjectId>) -> Result<ThumbnailSummary, String> {
+    let project = resolve_project(project_id)?;
+
+    tauri::async_runtime::spawn_blocking(move || render_thumbnails(&project))
+        .await
+        .map_err(|err| format!("Thumbnail task failed: {err}"))?
+        .map_err(|err| format!("Failed to generate thumbnails: {err}"))
+}
+
+/// Renders the thumbnails of a freshly created or opened project in the background.
+pub(crate) fn spawn_thumbnail_generation(project: Arc<Project>) {
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(err) = render_thumbnails(&project) {
+            warn!("Failed to generate thumbnails: {err}");
+        }
+    });
+}
+
+fn render_thumbnails(project: &Project) -> Result<ThumbnailSummary, ProjectError> {
+    let renderer = ioc::singleton::ioc().resolve::<DicomThumbnailRenderer>();
+    project.generate_thumbnails(renderer.as_ref())
+}
@@ -1,5 +1,6 @@
 mod commands;
 mod auth_state;
+mod autosave;
 
 use nova_auth::auth_service::*;
 use nova_di::ioc;
@@ -13,10 +14,20 @@ use tracing_subscriber::{fmt};
 use tracing_subscriber::fmt::{FormatEvent, FormatFields};
 use tracing_subscriber::registry::LookupSpan;
 use nova_fs::folder_resolver::FolderResolver;
+use nova_project::registry::ProjectRegistry;
+use nova_settings::settings_store::SettingsStore;
+use tauri::Emitter;
 use crate::auth_state::auth_state::AuthState;
 use crate::commands::auth::{login, logout, signup};
 use crate::commands::file_system::*;
 use crate::commands::project::*;
+use crate::commands::annotation::*;
+use crate::commands::settings::*;
+use crate::commands::history::*;
+use crate::commands::thumbnail::*;
+use crate::commands::search::*;
+use crate::commands::metadata::*;
+use crate::commands::audit::*;
 use crate::commands::auth::is_authenticated;
 
 struct LogFormatter;
@@ -96,6 +107,8 @@ async fn try_load_session(auth_state: &AuthState) {
 pub async fn run() {
     setup_logging();
     let _app = App::initialize();
+    ioc::singleton::ioc().register(ProjectRegistry::new);
+    ioc::singleton::ioc().register(load_project_search);
     let auth_state = AuthState::default();
 
     try_load_session(&auth_state).await;
@@ -118,6 +131,21 @@ pub async fn run() {
 
     builder
         .manage(auth_state)
+        .setup(|app| {
+            let handle = app.handle().clone();
+            ioc::singleton::ioc().resolve::<SettingsStore>().subscribe(move |change| {
+                if let Err(err) = handle.emit("settings-changed", change) {
+                    warn!("Failed to emit settings change: {err}");
+                }
+                if matches!(change.key.as_str(), "undo_limit" | "persist_undo_history")
+                    && let Ok(project) = resolve_project(None)
+                {
+                    apply_history_settings(&project);
+                }
+            });
+            autosave::spawn_autosave();
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             read_file_to_string,
             create_dir,
@@ -130,6 +158,49 @@ pub async fn run() {
             write_file,
             open_project,
             create_new_project,
+            list_open_projects,
+            get_active_project,
+            set_active_project,
+            close_project,
+            save_project,
+            rename_project,
+            is_project_dirty,
+            get_project_recovery,
+            restore_project_recovery,
+            discard_project_recovery,
+            get_project_studies,
+            get_project_metadata,
+            set_project_metadata,
+            set_project_tags,
+            set_project_description,
+            set_project_field,
+            get_series_thumbnail,
+            search_projects,
+            generate_thumbnails,
+            refresh_project_index,
+            verify_project,
+            duplicate_project,
+            export_project,
+            get_audit_log,
+            verify_audit_log,
+            list_annotations,
+            add_annotation,
+            update_annotation,
+            delete_annotation,
+            get_series_windowing,
+            set_series_windowing,
+            export_annotations,
+            import_annotations,
+            get_settings,
+            get_setting,
+            get_setting_overrides,
+            set_setting,
+            undo,
+            redo,
+            get_history,
+            begin_history_group,
+            end_history_group,
+            remove_import,
             is_empty,
             join,
             log,
@@ -138,6 +209,11 @@ pub async fn run() {
             is_authenticated,
             signup,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_, event| {
+            if let tauri::RunEvent::Exit = event {
+                close_all_projects();
+            }
+        });
 }
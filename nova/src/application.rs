@@ -1,9 +1,12 @@
 use std::path::PathBuf;
-use tracing::{info};
+use tracing::{info, warn};
 use nova_auth::auth_service::AuthService;
 use nova_di::ioc;
 use nova_fs::folder_resolver::FolderResolver;
+use nova_settings::settings_store::SettingsStore;
 use crate::dicom::bridge::dicom_bridge::{dicom_api, register_logger_service};
+use crate::dicom::thumbnail::DicomThumbnailRenderer;
+use crate::dicom::windowing::{apply_windowing_presets, WINDOWING_SETTINGS_KEYS};
 
 pub struct Settings {
     assets_directory: PathBuf,
@@ -17,8 +20,11 @@ impl App {
         info!("Initializing app");
 
         ioc::singleton::ioc().register(AuthService::new);
+        ioc::singleton::ioc().register(Self::load_settings);
+        ioc::singleton::ioc().register(DicomThumbnailRenderer::new);
         register_logger_service();
         dicom_api::init();
+        Self::sync_windowing_presets();
 
         App {
             settings: Settings {
@@ -26,4 +32,30 @@ impl App {
             }
         }
     }
+
+    /// Keeps the native windowing presets in step with the effective settings, including project overrides.
+    fn sync_windowing_presets() {
+        let store = ioc::singleton::ioc().resolve::<SettingsStore>();
+
+        if let Err(err) = apply_windowing_presets(&store.settings()) {
+            warn!("{err:#}");
+        }
+
+        store.subscribe(|change| {
+            if WINDOWING_SETTINGS_KEYS.contains(&change.key.as_str())
+                && let Err(err) = apply_windowing_presets(&ioc::singleton::ioc().resolve::<SettingsStore>().settings())
+            {
+                warn!("{err:#}");
+            }
+        });
+    }
+
+    fn load_settings() -> SettingsStore {
+        let settings_file = FolderResolver::resolve_settings_dir().join("settings.toml");
+
+        SettingsStore::load(&settings_file).unwrap_or_else(|err| {
+            warn!("Ignoring global settings from {:?}: {err}", settings_file);
+            SettingsStore::default()
+        })
+    }
 }
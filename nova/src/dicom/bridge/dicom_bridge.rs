@@ -13,6 +13,9 @@ pub mod dicom_api {
         #[namespace = "nova::api"]
         fn init();
 
+        #[namespace = "nova::api"]
+        fn set_windowing_presets(presets_json: &CxxString, default_preset: &CxxString) -> Result<()>;
+
         #[namespace = "nova::api"]
         type dicom_handle;
 
@@ -20,10 +23,13 @@ pub mod dicom_api {
         fn new_dicom_handle() -> Result<UniquePtr<dicom_handle>>;
 
         #[namespace = "nova::api"]
-        fn get_image_metadata(&self) -> UniquePtr<CxxString>;
+        fn open_dicom_handle(path_to_dicom: &CxxString) -> Result<UniquePtr<dicom_handle>>;
 
-        // #[namespace = "nova::api"]
-        // fn get_image_pixeldata(&self) -> UniquePtr<CxxVector<u8>>;
+        #[namespace = "nova::api"]
+        fn get_image_metadata(&self) -> Result<UniquePtr<CxxString>>;
+
+        #[namespace = "nova::api"]
+        fn get_image_pixeldata(&self) -> Result<UniquePtr<CxxVector<u8>>>;
     }
 }
 
@@ -1,13 +1,19 @@
 #include "dicom_api.h"
 #include <libassert/assert.hpp>
 #include "../dicom_image.h"
+#include "../dicom_windowing.h"
 #include "../lib/json.h"
 #include "../lib/logger.h"
 
 struct nova::api::dicom_handle::dicom_handle_impl {
     dicom_handle_impl()
         :
-        m_image(std::make_unique<dcm::dicom_image>("D:/repos/nova/nova-cli/input/CT-MONO2-16-brain.jls.dcm"))
+        dicom_handle_impl("D:/repos/nova/nova-cli/input/CT-MONO2-16-brain.jls.dcm")
+    {}
+
+    explicit dicom_handle_impl(const std::string& pathToDicom)
+        :
+        m_image(std::make_unique<dcm::dicom_image>(pathToDicom))
     {}
 
     result<ok> load_image() {
@@ -24,11 +30,20 @@ struct nova::api::dicom_handle::dicom_handle_impl {
     std::optional<dcm::image_data> m_imageData{std::nullopt};
 
     [[nodiscard]] std::string get_metadata() const {
-        DEBUG_ASSERT(m_image != nullptr && m_imageData != std::nullopt);
+        if (!m_imageData) {
+            throw std::runtime_error("no image loaded");
+        }
         auto str = json::to_string(*m_imageData);
         logger::info("get_image_metadata returning \n{}", str);
         return str;
     }
+
+    [[nodiscard]] const std::vector<uint8_t>& get_pixeldata() const {
+        if (!m_imageData) {
+            throw std::runtime_error("no image loaded");
+        }
+        return m_imageData->pixelData;
+    }
 };
 
 nova::api::dicom_handle::dicom_handle()
@@ -36,6 +51,11 @@ nova::api::dicom_handle::dicom_handle()
     m_impl(std::make_unique<dicom_handle_impl>())
 {}
 
+nova::api::dicom_handle::dicom_handle(const std::string& pathToDicom)
+    :
+    m_impl(std::make_unique<dicom_handle_impl>(pathToDicom))
+{}
+
 nova::api::dicom_handle::~dicom_handle() = default;
 
 void nova::api::dicom_handle::load_image() {
@@ -51,18 +71,35 @@ std::unique_ptr<std::string> nova::api::dicom_handle::get_image_metadata() const
     return std::make_unique<std::string>(m_impl->get_metadata());
 }
 
-// std::unique_ptr<std::vector<uint8_t>> nova::api::dicom_handle::get_image_pixeldata() const {
-//     DEBUG_ASSERT(m_imageData != nullptr);
-//     return std::make_unique<std::vector<uint8_t>>(m_imageData->pixelData);
-// }
+std::unique_ptr<std::vector<uint8_t>> nova::api::dicom_handle::get_image_pixeldata() const {
+    DEBUG_ASSERT(m_impl != nullptr);
+    return std::make_unique<std::vector<uint8_t>>(m_impl->get_pixeldata());
+}
 
 void nova::api::init() {
     logger::init();
     logger::info("nova::api::init()");
 }
 
+void nova::api::set_windowing_presets(const std::string& presetsJson, const std::string& defaultPreset) {
+    try {
+        auto presets = nlohmann::json::parse(presetsJson).get<nova::hash_map<std::string, dcm::dicom_window>>();
+        dcm::dicom_windowing::set_presets(std::move(presets), defaultPreset);
+    }
+    catch (const nlohmann::json::exception& e) {
+        logger::error("Failed to parse windowing presets. reason: {}", e.what());
+        throw std::runtime_error("invalid windowing presets");
+    }
+}
+
 std::unique_ptr<nova::api::dicom_handle> nova::api::new_dicom_handle() {
     auto handle = std::make_unique<dicom_handle>();
     handle->load_image();
     return handle;
+}
+
+std::unique_ptr<nova::api::dicom_handle> nova::api::open_dicom_handle(const std::string& pathToDicom) {
+    auto handle = std::make_unique<dicom_handle>(pathToDicom);
+    handle->load_image();
+    return handle;
 }
\ No newline at end of file
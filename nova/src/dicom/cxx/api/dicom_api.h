@@ -42,6 +42,8 @@ namespace nova::api {
         ImageDimensions dimensions;
         int32_t bytes_per_pixel;
         uint16_t samples_per_pixel;
+        // 0 for unsigned samples, 1 for two's complement.
+        uint16_t pixel_representation;
         double slope;
         double intercept;
         PhotometricInterpretation photometric_interpretation;
@@ -50,16 +52,20 @@ namespace nova::api {
     class NOVA_EXPORT dicom_handle {
     public:
         explicit dicom_handle();
+        explicit dicom_handle(const std::string& pathToDicom);
         ~dicom_handle();
 
         void load_image();
         [[nodiscard]] std::unique_ptr<std::string> get_image_metadata() const;
-        //std::unique_ptr<std::vector<uint8_t>> get_image_pixeldata() const;
+        [[nodiscard]] std::unique_ptr<std::vector<uint8_t>> get_image_pixeldata() const;
     private:
         struct dicom_handle_impl;
         std::unique_ptr<dicom_handle_impl> m_impl;
     };
 
     NOVA_EXPORT void init();
+    // `presetsJson` maps preset names to `{"level": [..], "width": [..]}`.
+    NOVA_EXPORT void set_windowing_presets(const std::string& presetsJson, const std::string& defaultPreset);
     NOVA_EXPORT std::unique_ptr<dicom_handle> new_dicom_handle();
+    NOVA_EXPORT std::unique_ptr<dicom_handle> open_dicom_handle(const std::string& pathToDicom);
 }
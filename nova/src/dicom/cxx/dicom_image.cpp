@@ -32,7 +32,10 @@ result<ok> dcm::dicom_ct_image_loader::load_image(dicom_image& image) {
 
     logger::debug("Successfully extracted image data.");
 
-    const int cvDepth = (image.m_imageData->bytesPerPixel == 1) ? CV_8U : CV_16U;
+    const bool isSigned = image.m_imageData->pixelRepresentation == 1;
+    const int cvDepth = (image.m_imageData->bytesPerPixel == 1)
+        ? (isSigned ? CV_8S : CV_8U)
+        : (isSigned ? CV_16S : CV_16U);
     const int cvType = CV_MAKETYPE(cvDepth, image.m_imageData->samplesPerPixel);
     const cv::Mat rawMat(
         image.m_imageData->dimensions.height,
@@ -125,6 +128,7 @@ bool dcm::dicom_image::fill_image_data() {
         .dimensions = resolve_image_dimensions(),
         .bytesPerPixel = m_dicomImage->GetPixelFormat().GetPixelSize() / samplesPerPixel,
         .samplesPerPixel = samplesPerPixel,
+        .pixelRepresentation = m_dicomImage->GetPixelFormat().GetPixelRepresentation(),
         .pixelData = std::move(*pixelData),
         .slope = m_dicomImage->GetSlope(),
         .intercept = m_dicomImage->GetIntercept(),
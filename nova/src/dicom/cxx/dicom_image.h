@@ -48,6 +48,8 @@ namespace nova::dcm {
         image_dimensions dimensions;
         int32_t bytesPerPixel;
         uint16_t samplesPerPixel;
+        // 0 for unsigned samples, 1 for two's complement.
+        uint16_t pixelRepresentation;
         std::vector<uint8_t> pixelData;
         double slope;
         double intercept;
@@ -59,6 +61,7 @@ namespace nova::dcm {
             dimensions,
             bytesPerPixel,
             samplesPerPixel,
+            pixelRepresentation,
             slope,
             intercept,
             photometricInterpretation,
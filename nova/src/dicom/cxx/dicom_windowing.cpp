@@ -8,29 +8,42 @@ using namespace nova;
 namespace rn = std::ranges;
 
 dcm::dicom_window dcm::dicom_windowing::load_presets(const dicom_image& image) {
-    auto result = json::parse<nova::hash_map<std::string, dicom_window>>(m_presetPath);
-
-    if (result) {
-        const auto tag = image.read_tag(dicom_tag::body_part_examined);
-        if (!tag) {
-            return default_window();
-        }
+    const auto tag = image.read_tag(dicom_tag::body_part_examined);
+    if (!tag) {
+        return default_window();
+    }
 
-        const auto key = normalize_body_part(*tag);
-        if (!key) {
-            return default_window();
-        }
+    const auto key = normalize_body_part(*tag);
+    if (!key) {
+        return default_window();
+    }
 
-        return result->contains(*key)
-            ? (*result)[*key]
-            : default_window();
+    const std::scoped_lock lock(m_presetMutex);
+    const auto preset = m_presets.find(*key);
+    if (preset == m_presets.end()) {
+        return default_window();
     }
-    return default_window();
+    return preset->second;
+}
+
+void dcm::dicom_windowing::set_presets(hash_map<std::string, dicom_window> presets, std::string defaultPreset) {
+    const std::scoped_lock lock(m_presetMutex);
+    logger::debug("received {} windowing presets, default '{}'", presets.size(), defaultPreset);
+
+    m_presets = std::move(presets);
+    m_defaultPreset = std::move(defaultPreset);
 }
 
 dcm::dicom_window dcm::dicom_windowing::default_window() {
     logger::debug("returning default windowing values");
 
+    const std::scoped_lock lock(m_presetMutex);
+    const auto preset = m_presets.find(m_defaultPreset);
+    if (preset != m_presets.end()) {
+        return preset->second;
+    }
+
+    // Only used until the settings have been handed over.
     return dicom_window {
         .width = { 400 },
         .level = { 40 }
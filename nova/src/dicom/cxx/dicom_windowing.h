@@ -1,6 +1,9 @@
 #pragma once
+#include <mutex>
+#include <string>
 #include <vector>
 #include <nlohmann/json.hpp>
+#include "lib/hash_map.h"
 
 namespace nova::dcm {
     struct dicom_window {
@@ -14,9 +17,13 @@ namespace nova::dcm {
     class dicom_windowing {
     public:
         static dicom_window load_presets(const dicom_image& image);
+        // The presets come from the settings store on the Rust side, which owns their defaults.
+        static void set_presets(hash_map<std::string, dicom_window> presets, std::string defaultPreset);
     private:
         static dicom_window default_window();
         static std::optional<std::string> normalize_body_part(std::string tagValue) noexcept;
-        static inline const std::filesystem::path m_presetPath = "D:/repos/nova/nova/src/dicom/cxx/assets/presets/windowing.json";
+        static inline std::mutex m_presetMutex;
+        static inline hash_map<std::string, dicom_window> m_presets;
+        static inline std::string m_defaultPreset;
     };
 }
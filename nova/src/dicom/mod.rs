@@ -1 +1,3 @@
-pub mod bridge;
\ No newline at end of file
+pub mod bridge;
+pub mod thumbnail;
+pub mod windowing;
\ No newline at end of file
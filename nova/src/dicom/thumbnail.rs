@@ -0,0 +1,199 @@
+use std::io::Cursor;
+use std::path::Path;
+use anyhow::{anyhow, bail, Context};
+use image::{DynamicImage, GrayImage, ImageFormat, RgbImage};
+use serde::Deserialize;
+use serde_repr::Deserialize_repr;
+use nova_project::thumbnails::ThumbnailRenderer;
+use crate::dicom::bridge::dicom_bridge::dicom_api;
+
+/// Mirrors `nova::dcm::photometric_interpretation`, which is serialized as its discriminant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize_repr)]
+#[repr(u8)]
+enum PhotometricInterpretation {
+    Unknown = 0,
+    Monochrome1,
+    Monochrome2,
+    PaletteColor,
+    Rgb,
+    Hsv,
+    Argb,
+    Cmyk,
+    YbrFull,
+    YbrFull422,
+    YbrPartial422,
+    YbrPartial420,
+    YbrIct,
+    YbrRct,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageDimensions {
+    width: u32,
+    height: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Windowing {
+    width: Vec<f32>,
+    level: Vec<f32>,
+}
+
+/// The JSON returned by `dicom_handle::get_image_metadata`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageMetadata {
+    dimensions: ImageDimensions,
+    bytes_per_pixel: i32,
+    samples_per_pixel: u16,
+    /// 1 if samples are two's complement. Older bridges don't send it.
+    #[serde(default)]
+    pixel_representation: u16,
+    slope: f64,
+    intercept: f64,
+    photometric_interpretation: PhotometricInterpretation,
+    windowing: Windowing,
+}
+
+/// Renders thumbnails through the native DICOM bridge: rescale to modality units, apply the
+/// first window stored in the file, scale down and encode as PNG.
+#[derive(Debug, Default)]
+pub struct DicomThumbnailRenderer;
+
+impl DicomThumbnailRenderer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn render_pixels(metadata: &ImageMetadata, pixels: &[u8], max_size: u32) -> anyhow::Result<Vec<u8>> {
+        let ImageDimensions { width, height } = metadata.dimensions;
+        let count = width as usize * height as usize;
+
+        let image = match (metadata.samples_per_pixel, metadata.bytes_per_pixel) {
+            (1, bytes_per_pixel @ (1 | 2)) => {
+                let signed = metadata.pixel_representation == 1;
+                let raw: Vec<f64> = match (bytes_per_pixel, signed) {
+                    (1, false) => pixels.iter().map(|value| *value as f64).collect(),
+                    (1, true) => pixels.iter().map(|value| *value as i8 as f64).collect(),
+                    (_, false) => pixels.chunks_exact(2).map(|value| u16::from_le_bytes([value[0], value[1]]) as f64).collect(),
+                    (_, true) => pixels.chunks_exact(2).map(|value| i16::from_le_bytes([value[0], value[1]]) as f64).collect(),
+                };
+
+                if raw.len() < count {
+                    bail!("pixel data holds {} values, expected {count}", raw.len());
+                }
+
+                let values: Vec<f64> = raw[..count].iter().map(|value| value * metadata.slope + metadata.intercept).collect();
+                let (lower, window_width) = Self::window(metadata, &values);
+                let invert = metadata.photometric_interpretation == PhotometricInterpretation::Monochrome1;
+
+                let luma = values
+                    .iter()
+                    .map(|value| {
+                        let scaled = (((value - lower) / window_width) * 255.0).clamp(0.0, 255.0) as u8;
+                        if invert { 255 - scaled } else { scaled }
+                    })
+                    .collect();
+
+                DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, luma).context("invalid image dimensions")?)
+            }
+            (3, 1) if metadata.photometric_interpretation == PhotometricInterpretation::Rgb => {
+                let rgb = pixels.get(..count * 3).context("pixel data is truncated")?.to_vec();
+                DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, rgb).context("invalid image dimensions")?)
+            }
+            (samples, bytes) => bail!(
+                "unsupported pixel format: {samples} samples of {bytes} bytes ({:?})",
+                metadata.photometric_interpretation
+            ),
+        };
+
+        let mut png = Vec::new();
+        image
+            .thumbnail(max_size, max_size)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+        Ok(png)
+    }
+
+    /// Lower bound and width of the window to apply. Falls back to the full value range.
+    fn window(metadata: &ImageMetadata, values: &[f64]) -> (f64, f64) {
+        if let (Some(width), Some(level)) = (metadata.windowing.width.first(), metadata.windowing.level.first())
+            && *width > 0.0
+        {
+            return (*level as f64 - *width as f64 / 2.0, *width as f64);
+        }
+
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        (min, (max - min).max(1.0))
+    }
+}
+
+impl ThumbnailRenderer for DicomThumbnailRenderer {
+    fn render(&self, file: &Path, max_size: u32) -> anyhow::Result<Vec<u8>> {
+        let path = file.to_str().ok_or_else(|| anyhow!("{:?} is not valid UTF-8", file))?;
+        cxx::let_cxx_string!(path_to_dicom = path);
+
+        let handle = dicom_api::open_dicom_handle(&path_to_dicom).with_context(|| format!("failed to load {:?}", file))?;
+
+        let metadata: ImageMetadata = serde_json::from_str(handle.get_image_metadata()?.to_str()?)
+            .context("unexpected image metadata")?;
+        let pixels = handle.get_image_pixeldata()?;
+
+        Self::render_pixels(&metadata, pixels.as_slice(), max_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_applies_window_and_fits_size() {
+        let metadata = ImageMetadata {
+            dimensions: ImageDimensions { width: 4, height: 2 },
+            bytes_per_pixel: 2,
+            samples_per_pixel: 1,
+            pixel_representation: 0,
+            slope: 1.0,
+            intercept: -1024.0,
+            photometric_interpretation: PhotometricInterpretation::Monochrome2,
+            windowing: Windowing { width: vec![400.0], level: vec![40.0] },
+        };
+        let pixels: Vec<u8> = [0u16, 1024, 1064, 2048, 0, 1024, 1064, 2048]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+
+        let png = DicomThumbnailRenderer::render_pixels(&metadata, &pixels, 2).unwrap();
+        let image = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap().to_luma8();
+
+        // Air and water on the left, soft tissue and bone on the right.
+        assert_eq!(image.dimensions(), (2, 1));
+        assert!(image.get_pixel(0, 0)[0] < 64);
+        assert!(image.get_pixel(1, 0)[0] > 160);
+    }
+
+    #[test]
+    fn test_render_reads_signed_samples() {
+        // Signed CT data stores Hounsfield units directly: air, water, soft tissue and bone.
+        let metadata = ImageMetadata {
+            dimensions: ImageDimensions { width: 4, height: 1 },
+            bytes_per_pixel: 2,
+            samples_per_pixel: 1,
+            pixel_representation: 1,
+            slope: 1.0,
+            intercept: 0.0,
+            photometric_interpretation: PhotometricInterpretation::Monochrome2,
+            windowing: Windowing { width: vec![400.0], level: vec![40.0] },
+        };
+        let pixels: Vec<u8> = [-1000i16, 0, 40, 1000].iter().flat_map(|value| value.to_le_bytes()).collect();
+
+        let png = DicomThumbnailRenderer::render_pixels(&metadata, &pixels, 4).unwrap();
+        let image = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap().to_luma8();
+
+        // Read as unsigned, air would wrap around to 64536 and render white.
+        assert_eq!(image.get_pixel(0, 0)[0], 0);
+        assert!((100..156).contains(&image.get_pixel(1, 0)[0]));
+        assert_eq!(image.get_pixel(3, 0)[0], 255);
+    }
+}
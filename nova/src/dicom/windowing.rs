@@ -0,0 +1,53 @@
+use std::collections::BTreeMap;
+use anyhow::Context;
+use serde::Serialize;
+use nova_settings::settings::Settings;
+use crate::dicom::bridge::dicom_bridge::dicom_api;
+
+/// Settings keys that change what [`apply_windowing_presets`] hands over.
+pub const WINDOWING_SETTINGS_KEYS: [&str; 2] = ["windowing_presets", "default_windowing_preset"];
+
+/// Mirrors `nova::dcm::dicom_window`.
+#[derive(Debug, PartialEq, Serialize)]
+struct DicomWindow {
+    level: Vec<f32>,
+    width: Vec<f32>,
+}
+
+/// Hands the windowing presets of `settings` to the native side, which picks one by body part when
+/// it loads an image. The settings are the only place the presets are defined.
+pub fn apply_windowing_presets(settings: &Settings) -> anyhow::Result<()> {
+    let presets = serde_json::to_string(&native_presets(settings))?;
+
+    cxx::let_cxx_string!(presets_json = presets);
+    cxx::let_cxx_string!(default_preset = settings.default_windowing_preset.as_str());
+
+    dicom_api::set_windowing_presets(&presets_json, &default_preset).context("failed to hand over windowing presets")
+}
+
+fn native_presets(settings: &Settings) -> BTreeMap<&str, DicomWindow> {
+    settings
+        .windowing_presets
+        .iter()
+        .map(|preset| (preset.name.as_str(), DicomWindow { level: vec![preset.center as f32], width: vec![preset.width as f32] }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_use_the_native_layout() {
+        let settings = Settings::default();
+        let presets = native_presets(&settings);
+
+        assert_eq!(presets.len(), settings.windowing_presets.len());
+        assert_eq!(presets["Lung"], DicomWindow { level: vec![-600.0], width: vec![1500.0] });
+        assert!(presets.contains_key(settings.default_windowing_preset.as_str()));
+        assert_eq!(
+            serde_json::to_value(&presets["Brain"]).unwrap(),
+            serde_json::json!({ "level": [40.0], "width": [80.0] })
+        );
+    }
+}